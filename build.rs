@@ -1,32 +1,23 @@
 use std::env;
-use std::fs;
 use std::path::Path;
 
+#[path = "build/level.rs"]
+mod level;
+
 fn main() {
 	println!("cargo:rerun-if-changed=.env");
 	println!("cargo:rerun-if-env-changed=LOG_LEVEL");
 
-	let mut level = None;
-
-	// Try .env file if present
-	if Path::new(".env").exists() {
-		if let Ok(content) = fs::read_to_string(".env") {
-			for line in content.lines() {
-				let line = line.trim();
-				if line.is_empty() || line.starts_with('#') { continue; }
-				if let Some(rest) = line.strip_prefix("LOG_LEVEL=") {
-					level = Some(rest.trim().to_string());
-					break;
-				}
-			}
-		}
-	}
+	let (level, source) = level::resolve_level(Path::new(".env"), env::var("LOG_LEVEL").ok());
 
-	// Fallback to environment variables at build time
-	if level.is_none() {
-		if let Ok(val) = env::var("LOG_LEVEL") { level = Some(val); }
+	if let Some(warning) = level::default_warning(&source) {
+		println!("cargo:warning={}", warning);
 	}
 
-	let level = level.unwrap_or_else(|| "debug".to_string());
 	println!("cargo:rustc-env=LOG_LEVEL={}", level);
+	println!("cargo:rustc-env=LOG_LEVEL_SOURCE={}", source);
+
+	// The same level as a cfg, so the crate can use it in constants.
+	println!("cargo:rustc-check-cfg=cfg(log_level, values(\"debug\", \"info\", \"warn\", \"error\", \"none\"))");
+	println!("cargo:rustc-cfg=log_level=\"{}\"", level::level_cfg(&level));
 }
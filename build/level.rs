@@ -0,0 +1,42 @@
+//! The build-time level, shared by `build.rs` and the tests in
+//! `tests/build_level.rs`.
+
+use std::fs;
+use std::path::Path;
+
+/// Printed as a cargo warning when the level fell back to the default.
+pub const DEFAULT_WARNING: &str = "rust_logger: LOG_LEVEL not set, defaulting to debug (set it in .env or the environment)";
+
+/// The `log_level` cfg value: the level name in lowercase, `debug` for an
+/// unknown name like `parse_level` does.
+pub fn level_cfg(level: &str) -> &'static str {
+	["debug", "info", "warn", "error", "none"]
+		.into_iter()
+		.find(|name| level.eq_ignore_ascii_case(name))
+		.unwrap_or("debug")
+}
+
+/// Returns the level and where it came from: `.env:<path>`, `env` or
+/// `default`. A `LOG_LEVEL` key in `dotenv` wins over `env_value`.
+pub fn resolve_level(dotenv: &Path, env_value: Option<String>) -> (String, String) {
+	if let Ok(content) = fs::read_to_string(dotenv) {
+		for line in content.lines() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') { continue; }
+			if let Some(rest) = line.strip_prefix("LOG_LEVEL=") {
+				let path = fs::canonicalize(dotenv).unwrap_or_else(|_| dotenv.to_path_buf());
+				return (rest.trim().to_string(), format!(".env:{}", path.display()));
+			}
+		}
+	}
+
+	// Fallback to environment variables at build time
+	if let Some(val) = env_value { return (val, "env".to_string()); }
+
+	("debug".to_string(), "default".to_string())
+}
+
+/// The warning to print for `source`, only for the default.
+pub fn default_warning(source: &str) -> Option<&'static str> {
+	(source == "default").then_some(DEFAULT_WARNING)
+}
@@ -0,0 +1,599 @@
+//! Formatting core that only needs `core`: levels, label rendering and the
+//! markup engine, writing into any `fmt::Write`. The std layer of
+//! `rust_logger` (timestamps, stdout, aliases, themes) is built on top of it,
+//! and `rust_logger` with `--no-default-features` is just this crate for
+//! `no_std` targets. Use it through `rust_logger::render`.
+
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::fmt::{self, Write};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    None,
+}
+
+impl Level {
+    /// OpenTelemetry `SeverityNumber` and `SeverityText` of this level:
+    /// `(5, "DEBUG")`, `(9, "INFO")`, `(13, "WARN")`, `(17, "ERROR")`, and
+    /// `(0, "UNSPECIFIED")` for `None`. A new level has to be added here.
+    pub const fn otel_severity(self) -> (u8, &'static str) {
+        match self {
+            Level::Debug => (5, "DEBUG"),
+            Level::Info => (9, "INFO"),
+            Level::Warn => (13, "WARN"),
+            Level::Error => (17, "ERROR"),
+            Level::None => (0, "UNSPECIFIED"),
+        }
+    }
+}
+
+/// Parses a level name case-insensitively; unknown names mean `Debug`.
+pub fn parse_level(s: &str) -> Level {
+    for (name, level) in [
+        ("debug", Level::Debug),
+        ("info", Level::Info),
+        ("warn", Level::Warn),
+        ("error", Level::Error),
+        ("none", Level::None),
+    ] {
+        if s.eq_ignore_ascii_case(name) { return level; }
+    }
+
+    Level::Debug
+}
+
+/// Whether `level` is printed when `max` is the configured level.
+pub const fn level_enabled(max: Level, level: Level) -> bool {
+    match (max, level) {
+        (Level::None, _) => false,
+        (Level::Error, Level::Error) => true,
+        (Level::Error, _) => false,
+        (Level::Warn, Level::Error | Level::Warn) => true,
+        (Level::Warn, _) => false,
+        (Level::Info, Level::Error | Level::Warn | Level::Info) => true,
+        (Level::Info, _) => false,
+        (Level::Debug, _) => true,
+    }
+}
+
+/// Borrowed view of a level style. Color fields hold SGR parameters; an empty
+/// string means no sequence is emitted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Style<'a> {
+    pub label: &'a str,
+    pub bg: &'a str,
+    pub fg: &'a str,
+    pub ts_color: &'a str,
+    pub msg_color: &'a str,
+}
+
+/// Default styles for Debug, Info, Warn and Error.
+pub const DEFAULT_STYLES: [Style<'static>; 4] = [
+    Style { label: "DBG", bg: "100", fg: "38;2;0;0;0", ts_color: "90", msg_color: "90" }, // bg bright black (gray), fg gray
+    Style { label: "LOG", bg: "44", fg: "38;2;0;0;0", ts_color: "34", msg_color: "37" },  // bg blue, fg blue
+    Style { label: "WRN", bg: "43", fg: "38;2;0;0;0", ts_color: "33", msg_color: "33" },  // bg yellow, fg yellow
+    Style { label: "ERR", bg: "41", fg: "38;2;0;0;0", ts_color: "31", msg_color: "31" },  // bg red, fg red
+];
+
+/// Default style for `level`, `None` for `Level::None`.
+pub fn default_style(level: Level) -> Option<Style<'static>> {
+    match level {
+        Level::Debug => Some(DEFAULT_STYLES[0]),
+        Level::Info => Some(DEFAULT_STYLES[1]),
+        Level::Warn => Some(DEFAULT_STYLES[2]),
+        Level::Error => Some(DEFAULT_STYLES[3]),
+        Level::None => None,
+    }
+}
+
+/// Decorative characters. Everything the crate draws (markers, gutters,
+/// rules, elisions, replacement characters) takes them from one table so
+/// the ASCII fallback covers all of it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyphs {
+    pub ellipsis: &'static str,
+    pub gutter: &'static str,
+    pub marker: &'static str,
+    pub rule: &'static str,
+    pub replacement: &'static str,
+    /// Ends a console line where a word too long for it was broken
+    pub wrap: &'static str,
+}
+
+pub const UNICODE_GLYPHS: Glyphs = Glyphs { ellipsis: "\u{2026}", gutter: "\u{2502}", marker: "\u{25B6}", rule: "\u{2500}", replacement: "\u{FFFD}", wrap: "\u{21A9}" };
+pub const ASCII_GLYPHS: Glyphs = Glyphs { ellipsis: "...", gutter: "|", marker: ">", rule: "-", replacement: "?", wrap: "\\" };
+
+/// Destination for raw bytes, e.g. a UART. Wrap it in `ByteWriter` to use it
+/// wherever a `fmt::Write` is expected.
+pub trait ByteSink {
+    fn write_bytes(&mut self, bytes: &[u8]);
+}
+
+pub struct ByteWriter<S: ByteSink>(pub S);
+
+impl<S: ByteSink> Write for ByteWriter<S> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Resolves markup aliases. The std layer implements it for its alias table.
+pub trait AliasLookup {
+    fn lookup(&self, name: &str) -> Option<&str>;
+}
+
+pub struct NoAliases;
+
+impl AliasLookup for NoAliases {
+    fn lookup(&self, _name: &str) -> Option<&str> {
+        None
+    }
+}
+
+/// Writes `\x1b[<codes joined by ;>m`, skipping empty codes. Writes nothing if
+/// every code is empty.
+pub fn write_sgr(out: &mut dyn Write, codes: &[&str]) -> fmt::Result {
+    let mut first = true;
+
+    for code in codes.iter().filter(|c| !c.is_empty()) {
+        out.write_str(if first { "\x1b[" } else { ";" })?;
+        out.write_str(code)?;
+        first = false;
+    }
+
+    if !first { out.write_char('m')?; }
+    Ok(())
+}
+
+/// Writes the colored label block, e.g. `\x1b[0;44;38;2;0;0;0m LOG \x1b[0m `.
+pub fn write_label(out: &mut dyn Write, style: &Style) -> fmt::Result {
+    write_sgr(out, &["0", style.bg, style.fg])?;
+    write!(out, " {} \x1b[0m ", style.label)
+}
+
+/// Writes the timestamp text (without brackets), e.g. from an RTC.
+pub type TimestampHook<'a> = &'a dyn Fn(&mut dyn Write) -> fmt::Result;
+
+/// Writes the `[timestamp] ` block in the style's timestamp color; the hook
+/// writes the timestamp text itself.
+pub fn write_timestamp(out: &mut dyn Write, style: &Style, timestamp: TimestampHook) -> fmt::Result {
+    write_sgr(out, &[style.ts_color])?;
+    out.write_char('[')?;
+    timestamp(out)?;
+    out.write_str("] ")
+}
+
+/// Writes a whole line without the trailing newline: label, optional
+/// timestamp, then the message with markup applied.
+pub fn write_line(
+    out: &mut dyn Write,
+    style: &Style,
+    timestamp: Option<TimestampHook>,
+    message: &str,
+    aliases: &dyn AliasLookup,
+) -> fmt::Result {
+    write_label(out, style)?;
+    if let Some(timestamp) = timestamp { write_timestamp(out, style, timestamp)?; }
+    write_sgr(out, &[style.msg_color])?;
+    write_markup(out, message, style.msg_color, aliases)?;
+    out.write_str(" \x1b[0m")
+}
+
+/// Same as `write_line` for `format_args!` input.
+#[cfg(feature = "alloc")]
+pub fn write_line_args(
+    out: &mut dyn Write,
+    style: &Style,
+    timestamp: Option<TimestampHook>,
+    args: fmt::Arguments,
+    aliases: &dyn AliasLookup,
+) -> fmt::Result {
+    let message = alloc::fmt::format(args);
+    write_line(out, style, timestamp, &message, aliases)
+}
+
+/// Markup color names with their foreground and background codes.
+#[cfg(feature = "markup")]
+const COLORS: [(&str, &str, &str); 11] = [
+    ("black", "30", "40"),
+    ("red", "31", "41"),
+    ("green", "32", "42"),
+    ("orange", "33", "43"),
+    ("yellow", "33", "43"),
+    ("blue", "34", "44"),
+    ("purple", "35", "45"),
+    ("magenta", "35", "45"),
+    ("cyan", "36", "46"),
+    ("white", "37", "47"),
+    ("gray", "90", "100"),
+];
+
+/// A markup color: a named code, a 256-palette index or 24-bit RGB.
+#[cfg(feature = "markup")]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Color {
+    Code(&'static str),
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+#[cfg(feature = "markup")]
+impl Color {
+    /// A color name, `#rrggbb` or `c<0-255>`; with `bg` the background code.
+    fn parse(token: &str, bg: bool) -> Option<Color> {
+        if let Some(hex) = token.strip_prefix('#') {
+            let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+            if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) { return None; }
+            return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+        }
+
+        if let Some(index) = token.strip_prefix(['c', 'C']) {
+            if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) {
+                return index.parse().ok().map(Color::Indexed);
+            }
+        }
+
+        COLORS.iter()
+            .find(|(color, _, _)| token.eq_ignore_ascii_case(color))
+            .map(|(_, fg, bg_code)| Color::Code(if bg { bg_code } else { fg }))
+    }
+
+    fn write(&self, out: &mut dyn Write, bg: bool) -> fmt::Result {
+        match self {
+            Color::Code(code) => out.write_str(code),
+            Color::Indexed(n) => write!(out, "{};5;{n}", if bg { 48 } else { 38 }),
+            Color::Rgb(r, g, b) => write!(out, "{};2;{r};{g};{b}", if bg { 48 } else { 38 }),
+        }
+    }
+}
+
+/// Whether a single tag token is a style: an attribute (`bold`, `i`, ...),
+/// a color name, `#rrggbb`, `c<0-255>`, or one of those after `bg-`.
+#[cfg(feature = "markup")]
+pub fn is_markup_token(token: &str) -> bool {
+    !token.contains(',') && MarkupStyle::default().with_tokens(token).is_some()
+}
+
+#[cfg(not(feature = "markup"))]
+pub fn write_markup(out: &mut dyn Write, input: &str, _default_codes: &str, _aliases: &dyn AliasLookup) -> fmt::Result {
+    out.write_str(input)
+}
+
+/// Deepest tag nesting that is styled; tags below it keep the style of the
+/// deepest level. Fixed so the core needs no allocator.
+#[cfg(feature = "markup")]
+const MAX_MARKUP_DEPTH: usize = 8;
+
+/// Combined style of the open tags.
+#[cfg(feature = "markup")]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+struct MarkupStyle {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    dim: bool,
+    strikethrough: bool,
+    reverse: bool,
+    fg: Option<Color>,
+    bg: Option<Color>,
+}
+
+#[cfg(feature = "markup")]
+impl MarkupStyle {
+    /// `self` with the tokens of one tag applied: attributes add up, the
+    /// first color (and `bg-` color) in the tag replaces the inherited one.
+    /// `None` unless every token is known, so text like `Vec<String>` is not
+    /// taken for a tag.
+    fn with_tokens(mut self, tokens: &str) -> Option<Self> {
+        let mut color_set = false;
+        let mut bg_set = false;
+        let mut any = false;
+
+        for token in tokens.split(',').map(str::trim) {
+            if token.is_empty() { continue; }
+            any = true;
+
+            let is = |names: &[&str]| names.iter().any(|name| token.eq_ignore_ascii_case(name));
+            let bg_name = token.get(..3).filter(|prefix| prefix.eq_ignore_ascii_case("bg-")).map(|_| &token[3..]);
+
+            if is(&["italic", "i"]) { self.italic = true; }
+            else if is(&["bold", "b"]) { self.bold = true; }
+            else if is(&["underline", "u"]) { self.underline = true; }
+            else if is(&["dim", "d"]) { self.dim = true; }
+            else if is(&["strikethrough", "s"]) { self.strikethrough = true; }
+            else if is(&["reverse", "r"]) { self.reverse = true; }
+            else if let Some(bg) = bg_name.and_then(|name| Color::parse(name, true)) {
+                if !bg_set {
+                    self.bg = Some(bg);
+                    bg_set = true;
+                }
+            }
+            else if let Some(fg) = Color::parse(token, false) {
+                if !color_set {
+                    self.fg = Some(fg);
+                    color_set = true;
+                }
+            }
+            else { return None; }
+        }
+
+        any.then_some(self)
+    }
+
+    /// Writes the SGR sequence for this style, nothing if it is plain.
+    fn write_sgr(&self, out: &mut dyn Write) -> fmt::Result {
+        let flags = [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.dim, "2"),
+            (self.strikethrough, "9"),
+            (self.reverse, "7"),
+        ];
+
+        let mut first = true;
+        let mut separator = |out: &mut dyn Write| {
+            let s = if first { "\x1b[" } else { ";" };
+            first = false;
+            out.write_str(s)
+        };
+
+        for (_, code) in flags.iter().filter(|(on, _)| *on) {
+            separator(out)?;
+            out.write_str(code)?;
+        }
+        if let Some(fg) = self.fg {
+            separator(out)?;
+            fg.write(out, false)?;
+        }
+        if let Some(bg) = self.bg {
+            separator(out)?;
+            bg.write(out, true)?;
+        }
+
+        if *self != MarkupStyle::default() { out.write_char('m')?; }
+        Ok(())
+    }
+}
+
+/// Renders `<tokens>Text</>` markup. Tags nest: an inner tag inherits the
+/// outer style and `</>` returns to the enclosing one. After the outermost
+/// tag closes the style is reset and `default_codes` re-applied.
+///
+/// Anything that is not a tag is printed as is: brackets around unknown
+/// tokens (`Vec<String>`), a tag without a later `</>`, and a `</>` without
+/// an open tag. `\<` and `\>` print a literal bracket. Tags left open end
+/// with the line.
+#[cfg(feature = "markup")]
+pub fn write_markup(out: &mut dyn Write, input: &str, default_codes: &str, aliases: &dyn AliasLookup) -> fmt::Result {
+    let mut stack = [MarkupStyle::default(); MAX_MARKUP_DEPTH + 1];
+    let mut depth = 0;
+    // Tags opened beyond MAX_MARKUP_DEPTH, only counted
+    let mut overflow = 0;
+
+    let mut i: usize = 0;
+
+    while i < input.len() {
+        let rest = &input[i..];
+
+        if rest.starts_with("\\<") || rest.starts_with("\\>") {
+            out.write_str(&rest[1..2])?;
+            i += 2;
+            continue;
+        }
+
+        if rest.starts_with("</>") && (depth > 0 || overflow > 0) {
+            i += 3;
+
+            if overflow > 0 {
+                overflow -= 1;
+                continue;
+            }
+
+            depth -= 1;
+            if stack[depth + 1] != stack[depth] {
+                out.write_str("\x1b[0m")?;
+                write_sgr(out, &[default_codes])?;
+                stack[depth].write_sgr(out)?;
+            }
+            continue;
+        }
+
+        // An opening tag needs its '>' and a "</>" somewhere after it.
+        if rest.starts_with('<') && !rest.starts_with("</>") {
+            if let Some(gt) = rest.find('>') {
+                let tag_inner = &rest[1..gt];
+                let tokens = aliases.lookup(tag_inner).unwrap_or(tag_inner);
+                let style = stack[depth].with_tokens(tokens);
+
+                if let Some(style) = style.filter(|_| rest[gt + 1..].contains("</>")) {
+                    if depth == MAX_MARKUP_DEPTH {
+                        overflow += 1;
+                    } else {
+                        stack[depth + 1] = style;
+                        depth += 1;
+                        if stack[depth] != stack[depth - 1] { stack[depth].write_sgr(out)?; }
+                    }
+
+                    i += gt + 1;
+                    continue;
+                }
+            }
+        }
+
+        let ch = rest.chars().next().unwrap();
+        out.write_char(ch)?;
+        i += ch.len_utf8();
+    }
+
+    if depth > 0 && stack[depth] != stack[0] {
+        out.write_str("\x1b[0m")?;
+        write_sgr(out, &[default_codes])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::String;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Raw bytes, as a UART driver would get them.
+    struct Bytes(Vec<u8>);
+
+    impl ByteSink for Bytes {
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+    }
+
+    #[test]
+    fn write_line_drives_a_byte_sink() {
+        let mut out = ByteWriter(Bytes(Vec::new()));
+        let uptime = |out: &mut dyn Write| write!(out, "{:05}", 1234);
+        write_line(&mut out, &DEFAULT_STYLES[1], Some(&uptime), "boot ok", &NoAliases).unwrap();
+        out.write_char('\n').unwrap();
+        write_line(&mut out, &DEFAULT_STYLES[3], None, "halt", &NoAliases).unwrap();
+
+        let ByteWriter(Bytes(bytes)) = out;
+        assert_eq!(
+            bytes,
+            b"\x1b[0;44;38;2;0;0;0m LOG \x1b[0m \x1b[34m[01234] \x1b[37mboot ok \x1b[0m\n\x1b[0;41;38;2;0;0;0m ERR \x1b[0m \x1b[31mhalt \x1b[0m"
+        );
+    }
+
+    #[cfg(feature = "markup")]
+    fn markup(input: &str) -> String {
+        let mut out = String::new();
+        write_markup(&mut out, input, "37", &NoAliases).unwrap();
+        out
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn nested_tags_return_to_the_enclosing_style() {
+        assert_eq!(markup("a <red>b <bold>c</> d</> e"), "a \x1b[31mb \x1b[1;31mc\x1b[0m\x1b[37m\x1b[31m d\x1b[0m\x1b[37m e");
+        assert_eq!(
+            markup("<red>1<bold>2<u,blue>3</>2</>1</>0"),
+            concat!(
+                "\x1b[31m1",
+                "\x1b[1;31m2",
+                "\x1b[1;4;34m3",
+                "\x1b[0m\x1b[37m\x1b[1;31m2",
+                "\x1b[0m\x1b[37m\x1b[31m1",
+                "\x1b[0m\x1b[37m0",
+            )
+        );
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn a_stray_close_tag_is_printed_as_is() {
+        assert_eq!(markup("done</> here"), "done</> here");
+        assert_eq!(markup("<red>x</></>"), "\x1b[31mx\x1b[0m\x1b[37m</>");
+        // A tag without a later `</>` is text too.
+        assert_eq!(markup("<red>never closed"), "<red>never closed");
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn brackets_that_are_not_tags_are_printed_as_is() {
+        assert_eq!(markup("HashMap<String, Vec<u8>> is empty"), "HashMap<String, Vec<u8>> is empty");
+        assert_eq!(markup("<nope>x</>"), "<nope>x</>");
+        // One unknown token makes the whole tag text.
+        assert_eq!(markup("<red,nope>x</> <red>y</>"), "<red,nope>x</> \x1b[31my\x1b[0m\x1b[37m");
+        assert_eq!(markup("a < b > c"), "a < b > c");
+        assert_eq!(markup("Vec<<bold>T</>>"), "Vec<\x1b[1mT\x1b[0m\x1b[37m>");
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn escaped_brackets_are_literal() {
+        assert_eq!(markup(r"\<red>x\</>"), "<red>x</>");
+        assert_eq!(markup(r"<red>\<b\></>"), "\x1b[31m<b>\x1b[0m\x1b[37m");
+        // Only before a bracket is a backslash an escape.
+        assert_eq!(markup(r"C:\dir \\ x"), r"C:\dir \\ x");
+    }
+
+    #[cfg(feature = "markup")]
+    struct TestAliases;
+
+    #[cfg(feature = "markup")]
+    impl AliasLookup for TestAliases {
+        fn lookup(&self, name: &str) -> Option<&str> {
+            match name {
+                "brand" => Some("#1e90ff, bold"),
+                "badge" => Some("bg-c22, white"),
+                _ => None,
+            }
+        }
+    }
+
+    #[cfg(feature = "markup")]
+    fn markup_with_aliases(input: &str) -> String {
+        let mut out = String::new();
+        write_markup(&mut out, input, "37", &TestAliases).unwrap();
+        out
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn rgb_and_palette_colors_write_extended_sequences() {
+        assert_eq!(markup("<#1e90ff>x</>"), "\x1b[38;2;30;144;255mx\x1b[0m\x1b[37m");
+        assert_eq!(markup("<#FF0000>x</>"), "\x1b[38;2;255;0;0mx\x1b[0m\x1b[37m");
+        assert_eq!(markup("<c208>x</>"), "\x1b[38;5;208mx\x1b[0m\x1b[37m");
+        assert_eq!(markup("<C0,bold>x</>"), "\x1b[1;38;5;0mx\x1b[0m\x1b[37m");
+        assert_eq!(markup_with_aliases("<brand>x</>"), "\x1b[1;38;2;30;144;255mx\x1b[0m\x1b[37m");
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn invalid_colors_leave_the_tag_as_text() {
+        for tag in ["#1e90f", "#1e90ffa", "#1e90fg", "#+1e90f", "c256", "c", "c-1", "c1x"] {
+            let input = std::format!("<{tag}>x</>");
+            assert_eq!(markup(&input), input);
+        }
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn background_colors() {
+        assert_eq!(markup("<bg-red>x</>"), "\x1b[41mx\x1b[0m\x1b[37m");
+        assert_eq!(markup("<bg-gray,white>x</>"), "\x1b[37;100mx\x1b[0m\x1b[37m");
+        assert_eq!(markup("<BG-#102030>x</>"), "\x1b[48;2;16;32;48mx\x1b[0m\x1b[37m");
+        assert_eq!(markup("<bg-c22>x</>"), "\x1b[48;5;22mx\x1b[0m\x1b[37m");
+        assert_eq!(markup_with_aliases("<badge>x</>"), "\x1b[37;48;5;22mx\x1b[0m\x1b[37m");
+        assert_eq!(markup("<bg-nope>x</>"), "<bg-nope>x</>");
+    }
+
+    #[cfg(feature = "markup")]
+    #[test]
+    fn a_background_does_not_bleed_past_its_tag() {
+        // The reset before the default codes clears the background.
+        assert_eq!(markup("<bg-red>x</> y"), "\x1b[41mx\x1b[0m\x1b[37m y");
+        // An inner tag keeps the outer background; closing it restores both.
+        assert_eq!(
+            markup("<bg-blue>a<red>b</>c</>d"),
+            "\x1b[44ma\x1b[31;44mb\x1b[0m\x1b[37m\x1b[44mc\x1b[0m\x1b[37md"
+        );
+        // A background opened inside is gone once its tag closes.
+        assert_eq!(
+            markup("<red>a<bg-white>b</>c</>"),
+            "\x1b[31ma\x1b[31;47mb\x1b[0m\x1b[37m\x1b[31mc\x1b[0m\x1b[37m"
+        );
+    }
+}
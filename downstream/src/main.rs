@@ -0,0 +1,1043 @@
+//! Exercises every macro and public type through the `rust_logger` facade
+//! only, the way a dependent crate sees it. Building it checks that the
+//! macros' `$crate` paths and the re-exports hold; running it checks the
+//! output. `cargo run -p rust_logger-downstream`
+
+use std::future::Future;
+use std::path::Path;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rust_logger::config::{config_report, set_stable_test_format, stable_test_format, ConfigError};
+use rust_logger::console::{self, BellMode, BellStyle, ColorChoice, ColorEnv, DrawCoordinator, LocationMode, SelfLog, SharedBuffer};
+use rust_logger::display::{self, Charset, Glyphs, Lossy, P};
+use rust_logger::format::{self, Format, JsonFormatter, LogfmtFormatter, PrettyFormatter, Record};
+use rust_logger::time::{self, TimeZoneMode, TimestampFormat};
+use rust_logger::theme::{self, LevelStyle, Theme, TimeOfDay};
+use rust_logger::{alias, audit, debug, dump_config, error, group_begin, group_end, heartbeat, log, mute, new_line};
+use rust_logger::{hexdump, record_group, section, separator, time_scope, warn};
+use rust_logger::dump;
+use rust_logger::ffi;
+use rust_logger::file::{self, RotationPolicy, SyncPolicy};
+use rust_logger::sink::{self, Sink, SinkId};
+use rust_logger::{init::InitError, level, render, stats, Level, OUTPUT_FORMAT_VERSION};
+
+struct Passthrough;
+
+mod net {
+    pub fn chatter() {
+        rust_logger::log!("net info");
+        rust_logger::warn!("net warn");
+    }
+
+    pub mod tcp {
+        pub fn chatter() {
+            rust_logger::debug!("tcp debug");
+        }
+    }
+}
+
+mod network {
+    pub fn chatter() {
+        rust_logger::log!("network info");
+    }
+}
+
+struct NoDebug;
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Endpoint {
+    host: &'static str,
+    ports: Vec<u16>,
+}
+
+/// A message with an optional cause, to build `source()` chains.
+#[derive(Debug)]
+struct Layered(&'static str, Option<Box<Layered>>);
+
+impl std::fmt::Display for Layered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for Layered {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.1.as_deref().map(|cause| cause as _)
+    }
+}
+
+struct Account {
+    owner: String,
+}
+
+impl Account {
+    #[rust_logger::instrument]
+    fn owner(&self) -> &str {
+        rust_logger::log!("inside");
+        &self.owner
+    }
+}
+
+#[rust_logger::instrument(level = "debug", skip(password), rename(user = "login"))]
+fn authenticate(user: &str, password: &str, _opaque: NoDebug) -> Result<u32, String> {
+    let id: u32 = user.len().to_string().parse().map_err(|_| String::from("bad"))?;
+    if password.is_empty() { return Err(format!("no password for {user}")); }
+    Ok(id)
+}
+
+#[rust_logger::instrument(name = "generic")]
+fn largest<T: PartialOrd + Copy>(items: &[T]) -> Option<T> {
+    items.iter().copied().fold(None, |max, x| match max { Some(m) if m >= x => Some(m), _ => Some(x) })
+}
+
+#[rust_logger::instrument]
+fn evens(limit: u32) -> impl Iterator<Item = u32> {
+    (0..limit).filter(|n| n % 2 == 0)
+}
+
+#[rust_logger::instrument]
+fn nothing() {}
+
+#[rust_logger::instrument(level = "warn")]
+async fn fetch(id: u32) -> Result<String, std::io::Error> {
+    YieldOnce(false).await;
+    Ok(format!("item {id}"))
+}
+
+/// Pending once, so the instrumented future is polled twice.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 { return Poll::Ready(()); }
+        self.0 = true;
+        Poll::Pending
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) { return output; }
+    }
+}
+
+impl DrawCoordinator for Passthrough {
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        f();
+    }
+}
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+fn main() {
+    let buffer = SharedBuffer::new();
+    let _guard = rust_logger::builder()
+        .level(Level::Debug)
+        .stable_test_format(true)
+        .color_choice(ColorChoice::Never)
+        .location_mode(LocationMode::Module)
+        .bell(BellMode::Off)
+        .bell_style(BellStyle::Audible)
+        .self_log(SelfLog::Console)
+        .charset(Charset::Ascii)
+        .draw_guard(Arc::new(Passthrough))
+        .writer(Box::new(buffer.clone()))
+        .init()
+        .expect("first init");
+    assert!(matches!(rust_logger::builder().init(), Err(InitError::AlreadyInitialized)));
+    assert!(stable_test_format());
+
+    debug!("debug {}", 1);
+    log!("info <bold>{}</>", 2);
+    warn!(emphasis: true, "warn");
+    error!("error");
+    audit!("user {} logged in", "ada");
+    new_line!();
+    new_line!(Level::Error);
+
+    alias!("brand", "#1e90ff, bold");
+    log!("<brand>branded</>");
+
+    group_begin!("outer");
+    group_begin!(level: Level::Warn, "inner");
+    log!("nested");
+    group_end!();
+    group_end!("outer done");
+    assert_eq!(rust_logger::group::current_depth(), 0);
+
+    {
+        let _mute = mute!(Level::Error);
+        warn!("muted");
+    }
+
+    {
+        let _hb = heartbeat!(Level::Info, Duration::from_secs(60), "working");
+    }
+
+    log!("{} {}", P(Path::new("/tmp/x")), Lossy(b"a\xffb"));
+    dump_config!();
+
+    let text = buffer.text();
+    for expected in [
+        " DBG  [TS] rust_logger_downstream: debug 1 ",
+        " LOG  [TS] rust_logger_downstream: info 2 ",
+        " WRN  [TS] rust_logger_downstream: !!! warn !!! ",
+        " ERR  [TS] rust_logger_downstream: error ",
+        " LOG  [TS] rust_logger_downstream: [audit] user ada logged in ",
+        " LOG  [TS] rust_logger_downstream:     nested ",
+        " LOG  [TS] rust_logger_downstream: working done in ",
+        " LOG  [TS] rust_logger_downstream: /tmp/x a?b ",
+    ] {
+        assert!(text.contains(expected), "missing {expected:?} in:\n{text}");
+    }
+    assert!(!text.contains("muted"));
+
+    // #[instrument]
+    buffer.clear();
+    assert_eq!(authenticate("ada", "secret", NoDebug), Ok(3));
+    assert!(authenticate("ada", "", NoDebug).is_err());
+    assert_eq!(largest(&[3, 9, 2]), Some(9));
+    assert_eq!(evens(5).count(), 3);
+    nothing();
+    let account = Account { owner: String::from("ada") };
+    assert_eq!(account.owner(), "ada");
+    assert_eq!(block_on(fetch(7)).unwrap(), "item 7");
+
+    let text = buffer.text();
+    for expected in [
+        " DBG  [TS] rust_logger_downstream: authenticate(login = \"ada\", _opaque = _) ",
+        " DBG  [TS] rust_logger_downstream: authenticate returned Ok(3) in ",
+        " WRN  [TS] rust_logger_downstream: authenticate failed: \"no password for ada\" in ",
+        // Without a `Debug` bound on `T` the generic values show as `_`.
+        " LOG  [TS] rust_logger_downstream: generic(items = _) ",
+        " LOG  [TS] rust_logger_downstream: generic returned _ in ",
+        " LOG  [TS] rust_logger_downstream: evens returned Filter { iter: 0..5 } in ",
+        " LOG  [TS] rust_logger_downstream: nothing() ",
+        " LOG  [TS] rust_logger_downstream: nothing returned in ",
+        " LOG  [TS] rust_logger_downstream: owner() ",
+        " LOG  [TS] rust_logger_downstream:   inside ",
+        " LOG  [TS] rust_logger_downstream: owner returned \"ada\" in ",
+        " WRN  [TS] rust_logger_downstream: fetch(id = 7) ",
+        " WRN  [TS] rust_logger_downstream: fetch returned Ok(\"item 7\") in ",
+    ] {
+        assert!(text.contains(expected), "missing {expected:?} in:\n{text}");
+    }
+    assert!(!text.contains("secret"));
+
+    // Module levels: the longest prefix wins, on `::` boundaries only.
+    buffer.clear();
+    level::set_level(Level::Info);
+    level::set_module_level("rust_logger_downstream::net", Level::Warn);
+    level::set_module_level("rust_logger_downstream::net::tcp", Level::Debug);
+    net::chatter();
+    net::tcp::chatter();
+    network::chatter();
+    debug!("root debug");
+    let text = buffer.text();
+    assert!(!text.contains("net info"));
+    assert!(text.contains("net warn"));
+    assert!(text.contains("tcp debug"));
+    assert!(text.contains("network info"), "`net` must not cover `network`");
+    assert!(!text.contains("root debug"), "unmatched targets fall back to the global level");
+    assert!(level::is_enabled_for(Level::Debug, "rust_logger_downstream::net::tcp::conn"));
+    level::clear_module_levels();
+    assert!(!level::is_enabled_for(Level::Debug, "rust_logger_downstream::net::tcp"));
+
+    let spec = level::parse_level_spec("debug, my_crate::net=warn,hyper=error,my_crate::net=info").unwrap();
+    assert_eq!(spec.default, Some(Level::Debug));
+    assert_eq!(spec.modules, [(String::from("hyper"), Level::Error), (String::from("my_crate::net"), Level::Info)]);
+    assert!(matches!(level::parse_level_spec("net=loud"), Err(ConfigError::UnknownLevel(_))));
+    assert!(matches!(level::parse_level_spec("=warn"), Err(ConfigError::BadLevelSpec(_))));
+    level::set_level_spec("warn,rust_logger_downstream::net=error").unwrap();
+    assert!(!level::is_enabled_for(Level::Warn, "rust_logger_downstream::net"));
+    assert!(level::is_enabled_for(Level::Warn, "rust_logger_downstream"));
+    let _: level::LevelSpec = spec;
+    level::clear_module_levels();
+
+    // Fields after the message; `?` for Debug.
+    buffer.clear();
+    level::set_level(Level::Debug);
+    let query = "a b";
+    log!("request finished in {}ms", 5; user_id = 42, query = ?query);
+    debug!("inline {query}"; ok = true,);
+    let json = Collect::default();
+    let json_sink = sink::add_sink(Box::new(json.clone()), Level::Debug, Format::Json);
+    warn!("fields"; user_id = 42);
+    sink::remove_sink(json_sink);
+    let text = buffer.text();
+    assert!(text.contains(" LOG  [TS] rust_logger_downstream: request finished in 5ms user_id=42 query=\"a b\" "), "{text}");
+    assert!(text.contains(": inline a b ok=true "));
+    // The first sink of the run also gets what was logged before it.
+    let lines = json.0.lock().unwrap().clone();
+    assert!(lines.last().unwrap().ends_with(r#""message":"fields","user_id":"42"}"#));
+    assert!(lines.iter().any(|line| line.ends_with(r#""message":"request finished in 5ms","user_id":"42","query":"\"a b\""}"#)), "{lines:?}");
+    sink::finish_initialization();
+    sink::set_startup_replay(100, Duration::from_secs(10));
+    buffer.clear();
+    log!(dest: file, "not on the console");
+    debug!(dest: console, "console only");
+    assert_eq!(buffer.text(), " DBG  [TS] rust_logger_downstream: console only \n");
+    assert_eq!(format::Destination::parse("file"), Some(format::Destination::File));
+    assert_eq!(Record::new(Level::Info, "").destination, format::Destination::All);
+    let fields = [format::Field::display("k", &1), format::Field::debug("s", &"v")];
+    let record = Record { fields: &fields, ..Record::new(Level::Info, "m") };
+    let mut line = String::new();
+    JsonFormatter.format(&record, &mut line).unwrap();
+    assert_eq!(line, r#"{"level":"info","message":"m","k":"1","s":"\"v\""}"#);
+    assert!(matches!(fields[1].value, format::FieldValue::Debug(_)));
+
+    // Sinks: each takes its own level and format, independent of the console.
+    buffer.clear();
+    level::set_level(Level::Warn);
+    let debug_lines = Collect::default();
+    let warn_lines = Collect::default();
+    let debug_sink = sink::add_sink(Box::new(debug_lines.clone()), Level::Debug, Format::Json);
+    let warn_sink: SinkId = sink::add_sink(Box::new(warn_lines.clone()), Level::Warn, Format::Pretty);
+    debug!("to sink a");
+    log!("to sink <bold>b</>");
+    warn!("everywhere");
+    {
+        let _quiet: sink::SinkMuteGuard = rust_logger::mute_sink!(debug_sink);
+        warn!("skips sink a");
+    }
+    assert!(config_report().contains("sinks = Debug/Json, Warn/Pretty"));
+    assert!(sink::remove_sink(debug_sink).is_some() && sink::remove_sink(debug_sink).is_none());
+    assert!(sink::remove_sink(warn_sink).is_some());
+    debug!("nowhere");
+    let debug_lines = debug_lines.0.lock().unwrap().clone();
+    assert_eq!(debug_lines.len(), 3);
+    assert!(debug_lines[1].starts_with('{') && debug_lines[1].contains(r#""message":"to sink b""#), "{debug_lines:?}");
+    assert_eq!(*warn_lines.0.lock().unwrap(), ["[WRN] [TS] rust_logger_downstream: everywhere", "[WRN] [TS] rust_logger_downstream: skips sink a"]);
+    let text = buffer.text();
+    assert!(text.contains("everywhere") && !text.contains("to sink") && !text.contains("nowhere"));
+    level::set_level(Level::Debug);
+
+    // Each line of a record parseable on its own.
+    let repeated = Collect::default();
+    let repeat_sink = sink::add_sink(Box::new(repeated.clone()), Level::Debug, Format::Pretty);
+    assert!(sink::set_sink_continuation(repeat_sink, sink::ContinuationStyle::RepeatPrefix));
+    log!("first\nsecond");
+    sink::remove_sink(repeat_sink);
+    let repeated = repeated.0.lock().unwrap()[0].clone();
+    let (first, second) = repeated.split_once('\n').unwrap();
+    assert!(first.starts_with("[LOG] [TS] rust_logger_downstream: #") && first.ends_with(" first"), "{repeated}");
+    assert_eq!(second, first.replace("first", "second"));
+    let raw = Record { raw: Some("<b>as formatted</>"), ..Record::new(Level::Info, "<b>as formatted</>: refused") };
+    assert_eq!(raw.raw_message(), "<b>as formatted</>");
+
+    // The C interface, called directly: invalid UTF-8 and null pointers.
+    buffer.clear();
+    let captured = Collect::default();
+    let capture = sink::add_sink(Box::new(captured.clone()), Level::Debug, Format::Pretty);
+    unsafe {
+        ffi::rust_logger_set_level(2);
+        ffi::rust_logger_log(1, c"engine".as_ptr(), c"below the level".as_ptr());
+        ffi::rust_logger_log(2, c"engine::io".as_ptr(), c"read \xff failed".as_ptr());
+        ffi::rust_logger_log(9, std::ptr::null(), c"no target".as_ptr());
+        ffi::rust_logger_log(3, c"engine".as_ptr(), std::ptr::null());
+        ffi::rust_logger_set_level(4);
+        ffi::rust_logger_log(3, c"engine".as_ptr(), c"off".as_ptr());
+    }
+    sink::remove_sink(capture);
+    level::set_level(Level::Debug);
+    let text = buffer.text();
+    // The ASCII charset shows the replacement as `?`.
+    assert!(text.contains(" WRN  [TS] engine::io: read ? failed "), "{text}");
+    assert!(text.contains(" ERR  [TS] no target "));
+    assert!(!text.contains("below the level") && !text.contains("off"));
+    assert_eq!(captured.0.lock().unwrap().len(), 4, "the Debug sink also takes the Info record and the one while off");
+
+    // Record groups come out in one piece, even with other threads logging.
+    buffer.clear();
+    let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let noise: Vec<_> = (0..3)
+        .map(|n| {
+            let stop = stop.clone();
+            std::thread::spawn(move || while !stop.load(std::sync::atomic::Ordering::Relaxed) { log!("noise {n}"); })
+        })
+        .collect();
+    {
+        let report = record_group!(Level::Warn);
+        report.log("report: 10 rows failed");
+        for row in 0..10 {
+            report.log(format_args!("report row {row}"));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    for thread in noise { thread.join().unwrap(); }
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    let first = lines.iter().position(|line| line.contains("report: 10 rows failed")).unwrap();
+    for (i, line) in lines[first + 1..first + 11].iter().enumerate() {
+        assert!(line.starts_with(" WRN ") && line.ends_with(&format!("report row {i} ")), "interleaved: {line}");
+    }
+    assert!(text.contains("noise 0"));
+
+    // `once:` fires once under contention; `every:` on the 1st, N+1th, ...
+    buffer.clear();
+    level::set_level(Level::Debug);
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| for _ in 0..1000 { warn!(once: "fallback path taken"); });
+        }
+    });
+    for n in 0..3500usize {
+        log!(every: 1000, "processed {} items", n);
+        debug!(every: 0, "every call {n}");
+        error!(once: "first error {}", n; n = n);
+        warn!(once: emphasis: true, "emphasized once");
+    }
+    let text = buffer.text();
+    assert_eq!(text.matches("fallback path taken").count(), 1);
+    let processed: Vec<&str> = text.lines().filter(|line| line.contains("processed")).collect();
+    assert_eq!(processed.len(), 4);
+    assert!(processed[3].contains("processed 3000 items"));
+    assert_eq!(text.matches("every call").count(), 3500);
+    assert_eq!(text.matches("first error").count(), 1);
+    assert_eq!(text.matches("emphasized once").count(), 1);
+    assert!(text.contains("first error 0 n=0"));
+    level::set_level(Level::Warn);
+    buffer.clear();
+    let limit = 3usize;
+    for _ in 0..10 { log!(once: "hidden"); log!(every: limit, "hidden too"); }
+    level::set_level(Level::Debug);
+    log!(once: "hidden");
+    assert_eq!(buffer.text().matches("hidden").count(), 1, "disabled calls do not use up `once`");
+
+    // Lines after the first of a message start under the message column.
+    buffer.clear();
+    let endpoint = Endpoint { host: "db", ports: vec![5432] };
+    debug!("endpoint {:#?}", endpoint);
+    log!("trailing newlines are dropped\n\n");
+    console::set_multiline(console::Multiline::Gutter);
+    assert_eq!(console::multiline(), console::Multiline::Gutter);
+    group_begin!("nested");
+    warn!("first\r\n\nthird");
+    group_end!();
+    console::set_multiline(console::Multiline::Align);
+    assert_eq!(buffer.text(), concat!(
+        " DBG  [TS] rust_logger_downstream: endpoint Endpoint { \n",
+        "                                       host: \"db\", \n",
+        "                                       ports: [ \n",
+        "                                           5432, \n",
+        "                                       ], \n",
+        "                                   } \n",
+        " LOG  [TS] rust_logger_downstream: trailing newlines are dropped \n",
+        " LOG  [TS] rust_logger_downstream: nested \n",
+        " WRN  [TS] rust_logger_downstream:   first \n",
+        "                                     |  \n",
+        "                                     | third \n",
+    ));
+    // Value limits and per-type formatters.
+    buffer.clear();
+    format::set_field_value_limit(4);
+    format::register_field_formatter::<Duration>(|d, out| write!(out, "{}ms", d.as_millis()), |d| format::JsonValue::Integer(d.as_millis() as i64));
+    log!("limited"; token = "abcdefgh", took = %Duration::from_millis(25));
+    format::set_field_value_limit(0);
+    assert_eq!(buffer.text(), " LOG  [TS] rust_logger_downstream: limited token=abcd...(+4 chars) took=25ms \n");
+
+    // Context fields, merged under a duplicate policy.
+    buffer.clear();
+    rust_logger::context::set_global_field("service", "downstream");
+    let request = rust_logger::context::push_field("request", 7);
+    rust_logger::context::set_duplicate_field_policy(rust_logger::context::DuplicateFieldPolicy::KeepBoth);
+    log!("context"; request = 8);
+    rust_logger::context::set_duplicate_field_policy(rust_logger::context::DuplicateFieldPolicy::LastWins);
+    drop(request);
+    rust_logger::context::clear_global_fields();
+    assert_eq!(buffer.text(), " LOG  [TS] rust_logger_downstream: context service=downstream request=7 request#2=8 \n");
+
+    // Renamed and reordered standard keys.
+    format::set_field_names(format::FieldNames::default().rename(format::StandardKey::Level, "severity").order(&[format::StandardKey::Message]));
+    let mut renamed = String::new();
+    format::LogfmtFormatter.format(&Record::new(Level::Warn, "hot"), &mut renamed).unwrap();
+    format::set_field_names(format::FieldNames::default());
+    assert_eq!(renamed, "msg=hot severity=warn");
+
+    // OpenTelemetry severities and attributes.
+    format::set_json_flavor(format::JsonFlavor::Otel);
+    let mut otel = String::new();
+    let fields = [format::Field::display("port", &8080)];
+    format::JsonFormatter.format(&Record { fields: &fields, ..Record::new(Level::Error, "down") }, &mut otel).unwrap();
+    format::set_json_flavor(format::JsonFlavor::Default);
+    assert_eq!(otel, r#"{"severity_number":17,"severity_text":"ERROR","message":"down","attributes":{"port":"8080"}}"#);
+
+    // Fields in a column of their own, or under the message.
+    buffer.clear();
+    console::set_fields_layout(console::FieldsLayout::AlignedColumn);
+    console::set_fields_column(48);
+    log!("aligned"; port = 8080);
+    console::set_fields_layout(console::FieldsLayout::NextLine);
+    log!("under"; port = 8080);
+    console::set_fields_layout(console::FieldsLayout::Inline);
+    console::set_fields_column(60);
+    assert_eq!(console::fields_layout(), console::FieldsLayout::Inline);
+    assert_eq!(buffer.text(), concat!(
+        " LOG  [TS] rust_logger_downstream: aligned      port=8080 \n",
+        " LOG  [TS] rust_logger_downstream: under \n",
+        "                                   port=8080 \n",
+    ));
+    // Markup open at a line break is re-opened on the next line.
+    let record = Record { target: Some("app"), ..Record::new(Level::Info, "a <red>b\nc</> d") };
+    let mut pretty = String::new();
+    PrettyFormatter::new(Theme::default()).timestamps(false).format(&record, &mut pretty).unwrap();
+    let (first, second) = pretty.split_once('\n').unwrap();
+    let red = &first[first.find("a ").unwrap() + 2..first.find('b').unwrap()];
+    assert!(red.contains("31"), "{pretty:?}");
+    assert!(second.starts_with("           ") && second.contains(&format!("{red}c")), "{pretty:?}");
+    let mut plain = String::new();
+    PrettyFormatter::new(Theme::default()).colors(false).timestamps(false).format(&record, &mut plain).unwrap();
+    assert_eq!(plain, " LOG  app: a b \n           c d ");
+    let plain_lines = Collect::default();
+    let plain_sink = sink::add_sink(Box::new(plain_lines.clone()), Level::Debug, Format::Pretty);
+    console::set_multiline(console::Multiline::Gutter);
+    warn!("two\n<bold>lines</>\n");
+    console::set_multiline(console::Multiline::Align);
+    error!(err = Layered("refused", None), "call\nfailed");
+    sink::remove_sink(plain_sink);
+    assert_eq!(*plain_lines.0.lock().unwrap(), [
+        "[WRN] [TS] rust_logger_downstream: two\n                                   | lines",
+        "[ERR] [TS] rust_logger_downstream: call\n                                   failed: refused",
+    ]);
+
+    // group! returns a guard; the depth is per thread and the indent goes before the message.
+    buffer.clear();
+    fn resolve(name: &str, children: &[&str]) {
+        let _group = rust_logger::group!("resolving {}", name);
+        for child in children { resolve(child, &[]); }
+        log!("resolved {}", name);
+    }
+    resolve("app", &["serde", "log"]);
+    assert_eq!(rust_logger::group::current_depth(), 0);
+    rust_logger::group::set_group_indent(rust_logger::group::GroupIndent::Dots);
+    {
+        let _outer = rust_logger::group!(level: Level::Debug, "outer");
+        std::thread::spawn(|| {
+            let _own = rust_logger::group!("other thread");
+            warn!("one level");
+        }).join().unwrap();
+        let _inner = rust_logger::group!("inner");
+        error!("two levels");
+    }
+    rust_logger::group::set_group_indent(rust_logger::group::GroupIndent::Spaces);
+    assert_eq!(buffer.text(), concat!(
+        " LOG  [TS] rust_logger_downstream: resolving app \n",
+        " LOG  [TS] rust_logger_downstream:   resolving serde \n",
+        " LOG  [TS] rust_logger_downstream:     resolved serde \n",
+        " LOG  [TS] rust_logger_downstream:   resolving log \n",
+        " LOG  [TS] rust_logger_downstream:     resolved log \n",
+        " LOG  [TS] rust_logger_downstream:   resolved app \n",
+        " DBG  [TS] rust_logger_downstream: outer \n",
+        " LOG  [TS] rust_logger_downstream: other thread \n",
+        " WRN  [TS] rust_logger_downstream: . one level \n",
+        " LOG  [TS] rust_logger_downstream: . inner \n",
+        " ERR  [TS] rust_logger_downstream: . . two levels \n",
+    ));
+
+    // progress! redraws its line in place; records are printed above it.
+    buffer.clear();
+    let progress = rust_logger::progress!(4, "processing");
+    progress.set(1);
+    log!("between");
+    progress.set(9);
+    progress.finish_with("done");
+    assert_eq!(buffer.text(), concat!(
+        "\r\x1b[2Kprocessing 0/4 (0%)",
+        "\r\x1b[2Kprocessing 1/4 (25%)",
+        "\r\x1b[2K LOG  [TS] rust_logger_downstream: between \nprocessing 1/4 (25%)",
+        "\r\x1b[2Kprocessing 4/4 (100%)",
+        "\r\x1b[2K",
+        " LOG  [TS] rust_logger_downstream: processing 4/4 (100%) done \n",
+    ));
+    buffer.clear();
+    drop(rust_logger::progress::Progress::new(0));
+    log!("after");
+    assert_eq!(buffer.text(), "\r\x1b[2K0/0 (100%)\r\x1b[2K LOG  [TS] rust_logger_downstream: after \n");
+    // The files and sinks get throttled progress records instead of the line.
+    let progress_lines = Collect::default();
+    let progress_sink = sink::add_sink(Box::new(progress_lines.clone()), Level::Debug, Format::Pretty);
+    drop(rust_logger::progress!(3, "syncing").record_interval(Duration::from_secs(60)));
+    sink::remove_sink(progress_sink);
+    assert_eq!(*progress_lines.0.lock().unwrap(), ["[DBG] [TS] rust_logger_downstream: [progress] syncing 0/3 (0%)"]);
+    // Without a terminal (here: JSON output) it degrades to plain records.
+    buffer.clear();
+    format::set_format(Format::Json);
+    let progress = rust_logger::progress::Progress::new(10).plain_interval(Duration::ZERO);
+    progress.set(5);
+    progress.set(5);
+    progress.finish_with("done");
+    format::set_format(Format::Pretty);
+    let messages: Vec<String> = buffer.text().lines().map(|line| line.split(r#""message":"#).nth(1).unwrap().to_string()).collect();
+    assert_eq!(messages, [r#""0/10 (0%)"}"#, r#""5/10 (50%)"}"#, r#""5/10 (50%) done"}"#]);
+    assert!(buffer.text().contains(r#""target":"progress""#));
+    level::set_level(Level::Warn);
+    buffer.clear();
+    let progress = rust_logger::progress!(2, "quiet");
+    progress.set(2);
+    progress.finish_with("done");
+    assert!(buffer.text().is_empty());
+    level::set_level(Level::Debug);
+
+    // separator! and section! fill the width: 80 columns for a custom writer.
+    buffer.clear();
+    assert_eq!((console::terminal_width(), console::refresh_terminal_width()), (80, 80));
+    separator!();
+    section!("Phase {}: migration", 2);
+    let rule_lines = Collect::default();
+    let rule_sink = sink::add_sink(Box::new(rule_lines.clone()), Level::Info, Format::Pretty);
+    group_begin!("indented");
+    section!("<red>{}</>", "x".repeat(90));
+    group_end!();
+    sink::remove_sink(rule_sink);
+    level::set_level(Level::Warn);
+    separator!();
+    section!("quiet");
+    level::set_level(Level::Debug);
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4, "{text}");
+    assert_eq!(lines[0], format!(" LOG  [TS] rust_logger_downstream: {} ", "-".repeat(44)));
+    assert_eq!(lines[1], format!(" LOG  [TS] rust_logger_downstream: {} Phase 2: migration {} ", "-".repeat(12), "-".repeat(12)));
+    assert!(lines.iter().take(2).all(|line| line.chars().count() == 80));
+    assert_eq!(lines[3], format!(" LOG  [TS] rust_logger_downstream:   - {} - ", "x".repeat(90)));
+    let rule_lines = rule_lines.0.lock().unwrap();
+    assert_eq!(rule_lines.last().unwrap(), &format!("[LOG] [TS] rust_logger_downstream:   - {} -", "x".repeat(90)));
+
+    // hexdump! from another crate; tests/hexdump.rs covers the layout.
+    buffer.clear();
+    hexdump!(max: 2, "frame", vec![1u8, 2, 3]);
+    assert_eq!(buffer.text().lines().last(), Some(" DBG  [TS] rust_logger_downstream: (+1 more bytes) "));
+
+    // dump! logs each expression with its pretty Debug value and returns it.
+    buffer.clear();
+    let endpoint = Endpoint { host: "db", ports: vec![5432] };
+    let ports = dump!(endpoint).ports;
+    let doubled = dump!(level: Warn, ports.len() * 2);
+    let (a, b) = dump!("a", 2u8);
+    assert_eq!((ports.len(), doubled, a, b), (1, 2, "a", 2));
+    level::set_level(Level::Info);
+    assert_eq!(dump!(String::from("quiet")), "quiet");
+    level::set_level(Level::Debug);
+    assert_eq!(buffer.text(), concat!(
+        " DBG  [TS] rust_logger_downstream: endpoint = Endpoint { \n",
+        "                                       host: \"db\", \n",
+        "                                       ports: [ \n",
+        "                                           5432, \n",
+        "                                       ], \n",
+        "                                   } \n",
+        " WRN  [TS] rust_logger_downstream: ports.len() * 2 = 2 \n",
+        " DBG  [TS] rust_logger_downstream: \"a\" = \"a\" \n",
+        " DBG  [TS] rust_logger_downstream: 2u8 = 2 \n",
+    ));
+
+    // `err =` prints the error and its source chain under the message column.
+    buffer.clear();
+    let chain = Layered("pool exhausted", Some(Box::new(Layered("connect to db:5432", Some(Box::new(Layered("connection refused", None)))))));
+    error!(err = chain, "db connection failed for {}", "orders");
+    group_begin!("sync");
+    error!(err = Layered("timed out", None), "retry failed");
+    group_end!();
+    error!(err = Layered("<red>not markup</>", None), "tags");
+    assert_eq!(buffer.text(), concat!(
+        " ERR  [TS] rust_logger_downstream: db connection failed for orders: pool exhausted \n",
+        "                                   caused by: connect to db:5432 \n",
+        "                                   caused by: connection refused \n",
+        " LOG  [TS] rust_logger_downstream: sync \n",
+        " ERR  [TS] rust_logger_downstream:   retry failed: timed out \n",
+        " ERR  [TS] rust_logger_downstream: tags: <red>not markup</> \n",
+    ));
+    let causes = ["pool exhausted", "connection refused"];
+    let record = Record { target: Some("db"), causes: &causes, ..Record::new(Level::Error, "query failed") };
+    let mut pretty = String::new();
+    PrettyFormatter::new(Theme::default()).colors(false).timestamps(false).format(&record, &mut pretty).unwrap();
+    assert_eq!(pretty, " ERR  db: query failed \n          caused by: pool exhausted \n          caused by: connection refused ");
+    let mut json = String::new();
+    JsonFormatter.format(&record, &mut json).unwrap();
+    assert_eq!(json, r#"{"level":"error","target":"db","message":"query failed","causes":["pool exhausted","connection refused"]}"#);
+    level::set_level(Level::None);
+    error!(err = Layered("unseen", None), "filtered out");
+    level::set_level(Level::Debug);
+    error!("after {}", "chain");
+    assert!(buffer.text().ends_with(" ERR  [TS] rust_logger_downstream: after chain \n"));
+
+    // time_scope! and time! log at Debug when the guard drops; nested scopes are independent.
+    buffer.clear();
+    {
+        let _outer = time_scope!("load config from {}", "app.toml");
+        let parsed: u32 = rust_logger::time!("parse", "42".parse().unwrap());
+        assert_eq!(parsed, 42);
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2, "{text}");
+    assert!(lines[0].starts_with(" DBG  [TS] rust_logger_downstream: parse \u{2014} done in ") && lines[0].ends_with("\u{b5}s "), "{text}");
+    assert!(lines[1].starts_with(" DBG  [TS] rust_logger_downstream: load config from app.toml \u{2014} done in ") && lines[1].ends_with("ms "), "{text}");
+    level::set_level(Level::Info);
+    buffer.clear();
+    drop(time_scope!("quiet"));
+    assert_eq!(rust_logger::time!("quiet", 7), 7);
+    assert!(buffer.text().is_empty());
+    level::set_level(Level::Debug);
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let scope = std::panic::catch_unwind(|| {
+        let _scope = time_scope!("unwound");
+        panic!("interrupted");
+    });
+    std::panic::set_hook(hook);
+    assert!(scope.is_err() && !buffer.text().contains("unwound"), "a scope left by a panic is not reported");
+
+    // `throttle:` lets one record through per window and counts the rest on the next one.
+    buffer.clear();
+    let throttled = |len: usize| warn!(throttle: Duration::from_millis(200), "queue is backed up: {} items", len; len = len);
+    for len in 0..50 { throttled(len); }
+    std::thread::sleep(Duration::from_millis(250));
+    throttled(50);
+    throttled(51);
+    std::thread::sleep(Duration::from_millis(250));
+    throttled(52);
+    let lines: Vec<String> = buffer.text().lines().map(str::to_string).collect();
+    assert_eq!(lines, [
+        " WRN  [TS] rust_logger_downstream: queue is backed up: 0 items len=0 ",
+        " WRN  [TS] rust_logger_downstream: queue is backed up: 50 items (suppressed 49 similar messages) len=50 ",
+        " WRN  [TS] rust_logger_downstream: queue is backed up: 52 items (suppressed 1 similar message) len=52 ",
+    ]);
+    let record = Record { suppressed: 342, ..Record::new(Level::Warn, "queue is backed up") };
+    let mut json = String::new();
+    JsonFormatter.format(&record, &mut json).unwrap();
+    assert_eq!(json, r#"{"level":"warn","message":"queue is backed up","suppressed":342}"#);
+    level::set_level(Level::Error);
+    for len in 0..10 { throttled(len); }
+    level::set_level(Level::Debug);
+    buffer.clear();
+    std::thread::sleep(Duration::from_millis(250));
+    throttled(60);
+    assert!(buffer.text().ends_with("backed up: 60 items len=60 \n"), "disabled calls are not counted");
+
+    // 8 threads x 1000 lines: every captured line is whole.
+    buffer.clear();
+    let threads: Vec<_> = (0..8)
+        .map(|t| std::thread::spawn(move || for i in 0..1000 { warn!("<bold>stress</> thread={t} line={i}"); }))
+        .collect();
+    for thread in threads { thread.join().unwrap(); }
+    let text = buffer.text();
+    let mut seen = 0;
+    for line in text.lines() {
+        let Some(rest) = line.strip_prefix(" WRN  [TS] rust_logger_downstream: stress thread=") else { panic!("torn line: {line:?}") };
+        let (t, i) = rest.strip_suffix(' ').and_then(|rest| rest.split_once(" line=")).expect("whole line");
+        assert!(t.parse::<u32>().unwrap() < 8 && i.parse::<u32>().unwrap() < 1000);
+        seen += 1;
+    }
+    assert_eq!(seen, 8000);
+
+    // Thread names, and stable numbers for unnamed threads.
+    buffer.clear();
+    console::show_thread(true);
+    std::thread::Builder::new().name(String::from("worker-a")).spawn(|| log!("from a")).unwrap().join().unwrap();
+    std::thread::spawn(|| {
+        log!("unnamed first");
+        log!("unnamed again");
+    })
+    .join()
+    .unwrap();
+    log!("from main");
+    console::show_thread(false);
+    let text = buffer.text();
+    assert!(text.contains(" LOG  [worker-a] [TS] rust_logger_downstream: from a "), "{text}");
+    assert!(text.contains(" LOG  [main] [TS] rust_logger_downstream: from main "));
+    let numbered: Vec<&str> = text.lines().filter(|line| line.contains("unnamed")).map(|line| &line[6..line.find("] ").unwrap()]).collect();
+    assert!(numbered.len() == 2 && numbered[0] == numbered[1] && numbered[0].starts_with("[#"), "{numbered:?}");
+    let parsed = rust_logger::replay::parse_pretty_line("[WRN] [worker-a] [2024.05.01 12:00:00.000] app: late").unwrap();
+    assert_eq!((parsed.thread.as_deref(), parsed.process), (Some("worker-a"), None));
+
+    assert!(ColorEnv::current().writer_set);
+
+    // A panicking Display impl: propagates by default, reported when caught.
+    struct Boom;
+    impl std::fmt::Display for Boom {
+        fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result { panic!("boom in Display") }
+    }
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    buffer.clear();
+    assert!(std::panic::catch_unwind(|| error!("value {}", Boom)).is_err());
+    console::catch_format_panics(true);
+    error!("value {}", Boom);
+    audit!("value {}", Boom);
+    console::catch_format_panics(false);
+    std::panic::set_hook(hook);
+    let text = buffer.text();
+    assert!(!text.contains("value"));
+    assert!(text.contains(&format!("[logger]: panic while formatting log message at {}:", file!())), "{text}");
+    assert!(text.contains(": boom in Display "));
+    assert!(config_report().contains("catch_format_panics = false"));
+
+    // Offsets across the day boundary, in both directions.
+    let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+    let minus_11 = TimeZoneMode::FixedOffset(-11 * 3600);
+    let plus_14 = TimeZoneMode::FixedOffset(14 * 3600);
+    assert_eq!(time::format_timestamp(at(1_709_269_200), minus_11, &TimestampFormat::Standard), "2024.02.29 18:00:00.000-11:00");
+    assert_eq!(time::format_timestamp(at(1_735_641_000), plus_14, &TimestampFormat::Standard), "2025.01.01 00:30:00.000+14:00");
+    assert_eq!(time::format_timestamp(at(0), minus_11, &TimestampFormat::Rfc3339), "1969-12-31T13:00:00.000-11:00");
+    assert_eq!(time::format_timestamp(at(1_735_689_599), plus_14, &TimestampFormat::Standard), "2025.01.01 13:59:59.000+14:00");
+    assert_eq!(time::format_timestamp(at(1_709_269_200), TimeZoneMode::Utc, &TimestampFormat::Standard), "2024.03.01 05:00:00.000Z");
+    assert_eq!(time::format_timestamp(at(1_709_269_200), TimeZoneMode::FixedOffset(5 * 3600 + 1800), &TimestampFormat::Rfc3339), "2024-03-01T10:30:00.000+05:30");
+    let _ = time::format_timestamp(SystemTime::now(), TimeZoneMode::Local, &TimestampFormat::Standard);
+    time::set_timezone(minus_11);
+    assert_eq!(time::timezone(), minus_11);
+    assert!(config_report().contains("FixedOffset(-39600)"));
+    time::set_timezone(TimeZoneMode::Utc);
+    for stamp in ["2024.05.01 12:00:00.000Z", "2024.05.01 01:00:00.000-11:00", "2024.05.02 02:00:00.000+14:00"] {
+        let parsed = rust_logger::replay::parse_pretty_line(&format!(" WRN  [{stamp}] app: disk full ")).unwrap();
+        assert_eq!((parsed.timestamp.as_str(), parsed.message.as_str()), (stamp, "disk full"));
+    }
+    assert!(rust_logger::replay::parse_pretty_line(" WRN  [2024.05.01 12:00:00.000+1100] app: disk full ").is_none());
+
+    // Timestamp formats: built-in, patterns with microseconds, and none at all.
+    let noon = at(1_714_564_800) + Duration::from_micros(123_456);
+    let utc = TimeZoneMode::Utc;
+    let formats = [
+        ("standard", "2024.05.01 12:00:00.123Z"),
+        ("rfc3339", "2024-05-01T12:00:00.123Z"),
+        ("compact", "20240501T120000.123Z"),
+        ("time-only", "12:00:00.123Z"),
+        ("unix-millis", "1714564800123"),
+        ("none", ""),
+        ("%Y-%m-%d %H:%M:%S.%6f", "2024-05-01 12:00:00.123456"),
+        ("%H%M%S.%3f %z %%", "120000.123 Z %"),
+    ];
+    for (spec, expected) in formats {
+        assert_eq!(time::format_timestamp(noon, utc, &TimestampFormat::parse(spec).unwrap()), expected, "{spec}");
+    }
+    assert_eq!(time::format_timestamp(noon, plus_14, &TimestampFormat::parse("%d %H:%M%z").unwrap()), "02 02:00+14:00");
+    for bad in ["iso", "%Y %q", "%4f", "%"] {
+        assert!(matches!(TimestampFormat::parse(bad), Err(ConfigError::BadTimestampFormat(_))), "{bad}");
+    }
+
+    level::set_level(Level::Debug);
+    buffer.clear();
+    time::set_timestamp_format(TimestampFormat::None);
+    log!("no timestamp");
+    time::set_timestamp_format(TimestampFormat::parse("%H:%M:%S.%6f").unwrap());
+    log!("stable");
+    assert_eq!(buffer.text(), " LOG  rust_logger_downstream: no timestamp \n LOG  [TS] rust_logger_downstream: stable \n");
+    let parsed = rust_logger::replay::parse_pretty_line(" WRN  [12:00:00.123456] app: late ").unwrap();
+    assert_eq!((parsed.timestamp.as_str(), parsed.message.as_str()), ("12:00:00.123456", "late"));
+    assert!(rust_logger::replay::parse_pretty_line(" WRN  [2024.05.01 12:00:00.000] app: late ").is_some());
+    assert!(matches!(time::timestamp_format(), TimestampFormat::Custom(_)));
+    time::set_timestamp_format(TimestampFormat::Standard);
+
+    // Uptime: zeroed by `reset_uptime`, right-aligned so columns line up.
+    time::reset_uptime();
+    std::thread::sleep(Duration::from_millis(20));
+    let millis = time::format_timestamp(noon, utc, &TimestampFormat::UptimeMillis);
+    let micros = time::format_timestamp(noon, plus_14, &TimestampFormat::parse("uptime-micros").unwrap());
+    assert!(millis.starts_with("    0.") && millis.ends_with('s') && millis.len() == 10, "{millis:?}");
+    assert!(micros.starts_with("    0.") && micros.ends_with('s') && micros.len() == 13, "{micros:?}");
+    assert!(millis.as_str() >= "    0.020s");
+    time::reset_uptime();
+    assert!(time::format_timestamp(noon, utc, &TimestampFormat::UptimeMillis).as_str() < "    0.020s");
+    time::set_timestamp_format(TimestampFormat::UptimeMicros);
+    let parsed = rust_logger::replay::parse_pretty_line(" WRN  [   12.043721s] app: slow ").unwrap();
+    assert_eq!((parsed.timestamp.as_str(), parsed.message.as_str()), ("   12.043721s", "slow"));
+    assert!(rust_logger::replay::parse_pretty_line(" WRN  [   12.043s] app: slow ").is_none());
+    time::set_timestamp_format(TimestampFormat::Standard);
+
+    // Levels and configuration errors.
+    level::set_level(Level::Warn);
+    assert_eq!(level::level(), Level::Warn);
+    assert!(!level::is_enabled(Level::Info));
+    assert!(matches!(level::try_parse_level("loud"), Err(ConfigError::UnknownLevel(_))));
+    assert_eq!(level::parse_level("error"), Level::Error);
+    level::set_level_by_name("debug").unwrap();
+    level::set_level_by_name_or_default("debug");
+    level::set_audit_bypasses_level(true);
+    let _ = level::init_from_env();
+    let _: Option<level::MuteGuard> = None;
+
+    // Themes.
+    let style = LevelStyle::try_new("OK", "42", "30", "32", "32").unwrap();
+    assert!(LevelStyle::try_new("OK", "x", "30", "32", "32").is_err());
+    let _ = LevelStyle::new("OK", "42", "30", "32", "32").as_style();
+    assert_eq!(style.label, "OK");
+    assert!(Theme::parse("nope").is_err());
+    assert_eq!(Theme::by_name("colorblind").unwrap().preset_name(), "colorblind");
+    theme::set_theme(Theme::high_contrast());
+    theme::set_theme_by_name("default").unwrap();
+    theme::set_theme_by_name_or_default("default");
+    let _ = theme::hash_color("worker");
+    theme::set_theme_schedule(Theme::default(), Theme::colorblind(), (TimeOfDay::new(7, 0), TimeOfDay::new(19, 0)));
+    theme::toggle_theme();
+    theme::set_theme(theme::theme());
+
+    // One level restyled with named, RGB and palette colors; None has no style.
+    use theme::Color;
+    let fallback = theme::theme().alert;
+    // The scheduled theme depends on the time of day.
+    let ts_color = theme::theme().warn.ts_color;
+    let warn_style = theme::theme().warn.label("WARN").bg(Color::Rgb(230, 159, 0)).fg(Color::Black).msg_color(Color::Indexed(214));
+    theme::set_level_style(Level::Warn, warn_style).unwrap();
+    let restyled = theme::theme().warn;
+    assert_eq!((restyled.bg.as_str(), restyled.fg.as_str(), restyled.msg_color.as_str()), ("48;2;230;159;0", "30", "38;5;214"));
+    assert_eq!(restyled.ts_color, ts_color);
+    buffer.clear();
+    level::set_level(Level::Debug);
+    warn!("restyled");
+    assert_eq!(buffer.text(), " WARN  [TS] rust_logger_downstream: restyled \n");
+    assert!(matches!(theme::set_level_style(Level::None, Theme::default().warn), Err(ConfigError::StyleForNone)));
+    assert_eq!(Color::parse("Orange").unwrap(), Color::Yellow);
+    assert_eq!(Color::parse("#0072b2").unwrap(), Color::Rgb(0, 0x72, 0xb2));
+    assert_eq!(Color::parse("c214").unwrap().sgr(true), "48;5;214");
+    assert!(Color::parse("#12345").is_err() && Color::parse("teal").is_err());
+    theme::reset_level_styles();
+    assert_eq!(theme::theme(), Theme { alert: fallback, ..Theme::default() });
+
+    // Formatters.
+    let record = Record { target: Some("app"), ..Record::new(Level::Warn, "disk <red>full</>") };
+    let mut pretty = String::new();
+    PrettyFormatter::new(Theme::default()).colors(false).timestamps(false).format(&record, &mut pretty).unwrap();
+    assert_eq!(pretty, " WRN  app: disk full ");
+    let mut json = String::new();
+    JsonFormatter.format(&record, &mut json).unwrap();
+    assert_eq!(json, r#"{"level":"warn","target":"app","message":"disk full"}"#);
+    let mut logfmt = String::new();
+    LogfmtFormatter.format(&record, &mut logfmt).unwrap();
+    assert_eq!(logfmt, r#"level=warn target=app msg="disk full""#);
+    assert_eq!(Format::parse("plain").unwrap(), Format::Plain);
+    assert!(matches!(Format::parse("yaml"), Err(ConfigError::UnknownFormat(_))));
+    format::set_format(Format::Pretty);
+    assert_eq!(format::output_format(), Format::Pretty);
+
+    // Console.
+    console::set_color_choice(console::color_choice());
+    console::set_location_mode(console::location_mode());
+    console::set_target_width(console::target_width());
+    console::set_console_queue(64).unwrap();
+    assert_eq!(console::console_queue(), 64);
+    console::set_queue_overflow(console::QueueOverflow::Spill { max_bytes: 1 << 20 }).unwrap();
+    assert_eq!(console::queue_overflow(), console::QueueOverflow::Spill { max_bytes: 1 << 20 });
+    console::flush_console_queue();
+    console::set_queue_overflow(console::QueueOverflow::Block).unwrap();
+    let _ = (console::spilled_records(), rust_logger::stats::queue_dropped_records());
+    console::set_console_queue(0).unwrap();
+    console::set_wrap(console::Wrap::Columns(120));
+    assert_eq!(console::wrap(), console::Wrap::Columns(120));
+    console::set_wrap(console::Wrap::Off);
+    format::set_message_limit(format::message_limit());
+    console::set_event_counter(console::EventCounter::PerCallsite);
+    assert_eq!(console::event_counter(), console::EventCounter::PerCallsite);
+    console::set_event_counter(console::EventCounter::Off);
+    console::reset_counters();
+    console::set_target_truncation(console::TargetTruncation::LastSegments);
+    assert_eq!(console::target_truncation(), console::TargetTruncation::LastSegments);
+    console::set_target_truncation(console::TargetTruncation::Middle);
+    rust_logger::theme::set_target_color("rust_logger_downstream::db", "cyan").unwrap();
+    assert_eq!(rust_logger::theme::target_color("rust_logger_downstream::db::pool"), Some(rust_logger::theme::Color::Cyan));
+    rust_logger::theme::clear_target_colors();
+    rust_logger::theme::set_target_colors(false);
+    console::set_thread_colors(false);
+    console::set_bell(BellMode::Off);
+    console::set_bell_style(BellStyle::Visual);
+    console::set_bell_cooldown(Duration::from_secs(1));
+    console::set_process_tag("");
+    assert_eq!(console::process_tag(), None);
+    console::set_max_records_per_second(0);
+    console::exempt_errors(false);
+    console::set_self_log(SelfLog::Silent);
+    let _ = console::dropped_records() + console::throttled_records();
+    console::clear_external_draw_guard();
+    let _: fn(bool) -> std::io::Result<()> = console::set_console_nonblocking;
+    let _: fn(Arc<dyn DrawCoordinator>) = console::set_external_draw_guard;
+
+    // Stats, display, render.
+    stats::set_suppressed_accounting(false);
+    stats::set_suppressed_summary_interval(None);
+    let _ = stats::suppressed_summary(5);
+    let _ = stats::dropped_records() + stats::throttled_records();
+    display::set_charset_by_name("ascii").unwrap();
+    display::set_charset_by_name_or_default("ascii");
+    display::set_charset(Charset::parse("auto").unwrap());
+    let _: &Glyphs = display::glyphs();
+    let _ = render::default_style(Level::Info);
+    let _ = OUTPUT_FORMAT_VERSION;
+
+    // Replay.
+    let parsed = rust_logger::replay::parse_pretty_line(" WRN  [2024.05.01 12:00:00.000] app: disk full ").unwrap();
+    let _: rust_logger::replay::ParsedRecord = parsed.clone();
+    assert_eq!((parsed.level, parsed.target.as_deref(), parsed.message.as_str()), (Level::Warn, Some("app"), "disk full"));
+
+    // File sink: plain text, rotated at 64 bytes keeping two old files.
+    let dir = std::env::temp_dir().join(format!("rust_logger-downstream-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.log");
+    let sink = file::add_file_sink(&path, RotationPolicy::Size { max_bytes: 64, keep: 2 }).unwrap();
+    for i in 0..6 { log!("file record {i}"); }
+    file::flush();
+    assert!(config_report().contains("app.log"));
+    drop(sink);
+    let current = std::fs::read_to_string(&path).unwrap();
+    assert!(current.contains("file record 5") && !current.contains('\x1b'));
+    assert!(std::fs::read_to_string(dir.join("app.log.2")).is_ok());
+    assert!(!dir.join("app.log.3").exists());
+    let path = dir.join("quiet.log");
+    let sink: file::FileSinkGuard = file::file_sink(&path).rotation(RotationPolicy::Never).session_header(false).open().unwrap();
+    log!("no header");
+    drop(sink);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "[LOG] [TS] rust_logger_downstream: no header\n");
+    let path = dir.join("synced.log");
+    let sink = file::file_sink(&path).session_header(false).sync_policy(SyncPolicy::OnError).open().unwrap();
+    error!("synced");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "[ERR] [TS] rust_logger_downstream: synced\n");
+    drop(sink);
+    let path = dir.join("reopened.log");
+    let sink = file::file_sink(&path).session_header(false).reopen_check(Duration::ZERO).open().unwrap();
+    log!("before");
+    std::fs::rename(&path, dir.join("reopened.log.1")).unwrap();
+    log!("after");
+    file::reopen().unwrap();
+    drop(sink);
+    assert_eq!(std::fs::read_to_string(dir.join("reopened.log.1")).unwrap(), "[LOG] [TS] rust_logger_downstream: before\n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "[LOG] [TS] rust_logger_downstream: after\n");
+    let path = dir.join("errors.log");
+    let sink = file::file_sink(&path).session_header(false).level(Level::Error).open().unwrap();
+    log!("not in errors.log");
+    error!("in errors.log");
+    drop(sink);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "[ERR] [TS] rust_logger_downstream: in errors.log\n");
+    assert_eq!(file::file_level(), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Prefix templates from another crate; tests/prefix_template.rs pins the bytes.
+    format::set_prefix_template("{ts} {level} {msg}").unwrap();
+    buffer.clear();
+    log!("ready");
+    assert_eq!(buffer.text(), "[TS]  INFO   ready \n");
+    assert!(format::set_prefix_template("{lvl} {msg}").is_err());
+    format::set_prefix_template(format::DEFAULT_PREFIX_TEMPLATE).unwrap();
+
+    assert!(config_report().contains("location = Module"));
+    set_stable_test_format(false);
+    let _: Option<rust_logger::heartbeat::Heartbeat> = None;
+    let _ = console::reset_writer();
+    console::shutdown();
+    let _ = console::shutdown_guard();
+}
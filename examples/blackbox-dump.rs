@@ -0,0 +1,30 @@
+//! Prints the records in a black box file written by
+//! `file::start_black_box`, oldest first, e.g. after a crash:
+//!
+//! ```bash
+//! cargo run --example blackbox-dump -- /var/tmp/my_app.blackbox
+//! ```
+
+#[cfg(unix)]
+fn main() {
+    let Some(path) = std::env::args_os().nth(1) else {
+        eprintln!("usage: blackbox-dump <file>");
+        std::process::exit(2);
+    };
+
+    match rust_logger::file::read_black_box(&path) {
+        Ok(records) => {
+            for record in records { println!("{record}"); }
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("black box files are only written on unix");
+    std::process::exit(1);
+}
@@ -0,0 +1,25 @@
+//! Drives the `no_std` formatting core directly, the way firmware would over a
+//! UART, here with a `Vec<u8>` standing in for the device.
+
+use rust_logger::render::{self, ByteSink, ByteWriter, Level, NoAliases};
+
+struct Uart(Vec<u8>);
+
+impl ByteSink for Uart {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+fn main() {
+    let mut out = ByteWriter(Uart(Vec::new()));
+    let uptime = |w: &mut dyn std::fmt::Write| write!(w, "{:>8}ms", 1234);
+
+    for level in [Level::Info, Level::Warn] {
+        let style = render::default_style(level).unwrap();
+        render::write_line(&mut out, &style, Some(&uptime), "sensor <bold>ready</>", &NoAliases).unwrap();
+        out.0.write_bytes(b"\r\n");
+    }
+
+    print!("{}", String::from_utf8_lossy(&out.0.0));
+}
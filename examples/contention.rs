@@ -0,0 +1,47 @@
+//! Times 16 threads logging at once to a file as the console, with the
+//! console written by each thread and through `set_console_queue`:
+//! `cargo run --release --example contention -- /path/on/a/disk`.
+
+use std::fs::File;
+use std::time::{Duration, Instant};
+
+use rust_logger::console;
+
+const THREADS: u32 = 16;
+const RECORDS: u32 = 20_000;
+
+fn run() -> Duration {
+    let started = Instant::now();
+    let threads: Vec<_> = (0..THREADS)
+        .map(|thread| {
+            std::thread::spawn(move || {
+                for i in 0..RECORDS {
+                    rust_logger::log!("thread {thread} record {i} of {RECORDS}"; attempt = i % 7);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    console::flush_console_queue();
+    started.elapsed()
+}
+
+fn main() -> std::io::Result<()> {
+    let dir = std::env::args_os().nth(1).map_or_else(std::env::temp_dir, Into::into);
+    let path = dir.join(format!("rust_logger-contention-{}.log", std::process::id()));
+    // Only the console is timed, not the records kept for sinks added later.
+    rust_logger::sink::finish_initialization();
+
+    for (name, queue) in [("direct", 0), ("queue of 4096", 4096)] {
+        console::set_writer(Box::new(File::create(&path)?));
+        console::set_console_queue(queue)?;
+        let elapsed = run();
+        console::set_console_queue(0)?;
+        let records = f64::from(THREADS * RECORDS);
+        println!("{name:>14}: {:>9.2?} for {records} records, {:>8.0} records/s", elapsed, records / elapsed.as_secs_f64());
+    }
+    console::reset_writer();
+    std::fs::remove_file(&path)
+}
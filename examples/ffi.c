@@ -0,0 +1,19 @@
+/*
+ * Logs from C through rust_logger. Build the library and run:
+ *
+ *   cargo build -p rust_logger-ffi
+ *   cc examples/ffi.c -Iffi/include -Ltarget/debug -lrust_logger_ffi -o target/ffi-example
+ *   LD_LIBRARY_PATH=target/debug target/ffi-example
+ */
+#include <stddef.h>
+
+#include <rust_logger.h>
+
+int main(void) {
+	rust_logger_set_level(RL_INFO);
+	rust_logger_log(RL_DEBUG, "engine", "not shown at info");
+	rust_logger_log(RL_INFO, "engine::io", "opened device");
+	rust_logger_log(RL_WARN, "engine::io", "retrying read \xff");
+	rust_logger_log(RL_ERROR, NULL, "no target");
+	return 0;
+}
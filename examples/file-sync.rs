@@ -0,0 +1,40 @@
+//! Times file sink writes under each `SyncPolicy`, to pick one for a disk:
+//! `cargo run --release --example file-sync -- /path/on/that/disk`.
+
+use std::time::{Duration, Instant};
+
+use rust_logger::file::{self, SyncPolicy};
+use rust_logger::{console, level, Level};
+
+const RECORDS: u32 = 2000;
+
+fn main() -> std::io::Result<()> {
+    let dir = std::env::args_os().nth(1).map_or_else(std::env::temp_dir, Into::into);
+    // Only the file sink is timed.
+    level::set_level(Level::Debug);
+    console::set_writer(Box::new(std::io::sink()));
+
+    let policies = [
+        ("Never", SyncPolicy::Never),
+        ("OnError", SyncPolicy::OnError),
+        ("Interval(100ms)", SyncPolicy::Interval(Duration::from_millis(100))),
+        ("EveryRecord", SyncPolicy::EveryRecord),
+    ];
+    for (name, policy) in policies {
+        let path = dir.join(format!("rust_logger-file-sync-{}.log", std::process::id()));
+        let sink = file::file_sink(&path).session_header(false).sync_policy(policy).open()?;
+        let started = Instant::now();
+        for i in 0..RECORDS {
+            if i % 100 == 99 {
+                rust_logger::error!("record {i} of {RECORDS}");
+            } else {
+                rust_logger::log!("record {i} of {RECORDS}");
+            }
+        }
+        let elapsed = started.elapsed();
+        drop(sink);
+        std::fs::remove_file(&path)?;
+        println!("{name:>16}: {:>9.2?} per record", elapsed / RECORDS);
+    }
+    Ok(())
+}
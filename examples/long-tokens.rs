@@ -0,0 +1,39 @@
+//! Times the worst cases of console wrapping: one token of 1 MB with no
+//! space to break at, the same size in words, and a 40 MB blob under
+//! `set_message_limit`. Wrapping is one pass over the line, so it costs a
+//! small multiple of the unwrapped record, never more for longer tokens:
+//! `cargo run --release --example long-tokens`.
+
+use std::time::{Duration, Instant};
+
+use rust_logger::console::{self, Wrap};
+use rust_logger::format;
+
+const RUNS: u32 = 10;
+
+fn time(message: &str) -> Duration {
+    let started = Instant::now();
+    for _ in 0..RUNS {
+        rust_logger::log!("{message}");
+    }
+    started.elapsed() / RUNS
+}
+
+fn main() {
+    // Only the formatting is timed.
+    console::set_writer(Box::new(std::io::sink()));
+    let token = "a".repeat(1 << 20);
+    let words = "lorem ipsum ".repeat((1 << 20) / 12);
+
+    for (name, message) in [("1 MB token", &token), ("1 MB of words", &words)] {
+        console::set_wrap(Wrap::Off);
+        let unwrapped = time(message);
+        console::set_wrap(Wrap::Columns(100));
+        let wrapped = time(message);
+        println!("{name:>14}: {unwrapped:>9.2?} unwrapped, {wrapped:>9.2?} wrapped at 100 columns");
+    }
+
+    let blob = "a".repeat(40 << 20);
+    format::set_message_limit(4096);
+    println!("{:>14}: {:>9.2?} wrapped, cut to 4096 characters", "40 MB blob", time(&blob));
+}
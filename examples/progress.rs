@@ -0,0 +1,65 @@
+//! A progress bar on the last terminal line with log records printed above
+//! it from several threads. The bar implements `DrawCoordinator`, so every
+//! record clears the bar, prints, and redraws it.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rust_logger::console::{self, DrawCoordinator};
+
+const TOTAL: usize = 40;
+
+struct Bar {
+    done: AtomicUsize,
+    draw: Mutex<()>,
+}
+
+impl Bar {
+    fn redraw(&self) {
+        let done = self.done.load(Ordering::Relaxed);
+        let mut out = io::stdout().lock();
+        let _ = write!(out, "\r\x1b[2K[{}{}] {done}/{TOTAL}", "#".repeat(done), " ".repeat(TOTAL - done));
+        let _ = out.flush();
+    }
+
+    fn tick(&self) {
+        let _draw = self.draw.lock().unwrap();
+        self.done.fetch_add(1, Ordering::Relaxed);
+        self.redraw();
+    }
+}
+
+impl DrawCoordinator for Bar {
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        let _draw = self.draw.lock().unwrap();
+        print!("\r\x1b[2K");
+        f();
+        self.redraw();
+    }
+}
+
+fn main() {
+    let bar = Arc::new(Bar { done: AtomicUsize::new(0), draw: Mutex::new(()) });
+    console::set_external_draw_guard(bar.clone());
+
+    let workers: Vec<_> = (0..4)
+        .map(|worker| {
+            let bar = bar.clone();
+            thread::spawn(move || {
+                for step in 0..TOTAL / 4 {
+                    thread::sleep(Duration::from_millis(50 + 20 * worker));
+                    rust_logger::log!("worker <cyan>{worker}</> finished step {step}");
+                    bar.tick();
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers { worker.join().unwrap(); }
+
+    console::clear_external_draw_guard();
+    println!();
+}
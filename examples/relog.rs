@@ -0,0 +1,23 @@
+//! Reads logger output on stdin and prints it again with the current settings,
+//! e.g. to re-colorize pasted output with another theme:
+//!
+//! ```bash
+//! LOG_THEME=colorblind cargo run --example relog < pasted.log
+//! ```
+//!
+//! Lines that were not produced by the logger are passed through unchanged.
+
+use std::io::{self, BufRead};
+
+use rust_logger::replay::parse_pretty_line;
+
+fn main() {
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+
+        match parse_pretty_line(&line) {
+            Some(record) => record.emit(),
+            None => println!("{}", line),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! With the `static_level` feature, calls below the build-time level compile
+//! to nothing. Run it with the level set to Error:
+//!
+//! ```text
+//! LOG_LEVEL=error cargo run --release --example static-level --features static_level
+//! ```
+//!
+//! The Debug and Warn calls below never evaluate their arguments, `set_level`
+//! cannot bring them back, and their format strings are not in the binary.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rust_logger::{debug, error, level, log, warn, Level};
+
+static EVALUATED: AtomicUsize = AtomicUsize::new(0);
+
+fn evaluated() -> usize {
+    EVALUATED.fetch_add(1, Ordering::Relaxed)
+}
+
+fn main() {
+    assert_eq!(level::BUILD_LEVEL, Level::Error, "build with LOG_LEVEL=error");
+    assert!(!level::static_enabled(Level::Warn) && level::static_enabled(Level::Error));
+
+    level::set_level(Level::Debug);
+    debug!("compiled-away-DBG-{}", evaluated());
+    log!(every: 2, "compiled-away-LOG-{}", evaluated());
+    warn!("compiled-away-WRN-{}", evaluated(); n = evaluated());
+    assert_eq!(EVALUATED.load(Ordering::Relaxed), 0);
+    assert!(!level::is_enabled(Level::Warn));
+
+    error!("still logged: {}", evaluated());
+    assert_eq!(EVALUATED.load(Ordering::Relaxed), 1);
+
+    // Optimized builds drop the dead calls with their format strings.
+    if !cfg!(debug_assertions) {
+        let binary = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        for label in ["DBG", "LOG", "WRN"] {
+            let needle = format!("{}-{label}-", "compiled-away");
+            assert!(!binary.windows(needle.len()).any(|window| window == needle.as_bytes()), "{needle} is in the binary");
+        }
+    }
+}
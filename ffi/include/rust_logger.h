@@ -0,0 +1,31 @@
+/* C interface of rust_logger; link with -lrust_logger_ffi. */
+#ifndef RUST_LOGGER_H
+#define RUST_LOGGER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RL_DEBUG 0
+#define RL_INFO 1
+#define RL_WARN 2
+#define RL_ERROR 3
+/* Only for rust_logger_set_level: logging off. */
+#define RL_NONE 4
+
+/*
+ * Logs msg at level (RL_DEBUG..RL_ERROR; out-of-range values are clamped)
+ * with target as its module path, e.g. "engine::io". Both strings are
+ * NUL-terminated; invalid UTF-8 is replaced. A NULL target logs without
+ * one, a NULL msg logs nothing.
+ */
+void rust_logger_log(int level, const char *target, const char *msg);
+
+/* Sets the global level: RL_DEBUG..RL_ERROR, or RL_NONE for off. */
+void rust_logger_set_level(int level);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
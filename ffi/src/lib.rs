@@ -0,0 +1,9 @@
+//! Builds the `rust_logger` C entry points into `librust_logger_ffi.so`
+//! (`.dylib`, `.dll`) and `librust_logger_ffi.a`. The functions live in the
+//! facade behind its `ffi` feature; this crate only adds the C library
+//! crate types, so Rust builds of the facade stay plain `rlib`s.
+//!
+//! `include/rust_logger.h` declares the functions and `examples/ffi.c` in
+//! the repository shows them in use.
+
+pub use rust_logger::ffi::*;
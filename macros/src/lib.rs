@@ -0,0 +1,275 @@
+//! Procedural macros for `rust_logger`, re-exported by the facade when its
+//! `macros` feature is on. Depend on `rust_logger`, not on this crate.
+//!
+//! Declarative macros (`log!`, `warn!`, ...) stay in the facade; this crate
+//! only holds what needs a proc macro. It has no dependencies, so the input
+//! is read straight from the token trees.
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Group, Spacing, TokenStream, TokenTree};
+
+/// Logs entry to the function with its arguments and exit with the return
+/// value and elapsed time:
+///
+/// ```ignore
+/// #[rust_logger::instrument(level = "debug", skip(password), rename(user = "login"))]
+/// fn authenticate(user: &str, password: &str) -> Result<Token, AuthError> { ... }
+/// ```
+///
+/// Arguments and return values are `Debug`-formatted, or shown as `_` when
+/// their type has no `Debug`. A returned `Err` is logged at Warn. Options:
+/// `level` (default `"info"`), `name` (default the function name), `skip(..)`
+/// and `rename(arg = "shown")`. Works on methods, generic functions and
+/// `async fn`, where the whole future is timed.
+#[proc_macro_attribute]
+pub fn instrument(attr: TokenStream, item: TokenStream) -> TokenStream {
+    match expand(attr, item.clone()) {
+        Ok(expanded) => expanded,
+        Err(message) => {
+            let mut out = parse(&format!("::core::compile_error!({message:?});"));
+            out.extend(item);
+            out
+        }
+    }
+}
+
+struct Options {
+    level: String,
+    name: Option<String>,
+    skip: Vec<String>,
+    rename: Vec<(String, String)>,
+}
+
+fn parse(code: &str) -> TokenStream {
+    code.parse().expect("generated code parses")
+}
+
+fn is_punct(tree: &TokenTree, ch: char) -> bool {
+    matches!(tree, TokenTree::Punct(p) if p.as_char() == ch)
+}
+
+fn is_ident(tree: &TokenTree, name: &str) -> bool {
+    matches!(tree, TokenTree::Ident(i) if i.to_string() == name)
+}
+
+fn string_literal(tree: Option<&TokenTree>) -> Result<String, String> {
+    let literal = match tree {
+        Some(TokenTree::Literal(literal)) => literal.to_string(),
+        _ => return Err(String::from("instrument: expected a string literal")),
+    };
+    literal
+        .strip_prefix('"')
+        .and_then(|l| l.strip_suffix('"'))
+        .map(str::to_string)
+        .ok_or_else(|| format!("instrument: expected a string literal, found {literal}"))
+}
+
+/// Splits at commas outside `<...>`; groups are single trees already.
+fn split_commas(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    let mut angle = 0usize;
+    let mut previous: Option<TokenTree> = None;
+
+    for tree in stream {
+        let after_dash = matches!(&previous, Some(TokenTree::Punct(p)) if p.as_char() == '-' && p.spacing() == Spacing::Joint);
+        match &tree {
+            TokenTree::Punct(p) if p.as_char() == '<' => angle += 1,
+            TokenTree::Punct(p) if p.as_char() == '>' && !after_dash => angle = angle.saturating_sub(1),
+            TokenTree::Punct(p) if p.as_char() == ',' && angle == 0 => {
+                parts.push(Vec::new());
+                previous = Some(tree);
+                continue;
+            }
+            _ => {}
+        }
+        parts.last_mut().unwrap().push(tree.clone());
+        previous = Some(tree);
+    }
+
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+fn parse_options(attr: TokenStream) -> Result<Options, String> {
+    let mut options = Options { level: String::from("Info"), name: None, skip: Vec::new(), rename: Vec::new() };
+
+    for option in split_commas(attr) {
+        let key = match option.first() {
+            Some(TokenTree::Ident(key)) => key.to_string(),
+            _ => return Err(String::from("instrument: expected an option like `level = \"debug\"`")),
+        };
+
+        match (key.as_str(), option.get(1)) {
+            ("level", Some(eq)) if is_punct(eq, '=') => {
+                let level = string_literal(option.get(2))?;
+                options.level = match level.to_ascii_lowercase().as_str() {
+                    "debug" => "Debug",
+                    "info" => "Info",
+                    "warn" => "Warn",
+                    "error" => "Error",
+                    _ => return Err(format!("instrument: unknown level {level:?}: expected debug, info, warn or error")),
+                }
+                .to_string();
+            }
+            ("name", Some(eq)) if is_punct(eq, '=') => options.name = Some(string_literal(option.get(2))?),
+            ("skip", Some(TokenTree::Group(args))) if args.delimiter() == Delimiter::Parenthesis => {
+                for arg in split_commas(args.stream()) {
+                    options.skip.push(arg.iter().map(ToString::to_string).collect());
+                }
+            }
+            ("rename", Some(TokenTree::Group(args))) if args.delimiter() == Delimiter::Parenthesis => {
+                for pair in split_commas(args.stream()) {
+                    match pair.as_slice() {
+                        [TokenTree::Ident(arg), eq, shown] if is_punct(eq, '=') => {
+                            options.rename.push((arg.to_string(), string_literal(Some(shown))?));
+                        }
+                        _ => return Err(String::from("instrument: expected `rename(arg = \"shown\")`")),
+                    }
+                }
+            }
+            _ => return Err(format!("instrument: unknown option `{key}`: expected level, name, skip or rename")),
+        }
+    }
+
+    Ok(options)
+}
+
+/// The name of a plain `name: Type` or `mut name: Type` argument; `None`
+/// for `self` and destructuring patterns, which are not logged.
+fn argument_name(argument: &[TokenTree]) -> Option<String> {
+    let mut tokens = argument;
+    // Attributes on the parameter, e.g. `#[cfg(..)]`.
+    while let [pound, TokenTree::Group(_), rest @ ..] = tokens {
+        if !is_punct(pound, '#') { break; }
+        tokens = rest;
+    }
+    if let [first, rest @ ..] = tokens {
+        if is_ident(first, "mut") { tokens = rest; }
+    }
+
+    match tokens {
+        [TokenTree::Ident(name), TokenTree::Punct(colon), ..]
+            if colon.as_char() == ':' && colon.spacing() == Spacing::Alone && name.to_string() != "self" =>
+        {
+            Some(name.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn expand(attr: TokenStream, item: TokenStream) -> Result<TokenStream, String> {
+    let options = parse_options(attr)?;
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(body)) if body.delimiter() == Delimiter::Brace => body,
+        _ => return Err(String::from("instrument: expected a function with a body")),
+    };
+    let fn_index = tokens.iter().position(|t| is_ident(t, "fn")).ok_or("instrument: expected a function")?;
+    let is_async = tokens[..fn_index].iter().any(|t| is_ident(t, "async"));
+    let fn_name = tokens.get(fn_index + 1).map(ToString::to_string).ok_or("instrument: expected a function name")?;
+
+    // The argument list is the first parenthesized group after the name
+    // outside the generics; `Fn(..)` bounds sit inside `<...>`.
+    let mut angle = 0usize;
+    let mut args_index = None;
+    for (i, tree) in tokens.iter().enumerate().skip(fn_index + 2) {
+        let after_dash = i > 0 && matches!(&tokens[i - 1], TokenTree::Punct(p) if p.as_char() == '-' && p.spacing() == Spacing::Joint);
+        match tree {
+            TokenTree::Punct(p) if p.as_char() == '<' => angle += 1,
+            TokenTree::Punct(p) if p.as_char() == '>' && !after_dash => angle = angle.saturating_sub(1),
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Parenthesis && angle == 0 => {
+                args_index = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let args_index = args_index.ok_or("instrument: expected an argument list")?;
+    let TokenTree::Group(args) = &tokens[args_index] else { unreachable!() };
+
+    // `-> Type` up to a `where` clause.
+    let mut return_type: Vec<TokenTree> = Vec::new();
+    if tokens.len() > args_index + 2 && is_punct(&tokens[args_index + 1], '-') && is_punct(&tokens[args_index + 2], '>') {
+        return_type = tokens[args_index + 3..].iter().take_while(|t| !is_ident(t, "where")).cloned().collect();
+    }
+    let returns_unit = match return_type.as_slice() {
+        [] => true,
+        [TokenTree::Group(g)] => g.delimiter() == Delimiter::Parenthesis && g.stream().is_empty(),
+        _ => false,
+    };
+    let returns_value = !returns_unit;
+    let returns_result = {
+        let before_generics = return_type.iter().take_while(|t| !is_punct(t, '<'));
+        before_generics.last().is_some_and(|t| is_ident(t, "Result"))
+    };
+    let nameable = !return_type.iter().any(|t| is_ident(t, "impl"));
+
+    let mut pieces = Vec::new();
+    let mut values = String::new();
+    for argument in split_commas(args.stream()) {
+        let Some(name) = argument_name(&argument) else { continue };
+        if options.skip.contains(&name) { continue; }
+
+        let shown = options.rename.iter().find(|(arg, _)| *arg == name).map_or(name.as_str(), |(_, shown)| shown.as_str());
+        pieces.push(format!("{shown} = {{}}"));
+        values.push_str(&format!(", ::rust_logger::__private::ShowDebug((&::rust_logger::__private::MaybeDebug(&{name})).as_debug())"));
+    }
+
+    let name = options.name.unwrap_or(fn_name);
+    let return_type: TokenStream = return_type.into_iter().collect();
+    let typed = if nameable && returns_value { format!("::<{return_type}, _>") } else { String::new() };
+    let result = if is_async {
+        format!("::rust_logger::__private::instrument_future{typed}(async move __RL_BODY).await")
+    } else {
+        format!("::rust_logger::__private::instrument_call{typed}(move || __RL_BODY)")
+    };
+    let exit = if returns_result {
+        "match &__rl_result {
+            ::core::result::Result::Err(__rl_error) => __rl_span.exit_err((&::rust_logger::__private::MaybeDebug(__rl_error)).as_debug()),
+            _ => __rl_span.exit_with((&::rust_logger::__private::MaybeDebug(&__rl_result)).as_debug()),
+        }"
+    } else if returns_value {
+        "__rl_span.exit_with((&::rust_logger::__private::MaybeDebug(&__rl_result)).as_debug());"
+    } else {
+        "__rl_span.exit();"
+    };
+
+    let code = format!(
+        "#[allow(unused_imports)]
+        use ::rust_logger::__private::{{ViaDebug as _, ViaNoDebug as _}};
+        let __rl_span = ::rust_logger::__private::instrument_enter(
+            ::rust_logger::Level::{level}, ::rust_logger::__location!(), {name:?}, {grouped},
+            format_args!({format:?}{values}),
+        );
+        let __rl_result = {result};
+        {exit}
+        __rl_result",
+        level = options.level,
+        grouped = !is_async,
+        format = pieces.join(", "),
+    );
+
+    // Splice the original body in place of the marker so its spans survive.
+    let mut new_body = TokenStream::new();
+    for tree in parse(&code) {
+        new_body.extend([splice(tree, &body)]);
+    }
+
+    tokens.push(TokenTree::Group(Group::new(Delimiter::Brace, new_body)));
+    Ok(tokens.into_iter().collect())
+}
+
+fn splice(tree: TokenTree, body: &Group) -> TokenTree {
+    match tree {
+        TokenTree::Ident(ident) if ident.to_string() == "__RL_BODY" => TokenTree::Group(body.clone()),
+        TokenTree::Group(group) => {
+            let stream = group.stream().into_iter().map(|t| splice(t, body)).collect();
+            let mut spliced = Group::new(group.delimiter(), stream);
+            spliced.set_span(group.span());
+            TokenTree::Group(spliced)
+        }
+        other => other,
+    }
+}
@@ -0,0 +1,51 @@
+#!/usr/bin/env sh
+# Builds and lints every combination of the optional features, with std and
+# for the no_std core, then runs the downstream crate against the facade.
+set -e
+
+cd "$(dirname "$0")/.."
+
+for markup in "" markup; do
+	for timestamps in "" timestamps; do
+		for aliases in "" aliases; do
+			features=$(echo "std $markup $timestamps $aliases" | xargs | tr ' ' ',')
+			echo "== features: [${features}]"
+			cargo clippy --quiet --all-targets --no-default-features --features "$features" -- -D warnings
+		done
+	done
+done
+
+for features in "" alloc markup alloc,markup; do
+	echo "== no_std features: [${features}]"
+	cargo clippy --quiet --lib --no-default-features --features "$features" -- -D warnings
+done
+
+echo "== core tests"
+cargo test --quiet -p rust_logger-core --features alloc,markup
+
+echo "== features: [default, macros]"
+cargo clippy --quiet --all-targets --features macros -- -D warnings
+cargo test --quiet --features macros --test instrument
+
+echo "== features: [default, ffi]"
+cargo clippy --quiet --all-targets --features ffi -- -D warnings
+cargo test --quiet --features ffi --test ffi
+
+echo "== features: [default, signals]"
+cargo clippy --quiet --all-targets --features signals -- -D warnings
+cargo test --quiet --features signals --test signal_dump
+
+echo "== features: [default, static_level]"
+cargo clippy --quiet --all-targets --features static_level -- -D warnings
+LOG_LEVEL=error cargo run --quiet --release --example static-level --features static_level
+LOG_LEVEL=error cargo clippy --quiet --all-targets --manifest-path tests/static-level/Cargo.toml -- -D warnings
+LOG_LEVEL=error cargo test --quiet --manifest-path tests/static-level/Cargo.toml
+LOG_LEVEL=error cargo test --quiet --release --manifest-path tests/static-level/Cargo.toml
+
+echo "== max_level features"
+cargo clippy --quiet --all-targets --manifest-path tests/max-level/Cargo.toml -- -D warnings
+cargo test --quiet --manifest-path tests/max-level/Cargo.toml
+cargo test --quiet --release --manifest-path tests/max-level/Cargo.toml
+
+echo "== downstream"
+cargo run --quiet -p rust_logger-downstream
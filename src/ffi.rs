@@ -0,0 +1,54 @@
+//! C entry points. The `rust_logger-ffi` crate builds them into a `cdylib`
+//! and a `staticlib`; `ffi/include/rust_logger.h` declares them.
+//!
+//! Records from C go through the same pipeline as the macros: level checks,
+//! module levels, sinks, file sinks and the console format.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::internal::{print_foreign, set_level, Lossy};
+use crate::Level;
+
+/// `RL_DEBUG` (0) to `RL_ERROR` (3); anything above is Error, anything
+/// below Debug.
+fn record_level(level: c_int) -> Level {
+    match level {
+        ..=0 => Level::Debug,
+        1 => Level::Info,
+        2 => Level::Warn,
+        _ => Level::Error,
+    }
+}
+
+/// Text of a C string, invalid UTF-8 replaced; `None` for a null pointer.
+///
+/// # Safety
+/// `text` is null or points to a NUL-terminated string.
+unsafe fn lossy_text(text: *const c_char) -> Option<String> {
+    if text.is_null() { return None; }
+    Some(Lossy(CStr::from_ptr(text).to_bytes()).to_string())
+}
+
+/// Logs `msg` at `level` with `target` as its module path. A null `target`
+/// logs without one; a null `msg` logs nothing. A panic on the way (a
+/// console writer that panics, a poisoned lock) is caught: unwinding into
+/// C would abort the host.
+///
+/// # Safety
+/// `target` and `msg` are null or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn rust_logger_log(level: c_int, target: *const c_char, msg: *const c_char) {
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        let Some(message) = lossy_text(msg) else { return };
+        let target = lossy_text(target);
+        print_foreign(record_level(level), target.as_deref(), &message);
+    }));
+}
+
+/// Sets the global level like `level::set_level`: 0 to 3 as for
+/// `rust_logger_log`, 4 (`RL_NONE`) or above switches logging off.
+#[no_mangle]
+pub extern "C" fn rust_logger_set_level(level: c_int) {
+    let _ = catch_unwind(|| set_level(if level >= 4 { Level::None } else { record_level(level) }));
+}
@@ -0,0 +1,8649 @@
+//! Implementation of the std layer. Not a stable API: use the public modules
+//! re-exported from the crate root; macros go through `__private`.
+
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, LazyLock, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+#[cfg(feature = "timestamps")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::render::{self, Glyphs, Style};
+
+#[cfg(feature = "aliases")]
+static ALIASES: LazyLock<Mutex<HashMap<String, AliasDef>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+static THEME: LazyLock<RwLock<Theme>> = LazyLock::new(|| RwLock::new(theme_from_env()));
+static BELL: Mutex<BellState> = Mutex::new(BellState::new());
+static PROCESS_TAG: LazyLock<RwLock<Option<String>>> = LazyLock::new(|| {
+    let tag = std::env::var("LOG_PROCESS_TAG").ok().filter(|tag| !tag.is_empty());
+    if let Some(tag) = &tag { note_config("process_tag", ConfigSource::Env("LOG_PROCESS_TAG"), tag); }
+    RwLock::new(tag)
+});
+
+/// Why `register_alias` rejected an alias.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum AliasError {
+    /// Empty, or containing `<`, `>` or `,`, so it cannot be used as a tag
+    BadName(String),
+    /// The alias has no tokens
+    NoTokens(String),
+    /// Tokens that are neither styles nor registered aliases
+    UnknownTokens { alias: String, tokens: Vec<String> },
+}
+
+impl fmt::Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasError::BadName(name) => write!(f, "bad alias name {name:?}: must be non-empty, without '<', '>' or ','"),
+            AliasError::NoTokens(alias) => write!(f, "alias {alias:?} has no tokens"),
+            AliasError::UnknownTokens { alias, tokens } => {
+                let tokens: Vec<String> = tokens.iter().map(|token| format!("{token:?}")).collect();
+                write!(
+                    f,
+                    "alias {alias:?}: unknown tokens {}: expected colors, bg- colors, bold, italic, underline, dim, strikethrough, reverse or other aliases",
+                    tokens.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AliasError {}
+
+/// Checks an alias before it is stored: every token must be a markup style
+/// or the name of a registered alias.
+#[cfg(feature = "aliases")]
+#[cfg_attr(not(feature = "markup"), allow(unused_variables))]
+fn validate_alias(alias: &str, tokens: &str, aliases: &HashMap<String, AliasDef>) -> Result<(), AliasError> {
+    if alias.is_empty() || alias.contains(['<', '>', ',']) { return Err(AliasError::BadName(alias.to_string())); }
+
+    let tokens: Vec<&str> = tokens.split(',').map(str::trim).filter(|token| !token.is_empty()).collect();
+    if tokens.is_empty() { return Err(AliasError::NoTokens(alias.to_string())); }
+
+    #[cfg(feature = "markup")]
+    let known = |token: &str| render::is_markup_token(token) || aliases.contains_key(token);
+    // Without markup nothing is styled, so any token is as good as another.
+    #[cfg(not(feature = "markup"))]
+    let known = |_: &str| true;
+
+    let unknown: Vec<String> = tokens.into_iter().filter(|token| !known(token)).map(str::to_string).collect();
+    if unknown.is_empty() { Ok(()) } else { Err(AliasError::UnknownTokens { alias: alias.to_string(), tokens: unknown }) }
+}
+
+/// An alias as registered, and with the aliases among its tokens expanded.
+#[cfg(feature = "aliases")]
+struct AliasDef {
+    tokens: String,
+    resolved: String,
+}
+
+/// Appends the style tokens of `name` to `out`, expanding aliases depth
+/// first. A token naming an alias that is already being expanded closes a
+/// cycle and is dropped, as is one naming a removed alias, unless it is a
+/// style itself.
+#[cfg(feature = "aliases")]
+fn expand_alias<'a>(name: &'a str, aliases: &'a HashMap<String, AliasDef>, expanding: &mut Vec<&'a str>, out: &mut Vec<&'a str>) {
+    let Some(def) = aliases.get(name) else { return };
+    expanding.push(name);
+    for token in def.tokens.split(',').map(str::trim).filter(|token| !token.is_empty()) {
+        if aliases.contains_key(token) && !expanding.contains(&token) {
+            expand_alias(token, aliases, expanding, out);
+        } else if is_style_token(token) && !out.contains(&token) {
+            out.push(token);
+        }
+    }
+    expanding.pop();
+}
+
+#[cfg(all(feature = "aliases", feature = "markup"))]
+fn is_style_token(token: &str) -> bool {
+    render::is_markup_token(token)
+}
+
+#[cfg(all(feature = "aliases", not(feature = "markup")))]
+fn is_style_token(_token: &str) -> bool {
+    true
+}
+
+/// Expands every alias again after one changed, so rendering is a single
+/// lookup however deep the aliases nest.
+#[cfg(feature = "aliases")]
+fn resolve_aliases(aliases: &mut HashMap<String, AliasDef>) {
+    let resolved: Vec<(String, String)> = aliases
+        .keys()
+        .map(|name| {
+            let mut tokens = Vec::new();
+            expand_alias(name, aliases, &mut Vec::new(), &mut tokens);
+            (name.clone(), tokens.join(", "))
+        })
+        .collect();
+    for (name, tokens) in resolved {
+        if let Some(def) = aliases.get_mut(&name) { def.resolved = tokens; }
+    }
+}
+
+/// Registers `<alias>text</>` as a shorthand for a tag with `tokens`, e.g.
+/// `register_alias("hot", "red, bold")`. Tokens may name other aliases,
+/// which are expanded here rather than at log time; a cycle is cut where it
+/// closes. Registering a name again replaces its tokens and updates the
+/// aliases built on it. A rejected alias leaves any earlier definition in
+/// place.
+#[cfg(feature = "aliases")]
+pub fn register_alias(alias: &str, tokens: &str) -> Result<(), AliasError> {
+    let mut aliases = ALIASES.lock().unwrap();
+    validate_alias(alias, tokens, &aliases)?;
+    aliases.insert(alias.to_string(), AliasDef { tokens: tokens.to_string(), resolved: String::new() });
+    resolve_aliases(&mut aliases);
+    Ok(())
+}
+
+/// What `alias!` does with a rejected alias outside debug builds, where it
+/// panics instead.
+pub fn report_bad_alias(error: &AliasError) {
+    self_log(Level::Warn, format_args!("ignoring {error}"));
+}
+
+/// Unregisters an alias and returns its tokens; tags using it then print as
+/// plain text, and aliases built on it lose its styles.
+#[cfg(feature = "aliases")]
+pub fn remove_alias(alias: &str) -> Option<String> {
+    let mut aliases = ALIASES.lock().unwrap();
+    let removed = aliases.remove(alias)?;
+    resolve_aliases(&mut aliases);
+    Some(removed.tokens)
+}
+
+/// The tokens an alias was registered with, other aliases not expanded.
+#[cfg(feature = "aliases")]
+pub fn get_alias(alias: &str) -> Option<String> {
+    let aliases = ALIASES.lock().unwrap();
+    aliases.get(alias).map(|def| def.tokens.clone())
+}
+
+#[cfg(feature = "aliases")]
+pub fn clear_aliases() {
+    let mut aliases = ALIASES.lock().unwrap();
+    aliases.clear();
+}
+
+// Without the `aliases` feature the API stays so `alias!` compiles, but nothing is stored.
+#[cfg(not(feature = "aliases"))]
+pub fn register_alias(_alias: &str, _tokens: &str) -> Result<(), AliasError> {
+    Ok(())
+}
+
+#[cfg(not(feature = "aliases"))]
+pub fn remove_alias(_alias: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "aliases"))]
+pub fn get_alias(_alias: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "aliases"))]
+pub fn clear_aliases() {}
+
+pub use crate::render::Level;
+
+/// The build-time `LOG_LEVEL`, from the `log_level` cfg set by build.rs.
+#[cfg(log_level = "info")]
+pub const BUILD_LEVEL: Level = Level::Info;
+#[cfg(log_level = "warn")]
+pub const BUILD_LEVEL: Level = Level::Warn;
+#[cfg(log_level = "error")]
+pub const BUILD_LEVEL: Level = Level::Error;
+#[cfg(log_level = "none")]
+pub const BUILD_LEVEL: Level = Level::None;
+#[cfg(not(any(log_level = "info", log_level = "warn", log_level = "error", log_level = "none")))]
+pub const BUILD_LEVEL: Level = Level::Debug;
+
+/// The most verbose level the `max_level_*` features leave in the binary;
+/// in builds without debug assertions the `release_max_level_*` ones apply
+/// too. When several are on, the strictest wins.
+pub const MAX_LEVEL: Level = max_level();
+
+const fn max_level() -> Level {
+    let release = !cfg!(debug_assertions);
+    if cfg!(feature = "max_level_off") || release && cfg!(feature = "release_max_level_off") {
+        Level::None
+    } else if cfg!(feature = "max_level_error") || release && cfg!(feature = "release_max_level_error") {
+        Level::Error
+    } else if cfg!(feature = "max_level_warn") || release && cfg!(feature = "release_max_level_warn") {
+        Level::Warn
+    } else if cfg!(feature = "max_level_info") || release && cfg!(feature = "release_max_level_info") {
+        Level::Info
+    } else {
+        Level::Debug
+    }
+}
+
+/// Whether `level` can be logged at all in this build: only levels
+/// [`MAX_LEVEL`] lets through, and with the `static_level` feature only those
+/// the build-time level lets through as well. The macros check it in a `const` block, so calls
+/// it rules out compile to nothing, arguments included.
+pub const fn static_enabled(level: Level) -> bool {
+    render::level_enabled(MAX_LEVEL, level) && (!cfg!(feature = "static_level") || render::level_enabled(BUILD_LEVEL, level))
+}
+
+/// `env`, `.env:<path>` or `default`, set by build.rs.
+const BUILD_LOG_LEVEL_SOURCE: &str = env!("LOG_LEVEL_SOURCE");
+
+/// Why a configuration value was rejected. The `*_or_default` variants of
+/// the fallible setters report it as a `[logger]` warning instead and fall
+/// back.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    UnknownLevel(String),
+    UnknownTheme(String),
+    UnknownCharset(String),
+    UnknownFormat(String),
+    /// Not a list of SGR parameters such as `"44"` or `"38;5;214"`
+    BadColor(String),
+    /// A level spec entry that is not `level`, `module=level` or
+    /// `kind:level`
+    BadLevelSpec(String),
+    /// A `kind:level` entry for a kind that is not `console` or `file`
+    UnknownSinkKind(String),
+    /// Neither a timestamp format name nor a valid pattern
+    BadTimestampFormat(String),
+    /// The console could not be switched to the requested mode
+    Console(io::Error),
+    /// `set_level_style` for `Level::None`, which is never printed
+    StyleForNone,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::UnknownLevel(name) => write!(f, "unknown level {name:?}: expected debug, info, warn, error or none"),
+            ConfigError::UnknownTheme(name) => write!(f, "unknown theme {name:?}: expected default, colorblind or high-contrast"),
+            ConfigError::UnknownCharset(name) => write!(f, "unknown charset {name:?}: expected unicode, ascii or auto"),
+            ConfigError::UnknownFormat(name) => write!(f, "unknown format {name:?}: expected pretty, json, logfmt or plain"),
+            ConfigError::BadColor(value) => write!(f, "bad color {value:?}: expected SGR parameters such as \"44\" or \"38;5;214\""),
+            ConfigError::BadLevelSpec(entry) => write!(f, "bad level spec entry {entry:?}: expected level, module=level or kind:level"),
+            ConfigError::UnknownSinkKind(name) => write!(f, "unknown sink kind {name:?}: expected console or file"),
+            ConfigError::BadTimestampFormat(spec) => write!(
+                f,
+                "bad timestamp format {spec:?}: expected standard, rfc3339, compact, time-only, unix-millis, none or a pattern of %Y %m %d %H %M %S %3f %6f %9f %a %j %z"
+            ),
+            ConfigError::Console(e) => write!(f, "console: {e}"),
+            ConfigError::StyleForNone => write!(f, "Level::None has no style: it is never printed"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Console(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Console(e)
+    }
+}
+
+/// Level of the log files without one of their own, `LEVEL_UNSET` while
+/// they follow the console.
+static FILE_LEVEL: AtomicU8 = AtomicU8::new(LEVEL_UNSET);
+static FILE_LEVEL_SET_BY_API: AtomicBool = AtomicBool::new(false);
+
+/// The level for log files opened without `FileSinkBuilder::level`:
+/// `Some(Level::Debug)` sends them Debug records the console does not show.
+/// `None` (the default) has them take what the console shows, module levels
+/// included. Set from `file:debug` in `LOG_LEVEL` too; this call wins.
+pub fn set_file_level(level: Option<Level>) {
+    FILE_LEVEL_SET_BY_API.store(true, Ordering::Relaxed);
+    store_file_level(ConfigSource::Api("set_file_level"), level);
+}
+
+pub fn file_level() -> Option<Level> {
+    stored_level(FILE_LEVEL.load(Ordering::Relaxed))
+}
+
+fn store_file_level(source: ConfigSource, level: Option<Level>) {
+    let sinks = FILE_SINKS.lock().unwrap();
+    note_config("file_level", source, level.map_or_else(|| String::from("(console)"), |level| format!("{level:?}")));
+    FILE_LEVEL.store(level.map_or(LEVEL_UNSET, |level| level as u8), Ordering::Relaxed);
+    store_file_sinks(&sinks);
+}
+
+/// Parses a level name case-insensitively. Unlike `parse_level`, unknown
+/// names are an error instead of `Debug`.
+pub fn try_parse_level(name: &str) -> Result<Level, ConfigError> {
+    let known = ["debug", "info", "warn", "error", "none"].iter().any(|known| name.trim().eq_ignore_ascii_case(known));
+    if !known { return Err(ConfigError::UnknownLevel(name.to_string())); }
+
+    Ok(render::parse_level(name.trim()))
+}
+
+const LEVEL_UNSET: u8 = u8::MAX;
+
+/// Runtime override of the build-time level, `LEVEL_UNSET` while there is none.
+static RUNTIME_LEVEL: AtomicU8 = AtomicU8::new(LEVEL_UNSET);
+static LEVEL_SET_BY_API: AtomicBool = AtomicBool::new(false);
+
+fn build_level() -> Level {
+    BUILD_LEVEL
+}
+
+/// A level stored as `level as u8`, `None` for `LEVEL_UNSET`.
+fn stored_level(value: u8) -> Option<Level> {
+    match value {
+        0 => Some(Level::Debug),
+        1 => Some(Level::Info),
+        2 => Some(Level::Warn),
+        3 => Some(Level::Error),
+        4 => Some(Level::None),
+        _ => None,
+    }
+}
+
+fn current_level() -> Level {
+    stored_level(RUNTIME_LEVEL.load(Ordering::Relaxed)).unwrap_or_else(build_level)
+}
+
+/// The level in effect: the last `set_level`/`init_from_env` value, or the
+/// build-time `LOG_LEVEL`.
+pub fn level() -> Level {
+    current_level()
+}
+
+/// Overrides the build-time level for every thread, effective for the next record.
+pub fn set_level(level: Level) {
+    note_config("level", ConfigSource::Api("set_level"), format_args!("{level:?}"));
+    LEVEL_SET_BY_API.store(true, Ordering::Relaxed);
+    RUNTIME_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Applies the `LOG_LEVEL` environment variable of the running process: a
+/// level, or a spec like `"info,my_crate::net=warn"` (see
+/// `parse_level_spec`). Parts already set through the API are kept. Returns
+/// the global level applied.
+pub fn init_from_env() -> Option<Level> {
+    let value = std::env::var("LOG_LEVEL").ok()?;
+
+    let spec = match parse_level_spec(&value) {
+        Ok(spec) => spec,
+        Err(e) => {
+            note_config("level", ConfigSource::Ignored("LOG_LEVEL"), format_args!("{value} ({e})"));
+            self_log(Level::Warn, format_args!("ignoring LOG_LEVEL: {e}"));
+            return None;
+        }
+    };
+
+    if !spec.modules.is_empty() && !MODULE_LEVELS_SET_BY_API.load(Ordering::Relaxed) {
+        store_module_levels(ConfigSource::Env("LOG_LEVEL"), |levels| levels.extend(spec.modules.iter().cloned()));
+    }
+    if let Some(level) = spec.file_level() {
+        if FILE_LEVEL_SET_BY_API.load(Ordering::Relaxed) {
+            note_config("file_level", ConfigSource::Ignored("LOG_LEVEL"), format_args!("{level:?} (set through the API)"));
+        } else {
+            store_file_level(ConfigSource::Env("LOG_LEVEL"), Some(level));
+        }
+    }
+
+    let level = spec.kind(SinkKind::Console).or(spec.default)?;
+    note_config("level", ConfigSource::Env("LOG_LEVEL"), format_args!("{level:?}"));
+    if LEVEL_SET_BY_API.load(Ordering::Relaxed) { return None; }
+
+    RUNTIME_LEVEL.store(level as u8, Ordering::Relaxed);
+    Some(level)
+}
+
+/// `set_level` from a name such as `"info"`.
+pub fn set_level_by_name(name: &str) -> Result<(), ConfigError> {
+    set_level(try_parse_level(name)?);
+    Ok(())
+}
+
+/// `set_level_by_name` that warns and keeps the current level on a bad name.
+pub fn set_level_by_name_or_default(name: &str) {
+    if let Err(e) = set_level_by_name(name) { self_log(Level::Warn, format_args!("keeping level {:?}: {e}", current_level())); }
+}
+
+/// Whether `level` passes the global level. Records from the macros are
+/// checked with `is_enabled_for`, which also applies module levels.
+pub fn is_enabled(level: Level) -> bool {
+    static_enabled(level) && render::level_enabled(current_level(), level) && !muted(level)
+}
+
+/// Levels for module path patterns, most specific first.
+static MODULE_LEVELS: RwLock<Vec<(String, Level)>> = RwLock::new(Vec::new());
+/// Keeps the check a single load while no module level is set.
+static HAS_MODULE_LEVELS: AtomicBool = AtomicBool::new(false);
+static MODULE_LEVELS_SET_BY_API: AtomicBool = AtomicBool::new(false);
+
+/// Whether `level` passes for a record logged in `target` (a module path):
+/// the level of the most specific matching `set_module_level` pattern, or
+/// the global level when none matches.
+pub fn is_enabled_for(level: Level, target: &str) -> bool {
+    passes_level_for(level, target) && !muted(level)
+}
+
+/// `is_enabled_for` as if nothing were muted.
+fn passes_level_for(level: Level, target: &str) -> bool {
+    let threshold = if HAS_MODULE_LEVELS.load(Ordering::Relaxed) {
+        module_level(target).unwrap_or_else(current_level)
+    } else {
+        current_level()
+    };
+    static_enabled(level) && render::level_enabled(threshold, level)
+}
+
+fn module_level(target: &str) -> Option<Level> {
+    let levels = MODULE_LEVELS.read().unwrap();
+    levels.iter().find(|(prefix, _)| module_matches(prefix, target)).map(|(_, level)| *level)
+}
+
+/// `a::b` covers `a::b` and `a::b::c`, but not `a::bc`. A `*` stands for
+/// any text, `::` included; the pattern still starts at the beginning of
+/// the path and ends at its end or at a `::`, so `*::db` covers `app::db`
+/// and `app::db::pool`, but not `app::mydb`.
+fn module_matches(pattern: &str, target: &str) -> bool {
+    if !pattern.contains('*') {
+        return target.strip_prefix(pattern).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+    }
+    let ends = target.match_indices("::").map(|(end, _)| end).chain([target.len()]);
+    ends.into_iter().any(|end| glob_matches(pattern, &target[..end]))
+}
+
+/// Whether all of `text` matches `pattern`, where `*` matches any run of
+/// characters. No other character is special.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = text.strip_prefix(parts.next().unwrap_or_default()) else { return false };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else { return rest.is_empty() };
+
+    // The first occurrence of each middle part leaves the most room for the rest.
+    for part in parts {
+        let Some(start) = rest.find(part) else { return false };
+        rest = &rest[start + part.len()..];
+    }
+    rest.ends_with(last)
+}
+
+/// How much of a module pattern must match literally: longer is more specific.
+fn pattern_specificity(pattern: &str) -> usize {
+    pattern.len() - pattern.matches('*').count()
+}
+
+fn store_module_levels(source: ConfigSource, update: impl FnOnce(&mut Vec<(String, Level)>)) {
+    let mut levels = MODULE_LEVELS.write().unwrap();
+    update(&mut levels);
+    levels.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern_specificity(pattern)));
+    HAS_MODULE_LEVELS.store(!levels.is_empty(), Ordering::Relaxed);
+
+    let listed: Vec<String> = levels.iter().map(|(module, level)| format!("{module}={level:?}")).collect();
+    note_config("module_levels", source, if listed.is_empty() { String::from("(none)") } else { listed.join(", ") });
+}
+
+/// Sets the level for a module and everything below it, e.g.
+/// `set_module_level("my_crate::net", Level::Warn)`, or for every module a
+/// pattern such as `"my_crate::*::db"` or `"*::metrics"` matches. Of the
+/// patterns matching a record, the one with the most characters besides
+/// `*` wins, then the one set first; any of them wins over the global level.
+pub fn set_module_level(module: &str, level: Level) {
+    MODULE_LEVELS_SET_BY_API.store(true, Ordering::Relaxed);
+    store_module_levels(ConfigSource::Api("set_module_level"), |levels| {
+        levels.retain(|(prefix, _)| prefix != module);
+        levels.push((module.to_string(), level));
+    });
+}
+
+pub fn clear_module_levels() {
+    store_module_levels(ConfigSource::Api("clear_module_levels"), Vec::clear);
+}
+
+/// A parsed level spec such as `"debug,my_crate::net=warn,hyper=error"`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct LevelSpec {
+    /// The bare level in the spec, if any
+    pub default: Option<Level>,
+    pub modules: Vec<(String, Level)>,
+    /// `console:warn` and `file:debug` entries
+    pub kinds: Vec<(SinkKind, Level)>,
+}
+
+impl LevelSpec {
+    /// The level of the `kind:level` entry for `kind`.
+    pub fn kind(&self, kind: SinkKind) -> Option<Level> {
+        self.kinds.iter().find(|(entry, _)| *entry == kind).map(|(_, level)| *level)
+    }
+
+    /// The level for the log files: the `file:` entry, or the bare level
+    /// when a `console:` entry sets the console apart. `None` leaves them
+    /// following the console.
+    fn file_level(&self) -> Option<Level> {
+        self.kind(SinkKind::File).or(self.default.filter(|_| self.kind(SinkKind::Console).is_some()))
+    }
+}
+
+/// Parses a comma-separated list of `level`, `module=level` and
+/// `kind:level` entries, where the kind is `console` or `file`. A later
+/// entry for the same module or kind wins.
+pub fn parse_level_spec(spec: &str) -> Result<LevelSpec, ConfigError> {
+    let mut parsed = LevelSpec::default();
+
+    for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        // A single colon, so `a::b` is not read as a kind.
+        let kind_entry = entry.split_once(':').filter(|(kind, level)| !kind.contains("::") && !level.starts_with(':'));
+        match entry.split_once('=') {
+            None if kind_entry.is_some() => {
+                let (kind, level) = kind_entry.unwrap_or_default();
+                let kind = SinkKind::parse(kind)?;
+                let level = try_parse_level(level)?;
+                parsed.kinds.retain(|(existing, _)| *existing != kind);
+                parsed.kinds.push((kind, level));
+            }
+            None => parsed.default = Some(try_parse_level(entry)?),
+            Some((module, level)) => {
+                let module = module.trim();
+                if module.is_empty() || module.contains(char::is_whitespace) {
+                    return Err(ConfigError::BadLevelSpec(entry.to_string()));
+                }
+                let level = try_parse_level(level)?;
+                parsed.modules.retain(|(prefix, _)| prefix != module);
+                parsed.modules.push((module.to_string(), level));
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Applies a level spec: the bare level (or the `console:` one) as
+/// `set_level`, each module entry as `set_module_level`, and the level for
+/// the log files as `file::set_file_level`. Nothing changes if the spec does
+/// not parse.
+pub fn set_level_spec(spec: &str) -> Result<(), ConfigError> {
+    let parsed = parse_level_spec(spec)?;
+    if let Some(level) = parsed.kind(SinkKind::Console).or(parsed.default) { set_level(level); }
+    for (module, level) in &parsed.modules { set_module_level(module, *level); }
+    if let Some(level) = parsed.file_level() { set_file_level(Some(level)); }
+    Ok(())
+}
+
+thread_local! {
+    /// Records below this level are muted on this thread.
+    static MUTED_BELOW: Cell<Level> = const { Cell::new(Level::Debug) };
+}
+
+fn muted(level: Level) -> bool {
+    MUTED_BELOW.try_with(|below| level < below.get()).unwrap_or(false)
+}
+
+/// Returned by `mute!`; unmutes when dropped.
+#[must_use = "records are muted only while the guard is alive"]
+pub struct MuteGuard {
+    previous: Level,
+    // Restores a thread-local, so it must be dropped on the thread that created it.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+/// Mutes records below `level` on the current thread until the guard is
+/// dropped. Nested mutes only ever mute more. The black box still records
+/// what would have been shown, so a muted section can be read back.
+pub fn mute(level: Level) -> MuteGuard {
+    let previous = MUTED_BELOW.with(|below| below.replace(below.get().max(level)));
+    MuteGuard { previous, _not_send: std::marker::PhantomData }
+}
+
+impl Drop for MuteGuard {
+    fn drop(&mut self) {
+        let _ = MUTED_BELOW.try_with(|below| below.set(self.previous));
+    }
+}
+
+/// A color for the `LevelStyle` builder methods: one of the eight basic
+/// colors or gray, a 256-palette index, or 24-bit RGB.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Gray,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// A color name as in markup (`red`, `orange`, `gray`, ...), `#rrggbb`
+    /// or `c<0-255>`.
+    pub fn parse(name: &str) -> Result<Color, ConfigError> {
+        let bad = || ConfigError::BadColor(name.to_string());
+        let name = name.trim();
+        if let Some(hex) = name.strip_prefix('#') {
+            let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok()).ok_or_else(bad);
+            if hex.len() != 6 { return Err(bad()); }
+            return Ok(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+        }
+        if let Some(index) = name.strip_prefix(['c', 'C']).filter(|i| !i.is_empty() && i.bytes().all(|b| b.is_ascii_digit())) {
+            return index.parse().map(Color::Indexed).map_err(|_| bad());
+        }
+
+        match name.to_ascii_lowercase().as_str() {
+            "black" => Ok(Color::Black),
+            "red" => Ok(Color::Red),
+            "green" => Ok(Color::Green),
+            "yellow" | "orange" => Ok(Color::Yellow),
+            "blue" => Ok(Color::Blue),
+            "magenta" | "purple" => Ok(Color::Magenta),
+            "cyan" => Ok(Color::Cyan),
+            "white" => Ok(Color::White),
+            "gray" | "grey" => Ok(Color::Gray),
+            _ => Err(bad()),
+        }
+    }
+
+    /// SGR parameters for the color as a foreground, or with `bg` as a
+    /// background.
+    pub fn sgr(self, bg: bool) -> String {
+        let basic = |n: u8| if bg { (n + 10).to_string() } else { n.to_string() };
+        match self {
+            Color::Black => basic(30),
+            Color::Red => basic(31),
+            Color::Green => basic(32),
+            Color::Yellow => basic(33),
+            Color::Blue => basic(34),
+            Color::Magenta => basic(35),
+            Color::Cyan => basic(36),
+            Color::White => basic(37),
+            Color::Gray => basic(90),
+            Color::Indexed(n) => format!("{};5;{n}", if bg { 48 } else { 38 }),
+            Color::Rgb(r, g, b) => format!("{};2;{r};{g};{b}", if bg { 48 } else { 38 }),
+        }
+    }
+}
+
+/// How one level is rendered. Color fields hold SGR parameters (e.g. `"44"`,
+/// `"38;5;214"`); an empty string means no sequence is emitted.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LevelStyle {
+    pub label: String,
+    pub bg: String,
+    pub fg: String,
+    pub ts_color: String,
+    pub msg_color: String,
+}
+
+impl LevelStyle {
+    pub fn new(label: &str, bg: &str, fg: &str, ts_color: &str, msg_color: &str) -> Self {
+        LevelStyle {
+            label: label.to_string(),
+            bg: bg.to_string(),
+            fg: fg.to_string(),
+            ts_color: ts_color.to_string(),
+            msg_color: msg_color.to_string(),
+        }
+    }
+
+    /// `new` that checks every color is a list of SGR parameters (digits
+    /// separated by `;`, or empty for none).
+    pub fn try_new(label: &str, bg: &str, fg: &str, ts_color: &str, msg_color: &str) -> Result<Self, ConfigError> {
+        for color in [bg, fg, ts_color, msg_color] {
+            let valid = color.is_empty() || color.split(';').all(|code| !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit()));
+            if !valid { return Err(ConfigError::BadColor(color.to_string())); }
+        }
+
+        Ok(LevelStyle::new(label, bg, fg, ts_color, msg_color))
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    /// Background of the label block.
+    pub fn bg(mut self, color: Color) -> Self {
+        self.bg = color.sgr(true);
+        self
+    }
+
+    /// Label text color.
+    pub fn fg(mut self, color: Color) -> Self {
+        self.fg = color.sgr(false);
+        self
+    }
+
+    pub fn ts_color(mut self, color: Color) -> Self {
+        self.ts_color = color.sgr(false);
+        self
+    }
+
+    pub fn msg_color(mut self, color: Color) -> Self {
+        self.msg_color = color.sgr(false);
+        self
+    }
+
+    pub fn as_style(&self) -> Style<'_> {
+        Style {
+            label: &self.label,
+            bg: &self.bg,
+            fg: &self.fg,
+            ts_color: &self.ts_color,
+            msg_color: &self.msg_color,
+        }
+    }
+}
+
+impl From<Style<'_>> for LevelStyle {
+    fn from(style: Style<'_>) -> Self {
+        LevelStyle::new(style.label, style.bg, style.fg, style.ts_color, style.msg_color)
+    }
+}
+
+static NONE_STYLE: LevelStyle = LevelStyle {
+    label: String::new(),
+    bg: String::new(),
+    fg: String::new(),
+    ts_color: String::new(),
+    msg_color: String::new(),
+};
+
+/// Styles for every printable level.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Theme {
+    pub debug: LevelStyle,
+    pub info: LevelStyle,
+    pub warn: LevelStyle,
+    pub error: LevelStyle,
+    /// SGR parameters for records logged with `emphasis: true`, applied to
+    /// the timestamp and message in place of the level colors
+    pub alert: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            debug: render::DEFAULT_STYLES[0].into(),
+            info: render::DEFAULT_STYLES[1].into(),
+            warn: render::DEFAULT_STYLES[2].into(),
+            error: render::DEFAULT_STYLES[3].into(),
+            alert: "1;97;41".to_string(), // bold white on red
+        }
+    }
+}
+
+impl Theme {
+    /// Okabe-Ito based palette that avoids red/green pairs. Labels carry a
+    /// leading shape (`..`, `--`, `!!`, `xx`) so levels stay distinguishable
+    /// even when colors are not.
+    pub fn colorblind() -> Self {
+        Theme {
+            debug: LevelStyle::new(".. DBG", "48;5;250", "38;2;0;0;0", "38;5;245", "38;5;245"), // light gray
+            info: LevelStyle::new("-- LOG", "48;5;74", "38;2;0;0;0", "38;5;74", "37"),         // sky blue
+            warn: LevelStyle::new("!! WRN", "48;5;214", "38;2;0;0;0", "38;5;214", "38;5;214"), // orange
+            error: LevelStyle::new("xx ERR", "48;5;175", "1;38;2;0;0;0", "38;5;175", "1;38;5;175"), // reddish purple
+            alert: "1;38;2;0;0;0;48;5;214".to_string(), // bold black on orange
+        }
+    }
+
+    /// Bright backgrounds and bright foregrounds only, with full level names.
+    pub fn high_contrast() -> Self {
+        Theme {
+            debug: LevelStyle::new("DEBUG", "47", "30", "37", "37"),
+            info: LevelStyle::new("INFO ", "107", "30", "97", "97"),
+            warn: LevelStyle::new("WARN ", "103", "1;30", "93", "93"),
+            error: LevelStyle::new("ERROR", "101", "1;30", "1;91", "1;91"),
+            alert: "1;30;107".to_string(),
+        }
+    }
+
+    /// Looks a preset up by the name accepted in `LOG_THEME`.
+    pub fn by_name(name: &str) -> Option<Self> {
+        Theme::parse(name).ok()
+    }
+
+    /// `by_name` with an error naming the accepted presets.
+    pub fn parse(name: &str) -> Result<Self, ConfigError> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "default" => Ok(Theme::default()),
+            "colorblind" => Ok(Theme::colorblind()),
+            "high-contrast" | "high_contrast" | "highcontrast" => Ok(Theme::high_contrast()),
+            _ => Err(ConfigError::UnknownTheme(name.to_string())),
+        }
+    }
+
+    /// Name of the built-in preset this theme equals, or `"custom"`.
+    pub fn preset_name(&self) -> &'static str {
+        if *self == Theme::default() { "default" }
+        else if *self == Theme::colorblind() { "colorblind" }
+        else if *self == Theme::high_contrast() { "high-contrast" }
+        else { "custom" }
+    }
+
+    fn style_mut(&mut self, level: Level) -> Option<&mut LevelStyle> {
+        match level {
+            Level::Debug => Some(&mut self.debug),
+            Level::Info => Some(&mut self.info),
+            Level::Warn => Some(&mut self.warn),
+            Level::Error => Some(&mut self.error),
+            Level::None => None,
+        }
+    }
+
+    pub fn style(&self, level: Level) -> &LevelStyle {
+        match level {
+            Level::Debug => &self.debug,
+            Level::Info => &self.info,
+            Level::Warn => &self.warn,
+            Level::Error => &self.error,
+            Level::None => &NONE_STYLE,
+        }
+    }
+}
+
+pub(crate) fn theme_from_env() -> Theme {
+    let Ok(name) = std::env::var("LOG_THEME") else { return Theme::default() };
+
+    match Theme::by_name(&name) {
+        Some(theme) => {
+            note_config("theme", ConfigSource::Env("LOG_THEME"), theme.preset_name());
+            theme
+        }
+        None => {
+            note_config("theme", ConfigSource::Ignored("LOG_THEME"), format!("{name} (unknown theme)"));
+            Theme::default()
+        }
+    }
+}
+
+/// Replaces the theme; also ends a schedule set by `set_theme_schedule`.
+pub fn set_theme(theme: Theme) {
+    *THEME_SCHEDULE.lock().unwrap() = None;
+    SCHEDULE_ACTIVE.store(false, Ordering::Relaxed);
+
+    let mut current = THEME.write().unwrap();
+    note_config("theme", ConfigSource::Api("set_theme"), theme.preset_name());
+    *current = theme;
+}
+
+pub fn theme() -> Theme {
+    THEME.read().unwrap().clone()
+}
+
+/// Replaces the style of one level in the current theme, e.g. a warning
+/// color that is easier to tell apart. With a theme schedule, both of its
+/// themes get the style and the schedule keeps running.
+pub fn set_level_style(level: Level, style: LevelStyle) -> Result<(), ConfigError> {
+    if level == Level::None { return Err(ConfigError::StyleForNone); }
+    restyle(|theme| {
+        if let Some(current) = theme.style_mut(level) { *current = style.clone(); }
+    });
+    Ok(())
+}
+
+/// Puts the default theme's level styles back, in the scheduled themes too;
+/// the alert style stays.
+pub fn reset_level_styles() {
+    restyle(|theme| *theme = Theme { alert: std::mem::take(&mut theme.alert), ..Theme::default() });
+}
+
+/// Applies `change` to the current theme and to both scheduled ones, under
+/// the locks `toggle_theme` takes, so concurrent changes don't undo each other.
+fn restyle(change: impl Fn(&mut Theme)) {
+    let mut schedule = THEME_SCHEDULE.lock().unwrap();
+    let mut current = THEME.write().unwrap();
+    change(&mut current);
+    if let Some(schedule) = schedule.as_mut() {
+        change(&mut schedule.light);
+        change(&mut schedule.dark);
+    }
+    note_config("theme", ConfigSource::Api("set_level_style"), current.preset_name());
+}
+
+/// `set_theme` with a preset name, as in `LOG_THEME`.
+pub fn set_theme_by_name(name: &str) -> Result<(), ConfigError> {
+    set_theme(Theme::parse(name)?);
+    Ok(())
+}
+
+/// `set_theme_by_name` that warns and uses the default theme on a bad name.
+pub fn set_theme_by_name_or_default(name: &str) {
+    if let Err(e) = set_theme_by_name(name) {
+        self_log(Level::Warn, format_args!("using the default theme: {e}"));
+        set_theme(Theme::default());
+    }
+}
+
+/// Hour and minute of local time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    pub const fn new(hour: u8, minute: u8) -> Self {
+        TimeOfDay { hour, minute }
+    }
+
+    fn minute_of_day(self) -> u16 {
+        (self.hour.min(23) as u16) * 60 + self.minute.min(59) as u16
+    }
+}
+
+struct ThemeSchedule {
+    light: Theme,
+    dark: Theme,
+    light_from: u16,
+    dark_from: u16,
+    /// Whether the schedule said dark at the last check
+    scheduled_dark: bool,
+    /// Flipped by `toggle_theme` until the schedule next switches
+    toggled: bool,
+}
+
+impl ThemeSchedule {
+    fn is_dark_at(&self, minute: u16) -> bool {
+        if self.light_from <= self.dark_from {
+            !(self.light_from..self.dark_from).contains(&minute)
+        } else {
+            (self.dark_from..self.light_from).contains(&minute)
+        }
+    }
+
+    fn active(&self) -> &Theme {
+        if self.scheduled_dark != self.toggled { &self.dark } else { &self.light }
+    }
+}
+
+static THEME_SCHEDULE: Mutex<Option<ThemeSchedule>> = Mutex::new(None);
+static SCHEDULE_ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Unix minute of the last schedule check; the schedule is evaluated at most once a minute.
+static SCHEDULE_CHECKED: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Switches between `light` and `dark` by local time: `light` from
+/// `switch_times.0`, `dark` from `switch_times.1`. Checked at most once a
+/// minute, when a record is printed.
+pub fn set_theme_schedule(light: Theme, dark: Theme, switch_times: (TimeOfDay, TimeOfDay)) {
+    let (light_from, dark_from) = (switch_times.0.minute_of_day(), switch_times.1.minute_of_day());
+    note_config("theme", ConfigSource::Api("set_theme_schedule"), format_args!(
+        "{} from {:02}:{:02}, {} from {:02}:{:02}",
+        light.preset_name(), switch_times.0.hour, switch_times.0.minute,
+        dark.preset_name(), switch_times.1.hour, switch_times.1.minute,
+    ));
+
+    let mut schedule = THEME_SCHEDULE.lock().unwrap();
+    *schedule = Some(ThemeSchedule { light, dark, light_from, dark_from, scheduled_dark: false, toggled: false });
+    SCHEDULE_CHECKED.store(u64::MAX, Ordering::Relaxed);
+    SCHEDULE_ACTIVE.store(true, Ordering::Relaxed);
+    drop(schedule);
+
+    apply_theme_schedule();
+}
+
+/// Switches to the other theme of the schedule until its next scheduled
+/// switch, e.g. from a keybinding. Returns `false` if no schedule is set.
+pub fn toggle_theme() -> bool {
+    let mut schedule = THEME_SCHEDULE.lock().unwrap();
+    let Some(schedule) = schedule.as_mut() else { return false };
+
+    schedule.toggled = !schedule.toggled;
+    *THEME.write().unwrap() = schedule.active().clone();
+    true
+}
+
+fn apply_theme_schedule() {
+    if !SCHEDULE_ACTIVE.load(Ordering::Relaxed) { return; }
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let minute = now / 60;
+    if SCHEDULE_CHECKED.swap(minute, Ordering::Relaxed) == minute { return; }
+
+    let mut schedule = THEME_SCHEDULE.lock().unwrap();
+    let Some(schedule) = schedule.as_mut() else { return };
+
+    let dark = schedule.is_dark_at(local_minute_of_day(now));
+    if dark != schedule.scheduled_dark {
+        schedule.scheduled_dark = dark;
+        schedule.toggled = false;
+    }
+    *THEME.write().unwrap() = schedule.active().clone();
+}
+
+/// Minute of the local day for a unix time.
+fn local_minute_of_day(unix_secs: u64) -> u16 {
+    let local = unix_secs as i64 + i64::from(local_offset_at(unix_secs as i64));
+    (local.rem_euclid(86_400) / 60) as u16
+}
+
+/// Seconds east of UTC at a unix time, from the C library's time zone rules.
+#[cfg(unix)]
+fn local_offset_at(unix_secs: i64) -> i32 {
+    use std::os::raw::{c_char, c_int, c_long};
+
+    #[repr(C)]
+    struct Tm {
+        tm_sec: c_int,
+        tm_min: c_int,
+        tm_hour: c_int,
+        tm_mday: c_int,
+        tm_mon: c_int,
+        tm_year: c_int,
+        tm_wday: c_int,
+        tm_yday: c_int,
+        tm_isdst: c_int,
+        tm_gmtoff: c_long,
+        tm_zone: *const c_char,
+    }
+
+    /// `time_t`: `long` on Linux and Apple platforms, 64 bits on the BSDs.
+    #[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+    type TimeT = c_long;
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple")))]
+    type TimeT = i64;
+
+    extern "C" {
+        fn tzset();
+        fn localtime_r(time: *const TimeT, result: *mut Tm) -> *mut Tm;
+    }
+
+    // localtime_r need not read `TZ` itself; tzset does, once per process.
+    static TZSET: std::sync::Once = std::sync::Once::new();
+    // SAFETY: tzset has no preconditions.
+    TZSET.call_once(|| unsafe { tzset() });
+
+    // Out of range for a 32-bit time_t: no offset.
+    let Some(time) = TimeT::try_from(unix_secs).ok() else { return 0 };
+    let mut tm = std::mem::MaybeUninit::<Tm>::uninit();
+    // SAFETY: localtime_r fills `tm` when it returns non-null.
+    unsafe {
+        if localtime_r(&time, tm.as_mut_ptr()).is_null() { return 0; }
+        tm.assume_init().tm_gmtoff as i32
+    }
+}
+
+/// UTC elsewhere.
+#[cfg(not(unix))]
+fn local_offset_at(_unix_secs: i64) -> i32 {
+    0
+}
+
+/// The registered aliases as the core's lookup.
+#[cfg(feature = "aliases")]
+struct AliasTable<'a>(&'a HashMap<String, AliasDef>);
+
+#[cfg(feature = "aliases")]
+impl render::AliasLookup for AliasTable<'_> {
+    fn lookup(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(|def| def.resolved.as_str())
+    }
+}
+
+/// `text` as markup that prints it as is.
+fn escape_markup(text: &str) -> Cow<'_, str> {
+    if !cfg!(feature = "markup") || !text.contains('<') { return Cow::Borrowed(text); }
+    Cow::Owned(text.replace('<', "\\<"))
+}
+
+fn apply_markup(input: &str, default_codes: &str) -> String {
+    let mut out = String::with_capacity(input.len() + 16);
+
+    #[cfg(feature = "aliases")]
+    let aliases = ALIASES.lock().unwrap();
+    #[cfg(feature = "aliases")]
+    let aliases = &AliasTable(&aliases);
+    #[cfg(not(feature = "aliases"))]
+    let aliases = &render::NoAliases;
+
+    let _ = render::write_markup(&mut out, input, default_codes, aliases);
+    out
+}
+
+/// A proleptic Gregorian date in UTC, computed from days since 1970-01-01.
+#[cfg(feature = "timestamps")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CivilDate {
+    pub year: i32,
+    /// 1..=12
+    pub month: u32,
+    /// 1..=31
+    pub day: u32,
+    days_since_epoch: i64,
+}
+
+#[cfg(feature = "timestamps")]
+const WEEKDAY_ABBRS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+#[cfg(feature = "timestamps")]
+impl CivilDate {
+    pub fn from_days(days_since_epoch: i64) -> Self {
+        let z = days_since_epoch + 719_468;
+        let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+        let doe = z - era * 146_097; // [0, 146096]
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365; // [0, 399]
+        let y = (yoe as i32) + (era as i32) * 400;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100 + yoe / 400); // [0, 365], counted from March 1st
+        let mp = (5 * doy + 2) / 153; // [0, 11]
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32; // [1, 31]
+        let month = (mp + if mp < 10 { 3 } else { -9 }) as u32; // [1, 12]
+        let year = y + if month <= 2 { 1 } else { 0 };
+
+        CivilDate { year, month, day, days_since_epoch }
+    }
+
+    /// 0 = Sunday .. 6 = Saturday; 1970-01-01 was a Thursday.
+    pub fn weekday(&self) -> u32 {
+        (self.days_since_epoch + 4).rem_euclid(7) as u32
+    }
+
+    /// `Sun`, `Mon`, ... `Sat`.
+    pub fn weekday_abbr(&self) -> &'static str {
+        WEEKDAY_ABBRS[self.weekday() as usize]
+    }
+
+    /// Day of the year, 1..=366.
+    pub fn ordinal(&self) -> u32 {
+        const DAYS_BEFORE_MONTH: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
+        let leap = (self.year % 4 == 0 && self.year % 100 != 0) || self.year % 400 == 0;
+        DAYS_BEFORE_MONTH[self.month as usize - 1] + self.day + u32::from(leap && self.month > 2)
+    }
+}
+
+/// The clock timestamps are shown in.
+#[cfg(feature = "timestamps")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimeZoneMode {
+    /// UTC, suffixed `Z`
+    Utc,
+    /// The platform's offset, read once when the mode is set; set it again
+    /// to pick up a daylight saving change
+    Local,
+    /// Seconds east of UTC, e.g. `-11 * 3600`; clamped to under a day
+    FixedOffset(i32),
+}
+
+#[cfg(feature = "timestamps")]
+impl TimeZoneMode {
+    fn offset_at(self, unix_secs: i64) -> i32 {
+        match self {
+            TimeZoneMode::Utc => 0,
+            TimeZoneMode::Local => local_offset_at(unix_secs),
+            TimeZoneMode::FixedOffset(offset) => offset.clamp(-86_399, 86_399),
+        }
+    }
+}
+
+/// The mode and its offset in seconds; `None` until `set_timezone` is
+/// called, which keeps the plain UTC timestamp without a suffix.
+#[cfg(feature = "timestamps")]
+static TIMEZONE: RwLock<Option<(TimeZoneMode, i32)>> = RwLock::new(None);
+
+/// Shows timestamps in `mode` and adds the offset after them: `Z` for UTC,
+/// otherwise e.g. `+02:00`. Without a call they stay in UTC, unmarked
+/// except in JSON.
+#[cfg(feature = "timestamps")]
+pub fn set_timezone(mode: TimeZoneMode) {
+    note_config("timezone", ConfigSource::Api("set_timezone"), format_args!("{mode:?}"));
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    *TIMEZONE.write().unwrap() = Some((mode, mode.offset_at(now)));
+}
+
+#[cfg(feature = "timestamps")]
+pub fn timezone() -> TimeZoneMode {
+    TIMEZONE.read().unwrap().map_or(TimeZoneMode::Utc, |(mode, _)| mode)
+}
+
+/// `time` as the logger prints it in `mode` and `format`, e.g.
+/// `2024.05.01 14:00:00.000+02:00`; empty for `TimestampFormat::None`.
+/// `Local` uses the offset in effect at `time`. The uptime formats ignore
+/// `time` and show the uptime now.
+#[cfg(feature = "timestamps")]
+pub fn format_timestamp(time: SystemTime, mode: TimeZoneMode, format: &TimestampFormat) -> String {
+    let dur = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format.render(dur, Some(mode.offset_at(dur.as_secs() as i64))).unwrap_or_default()
+}
+
+/// How timestamps are written in text output. JSON always uses RFC 3339,
+/// unless the format is `None`.
+#[cfg(feature = "timestamps")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TimestampFormat {
+    /// `2024.05.01 12:00:00.000`, the default
+    Standard,
+    /// `2024-05-01T12:00:00.000Z`, always with the offset
+    Rfc3339,
+    /// `20240501T120000.000`
+    Compact,
+    /// `12:00:00.000`
+    TimeOnly,
+    /// Milliseconds since the Unix epoch, `1714564800000`; never has an offset
+    UnixMillis,
+    /// Seconds since the first record in an uptime format or the last
+    /// `reset_uptime`, right-aligned: `   12.043s`
+    UptimeMillis,
+    /// Like `UptimeMillis` with microseconds: `   12.043721s`
+    UptimeMicros,
+    /// No timestamp, and no brackets around it
+    None,
+    /// A pattern from `TimestampFormat::parse`, e.g. `%H:%M:%S.%6f`
+    Custom(TimestampPattern),
+}
+
+/// A strftime-like pattern checked by `TimestampFormat::parse`.
+#[cfg(feature = "timestamps")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct TimestampPattern {
+    source: String,
+    pieces: Vec<Piece>,
+}
+
+#[cfg(feature = "timestamps")]
+impl fmt::Debug for TimestampPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+/// One part of a timestamp: a field or a literal character.
+#[cfg(feature = "timestamps")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Piece {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Millis,
+    Micros,
+    Nanos,
+    /// `Mon`
+    Weekday,
+    /// Day of the year, `001`
+    Ordinal,
+    /// `Z` or `+HH:MM`
+    Offset,
+    UnixMillis,
+    UptimeMillis,
+    UptimeMicros,
+    Literal(char),
+}
+
+#[cfg(feature = "timestamps")]
+impl Piece {
+    /// The rest of `s` after this piece, if `s` starts with something it could have printed.
+    fn strip(self, s: &str) -> Option<&str> {
+        let digits = |n: usize| {
+            let (digits, rest) = s.split_at_checked(n)?;
+            digits.bytes().all(|b| b.is_ascii_digit()).then_some(rest)
+        };
+        match self {
+            Piece::Year => digits(4),
+            Piece::Month | Piece::Day | Piece::Hour | Piece::Minute | Piece::Second => digits(2),
+            Piece::Millis => digits(3),
+            Piece::Micros => digits(6),
+            Piece::Nanos => digits(9),
+            Piece::Weekday => {
+                let (name, rest) = s.split_at_checked(3)?;
+                WEEKDAY_ABBRS.contains(&name).then_some(rest)
+            }
+            Piece::Ordinal => digits(3),
+            Piece::Offset => s.strip_prefix('Z').or_else(|| {
+                let rest = s.strip_prefix(['+', '-'])?;
+                let (hours, rest) = rest.split_at_checked(2)?;
+                let (minutes, rest) = rest.strip_prefix(':')?.split_at_checked(2)?;
+                hours.bytes().chain(minutes.bytes()).all(|b| b.is_ascii_digit()).then_some(rest)
+            }),
+            Piece::UnixMillis => {
+                let end = s.bytes().position(|b| !b.is_ascii_digit()).unwrap_or(s.len());
+                (end > 0).then(|| &s[end..])
+            }
+            Piece::UptimeMillis | Piece::UptimeMicros => {
+                let precision = if self == Piece::UptimeMillis { Piece::Millis } else { Piece::Micros };
+                let rest = Piece::UnixMillis.strip(s.trim_start_matches(' '))?;
+                precision.strip(rest.strip_prefix('.')?)?.strip_prefix('s')
+            }
+            Piece::Literal(c) => s.strip_prefix(c),
+        }
+    }
+}
+
+#[cfg(feature = "timestamps")]
+impl TimestampFormat {
+    /// `standard`, `rfc3339`, `compact`, `time-only`, `unix-millis`,
+    /// `uptime`, `uptime-micros` or `none`, case-insensitively, or a pattern of `%Y %m %d %H %M %S`,
+    /// `%3f` (milliseconds), `%6f` (microseconds), `%9f` (nanoseconds), `%a` (`Mon`), `%j` (day
+    /// of the year, `001`), `%z` (`Z` or `+02:00`) and `%%`, with any other
+    /// text kept as is.
+    pub fn parse(spec: &str) -> Result<TimestampFormat, ConfigError> {
+        match spec.trim().to_ascii_lowercase().as_str() {
+            "standard" => return Ok(TimestampFormat::Standard),
+            "rfc3339" => return Ok(TimestampFormat::Rfc3339),
+            "compact" => return Ok(TimestampFormat::Compact),
+            "time-only" => return Ok(TimestampFormat::TimeOnly),
+            "unix-millis" => return Ok(TimestampFormat::UnixMillis),
+            "uptime" => return Ok(TimestampFormat::UptimeMillis),
+            "uptime-micros" => return Ok(TimestampFormat::UptimeMicros),
+            "none" => return Ok(TimestampFormat::None),
+            _ => {}
+        }
+
+        let bad = || ConfigError::BadTimestampFormat(spec.to_string());
+        if !spec.contains('%') { return Err(bad()); }
+
+        let mut pieces = Vec::new();
+        let mut chars = spec.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                pieces.push(Piece::Literal(c));
+                continue;
+            }
+            pieces.push(match chars.next() {
+                Some('Y') => Piece::Year,
+                Some('m') => Piece::Month,
+                Some('d') => Piece::Day,
+                Some('H') => Piece::Hour,
+                Some('M') => Piece::Minute,
+                Some('S') => Piece::Second,
+                Some('a') => Piece::Weekday,
+                Some('j') => Piece::Ordinal,
+                Some('z') => Piece::Offset,
+                Some('%') => Piece::Literal('%'),
+                Some('3') if chars.next() == Some('f') => Piece::Millis,
+                Some('6') if chars.next() == Some('f') => Piece::Micros,
+                Some('9') if chars.next() == Some('f') => Piece::Nanos,
+                _ => return Err(bad()),
+            });
+        }
+        Ok(TimestampFormat::Custom(TimestampPattern { source: spec.to_string(), pieces }))
+    }
+
+    fn pieces(&self) -> &[Piece] {
+        use Piece::*;
+
+        const STANDARD: &[Piece] =
+            &[Year, Literal('.'), Month, Literal('.'), Day, Literal(' '), Hour, Literal(':'), Minute, Literal(':'), Second, Literal('.'), Millis];
+        const RFC3339: &[Piece] =
+            &[Year, Literal('-'), Month, Literal('-'), Day, Literal('T'), Hour, Literal(':'), Minute, Literal(':'), Second, Literal('.'), Millis, Offset];
+        const COMPACT: &[Piece] = &[Year, Month, Day, Literal('T'), Hour, Minute, Second, Literal('.'), Millis];
+        const TIME_ONLY: &[Piece] = &[Hour, Literal(':'), Minute, Literal(':'), Second, Literal('.'), Millis];
+
+        match self {
+            TimestampFormat::Standard => STANDARD,
+            TimestampFormat::Rfc3339 => RFC3339,
+            TimestampFormat::Compact => COMPACT,
+            TimestampFormat::TimeOnly => TIME_ONLY,
+            TimestampFormat::UnixMillis => &[UnixMillis],
+            TimestampFormat::UptimeMillis => &[UptimeMillis],
+            TimestampFormat::UptimeMicros => &[UptimeMicros],
+            TimestampFormat::None => &[],
+            TimestampFormat::Custom(pattern) => &pattern.pieces,
+        }
+    }
+
+    /// Whether the offset follows the timestamp once a time zone is set.
+    fn appends_offset(&self) -> bool {
+        matches!(self, TimestampFormat::Standard | TimestampFormat::Compact | TimestampFormat::TimeOnly)
+    }
+
+    /// The timestamp `since_epoch` at `offset` seconds east of UTC; `None`
+    /// for no time zone, which keeps UTC and adds no suffix.
+    fn render(&self, since_epoch: Duration, offset: Option<i32>) -> Option<String> {
+        use std::fmt::Write as _;
+
+        if *self == TimestampFormat::None { return None; }
+
+        // A negative offset can put the local time before midnight of the UTC day.
+        let local = since_epoch.as_secs() as i64 + i64::from(offset.unwrap_or(0));
+        let date = CivilDate::from_days(local.div_euclid(86_400));
+        let sod = local.rem_euclid(86_400);
+
+        let mut out = String::with_capacity(32);
+        for piece in self.pieces() {
+            let _ = match *piece {
+                Piece::Year => write!(out, "{:04}", date.year),
+                Piece::Month => write!(out, "{:02}", date.month),
+                Piece::Day => write!(out, "{:02}", date.day),
+                Piece::Hour => write!(out, "{:02}", sod / 3_600),
+                Piece::Minute => write!(out, "{:02}", (sod % 3_600) / 60),
+                Piece::Second => write!(out, "{:02}", sod % 60),
+                Piece::Millis => write!(out, "{:03}", since_epoch.subsec_millis()),
+                Piece::Micros => write!(out, "{:06}", since_epoch.subsec_micros()),
+                Piece::Nanos => write!(out, "{:09}", since_epoch.subsec_nanos()),
+                Piece::Weekday => write!(out, "{}", date.weekday_abbr()),
+                Piece::Ordinal => write!(out, "{:03}", date.ordinal()),
+                Piece::Offset => write!(out, "{}", offset_suffix(offset.unwrap_or(0))),
+                Piece::UnixMillis => write!(out, "{}", since_epoch.as_millis()),
+                Piece::UptimeMillis => {
+                    let uptime = uptime();
+                    write!(out, "{:>5}.{:03}s", uptime.as_secs(), uptime.subsec_millis())
+                }
+                Piece::UptimeMicros => {
+                    let uptime = uptime();
+                    write!(out, "{:>5}.{:06}s", uptime.as_secs(), uptime.subsec_micros())
+                }
+                Piece::Literal(c) => write!(out, "{c}"),
+            };
+        }
+        if let Some(offset) = offset.filter(|_| self.appends_offset()) {
+            out.push_str(&offset_suffix(offset));
+        }
+        Some(out)
+    }
+
+    /// Whether `s` could be a timestamp printed in this format.
+    fn matches(&self, s: &str) -> bool {
+        let mut rest = s;
+        for piece in self.pieces() {
+            let Some(after) = piece.strip(rest) else { return false };
+            rest = after;
+        }
+        rest.is_empty() || (self.appends_offset() && Piece::Offset.strip(rest) == Some(""))
+    }
+}
+
+#[cfg(feature = "timestamps")]
+static TIMESTAMP_FORMAT: RwLock<TimestampFormat> = RwLock::new(TimestampFormat::Standard);
+/// RFC 3339 with nanoseconds, for `JsonFlavor::Otel`.
+#[cfg(feature = "timestamps")]
+static RFC3339_NANOS: LazyLock<TimestampFormat> = LazyLock::new(|| {
+    use Piece::*;
+
+    let pieces = vec![
+        Year, Literal('-'), Month, Literal('-'), Day, Literal('T'), Hour, Literal(':'), Minute, Literal(':'), Second, Literal('.'), Nanos, Offset,
+    ];
+    TimestampFormat::Custom(TimestampPattern { source: String::from("%Y-%m-%dT%H:%M:%S.%9f%z"), pieces })
+});
+/// Zero of the uptime formats, set by the first record that shows it.
+#[cfg(feature = "timestamps")]
+static UPTIME_START: RwLock<Option<Instant>> = RwLock::new(None);
+
+#[cfg(feature = "timestamps")]
+fn uptime() -> Duration {
+    if let Some(start) = *UPTIME_START.read().unwrap() { return start.elapsed(); }
+    UPTIME_START.write().unwrap().get_or_insert_with(Instant::now).elapsed()
+}
+
+/// Zeroes the clock of `TimestampFormat::UptimeMillis` and `UptimeMicros`,
+/// e.g. between benchmark iterations.
+#[cfg(feature = "timestamps")]
+pub fn reset_uptime() {
+    *UPTIME_START.write().unwrap() = Some(Instant::now());
+}
+
+/// See `TimestampFormat`; `set_timezone` picks the clock.
+#[cfg(feature = "timestamps")]
+pub fn set_timestamp_format(format: TimestampFormat) {
+    note_config("timestamp_format", ConfigSource::Api("set_timestamp_format"), format_args!("{format:?}"));
+    *TIMESTAMP_FORMAT.write().unwrap() = format;
+}
+
+#[cfg(feature = "timestamps")]
+pub fn timestamp_format() -> TimestampFormat {
+    TIMESTAMP_FORMAT.read().unwrap().clone()
+}
+
+/// `Z`, or `+HH:MM` / `-HH:MM`; seconds of the offset are dropped.
+#[cfg(feature = "timestamps")]
+fn offset_suffix(offset: i32) -> String {
+    if offset == 0 { return String::from("Z"); }
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.unsigned_abs() / 60;
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BellMode {
+    Off,
+    OnError,
+    OnWarnAndError,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BellStyle {
+    /// BEL (`\x07`)
+    Audible,
+    /// Reverse video on/off (DECSCNM), flashes on terminals that support it
+    Visual,
+}
+
+pub(crate) struct BellState {
+    style: BellStyle,
+    cooldown: Duration,
+    last: Option<Instant>,
+}
+
+impl BellState {
+    pub(crate) const fn new() -> Self {
+        BellState { style: BellStyle::Audible, cooldown: Duration::from_secs(2), last: None }
+    }
+}
+
+static BELL_ENV_DISABLED: LazyLock<bool> = LazyLock::new(|| {
+    let disabled = matches!(std::env::var("LOG_BELL").as_deref(), Ok("0") | Ok("off") | Ok("false"));
+    if disabled { note_config("bell", ConfigSource::Env("LOG_BELL"), "Off"); }
+    disabled
+});
+
+static STDOUT_IS_TTY: LazyLock<bool> = LazyLock::new(|| std::io::stdout().is_terminal());
+
+/// Kept apart from `BELL` so records that want no bell never take its lock.
+static BELL_MODE: AtomicU8 = AtomicU8::new(BellMode::Off as u8);
+
+pub fn set_bell(mode: BellMode) {
+    note_config("bell", ConfigSource::Api("set_bell"), format!("{mode:?}"));
+    BELL_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+fn bell_mode() -> BellMode {
+    match BELL_MODE.load(Ordering::Relaxed) {
+        m if m == BellMode::OnError as u8 => BellMode::OnError,
+        m if m == BellMode::OnWarnAndError as u8 => BellMode::OnWarnAndError,
+        _ => BellMode::Off,
+    }
+}
+
+pub fn set_bell_style(style: BellStyle) {
+    note_config("bell_style", ConfigSource::Api("set_bell_style"), format!("{style:?}"));
+    BELL.lock().unwrap().style = style;
+}
+
+/// Minimum time between two bells; records inside the window stay silent.
+pub fn set_bell_cooldown(cooldown: Duration) {
+    note_config("bell_cooldown", ConfigSource::Api("set_bell_cooldown"), format!("{cooldown:?}"));
+    BELL.lock().unwrap().cooldown = cooldown;
+}
+
+fn bell_for(level: Level) -> &'static str {
+    if !bell_wanted(bell_mode(), level) { return ""; }
+    let on_terminal = !*BELL_ENV_DISABLED && *STDOUT_IS_TTY && !WRITER_SET.load(Ordering::Relaxed);
+    ring_bell(&mut BELL.lock().unwrap(), on_terminal, Instant::now())
+}
+
+fn bell_wanted(mode: BellMode, level: Level) -> bool {
+    match mode {
+        BellMode::Off => false,
+        BellMode::OnError => level == Level::Error,
+        BellMode::OnWarnAndError => matches!(level, Level::Warn | Level::Error),
+    }
+}
+
+/// The bytes for a record that wants a bell: none unless the console is a
+/// terminal, and none inside the cooldown after the last bell.
+fn ring_bell(bell: &mut BellState, on_terminal: bool, now: Instant) -> &'static str {
+    if !on_terminal { return ""; }
+    if let Some(last) = bell.last {
+        if now.duration_since(last) < bell.cooldown { return ""; }
+    }
+    bell.last = Some(now);
+
+    match bell.style {
+        BellStyle::Audible => "\x07",
+        BellStyle::Visual => "\x1b[?5h\x1b[?5l",
+    }
+}
+
+const MAX_GROUP_INDENT: usize = 32;
+
+/// What each open group puts in front of a record's message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GroupIndent {
+    /// Two spaces (default)
+    Spaces,
+    /// A dimmed `· `, or `. ` with the ASCII charset
+    Dots,
+}
+
+static GROUP_INDENT: AtomicU8 = AtomicU8::new(GroupIndent::Spaces as u8);
+
+pub fn set_group_indent(indent: GroupIndent) {
+    note_config("group_indent", ConfigSource::Api("set_group_indent"), format_args!("{indent:?}"));
+    GROUP_INDENT.store(indent as u8, Ordering::Relaxed);
+}
+
+pub fn group_indent() -> GroupIndent {
+    if GROUP_INDENT.load(Ordering::Relaxed) == GroupIndent::Dots as u8 { GroupIndent::Dots } else { GroupIndent::Spaces }
+}
+
+fn indent_dot() -> &'static str {
+    if *glyphs() == render::ASCII_GLYPHS { ". " } else { "\u{b7} " }
+}
+
+static UNBALANCED_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Open groups of the current thread, innermost last.
+struct GroupStack(Vec<Level>);
+
+impl Drop for GroupStack {
+    fn drop(&mut self) {
+        if !self.0.is_empty() && !UNBALANCED_WARNED.swap(true, Ordering::Relaxed) && is_enabled(Level::Warn) {
+            self_log(Level::Warn, format_args!("{} group(s) still open when the thread exited", self.0.len()));
+        }
+    }
+}
+
+thread_local! {
+    static GROUPS: RefCell<GroupStack> = const { RefCell::new(GroupStack(Vec::new())) };
+}
+
+/// Number of groups currently open on this thread.
+pub fn current_depth() -> usize {
+    // try_with: the unbalanced warning is printed while the thread-local is being destroyed
+    GROUPS.try_with(|groups| groups.borrow().0.len()).unwrap_or(0)
+}
+
+pub fn group_begin(level: Level, location: Location, args: fmt::Arguments) {
+    if record_enabled(level, location.module) {
+        print_with_prefix(level, location, args);
+    }
+
+    // Past the thread's teardown there is no stack to indent by.
+    let _ = GROUPS.try_with(|groups| groups.borrow_mut().0.push(level));
+}
+
+/// Open group from `group!`; dropping it ends the group. Tied to the thread
+/// that opened it, like the depth it counts toward.
+#[must_use = "the group ends as soon as the guard is dropped"]
+pub struct GroupGuard {
+    location: Location,
+    _thread: std::marker::PhantomData<*const ()>,
+}
+
+pub fn group_guard(level: Level, location: Location, args: fmt::Arguments) -> GroupGuard {
+    group_begin(level, location, args);
+    GroupGuard { location, _thread: std::marker::PhantomData }
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        group_end(self.location, None);
+    }
+}
+
+pub fn group_end(location: Location, summary: Option<fmt::Arguments>) {
+    let Ok(level) = GROUPS.try_with(|groups| groups.borrow_mut().0.pop()) else { return };
+
+    match level {
+        Some(level) => {
+            if let Some(summary) = summary {
+                if record_enabled(level, location.module) { print_with_prefix(level, location, summary); }
+            }
+        }
+        None => {
+            if !UNBALANCED_WARNED.swap(true, Ordering::Relaxed) && is_enabled(Level::Warn) {
+                self_log(Level::Warn, format_args!("group_end! called without a matching group_begin!"));
+            }
+        }
+    }
+}
+
+/// Records a `RecordGroup` holds before it writes them out early.
+pub const RECORD_GROUP_CAP: usize = 256;
+
+/// Console and file output of records logged through a `RecordGroup`.
+#[derive(Default)]
+struct Batch {
+    console: String,
+    /// JSON or logfmt lines
+    files: String,
+    /// Plain text lines by `ContinuationStyle`, in the styles the files use
+    files_plain: [String; 4],
+    records: usize,
+}
+
+thread_local! {
+    /// The batch of the `RecordGroup::log` call running on this thread.
+    static BATCH: RefCell<Option<Batch>> = const { RefCell::new(None) };
+}
+
+/// Appends `text` to the running batch instead of writing it; `false` if
+/// no `RecordGroup::log` call is running.
+fn batched(text: &str) -> bool {
+    with_batch(|batch| batch.console.push_str(text))
+}
+
+/// `batched` for the text of the file sinks, see `write_file_sinks`.
+fn batched_files(same: &str, plain: &[String; 4]) -> bool {
+    with_batch(|batch| {
+        batch.files.push_str(same);
+        for (batched, text) in batch.files_plain.iter_mut().zip(plain) { batched.push_str(text); }
+    })
+}
+
+fn with_batch(add: impl FnOnce(&mut Batch)) -> bool {
+    // try_with: records are also printed while the thread-locals are being
+    // destroyed, e.g. the unbalanced group warning
+    BATCH.try_with(|batch| batch.borrow_mut().as_mut().map(add).is_some()).unwrap_or(false)
+}
+
+/// Records that are written out together, with no lines of other threads
+/// in between, when the group is dropped; returned by `record_group!`.
+/// Every record keeps the timestamp of its own `log` call. A group that
+/// reaches `RECORD_GROUP_CAP` records writes them out early and goes on.
+#[must_use = "the records are written when the group is dropped"]
+pub struct RecordGroup {
+    level: Level,
+    location: Location,
+    batch: RefCell<Batch>,
+}
+
+pub fn record_group(level: Level, location: Location) -> RecordGroup {
+    RecordGroup { level, location, batch: RefCell::new(Batch::default()) }
+}
+
+impl RecordGroup {
+    /// Adds a record at the group's level: `g.log(format_args!("row {i}: {err}"))`.
+    /// Sinks from `add_sink` get it right away; the console and the file
+    /// sinks when the group is written out.
+    pub fn log(&self, message: impl fmt::Display) {
+        if !record_enabled(self.level, self.location.module) { return; }
+
+        let batch = self.batch.take();
+        let _ = BATCH.try_with(|running| *running.borrow_mut() = Some(batch));
+        print_with_prefix(self.level, self.location, format_args!("{message}"));
+        let mut batch = BATCH.try_with(|running| running.borrow_mut().take()).ok().flatten().unwrap_or_default();
+
+        batch.records += 1;
+        if batch.records >= RECORD_GROUP_CAP { flush_batch(&mut batch, self.level); }
+        self.batch.replace(batch);
+    }
+
+    /// Writes out the records so far.
+    pub fn flush(&self) {
+        flush_batch(&mut self.batch.borrow_mut(), self.level);
+    }
+}
+
+impl Drop for RecordGroup {
+    fn drop(&mut self) {
+        flush_batch(self.batch.get_mut(), self.level);
+    }
+}
+
+fn flush_batch(batch: &mut Batch, level: Level) {
+    let batch = std::mem::take(batch);
+    if !batch.files.is_empty() || batch.files_plain.iter().any(|text| !text.is_empty()) {
+        write_file_sinks(&batch.files, &batch.files_plain, level, true, None, &mut Timings(None));
+    }
+    if !batch.console.is_empty() { emit(&batch.console); }
+}
+
+/// A `dump!` record: the expression in cyan, then its `{:#?}` value.
+pub fn print_dump(level: Level, location: Location, expression: &str, value: &dyn fmt::Debug) {
+    if cfg!(feature = "markup") {
+        print_with_prefix(level, location, format_args!("<cyan>{expression}</> = {value:#?}"));
+    } else {
+        print_with_prefix(level, location, format_args!("{expression} = {value:#?}"));
+    }
+}
+
+/// Bytes per `hexdump!` row.
+const HEXDUMP_ROW: usize = 16;
+
+/// Logs `bytes` for `hexdump!` as one record group: a header with the label
+/// and the length, then rows of offset, hex in two groups of 8 and ASCII
+/// between gutters. Bytes outside printable ASCII are gray. Past `max`
+/// bytes a trailer counts the rest.
+pub fn hexdump(level: Level, location: Location, label: &dyn fmt::Display, bytes: &[u8], max: Option<usize>) {
+    use std::fmt::Write as _;
+
+    let group = record_group(level, location);
+    let plural = if bytes.len() == 1 { "" } else { "s" };
+    group.log(format_args!("{label} ({} byte{plural})", bytes.len()));
+
+    let shown = &bytes[..bytes.len().min(max.unwrap_or(usize::MAX))];
+    let markup = cfg!(feature = "markup");
+    let gutter = glyphs().gutter;
+    for (row, chunk) in shown.chunks(HEXDUMP_ROW).enumerate() {
+        let mut line = format!("{:08x} ", row * HEXDUMP_ROW);
+        for i in 0..HEXDUMP_ROW {
+            if i % 8 == 0 { line.push(' '); }
+            let _ = match chunk.get(i) {
+                Some(&byte) if markup && !(0x20..0x7f).contains(&byte) => write!(line, "<gray>{byte:02x}</> "),
+                Some(byte) => write!(line, "{byte:02x} "),
+                None => line.write_str("   "),
+            };
+        }
+        line.push(' ');
+        line.push_str(gutter);
+        for &byte in chunk {
+            match byte {
+                b'<' | b'>' if markup => { line.push('\\'); line.push(byte as char); }
+                0x20..0x7f => line.push(byte as char),
+                _ if markup => line.push_str("<gray>.</>"),
+                _ => line.push('.'),
+            }
+        }
+        line.push_str(gutter);
+        group.log(line);
+    }
+
+    if shown.len() < bytes.len() {
+        group.log(format_args!("(+{} more bytes)", bytes.len() - shown.len()));
+    }
+}
+
+struct HeartbeatEntry {
+    id: u64,
+    level: Level,
+    location: Location,
+    interval: Duration,
+    message: String,
+    started: Instant,
+    next: Instant,
+}
+
+/// Every running heartbeat; one shared thread ticks them all.
+static HEARTBEATS: Mutex<Vec<HeartbeatEntry>> = Mutex::new(Vec::new());
+static HEARTBEAT_WAKE: Condvar = Condvar::new();
+/// Held while ticks are printed, and by `Heartbeat::drop` while it
+/// unregisters, so no tick follows a completion line.
+static HEARTBEAT_PRINT: Mutex<()> = Mutex::new(());
+static HEARTBEAT_THREAD: LazyLock<()> = LazyLock::new(|| {
+    std::thread::Builder::new()
+        .name("rust_logger-heartbeat".into())
+        .spawn(|| run_heartbeats(&SystemClock))
+        .expect("cannot spawn the heartbeat thread");
+});
+
+thread_local! {
+    /// Set on the heartbeat thread while it prints ticks.
+    static PRINTING_TICKS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Time source of the heartbeat thread; tests drive a manual one.
+trait Clock {
+    fn now(&self) -> Instant;
+}
+
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A tick taken from the registry, printed after the lock is released.
+struct Tick {
+    id: u64,
+    level: Level,
+    location: Location,
+    line: String,
+}
+static NEXT_HEARTBEAT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Running heartbeat started by `heartbeat!`. Dropping it stops the ticks and
+/// logs the completion line with the total duration.
+#[must_use = "the heartbeat stops as soon as the guard is dropped"]
+pub struct Heartbeat {
+    id: u64,
+}
+
+pub fn start_heartbeat(level: Level, interval: Duration, location: Location, args: fmt::Arguments) -> Heartbeat {
+    LazyLock::force(&HEARTBEAT_THREAD);
+
+    let id = NEXT_HEARTBEAT_ID.fetch_add(1, Ordering::Relaxed);
+    let started = Instant::now();
+    let interval = interval.max(Duration::from_millis(1));
+
+    HEARTBEATS.lock().unwrap().push(HeartbeatEntry {
+        id, level, location, interval, message: args.to_string(), started, next: started + interval,
+    });
+    HEARTBEAT_WAKE.notify_one();
+
+    Heartbeat { id }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        // Waits for ticks being printed, unless this runs inside one of them
+        // or inside a sink, which a tick may be waiting for.
+        let entry = {
+            let _printing = (!PRINTING_TICKS.with(Cell::get) && !IN_SINK_WRITE.with(Cell::get))
+                .then(|| HEARTBEAT_PRINT.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+            let mut heartbeats = HEARTBEATS.lock().unwrap();
+            let Some(i) = heartbeats.iter().position(|entry| entry.id == self.id) else { return };
+            heartbeats.swap_remove(i)
+        };
+
+        if record_enabled(entry.level, entry.location.module) {
+            print_with_prefix(entry.level, entry.location, format_args!("{} done in {}", entry.message, Elapsed(entry.started.elapsed())));
+        }
+    }
+}
+
+fn run_heartbeats(clock: &dyn Clock) {
+    loop {
+        let ticks = due_ticks(&mut HEARTBEATS.lock().unwrap(), clock.now());
+        if !ticks.is_empty() { print_ticks(ticks); }
+
+        let heartbeats = HEARTBEATS.lock().unwrap();
+        drop(match heartbeats.iter().map(|entry| entry.next).min() {
+            Some(next) => HEARTBEAT_WAKE.wait_timeout(heartbeats, next.saturating_duration_since(clock.now())).unwrap().0,
+            None => HEARTBEAT_WAKE.wait(heartbeats).unwrap(),
+        });
+    }
+}
+
+/// One tick for every heartbeat due at `now`, each moved to its next interval
+/// after `now`, so ticks missed while the thread slept are not made up.
+fn due_ticks(heartbeats: &mut [HeartbeatEntry], now: Instant) -> Vec<Tick> {
+    let mut ticks = Vec::new();
+    for entry in heartbeats.iter_mut().filter(|entry| entry.next <= now) {
+        if record_enabled(entry.level, entry.location.module) {
+            ticks.push(Tick {
+                id: entry.id, level: entry.level, location: entry.location,
+                line: format!("{} (still running, {})", entry.message, Elapsed(now - entry.started)),
+            });
+        }
+        while entry.next <= now { entry.next += entry.interval; }
+    }
+    ticks
+}
+
+/// Prints ticks without the registry lock, so a sink or writer may start or
+/// stop heartbeats; ticks of heartbeats stopped meanwhile are dropped.
+fn print_ticks(ticks: Vec<Tick>) {
+    let _printing = HEARTBEAT_PRINT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    PRINTING_TICKS.with(|printing| printing.set(true));
+    for tick in ticks {
+        if !HEARTBEATS.lock().unwrap().iter().any(|entry| entry.id == tick.id) { continue; }
+        print_with_prefix(tick.level, tick.location, format_args!("{}", tick.line));
+    }
+    PRINTING_TICKS.with(|printing| printing.set(false));
+}
+
+/// Running scope timed by `time_scope!` or `time!`. Dropping it logs the
+/// label with the elapsed time at Debug; it is inert when Debug is off.
+#[must_use = "the scope is timed until the guard is dropped"]
+pub struct TimeScope {
+    running: Option<(Location, String, Instant)>,
+}
+
+pub fn time_scope(location: Location, args: fmt::Arguments) -> TimeScope {
+    // The clock is only read once the record is known to be wanted.
+    let running = record_enabled(Level::Debug, location.module).then(|| (location, args.to_string(), Instant::now()));
+    TimeScope { running }
+}
+
+impl Drop for TimeScope {
+    fn drop(&mut self) {
+        // A scope left by a panic did not finish, so it has nothing to report.
+        let Some((location, label, started)) = self.running.take() else { return };
+        if std::thread::panicking() { return; }
+        print_with_prefix(Level::Debug, location, format_args!("{label} \u{2014} done in {}", Elapsed(started.elapsed())));
+    }
+}
+
+/// Displays a duration as `850µs`, `12.3ms`, `4.2s`, `3m 05s` or `1h 02m`.
+struct Elapsed(Duration);
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Rounded to what each unit shows before the unit is picked, so
+        // 999.96ms is `1.0s` rather than `1000.0ms`.
+        let nanos = self.0.as_nanos();
+        let tenth_ms = (nanos + 50_000) / 100_000;
+        let tenth_s = (nanos + 50_000_000) / 100_000_000;
+        let secs = (nanos + 500_000_000) / 1_000_000_000;
+        if self.0 < Duration::from_millis(1) {
+            write!(f, "{}\u{b5}s", self.0.as_micros())
+        } else if tenth_ms < 10_000 {
+            write!(f, "{}.{}ms", tenth_ms / 10, tenth_ms % 10)
+        } else if tenth_s < 600 {
+            write!(f, "{}.{}s", tenth_s / 10, tenth_s % 10)
+        } else if secs < 3600 {
+            write!(f, "{}m {:02}s", secs / 60, secs % 60)
+        } else {
+            write!(f, "{}h {:02}m", secs / 3600, secs % 3600 / 60)
+        }
+    }
+}
+
+/// Entry/exit of a function under `#[instrument]`. Sync functions open a
+/// group, so records inside are indented; async ones do not, since their
+/// polls may move between threads.
+pub struct InstrumentSpan {
+    level: Level,
+    location: Location,
+    name: &'static str,
+    started: Instant,
+    grouped: bool,
+}
+
+pub fn instrument_enter(level: Level, location: Location, name: &'static str, grouped: bool, args: fmt::Arguments) -> InstrumentSpan {
+    if grouped {
+        group_begin(level, location, format_args!("{name}({args})"));
+    } else if record_enabled(level, location.module) {
+        print_with_prefix(level, location, format_args!("{name}({args})"));
+    }
+
+    InstrumentSpan { level, location, name, started: Instant::now(), grouped }
+}
+
+impl InstrumentSpan {
+    /// Ends the span of a function that returns nothing.
+    pub fn exit(self) {
+        let (level, name, elapsed) = (self.level, self.name, Elapsed(self.started.elapsed()));
+        self.finish(level, format_args!("{name} returned in {elapsed}"));
+    }
+
+    pub fn exit_with(self, value: Option<&dyn fmt::Debug>) {
+        let (level, name, elapsed) = (self.level, self.name, Elapsed(self.started.elapsed()));
+        self.finish(level, format_args!("{name} returned {} in {elapsed}", ShowDebug(value)));
+    }
+
+    /// Ends the span of a call that returned `Err`, at Warn.
+    pub fn exit_err(self, error: Option<&dyn fmt::Debug>) {
+        let (name, elapsed) = (self.name, Elapsed(self.started.elapsed()));
+        self.finish(Level::Warn, format_args!("{name} failed: {} in {elapsed}", ShowDebug(error)));
+    }
+
+    fn finish(mut self, level: Level, args: fmt::Arguments) {
+        if self.grouped {
+            self.grouped = false;
+            group_end(self.location, None);
+        }
+        if record_enabled(level, self.location.module) { print_with_prefix(level, self.location, args); }
+    }
+}
+
+impl Drop for InstrumentSpan {
+    /// A panic unwinding through the function still closes its group.
+    fn drop(&mut self) {
+        if self.grouped { group_end(self.location, None); }
+    }
+}
+
+/// Lets `#[instrument]` format values whether or not they implement `Debug`:
+/// `(&MaybeDebug(&value)).as_debug()` picks `ViaDebug` when they do and falls
+/// back to `ViaNoDebug` through auto-ref when they do not.
+pub struct MaybeDebug<'a, T>(pub &'a T);
+
+pub trait ViaDebug {
+    fn as_debug(&self) -> Option<&dyn fmt::Debug>;
+}
+
+impl<T: fmt::Debug> ViaDebug for MaybeDebug<'_, T> {
+    fn as_debug(&self) -> Option<&dyn fmt::Debug> {
+        Some(self.0)
+    }
+}
+
+pub trait ViaNoDebug {
+    fn as_debug(&self) -> Option<&dyn fmt::Debug>;
+}
+
+impl<T> ViaNoDebug for &MaybeDebug<'_, T> {
+    fn as_debug(&self) -> Option<&dyn fmt::Debug> {
+        None
+    }
+}
+
+/// `Debug` of the value, or `_` when it has none.
+pub struct ShowDebug<'a>(pub Option<&'a dyn fmt::Debug>);
+
+impl fmt::Display for ShowDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "{value:?}"),
+            None => f.write_str("_"),
+        }
+    }
+}
+
+/// Calls `f`; naming the return type here lets `?` inside an instrumented
+/// body infer its error type.
+pub fn instrument_call<T, F: FnOnce() -> T>(f: F) -> T {
+    f()
+}
+
+/// `instrument_call` for async bodies.
+pub fn instrument_future<T, F: std::future::Future<Output = T>>(future: F) -> F {
+    future
+}
+
+/// 256-color indices that read well on dark and light backgrounds and stay
+/// clear of the reds and yellows used for warnings and errors.
+const HASH_PALETTE: [u8; 12] = [33, 38, 43, 71, 75, 79, 99, 111, 141, 153, 176, 183];
+
+/// Picks a palette color for `name` using 32-bit FNV-1a, so the same name
+/// gets the same color on every run and every machine.
+pub fn hash_color(name: &str) -> u8 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+
+    HASH_PALETTE[(hash % HASH_PALETTE.len() as u32) as usize]
+}
+
+/// How the target column is tinted: explicit colors for module patterns,
+/// most specific first, and the decision per target so far.
+#[derive(Default)]
+struct TargetColors {
+    hashed: bool,
+    patterns: Vec<(String, Color)>,
+    decided: HashMap<String, Option<Color>>,
+}
+
+/// Targets are module paths, so this is only reached by foreign targets;
+/// past it, the decision is made again for each record.
+const MAX_DECIDED_TARGETS: usize = 1024;
+
+static TARGET_COLORS: LazyLock<RwLock<TargetColors>> = LazyLock::new(RwLock::default);
+/// Keeps the default, untinted target a single load.
+static HAS_TARGET_COLORS: AtomicBool = AtomicBool::new(false);
+
+fn update_target_colors(source: ConfigSource, update: impl FnOnce(&mut TargetColors)) {
+    let mut colors = TARGET_COLORS.write().unwrap();
+    update(&mut colors);
+    colors.patterns.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern_specificity(pattern)));
+    colors.decided.clear();
+    HAS_TARGET_COLORS.store(colors.hashed || !colors.patterns.is_empty(), Ordering::Relaxed);
+
+    let mut listed: Vec<String> = colors.patterns.iter().map(|(pattern, color)| format!("{pattern}={color:?}")).collect();
+    if colors.hashed { listed.push(String::from("hashed")); }
+    note_config("target_colors", source, if listed.is_empty() { String::from("(none)") } else { listed.join(", ") });
+}
+
+/// Whether each target is tinted with its `hash_color` in the pretty
+/// format instead of being dimmed, so subsystems stand apart. Off by
+/// default; `set_target_color` assignments apply either way.
+pub fn set_target_colors(hashed: bool) {
+    update_target_colors(ConfigSource::Api("set_target_colors"), |colors| colors.hashed = hashed);
+}
+
+/// Tints the targets a module pattern covers (as in `set_module_level`,
+/// e.g. `my_app::db` or `*::db`) with `color`, a name as in
+/// `Color::parse`. Beats `set_target_colors`; of several matching patterns
+/// the most specific wins.
+pub fn set_target_color(pattern: &str, color: &str) -> Result<(), ConfigError> {
+    let color = Color::parse(color)?;
+    update_target_colors(ConfigSource::Api("set_target_color"), |colors| {
+        colors.patterns.retain(|(existing, _)| existing != pattern);
+        colors.patterns.push((pattern.to_string(), color));
+    });
+    Ok(())
+}
+
+/// Removes the `set_target_color` assignments.
+pub fn clear_target_colors() {
+    update_target_colors(ConfigSource::Api("clear_target_colors"), |colors| colors.patterns.clear());
+}
+
+/// The color `target` is drawn in, or `None` for the default dim.
+pub fn target_color(target: &str) -> Option<Color> {
+    if !HAS_TARGET_COLORS.load(Ordering::Relaxed) { return None; }
+    if let Some(decided) = TARGET_COLORS.read().unwrap().decided.get(target) { return *decided; }
+
+    let mut colors = TARGET_COLORS.write().unwrap();
+    let explicit = colors.patterns.iter().find(|(pattern, _)| module_matches(pattern, target)).map(|(_, color)| *color);
+    let color = explicit.or_else(|| colors.hashed.then(|| Color::Indexed(hash_color(target))));
+    if colors.decided.len() < MAX_DECIDED_TARGETS { colors.decided.insert(target.to_string(), color); }
+    color
+}
+
+/// Short tag printed after the level label, e.g. the worker name when
+/// several processes share a terminal. An empty string removes it.
+/// Defaults to `LOG_PROCESS_TAG`.
+pub fn set_process_tag(tag: &str) {
+    let mut current = PROCESS_TAG.write().unwrap();
+    note_config("process_tag", ConfigSource::Api("set_process_tag"), if tag.is_empty() { "(none)" } else { tag });
+    *current = if tag.is_empty() { None } else { Some(tag.to_string()) };
+}
+
+pub fn process_tag() -> Option<String> {
+    PROCESS_TAG.read().unwrap().clone()
+}
+
+/// Where a record was logged, captured by the macros.
+#[derive(Clone, Copy, Debug)]
+pub struct Location {
+    pub module: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+/// How much of the call site is printed with each record.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LocationMode {
+    None,
+    /// The module path, e.g. `my_app::db` (default)
+    Module,
+    /// The source file and line, e.g. `src/db.rs:42`
+    Full,
+}
+
+static LOCATION_MODE: AtomicU8 = AtomicU8::new(LocationMode::Module as u8);
+
+pub fn set_location_mode(mode: LocationMode) {
+    note_config("location", ConfigSource::Api("set_location_mode"), format_args!("{mode:?}"));
+    LOCATION_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn location_mode() -> LocationMode {
+    match LOCATION_MODE.load(Ordering::Relaxed) {
+        0 => LocationMode::None,
+        2 => LocationMode::Full,
+        _ => LocationMode::Module,
+    }
+}
+
+/// Which records share an event counter, drawn as `#n` after the target and
+/// written as `event_seq` in JSON and logfmt: "the 3rd reconnect attempt"
+/// then has the same number on the console and in the files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventCounter {
+    /// No counter (default)
+    Off,
+    /// One counter per module path
+    PerTarget,
+    /// One counter per call site
+    PerCallsite,
+}
+
+static EVENT_COUNTER: AtomicU8 = AtomicU8::new(EventCounter::Off as u8);
+/// Keyed by module path and 0, or by file and line.
+static EVENT_COUNTERS: LazyLock<RwLock<HashMap<(&'static str, u32), AtomicU64>>> = LazyLock::new(RwLock::default);
+
+pub fn set_event_counter(counter: EventCounter) {
+    note_config("event_counter", ConfigSource::Api("set_event_counter"), format_args!("{counter:?}"));
+    EVENT_COUNTER.store(counter as u8, Ordering::Relaxed);
+}
+
+pub fn event_counter() -> EventCounter {
+    match EVENT_COUNTER.load(Ordering::Relaxed) {
+        1 => EventCounter::PerTarget,
+        2 => EventCounter::PerCallsite,
+        _ => EventCounter::Off,
+    }
+}
+
+/// Starts every event counter again from 1, e.g. between tests.
+pub fn reset_counters() {
+    EVENT_COUNTERS.write().unwrap().clear();
+}
+
+/// The number of this record among those of its target or call site, from
+/// 1; `None` while the counter is off.
+fn next_event_seq(location: &Location) -> Option<u64> {
+    let key = match event_counter() {
+        EventCounter::Off => return None,
+        EventCounter::PerTarget => (location.module, 0),
+        EventCounter::PerCallsite => (location.file, location.line),
+    };
+    // Only a target's first record takes the write lock.
+    if let Some(counter) = EVENT_COUNTERS.read().unwrap().get(&key) {
+        return Some(counter.fetch_add(1, Ordering::Relaxed) + 1);
+    }
+    let mut counters = EVENT_COUNTERS.write().unwrap();
+    Some(counters.entry(key).or_default().fetch_add(1, Ordering::Relaxed) + 1)
+}
+
+/// How the lines after the first of a multi-line message are drawn. Trailing
+/// line breaks are dropped either way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Multiline {
+    /// Indented to start under the message (default)
+    Align,
+    /// Under the message behind a dimmed `│ ` gutter
+    Gutter,
+}
+
+static MULTILINE: AtomicU8 = AtomicU8::new(Multiline::Align as u8);
+
+pub fn set_multiline(mode: Multiline) {
+    note_config("multiline", ConfigSource::Api("set_multiline"), format_args!("{mode:?}"));
+    MULTILINE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn multiline() -> Multiline {
+    if MULTILINE.load(Ordering::Relaxed) == Multiline::Gutter as u8 { Multiline::Gutter } else { Multiline::Align }
+}
+
+/// How the plain text lines of a log file or a `Format::Pretty` sink draw
+/// what follows the first line of a record: the other message lines and the
+/// `caused by:` lines. Chosen per destination with
+/// `FileSinkBuilder::continuation` and `set_sink_continuation`; without
+/// one, the lines follow the console's `set_multiline`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContinuationStyle {
+    /// Under the message behind a `│ ` gutter; `caused by:` lines are only
+    /// indented
+    Gutter,
+    /// Indented to start under the message
+    Indent,
+    /// After the whole prefix again, so each line parses on its own. Every
+    /// line of the record, the first one too, shows its `#seq` after the
+    /// prefix to tell the records apart.
+    RepeatPrefix,
+    /// At the start of the line
+    None,
+}
+
+impl ContinuationStyle {
+    const ALL: [ContinuationStyle; 4] =
+        [ContinuationStyle::Gutter, ContinuationStyle::Indent, ContinuationStyle::RepeatPrefix, ContinuationStyle::None];
+
+    /// The style the console's `multiline()` draws.
+    fn of_console() -> ContinuationStyle {
+        match multiline() {
+            Multiline::Align => ContinuationStyle::Indent,
+            Multiline::Gutter => ContinuationStyle::Gutter,
+        }
+    }
+
+    /// Decodes `style as u8 + 1`; 0 is "follow the console".
+    fn stored(stored: u8) -> ContinuationStyle {
+        stored.checked_sub(1).and_then(|i| ContinuationStyle::ALL.get(usize::from(i)).copied()).unwrap_or_else(ContinuationStyle::of_console)
+    }
+}
+
+/// Where the console draws the fields of a record. Log files, sinks, JSON
+/// and logfmt always get them inline.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FieldsLayout {
+    /// `k=v k2=v2` right after the message (default)
+    Inline,
+    /// After the message, padded to start at `fields_column()`; one space
+    /// after a message that reaches past it
+    AlignedColumn,
+    /// On a line of their own under the message, keys dimmed and values
+    /// colored by type: numbers cyan, `true` and `false` magenta
+    NextLine,
+}
+
+static FIELDS_LAYOUT: AtomicU8 = AtomicU8::new(FieldsLayout::Inline as u8);
+static FIELDS_COLUMN: AtomicUsize = AtomicUsize::new(60);
+
+pub fn set_fields_layout(layout: FieldsLayout) {
+    note_config("fields_layout", ConfigSource::Api("set_fields_layout"), format_args!("{layout:?}"));
+    FIELDS_LAYOUT.store(layout as u8, Ordering::Relaxed);
+}
+
+pub fn fields_layout() -> FieldsLayout {
+    match FIELDS_LAYOUT.load(Ordering::Relaxed) {
+        1 => FieldsLayout::AlignedColumn,
+        2 => FieldsLayout::NextLine,
+        _ => FieldsLayout::Inline,
+    }
+}
+
+/// The column, counted in terminal cells from the start of the line, where
+/// `FieldsLayout::AlignedColumn` starts the fields (default 60).
+pub fn set_fields_column(column: usize) {
+    note_config("fields_column", ConfigSource::Api("set_fields_column"), column);
+    FIELDS_COLUMN.store(column, Ordering::Relaxed);
+}
+
+pub fn fields_column() -> usize {
+    FIELDS_COLUMN.load(Ordering::Relaxed)
+}
+
+static SHOW_THREAD: AtomicBool = AtomicBool::new(false);
+static NEXT_THREAD_NUMBER: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_LABEL: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Whether lines name the thread that logged them, dimmed in brackets after
+/// the label: its name, or `#n` for unnamed threads, numbered in the order
+/// they first log. Off by default.
+pub fn show_thread(show: bool) {
+    note_config("show_thread", ConfigSource::Api("show_thread"), show);
+    SHOW_THREAD.store(show, Ordering::Relaxed);
+}
+
+static THREAD_COLORS: AtomicBool = AtomicBool::new(false);
+
+/// Whether the thread names from `show_thread` are drawn in their
+/// `hash_color`, like process tags, instead of dimmed, so a pool's
+/// interleaved lines can be told apart. A named thread gets the same color
+/// on every run; `#n` threads are numbered in the order they first log.
+/// Two names can share a color. Off by default.
+pub fn set_thread_colors(colored: bool) {
+    note_config("thread_colors", ConfigSource::Api("set_thread_colors"), colored);
+    THREAD_COLORS.store(colored, Ordering::Relaxed);
+}
+
+/// The current thread's label; `None` while its thread-locals are being
+/// destroyed.
+fn thread_label() -> Option<Arc<str>> {
+    THREAD_LABEL
+        .try_with(|label| {
+            label
+                .borrow_mut()
+                .get_or_insert_with(|| match std::thread::current().name() {
+                    Some(name) => Arc::from(name),
+                    None => Arc::from(format!("#{}", NEXT_THREAD_NUMBER.fetch_add(1, Ordering::Relaxed))),
+                })
+                .clone()
+        })
+        .ok()
+}
+
+/// Bytes of text `INTERNER` keeps at most. Targets, thread names and field
+/// keys come from a small set; past this, dynamic ones stay owned.
+const INTERN_LIMIT: usize = 64 * 1024;
+
+static INTERNER: LazyLock<Interner> = LazyLock::new(|| Interner::new(INTERN_LIMIT));
+
+/// Append-only set of the short strings every record repeats, kept for the
+/// rest of the run so they are copied once instead of once per record.
+struct Interner {
+    strings: RwLock<HashSet<&'static str>>,
+    bytes: AtomicUsize,
+    limit: usize,
+}
+
+impl Interner {
+    fn new(limit: usize) -> Interner {
+        Interner { strings: RwLock::default(), bytes: AtomicUsize::new(0), limit }
+    }
+
+    /// The kept copy of `text`, the same one for every caller; `None` once
+    /// keeping it would pass the limit.
+    fn get(&self, text: &str) -> Option<&'static str> {
+        // Only a string's first use takes the write lock.
+        if let Some(kept) = self.strings.read().unwrap().get(text) { return Some(kept); }
+        let mut strings = self.strings.write().unwrap();
+        if let Some(kept) = strings.get(text) { return Some(kept); }
+        if self.bytes.load(Ordering::Relaxed) + text.len() > self.limit { return None; }
+        self.bytes.fetch_add(text.len(), Ordering::Relaxed);
+        let kept: &'static str = Box::leak(Box::from(text));
+        strings.insert(kept);
+        Some(kept)
+    }
+
+    fn cow(&self, text: &str) -> Cow<'static, str> {
+        self.get(text).map_or_else(|| Cow::Owned(text.to_string()), Cow::Borrowed)
+    }
+}
+
+thread_local! {
+    /// Where `file:line` is put together to look it up in `INTERNER`.
+    static LOCATION_SCRATCH: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// The location text for the current mode: the module path or `file:line`.
+fn location_text(location: &Location) -> Option<Cow<'static, str>> {
+    match location_mode() {
+        LocationMode::None => None,
+        LocationMode::Module => Some(Cow::Borrowed(location.module)),
+        LocationMode::Full => Some(full_location(location)),
+    }
+}
+
+/// `file:line`, allocated only the first time a call site logs.
+fn full_location(location: &Location) -> Cow<'static, str> {
+    let interned = LOCATION_SCRATCH.try_with(|scratch| {
+        let mut scratch = scratch.try_borrow_mut().ok()?;
+        scratch.clear();
+        fmt::Write::write_fmt(&mut *scratch, format_args!("{}:{}", location.file, location.line)).ok()?;
+        INTERNER.get(&scratch)
+    });
+    interned.ok().flatten().map_or_else(|| Cow::Owned(format!("{}:{}", location.file, location.line)), Cow::Borrowed)
+}
+
+/// How `set_target_width` shortens a target that does not fit. Both cut at
+/// `::` and keep the last segment whole when it fits at all.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TargetTruncation {
+    /// The first segment, then as many of the last ones as fit:
+    /// `my_app::…::worker` (default)
+    Middle,
+    /// As many of the last segments as fit: `…::reconciliation::worker`
+    LastSegments,
+}
+
+static TARGET_WIDTH: AtomicUsize = AtomicUsize::new(0);
+static TARGET_TRUNCATION: AtomicU8 = AtomicU8::new(TargetTruncation::Middle as u8);
+
+/// Draws the target of console lines in exactly `width` characters: longer
+/// ones are shortened (see `set_target_truncation`), shorter ones padded
+/// after the colon, so messages start in one column. `0` (the default)
+/// draws targets as they are. JSON, logfmt and log files always get the
+/// full target.
+pub fn set_target_width(width: usize) {
+    note_config("target_width", ConfigSource::Api("set_target_width"), width);
+    TARGET_WIDTH.store(width, Ordering::Relaxed);
+}
+
+pub fn target_width() -> usize {
+    TARGET_WIDTH.load(Ordering::Relaxed)
+}
+
+pub fn set_target_truncation(strategy: TargetTruncation) {
+    note_config("target_truncation", ConfigSource::Api("set_target_truncation"), format_args!("{strategy:?}"));
+    TARGET_TRUNCATION.store(strategy as u8, Ordering::Relaxed);
+}
+
+pub fn target_truncation() -> TargetTruncation {
+    if TARGET_TRUNCATION.load(Ordering::Relaxed) == TargetTruncation::LastSegments as u8 {
+        TargetTruncation::LastSegments
+    } else {
+        TargetTruncation::Middle
+    }
+}
+
+/// The target as drawn in the prefix, and the padding to draw after its
+/// colon.
+fn target_column(target: &str) -> (Cow<'_, str>, usize) {
+    let width = target_width();
+    if width == 0 { return (Cow::Borrowed(target), 0); }
+    let shown = shorten_target(target, width, target_truncation(), glyphs().ellipsis);
+    let pad = width.saturating_sub(shown.chars().count());
+    (shown, pad)
+}
+
+/// The ` #n` of the event counter after the target, and what is left of
+/// the target's padding.
+fn event_seq_column(record: &Record, pad: usize) -> (String, usize) {
+    let Some(seq) = record.event_seq else { return (String::new(), pad) };
+    let counter = format!(" #{seq}");
+    let pad = pad.saturating_sub(counter.len());
+    (counter, pad)
+}
+
+/// `target` in at most `width` characters, counted in `char`s, with
+/// `ellipsis` where segments were left out.
+fn shorten_target<'a>(target: &'a str, width: usize, strategy: TargetTruncation, ellipsis: &str) -> Cow<'a, str> {
+    let len = |text: &str| text.chars().count();
+    if len(target) <= width { return Cow::Borrowed(target); }
+
+    let segments: Vec<&str> = target.split("::").collect();
+    let n = segments.len();
+    let first = (strategy == TargetTruncation::Middle && n > 2).then_some(segments[0]);
+    let lead = first.map_or_else(|| String::from(ellipsis), |first| format!("{first}::{ellipsis}"));
+    let mut kept = 0;
+    let mut used = len(&lead);
+    while kept < n - usize::from(first.is_some()) {
+        let next = len(segments[n - 1 - kept]) + 2;
+        if used + next > width { break; }
+        used += next;
+        kept += 1;
+    }
+    if kept > 0 { return Cow::Owned(format!("{lead}::{}", segments[n - kept..].join("::"))); }
+    if first.is_some() { return shorten_target(target, width, TargetTruncation::LastSegments, ellipsis); }
+
+    // Not even the last segment fits: its end is the most telling part.
+    let last = segments[n - 1];
+    if width <= len(ellipsis) { return Cow::Owned(ellipsis.chars().take(width).collect()); }
+    let skip = len(last).saturating_sub(width - len(ellipsis));
+    Cow::Owned(format!("{ellipsis}{}", last.chars().skip(skip).collect::<String>()))
+}
+
+static CATCH_FORMAT_PANICS: AtomicBool = AtomicBool::new(false);
+
+/// Whether a panic in a `Display` or `Debug` impl while a message is
+/// formatted is caught. The record is then replaced by an Error `[logger]`
+/// diagnostic naming the call site and the panic message. Off by default:
+/// the panic propagates out of the macro. The panic hook runs either way.
+pub fn catch_format_panics(catch: bool) {
+    note_config("catch_format_panics", ConfigSource::Api("catch_format_panics"), catch);
+    CATCH_FORMAT_PANICS.store(catch, Ordering::Relaxed);
+}
+
+/// The formatted message, or `None` if formatting panicked and the panic was
+/// caught and reported.
+fn format_message(location: &Location, args: fmt::Arguments) -> Option<String> {
+    if !CATCH_FORMAT_PANICS.load(Ordering::Relaxed) {
+        return Some(args.to_string());
+    }
+
+    let payload = match std::panic::catch_unwind(AssertUnwindSafe(|| args.to_string())) {
+        Ok(message) => return Some(message),
+        Err(payload) => payload,
+    };
+    self_log(Level::Error, format_args!(
+        "panic while formatting log message at {}:{}: {}",
+        location.file,
+        location.line,
+        panic_message(&*payload)
+    ));
+    None
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("(non-string panic payload)")
+}
+
+pub fn print_with_prefix(level: Level, location: Location, args: fmt::Arguments) {
+    print_record(level, false, None, location, &[], args);
+}
+
+/// Like `print_with_prefix`, but `emphasis` renders the timestamp and message
+/// with the theme's alert style.
+pub fn print_emphasized(level: Level, emphasis: bool, location: Location, args: fmt::Arguments) {
+    print_record(level, emphasis, None, location, &[], args);
+}
+
+/// Like `print_with_prefix`, with `key=value` fields after the message.
+pub fn print_with_fields(level: Level, location: Location, fields: &[Field], args: fmt::Arguments) {
+    print_record(level, false, None, location, fields, args);
+}
+
+/// Like `print_with_prefix`, tagged with `category` for the badge and for
+/// sinks routed with `Route::Category`.
+pub fn print_in_category(level: Level, category: &str, location: Location, args: fmt::Arguments) {
+    print_record(level, false, Some(category), location, &[], args);
+}
+
+fn print_record(
+    level: Level, emphasis: bool, category: Option<&str>, location: Location, fields: &[Field], args: fmt::Arguments,
+) {
+    if SHUT_DOWN.load(Ordering::Acquire) {
+        print_plain_stderr(level, format_args!("{args}{}", Fields(fields)));
+        return;
+    }
+
+    if !rate_limit_allows(level) { return; }
+
+    maybe_log_suppressed_summary();
+
+    let Some(message_raw) = format_message(&location, args) else { return };
+    let target = location_text(&location);
+    let tag = PROCESS_TAG.read().unwrap();
+    let ts = record_timestamp();
+    // The error itself goes after the message, its sources under it.
+    let mut chain = ERROR_CHAIN.try_with(RefCell::take).unwrap_or_default().into_iter();
+    let capped = capped_message(&message_raw);
+    let message = capped.as_deref().unwrap_or(&message_raw);
+    let with_error = chain.next().map(|error| format!("{message}: {}", escape_markup(&error)));
+    let chain: Vec<String> = chain.collect();
+    let causes: Vec<&str> = chain.iter().map(String::as_str).collect();
+    let event_seq = next_event_seq(&location);
+    with_context_fields(fields, |fields| print_line(Record {
+        emphasis, category, process: tag.as_deref(), timestamp: ts.as_deref(), target: target.as_deref(), fields,
+        suppressed: SUPPRESSED_SIMILAR.with(Cell::take), causes: &causes, event_seq,
+        raw: (with_error.is_some() || capped.is_some()).then_some(message_raw.as_str()),
+        ..Record::new(level, with_error.as_deref().unwrap_or(message))
+    }, is_enabled_for(level, location.module)));
+}
+
+/// A record from outside Rust (the C interface): `target` stands in for the
+/// module path, and there is no file or line.
+#[cfg(feature = "ffi")]
+pub fn print_foreign(level: Level, target: Option<&str>, message: &str) {
+    let module = target.unwrap_or("");
+    if !record_enabled(level, module) { return; }
+
+    if SHUT_DOWN.load(Ordering::Acquire) {
+        print_plain_stderr(level, format_args!("{message}"));
+        return;
+    }
+
+    if !rate_limit_allows(level) { return; }
+
+    maybe_log_suppressed_summary();
+
+    let target = target.filter(|_| location_mode() != LocationMode::None);
+    let tag = PROCESS_TAG.read().unwrap();
+    let ts = record_timestamp();
+    print_line(Record {
+        process: tag.as_deref(), timestamp: ts.as_deref(), target,
+        ..Record::new(level, message)
+    }, is_enabled_for(level, module));
+}
+
+static AUDIT_BYPASSES_LEVEL: AtomicBool = AtomicBool::new(true);
+
+/// Whether `audit!` records are printed even when Info is filtered out by
+/// the level. On by default.
+pub fn set_audit_bypasses_level(bypass: bool) {
+    note_config("audit_bypasses_level", ConfigSource::Api("set_audit_bypasses_level"), bypass);
+    AUDIT_BYPASSES_LEVEL.store(bypass, Ordering::Relaxed);
+}
+
+pub fn audit_enabled(target: &str) -> bool {
+    AUDIT_BYPASSES_LEVEL.load(Ordering::Relaxed) || record_enabled(Level::Info, target)
+}
+
+/// Category of `audit!` records.
+pub const AUDIT_CATEGORY: &str = "audit";
+
+static AUDIT_SINK_CHECKED: AtomicBool = AtomicBool::new(false);
+
+/// Prints an `audit!` record: Info with an `[audit]` badge, never dropped by
+/// the rate limit. The first one warns if no sink is routed to the `audit`
+/// category, since the console alone is rarely where audit events belong.
+pub fn print_audit(location: Location, args: fmt::Arguments) {
+    if SHUT_DOWN.load(Ordering::Acquire) {
+        print_plain_stderr(Level::Info, format_args!("[{AUDIT_CATEGORY}] {args}"));
+        return;
+    }
+    if !AUDIT_SINK_CHECKED.swap(true, Ordering::Relaxed) && !has_category_sink(AUDIT_CATEGORY) {
+        self_log(Level::Warn, format_args!(
+            "audit! used without a sink routed to the {AUDIT_CATEGORY} category; add one with sink::add_routed_sink",
+        ));
+    }
+
+    let Some(message_raw) = format_message(&location, args) else { return };
+    let target = location_text(&location);
+    let tag = PROCESS_TAG.read().unwrap();
+    let ts = record_timestamp();
+    let event_seq = next_event_seq(&location);
+    let capped = capped_message(&message_raw);
+    with_context_fields(&[], |fields| print_line(Record {
+        category: Some(AUDIT_CATEGORY), process: tag.as_deref(), timestamp: ts.as_deref(), target: target.as_deref(), fields,
+        event_seq, raw: capped.is_some().then_some(message_raw.as_str()),
+        ..Record::new(Level::Info, capped.as_deref().unwrap_or(&message_raw))
+    }, AUDIT_BYPASSES_LEVEL.load(Ordering::Relaxed) || is_enabled_for(Level::Info, location.module)));
+}
+
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+static MAX_PER_SECOND: AtomicU32 = AtomicU32::new(0);
+static RATE_EXEMPT_ERRORS: AtomicBool = AtomicBool::new(false);
+/// When the bucket is full again, in microseconds since `START`.
+static RATE_FULL_AT: AtomicU64 = AtomicU64::new(0);
+static RATE_NOTICE_WINDOW: AtomicU64 = AtomicU64::new(u64::MAX);
+static THROTTLED: AtomicU64 = AtomicU64::new(0);
+
+/// Caps the number of records printed per second across all threads; the rest
+/// are dropped and counted. `0` (the default) disables the limit.
+pub fn set_max_records_per_second(max: u32) {
+    note_config("max_records_per_second", ConfigSource::Api("set_max_records_per_second"), max);
+    MAX_PER_SECOND.store(max, Ordering::Relaxed);
+}
+
+/// Lets Warn and Error records through even when the limit is reached.
+pub fn exempt_errors(exempt: bool) {
+    note_config("exempt_errors", ConfigSource::Api("exempt_errors"), exempt);
+    RATE_EXEMPT_ERRORS.store(exempt, Ordering::Relaxed);
+}
+
+/// Records dropped by `set_max_records_per_second`.
+pub fn throttled_records() -> u64 {
+    THROTTLED.load(Ordering::Relaxed)
+}
+
+/// A token bucket of `max` tokens refilled at `max` per second, lock-free.
+/// It is kept as the time the bucket is full again: each record moves that
+/// time on by one token, and a record that would move it more than a second
+/// past now finds the bucket empty. Unlike a window that resets every second,
+/// a burst at the end of one second is not followed by a second burst.
+fn rate_limit_allows(level: Level) -> bool {
+    let max = MAX_PER_SECOND.load(Ordering::Relaxed);
+    if max == 0 { return true; }
+    if RATE_EXEMPT_ERRORS.load(Ordering::Relaxed) && matches!(level, Level::Warn | Level::Error) { return true; }
+
+    let now = START.elapsed();
+    let now_us = now.as_micros() as u64;
+    let token_us = (1_000_000 / u64::from(max)).max(1);
+    let took = RATE_FULL_AT.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |full_at| {
+        let full_at = full_at.max(now_us) + token_us;
+        (full_at <= now_us + 1_000_000).then_some(full_at)
+    });
+    if took.is_ok() { return true; }
+
+    let window = now.as_secs();
+    let dropped = THROTTLED.fetch_add(1, Ordering::Relaxed) + 1;
+    let noticed = RATE_NOTICE_WINDOW.load(Ordering::Relaxed);
+    if noticed != window && RATE_NOTICE_WINDOW.compare_exchange(noticed, window, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+        self_log(Level::Error, format_args!("log output throttled: dropping records ({dropped} dropped so far)"));
+    }
+
+    false
+}
+
+static SUPPRESSED_ACCOUNTING: AtomicBool = AtomicBool::new(false);
+static SUPPRESSED_SITES: Mutex<Vec<&'static SuppressedSite>> = Mutex::new(Vec::new());
+static SUPPRESSED_SUMMARY: Mutex<Option<(Duration, Instant)>> = Mutex::new(None);
+
+const SUPPRESSED_SUMMARY_TOP: usize = 5;
+
+thread_local! {
+    /// Count for the record a `throttle:` call site is about to log.
+    static SUPPRESSED_SIMILAR: Cell<u64> = const { Cell::new(0) };
+    /// The error chain for the record an `err =` call is about to log.
+    static ERROR_CHAIN: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// Where the record a `dest:` call is about to log goes.
+    static DESTINATION: Cell<Destination> = const { Cell::new(Destination::All) };
+}
+
+static THROTTLE_EPOCH: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Window and held-back count of one `throttle:` macro call site.
+#[derive(Default)]
+pub struct Throttle {
+    /// Start of the current window in nanoseconds after `THROTTLE_EPOCH`,
+    /// plus one; 0 before the first record.
+    window_start: AtomicU64,
+    suppressed: AtomicU64,
+}
+
+impl Throttle {
+    pub const fn new() -> Self {
+        Throttle { window_start: AtomicU64::new(0), suppressed: AtomicU64::new(0) }
+    }
+
+    /// Whether a record may go out now, with the number held back since the
+    /// last one; `None` while the window started by that record is open.
+    pub fn pass(&self, window: Duration) -> Option<u64> {
+        let now = THROTTLE_EPOCH.elapsed().as_nanos() as u64 + 1;
+        let start = self.window_start.load(Ordering::Relaxed);
+        let open = start != 0 && u128::from(now - start.min(now)) < window.as_nanos();
+        // Of the threads reaching a closed window at once, one opens the next.
+        if open || self.window_start.compare_exchange(start, now, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(self.suppressed.swap(0, Ordering::Relaxed))
+    }
+}
+
+/// Runs `log` with `suppressed` attached to the record it prints.
+pub fn with_suppressed_count(suppressed: u64, log: impl FnOnce()) {
+    SUPPRESSED_SIMILAR.with(|count| count.set(suppressed));
+    log();
+    SUPPRESSED_SIMILAR.with(|count| count.set(0));
+}
+
+/// Where a record goes, picked per call with `dest:` in the macros.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Destination {
+    /// Everywhere (default)
+    All,
+    /// The console and `SinkKind::Console` sinks only: spinners, separators
+    /// and other decoration
+    Console,
+    /// Log files, the black box and `SinkKind::File` sinks only: details
+    /// for the record that would clutter the terminal
+    File,
+}
+
+impl Destination {
+    /// `all`, `console` or `file`, as written after `dest:`.
+    pub fn parse(name: &str) -> Option<Destination> {
+        [("all", Destination::All), ("console", Destination::Console), ("file", Destination::File)]
+            .into_iter()
+            .find(|(known, _)| name.eq_ignore_ascii_case(known))
+            .map(|(_, destination)| destination)
+    }
+
+    fn console(self) -> bool {
+        self != Destination::File
+    }
+
+    fn files(self) -> bool {
+        self != Destination::Console
+    }
+
+    fn takes(self, kind: SinkKind) -> bool {
+        match kind {
+            SinkKind::Console => self.console(),
+            SinkKind::File => self.files(),
+        }
+    }
+}
+
+static UNKNOWN_DESTINATION_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Runs `log` with its record sent to the destination `name`. An unknown
+/// name logs nothing, and the first one is reported.
+pub fn with_destination(name: &str, log: impl FnOnce()) {
+    let Some(destination) = Destination::parse(name) else {
+        if !UNKNOWN_DESTINATION_REPORTED.swap(true, Ordering::Relaxed) {
+            self_log(Level::Warn, format_args!("unknown log destination {name:?}: expected all, console or file; the record is dropped"));
+        }
+        return;
+    };
+    let previous = DESTINATION.with(|current| current.replace(destination));
+    log();
+    DESTINATION.with(|current| current.set(previous));
+}
+
+/// Runs `log` with `error` attached to the record it prints: the error
+/// after the message, then its `source()` chain as causes.
+/// Without the chain once the thread-local is gone, in a late destructor.
+pub fn with_error_chain(error: &dyn std::error::Error, log: impl FnOnce()) {
+    let chain = std::iter::successors(Some(error), |error| error.source()).map(ToString::to_string).collect();
+    let _ = ERROR_CHAIN.try_with(|causes| *causes.borrow_mut() = chain);
+    log();
+    let _ = ERROR_CHAIN.try_with(|causes| causes.borrow_mut().clear());
+}
+
+/// Lets `err =` take any error, boxed ones included, which are not `Error`
+/// themselves: `(&AnyError(&err)).as_error()` picks `ViaError` for an
+/// `Error` and falls back to `ViaBoxedError` through auto-ref for a box.
+pub struct AnyError<'a, T: ?Sized>(pub &'a T);
+
+pub trait ViaError {
+    fn as_error(&self) -> &dyn std::error::Error;
+}
+
+impl<T: std::error::Error> ViaError for AnyError<'_, T> {
+    fn as_error(&self) -> &dyn std::error::Error {
+        self.0
+    }
+}
+
+pub trait ViaBoxedError {
+    fn as_error(&self) -> &dyn std::error::Error;
+}
+
+impl ViaBoxedError for &AnyError<'_, Box<dyn std::error::Error>> {
+    fn as_error(&self) -> &dyn std::error::Error {
+        &**self.0
+    }
+}
+
+impl ViaBoxedError for &AnyError<'_, Box<dyn std::error::Error + Send>> {
+    fn as_error(&self) -> &dyn std::error::Error {
+        &**self.0
+    }
+}
+
+impl ViaBoxedError for &AnyError<'_, Box<dyn std::error::Error + Send + Sync>> {
+    fn as_error(&self) -> &dyn std::error::Error {
+        &**self.0
+    }
+}
+
+/// Enabled calls of one `once:` or `every:` macro call site.
+#[derive(Default)]
+pub struct CallCount(AtomicU64);
+
+impl CallCount {
+    pub const fn new() -> Self {
+        CallCount(AtomicU64::new(0))
+    }
+
+    /// `true` for the first call only, however many threads race for it.
+    pub fn first(&self) -> bool {
+        self.0.load(Ordering::Relaxed) == 0 && self.0.fetch_add(1, Ordering::Relaxed) == 0
+    }
+
+    /// `true` for the 1st, `n + 1`th, `2n + 1`th, ... call; every call for
+    /// `n` of 0 or 1.
+    pub fn every(&self, n: u64) -> bool {
+        self.0.fetch_add(1, Ordering::Relaxed).is_multiple_of(n.max(1))
+    }
+}
+
+/// Counter for one macro call site, bumped when its record is filtered out
+/// by the level check while accounting is on.
+pub struct SuppressedSite {
+    target: &'static str,
+    count: AtomicU64,
+    reported: AtomicU64,
+    registered: AtomicBool,
+}
+
+impl SuppressedSite {
+    pub const fn new(target: &'static str) -> Self {
+        SuppressedSite { target, count: AtomicU64::new(0), reported: AtomicU64::new(0), registered: AtomicBool::new(false) }
+    }
+
+    pub fn hit(&'static self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+
+        if !self.registered.load(Ordering::Relaxed) && !self.registered.swap(true, Ordering::Relaxed) {
+            SUPPRESSED_SITES.lock().unwrap().push(self);
+        }
+    }
+}
+
+/// Counts records suppressed by the level check per target (module path).
+/// Off by default; when off the suppressed path costs one relaxed load.
+pub fn set_suppressed_accounting(enabled: bool) {
+    note_config("suppressed_accounting", ConfigSource::Api("set_suppressed_accounting"), enabled);
+    SUPPRESSED_ACCOUNTING.store(enabled, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn suppressed_accounting_enabled() -> bool {
+    SUPPRESSED_ACCOUNTING.load(Ordering::Relaxed)
+}
+
+/// Top `n` targets by records suppressed since the previous call, highest first.
+pub fn suppressed_summary(n: usize) -> Vec<(&'static str, u64)> {
+    let sites = SUPPRESSED_SITES.lock().unwrap();
+    let mut totals: Vec<(&'static str, u64)> = Vec::new();
+
+    for site in sites.iter() {
+        let count = site.count.load(Ordering::Relaxed);
+        let delta = count - site.reported.swap(count, Ordering::Relaxed);
+        if delta == 0 { continue; }
+
+        match totals.iter_mut().find(|(target, _)| *target == site.target) {
+            Some((_, total)) => *total += delta,
+            None => totals.push((site.target, delta)),
+        }
+    }
+
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    totals.truncate(n);
+    totals
+}
+
+/// Logs `suppressed_summary` at Info every `interval` (checked when records
+/// are printed), regardless of the level. `None` turns it off.
+pub fn set_suppressed_summary_interval(interval: Option<Duration>) {
+    note_config("suppressed_summary_interval", ConfigSource::Api("set_suppressed_summary_interval"), format!("{interval:?}"));
+    *SUPPRESSED_SUMMARY.lock().unwrap() = interval.map(|interval| (interval, Instant::now()));
+}
+
+fn maybe_log_suppressed_summary() {
+    {
+        let mut schedule = SUPPRESSED_SUMMARY.lock().unwrap();
+        let Some((interval, last)) = schedule.as_mut() else { return };
+
+        let now = Instant::now();
+        if now.duration_since(*last) < *interval { return; }
+        *last = now;
+    }
+
+    let summary = suppressed_summary(SUPPRESSED_SUMMARY_TOP);
+    if summary.is_empty() { return; }
+
+    let counts: Vec<String> = summary.iter().map(|(target, count)| format!("{target}={count}")).collect();
+    let message = format!("suppressed records since last summary: {}", counts.join(", "));
+    let tag = PROCESS_TAG.read().unwrap();
+    let ts = record_timestamp();
+    print_line(Record { process: tag.as_deref(), timestamp: ts.as_deref(), ..Record::new(Level::Info, &message) }, true);
+}
+
+#[cfg(feature = "timestamps")]
+fn record_timestamp() -> Option<String> {
+    let format = TIMESTAMP_FORMAT.read().unwrap();
+    if *format == TimestampFormat::None { return None; }
+    if stable_test_format() { return Some(String::from("TS")); }
+
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let offset = TIMEZONE.read().unwrap().map(|(_, offset)| offset);
+    match output_format() {
+        Format::Json if json_flavor() == JsonFlavor::Otel => RFC3339_NANOS.render(since_epoch, offset),
+        Format::Json | Format::Logfmt => TimestampFormat::Rfc3339.render(since_epoch, offset),
+        _ => format.render(since_epoch, offset),
+    }
+}
+
+#[cfg(not(feature = "timestamps"))]
+fn record_timestamp() -> Option<String> {
+    None
+}
+
+/// `record_timestamp` with `try_read`: a format or time zone being changed
+/// right now, or poisoned, falls back to the default, and the uptime formats,
+/// whose clock is behind another lock, to `Standard`.
+#[cfg(feature = "timestamps")]
+fn plain_stderr_timestamp() -> Option<String> {
+    let format = match TIMESTAMP_FORMAT.try_read() {
+        Ok(format) if !matches!(*format, TimestampFormat::UptimeMillis | TimestampFormat::UptimeMicros) => format.clone(),
+        _ => TimestampFormat::Standard,
+    };
+    if format == TimestampFormat::None { return None; }
+    if stable_test_format() { return Some(String::from("TS")); }
+
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let offset = TIMEZONE.try_read().ok().and_then(|zone| zone.map(|(_, offset)| offset));
+    format.render(since_epoch, offset)
+}
+
+#[cfg(not(feature = "timestamps"))]
+fn plain_stderr_timestamp() -> Option<String> {
+    None
+}
+
+static STABLE_FORMAT: LazyLock<AtomicBool> = LazyLock::new(|| {
+    let enabled = matches!(std::env::var("LOG_STABLE_FORMAT").as_deref(), Ok("1") | Ok("on") | Ok("true"));
+    if enabled { note_config("stable_test_format", ConfigSource::Env("LOG_STABLE_FORMAT"), true); }
+    AtomicBool::new(enabled)
+});
+
+/// For snapshot tests only: timestamps become `[TS]` and every escape
+/// sequence (colors, markup, bell) is left out; message text and layout are
+/// unchanged. Also enabled by `LOG_STABLE_FORMAT=1`.
+pub fn set_stable_test_format(enabled: bool) {
+    note_config("stable_test_format", ConfigSource::Api("set_stable_test_format"), enabled);
+    STABLE_FORMAT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn stable_test_format() -> bool {
+    STABLE_FORMAT.load(Ordering::Relaxed)
+}
+
+/// Whether console records are colored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorChoice {
+    /// Colors when stdout is a terminal (or a writer from `set_writer` is
+    /// installed) and `NO_COLOR` is not set (default)
+    Auto,
+    Always,
+    /// Plain text: `[ERR] [timestamp] message`, markup stripped
+    Never,
+    /// Like `Auto`, but colored under `cargo test` too, where libtest
+    /// captures the output and replays it to the terminal when a test
+    /// fails. `CARGO_TERM_COLOR=never` and `NO_COLOR` still turn colors off.
+    AlwaysInTests,
+}
+
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+pub fn set_color_choice(choice: ColorChoice) {
+    note_config("color", ConfigSource::Api("set_color_choice"), format_args!("{choice:?}"));
+    COLOR_CHOICE.store(choice as u8, Ordering::Relaxed);
+}
+
+pub fn color_choice() -> ColorChoice {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        1 => ColorChoice::Always,
+        2 => ColorChoice::Never,
+        3 => ColorChoice::AlwaysInTests,
+        _ => ColorChoice::Auto,
+    }
+}
+
+/// What the color decision depends on besides the `ColorChoice`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ColorEnv {
+    /// `NO_COLOR` is set and not empty
+    pub no_color: bool,
+    pub stdout_is_tty: bool,
+    /// A writer from `set_writer` is installed
+    pub writer_set: bool,
+    /// Running as a libtest binary: `RUST_TEST_THREADS` is set, or cargo
+    /// started an executable from a `deps` directory
+    pub in_test: bool,
+    /// `CARGO_TERM_COLOR`: `Some(true)` for `always`, `Some(false)` for
+    /// `never`, `None` for `auto` or unset
+    pub cargo_term_color: Option<bool>,
+}
+
+/// The part of `ColorEnv::current` that comes from environment variables.
+static PROCESS_COLOR_ENV: LazyLock<ColorEnv> = LazyLock::new(|| {
+    let exe = std::env::current_exe().ok();
+    let env = ColorEnv::from_vars(|name| std::env::var_os(name), exe.as_deref());
+    if env.no_color { note_config("color", ConfigSource::Env("NO_COLOR"), "Never"); }
+    env
+});
+
+impl ColorEnv {
+    /// The environment of this process, its variables read once.
+    pub fn current() -> Self {
+        ColorEnv {
+            stdout_is_tty: *STDOUT_IS_TTY,
+            writer_set: WRITER_SET.load(Ordering::Relaxed),
+            ..*PROCESS_COLOR_ENV
+        }
+    }
+
+    /// The environment that the variables `var` looks up and the path of
+    /// the running executable describe, e.g. a simulated one in a test.
+    /// `stdout_is_tty` and `writer_set` are false.
+    pub fn from_vars(var: impl Fn(&str) -> Option<OsString>, exe: Option<&Path>) -> Self {
+        let cargo_deps = var("CARGO").is_some() && exe.and_then(Path::parent).and_then(Path::file_name).is_some_and(|dir| dir == "deps");
+        let cargo_term_color = var("CARGO_TERM_COLOR").and_then(|value| value.into_string().ok()).map(|value| value.to_ascii_lowercase());
+        ColorEnv {
+            no_color: var("NO_COLOR").is_some_and(|value| !value.is_empty()),
+            stdout_is_tty: false,
+            writer_set: false,
+            in_test: var("RUST_TEST_THREADS").is_some() || cargo_deps,
+            cargo_term_color: match cargo_term_color.as_deref() {
+                Some("always") => Some(true),
+                Some("never") => Some(false),
+                _ => None,
+            },
+        }
+    }
+
+    /// Whether console records are colored under `choice`.
+    pub fn colors(&self, choice: ColorChoice) -> bool {
+        let auto = !self.no_color && (self.writer_set || self.stdout_is_tty);
+        match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => auto,
+            ColorChoice::AlwaysInTests if self.in_test => !self.no_color && self.cargo_term_color != Some(false),
+            ColorChoice::AlwaysInTests => auto,
+        }
+    }
+}
+
+fn colors_enabled() -> bool {
+    match color_choice() {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        choice => ColorEnv::current().colors(choice),
+    }
+}
+
+/// One record as a formatter sees it.
+#[derive(Clone, Copy, Debug)]
+pub struct Record<'a> {
+    pub level: Level,
+    /// Render timestamp and message in the theme's alert style, or put
+    /// `!!!` around the message without colors
+    pub emphasis: bool,
+    /// Drawn as a bold `[category]` badge in front of the message, e.g. `audit`
+    pub category: Option<&'a str>,
+    pub process: Option<&'a str>,
+    /// Thread name or `#n`, drawn dimmed in brackets before the timestamp
+    pub thread: Option<&'a str>,
+    pub timestamp: Option<&'a str>,
+    /// Where the record was logged: a module path, or `file:line`
+    pub target: Option<&'a str>,
+    /// Group depth; the message is indented two spaces per level
+    pub depth: usize,
+    /// Message text, markup not yet applied
+    pub message: &'a str,
+    /// What the call's format arguments produced, when `message` is not
+    /// just that (an `err =` error was added to it); see `raw_message`
+    pub raw: Option<&'a str>,
+    /// Key-value pairs after the message, in the order given
+    pub fields: &'a [Field<'a>],
+    /// Records a `throttle:` call site held back since its previous record;
+    /// drawn as `(suppressed N similar messages)` after the message
+    pub suppressed: u64,
+    /// The error passed with `err =` and its `source()` chain, one
+    /// `caused by:` line each under the message
+    pub causes: &'a [&'a str],
+    /// Where the record goes, from `dest:`
+    pub destination: Destination,
+    /// Number of the record in this process, from 1, set by the logger
+    /// when it writes the record; shown by `ContinuationStyle::RepeatPrefix`
+    pub seq: u64,
+    /// Number of the record among those of its target or call site, while
+    /// `set_event_counter` is on; drawn as `#n` after the target
+    pub event_seq: Option<u64>,
+}
+
+/// A `key = value` pair from a logging macro:
+/// `log!("request finished"; user_id = uid, query = ?q)`.
+#[derive(Clone, Copy)]
+pub struct Field<'a> {
+    pub key: &'a str,
+    pub value: FieldValue<'a>,
+}
+
+/// A field value, formatted only when a line is written.
+#[derive(Clone, Copy)]
+pub enum FieldValue<'a> {
+    Display(&'a dyn fmt::Display),
+    /// Written with `{:?}`; the `?value` sigil in the macros
+    Debug(&'a dyn fmt::Debug),
+    /// Written by the formatter registered for its type, or with `{:?}`;
+    /// the `%value` sigil. `value` and `debug` are the same value.
+    Typed { value: &'a dyn Any, debug: &'a dyn fmt::Debug },
+}
+
+impl<'a> Field<'a> {
+    pub fn display(key: &'a str, value: &'a dyn fmt::Display) -> Self {
+        Field { key, value: FieldValue::Display(value) }
+    }
+
+    pub fn debug(key: &'a str, value: &'a dyn fmt::Debug) -> Self {
+        Field { key, value: FieldValue::Debug(value) }
+    }
+
+    /// A field for the formatter registered for `T` with
+    /// `register_field_formatter`, written with `{:?}` while there is none.
+    pub fn typed<T: Any + fmt::Debug>(key: &'a str, value: &'a T) -> Self {
+        Field { key, value: FieldValue::Typed { value, debug: value } }
+    }
+}
+
+impl fmt::Display for FieldValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Display(value) => value.fmt(f),
+            FieldValue::Debug(value) => value.fmt(f),
+            FieldValue::Typed { value, debug } => match field_formatter(Any::type_id(*value)) {
+                Some(formatter) => (formatter.console)(*value, f),
+                None => debug.fmt(f),
+            },
+        }
+    }
+}
+
+/// A JSON value from a field formatter, written as it is instead of as a
+/// string.
+#[derive(Clone, PartialEq, Debug)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    /// `null` when not finite
+    Number(f64),
+    String(String),
+}
+
+type ConsoleFieldFn = Box<dyn Fn(&dyn Any, &mut dyn fmt::Write) -> fmt::Result + Send + Sync>;
+type JsonFieldFn = Box<dyn Fn(&dyn Any) -> JsonValue + Send + Sync>;
+
+struct FieldFormatter {
+    console: ConsoleFieldFn,
+    json: JsonFieldFn,
+}
+
+static FIELD_FORMATTERS: LazyLock<RwLock<HashMap<TypeId, Arc<FieldFormatter>>>> = LazyLock::new(RwLock::default);
+static HAS_FIELD_FORMATTERS: AtomicBool = AtomicBool::new(false);
+
+/// Sets how `%value` fields of type `T` are written: `console` for the
+/// console, log files and logfmt, `json` for JSON records. Replaces an
+/// earlier registration for `T`. Bare and `?value` fields keep `Display` and
+/// `Debug`, and `%value` of a type without a formatter is written with
+/// `{:?}`.
+pub fn register_field_formatter<T: Any>(console: fn(&T, &mut dyn fmt::Write) -> fmt::Result, json: fn(&T) -> JsonValue) {
+    note_config("field_formatters", ConfigSource::Api("register_field_formatter"), std::any::type_name::<T>());
+    let formatter = FieldFormatter {
+        console: Box::new(move |value, out| value.downcast_ref::<T>().map_or(Ok(()), |value| console(value, out))),
+        json: Box::new(move |value| value.downcast_ref::<T>().map_or(JsonValue::Null, json)),
+    };
+    FIELD_FORMATTERS.write().unwrap().insert(TypeId::of::<T>(), Arc::new(formatter));
+    HAS_FIELD_FORMATTERS.store(true, Ordering::Relaxed);
+}
+
+fn field_formatter(type_id: TypeId) -> Option<Arc<FieldFormatter>> {
+    if !HAS_FIELD_FORMATTERS.load(Ordering::Relaxed) { return None; }
+    FIELD_FORMATTERS.read().unwrap().get(&type_id).cloned()
+}
+
+static MESSAGE_LIMIT: AtomicUsize = AtomicUsize::new(0);
+
+/// Cuts messages after `chars` characters and ends them with `…(+N chars)`,
+/// before anything else is done with them: markup, line breaks, wrapping
+/// and every destination then see at most `chars` of a huge blob.
+/// `Record::raw_message` keeps the whole message. `0` (the default) keeps
+/// them whole.
+pub fn set_message_limit(chars: usize) {
+    note_config("message_limit", ConfigSource::Api("set_message_limit"), chars);
+    MESSAGE_LIMIT.store(chars, Ordering::Relaxed);
+}
+
+pub fn message_limit() -> usize {
+    MESSAGE_LIMIT.load(Ordering::Relaxed)
+}
+
+/// `message` cut to the message limit; `None` if it fits. Never cuts an
+/// escape sequence or a markup tag in two.
+fn capped_message(message: &str) -> Option<String> {
+    let limit = message_limit();
+    if limit == 0 || message.len() <= limit { return None; }
+    let (mut end, _) = message.char_indices().nth(limit)?;
+    if let Some(escape) = message[..end].rfind('\x1b') {
+        let complete = message.get(escape + 2..end).is_some_and(|rest| rest.contains(|c| ('@'..='~').contains(&c)));
+        if !complete { end = escape; }
+    }
+    if let Some(open) = message[..end].rfind('<').filter(|&open| !message[open..end].contains('>')) {
+        let tag = &message[open + 1..end];
+        if tag.len() <= 32 && tag.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ',' | ' ' | '/')) { end = open; }
+    }
+    let cut = message[end..].chars().count();
+    Some(format!("{}{}(+{cut} chars)", &message[..end], glyphs().ellipsis))
+}
+
+/// The value limit for every field without one of its own, 0 while off.
+static FIELD_VALUE_LIMIT: AtomicUsize = AtomicUsize::new(0);
+static FIELD_VALUE_LIMITS: RwLock<Vec<(String, usize)>> = RwLock::new(Vec::new());
+static HAS_FIELD_VALUE_LIMITS: AtomicBool = AtomicBool::new(false);
+
+/// Cuts field values after `chars` characters, everywhere they are written,
+/// and ends them with `…(+N chars)`. `0` (the default) writes them whole.
+pub fn set_field_value_limit(chars: usize) {
+    note_config("field_value_limit", ConfigSource::Api("set_field_value_limit"), chars);
+    FIELD_VALUE_LIMIT.store(chars, Ordering::Relaxed);
+    update_field_value_limits(&FIELD_VALUE_LIMITS.read().unwrap());
+}
+
+/// `set_field_value_limit` for the fields named `key` only, winning over
+/// it; `0` writes them whole.
+pub fn set_field_value_limit_for(key: &str, chars: usize) {
+    let mut limits = FIELD_VALUE_LIMITS.write().unwrap();
+    note_config("field_value_limit", ConfigSource::Api("set_field_value_limit_for"), format_args!("{key}={chars}"));
+    limits.retain(|(existing, _)| existing != key);
+    limits.push((key.to_string(), chars));
+    update_field_value_limits(&limits);
+}
+
+/// Removes the limits of `set_field_value_limit_for`.
+pub fn clear_field_value_limits() {
+    let mut limits = FIELD_VALUE_LIMITS.write().unwrap();
+    limits.clear();
+    update_field_value_limits(&limits);
+}
+
+fn update_field_value_limits(limits: &[(String, usize)]) {
+    HAS_FIELD_VALUE_LIMITS.store(FIELD_VALUE_LIMIT.load(Ordering::Relaxed) > 0 || !limits.is_empty(), Ordering::Relaxed);
+}
+
+fn field_value_limit(key: &str) -> usize {
+    if !HAS_FIELD_VALUE_LIMITS.load(Ordering::Relaxed) { return 0; }
+    let limits = FIELD_VALUE_LIMITS.read().unwrap();
+    limits.iter().find(|(existing, _)| existing == key).map_or_else(|| FIELD_VALUE_LIMIT.load(Ordering::Relaxed), |(_, chars)| *chars)
+}
+
+/// A field value as it is written, cut to its limit.
+struct Shown<'a>(&'a Field<'a>);
+
+impl fmt::Display for Shown<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = field_value_limit(self.0.key);
+        if limit == 0 { return write!(f, "{}", self.0.value); }
+        let mut capped = Capped { out: f, limit, written: 0 };
+        fmt::Write::write_fmt(&mut capped, format_args!("{}", self.0.value))?;
+        let (out, cut) = (capped.out, capped.written.saturating_sub(limit));
+        if cut > 0 { write!(out, "{}(+{cut} chars)", glyphs().ellipsis)?; }
+        Ok(())
+    }
+}
+
+/// Writes the first `limit` characters and counts the rest.
+struct Capped<'a, 'f> {
+    out: &'a mut fmt::Formatter<'f>,
+    limit: usize,
+    written: usize,
+}
+
+impl fmt::Write for Capped<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let room = self.limit.saturating_sub(self.written);
+        let end = s.char_indices().nth(room).map_or(s.len(), |(at, _)| at);
+        self.written += s.chars().count();
+        if end > 0 { self.out.write_str(&s[..end])?; }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Field<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+/// Fields of a context, owned so they outlive the call that set them.
+type ContextFields = Arc<Vec<(String, String)>>;
+
+static GLOBAL_FIELDS: RwLock<Option<ContextFields>> = RwLock::new(None);
+static HAS_GLOBAL_FIELDS: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// `push_field` entries with the id their guard removes.
+    static THREAD_FIELDS: RefCell<Vec<(u64, String, String)>> = const { RefCell::new(Vec::new()) };
+    /// The same, as handed to records; rebuilt when they change.
+    static THREAD_SNAPSHOT: RefCell<Option<ContextFields>> = const { RefCell::new(None) };
+    /// The fields of the `with_task_fields` future being polled.
+    static TASK_FIELDS: RefCell<Option<ContextFields>> = const { RefCell::new(None) };
+}
+
+static NEXT_FIELD_GUARD: AtomicU64 = AtomicU64::new(0);
+
+/// Adds `key=value` to every record from now on, before the thread, task
+/// and call fields. Setting a key again replaces its value.
+pub fn set_global_field(key: &str, value: impl fmt::Display) {
+    update_global_fields(|fields| {
+        let value = value.to_string();
+        match fields.iter_mut().find(|(existing, _)| existing == key) {
+            Some((_, existing)) => *existing = value,
+            None => fields.push((key.to_string(), value)),
+        }
+    });
+}
+
+pub fn remove_global_field(key: &str) {
+    update_global_fields(|fields| fields.retain(|(existing, _)| existing != key));
+}
+
+pub fn clear_global_fields() {
+    update_global_fields(Vec::clear);
+}
+
+fn update_global_fields(update: impl FnOnce(&mut Vec<(String, String)>)) {
+    let mut global = GLOBAL_FIELDS.write().unwrap();
+    let mut fields = global.as_deref().cloned().unwrap_or_default();
+    update(&mut fields);
+    let keys: Vec<&str> = fields.iter().map(|(key, _)| key.as_str()).collect();
+    note_config("global_fields", ConfigSource::Api("set_global_field"), if keys.is_empty() { String::from("(none)") } else { keys.join(", ") });
+    HAS_GLOBAL_FIELDS.store(!fields.is_empty(), Ordering::Relaxed);
+    *global = (!fields.is_empty()).then(|| Arc::new(fields));
+}
+
+/// Removes its `push_field` field when dropped.
+#[must_use = "the field is removed as soon as the guard is dropped"]
+pub struct FieldGuard {
+    id: u64,
+    // The field belongs to the thread that pushed it.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+/// Adds `key=value` to the records of this thread until the guard is
+/// dropped, after the global fields and before the task and call fields.
+pub fn push_field(key: &str, value: impl fmt::Display) -> FieldGuard {
+    let id = NEXT_FIELD_GUARD.fetch_add(1, Ordering::Relaxed);
+    let entry = (id, key.to_string(), value.to_string());
+    let _ = THREAD_FIELDS.try_with(|fields| fields.borrow_mut().push(entry));
+    update_thread_snapshot();
+    FieldGuard { id, _not_send: std::marker::PhantomData }
+}
+
+impl Drop for FieldGuard {
+    fn drop(&mut self) {
+        let _ = THREAD_FIELDS.try_with(|fields| fields.borrow_mut().retain(|(id, _, _)| *id != self.id));
+        update_thread_snapshot();
+    }
+}
+
+fn update_thread_snapshot() {
+    let fields = THREAD_FIELDS.try_with(|fields| {
+        let fields = fields.borrow();
+        (!fields.is_empty()).then(|| Arc::new(fields.iter().map(|(_, key, value)| (key.clone(), value.clone())).collect()))
+    });
+    let _ = THREAD_SNAPSHOT.try_with(|snapshot| *snapshot.borrow_mut() = fields.ok().flatten());
+}
+
+/// A future whose records carry its fields, on whatever thread it is polled.
+pub struct TaskFields<F> {
+    future: std::pin::Pin<Box<F>>,
+    fields: ContextFields,
+}
+
+/// Adds the fields to the records logged while `future` is polled, after
+/// the global and thread fields and before the call fields. Nested tasks
+/// see only their own.
+pub fn with_task_fields<F: std::future::Future>(fields: &[(&str, &dyn fmt::Display)], future: F) -> TaskFields<F> {
+    let fields = fields.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+    TaskFields { future: Box::pin(future), fields: Arc::new(fields) }
+}
+
+impl<F: std::future::Future> std::future::Future for TaskFields<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<F::Output> {
+        let fields = Some(self.fields.clone());
+        let outer = TASK_FIELDS.try_with(|task| task.replace(fields)).ok().flatten();
+        /// Puts the outer task's fields back, also when `poll` panics.
+        struct Restore(Option<ContextFields>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let _ = TASK_FIELDS.try_with(|task| *task.borrow_mut() = self.0.take());
+            }
+        }
+        let _restore = Restore(outer);
+        self.future.as_mut().poll(cx)
+    }
+}
+
+/// What happens to a field whose key an earlier one already has, in the
+/// order global, thread, task, call.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DuplicateFieldPolicy {
+    /// The later field replaces the earlier one (default)
+    LastWins,
+    /// The earlier field stays, the later one is dropped
+    FirstWins,
+    /// Both are written, the later as `key#2` (then `key#3`, ...)
+    KeepBoth,
+    /// The later field wins, and a `[logger]` warning names the key and
+    /// where the two came from
+    ErrorMeta,
+}
+
+static DUPLICATE_FIELD_POLICY: AtomicU8 = AtomicU8::new(DuplicateFieldPolicy::LastWins as u8);
+static SORT_FIELDS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_duplicate_field_policy(policy: DuplicateFieldPolicy) {
+    note_config("duplicate_field_policy", ConfigSource::Api("set_duplicate_field_policy"), format_args!("{policy:?}"));
+    DUPLICATE_FIELD_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+pub fn duplicate_field_policy() -> DuplicateFieldPolicy {
+    match DUPLICATE_FIELD_POLICY.load(Ordering::Relaxed) {
+        1 => DuplicateFieldPolicy::FirstWins,
+        2 => DuplicateFieldPolicy::KeepBoth,
+        3 => DuplicateFieldPolicy::ErrorMeta,
+        _ => DuplicateFieldPolicy::LastWins,
+    }
+}
+
+/// Whether fields are written sorted by key (off by default: in the order
+/// global, thread, task, call). Duplicates kept with `KeepBoth` stay in
+/// their order.
+pub fn set_sort_fields(sort: bool) {
+    note_config("sort_fields", ConfigSource::Api("set_sort_fields"), sort);
+    SORT_FIELDS.store(sort, Ordering::Relaxed);
+}
+
+/// Calls `write` with the context fields and `call`, merged by the
+/// duplicate policy. Without context fields, duplicates or sorting, that
+/// is `call` itself.
+fn with_context_fields<R>(call: &[Field], write: impl FnOnce(&[Field]) -> R) -> R {
+    let global = if HAS_GLOBAL_FIELDS.load(Ordering::Relaxed) { GLOBAL_FIELDS.read().unwrap().clone() } else { None };
+    let thread = THREAD_SNAPSHOT.try_with(|snapshot| snapshot.borrow().clone()).ok().flatten();
+    let task = TASK_FIELDS.try_with(|task| task.borrow().clone()).ok().flatten();
+    let sort = SORT_FIELDS.load(Ordering::Relaxed);
+    let has_duplicates = |fields: &[Field]| fields.iter().enumerate().any(|(i, field)| fields[..i].iter().any(|earlier| earlier.key == field.key));
+    if global.is_none() && thread.is_none() && task.is_none() && !has_duplicates(call) && (!sort || call.is_sorted_by_key(|field| field.key)) {
+        return write(call);
+    }
+
+    let mut all: Vec<(&str, Field)> = Vec::new();
+    for (source, fields) in [("global", &global), ("thread", &thread), ("task", &task)] {
+        for (key, value) in fields.iter().flat_map(|fields| fields.iter()) {
+            all.push((source, Field::display(key, value)));
+        }
+    }
+    all.extend(call.iter().map(|field| ("call", *field)));
+
+    let policy = duplicate_field_policy();
+    let mut collisions = Vec::new();
+    // Per field: kept, and which occurrence of its key it is.
+    let mut plan = Vec::with_capacity(all.len());
+    for (i, (source, field)) in all.iter().enumerate() {
+        let earlier = all[..i].iter().filter(|(_, earlier)| earlier.key == field.key).count();
+        let later = all[i + 1..].iter().any(|(_, later)| later.key == field.key);
+        let kept = match policy {
+            DuplicateFieldPolicy::LastWins | DuplicateFieldPolicy::ErrorMeta => !later,
+            DuplicateFieldPolicy::FirstWins => earlier == 0,
+            DuplicateFieldPolicy::KeepBoth => true,
+        };
+        if policy == DuplicateFieldPolicy::ErrorMeta && earlier > 0 {
+            let first = all[..i].iter().rev().find(|(_, earlier)| earlier.key == field.key).map_or("", |(source, _)| *source);
+            collisions.push(format!("duplicate field {:?} from {first} and {source}; the {source} value is kept", field.key));
+        }
+        plan.push((kept, earlier + 1));
+    }
+    let renamed: Vec<Option<String>> = all.iter().zip(&plan)
+        .map(|((_, field), (_, occurrence))| (*occurrence > 1 && policy == DuplicateFieldPolicy::KeepBoth).then(|| format!("{}#{occurrence}", field.key)))
+        .collect();
+    let mut kept: Vec<(&str, Field)> = all.iter().zip(&plan).zip(&renamed)
+        .filter(|((_, (kept, _)), _)| *kept)
+        .map(|(((_, field), _), renamed)| (field.key, Field { key: renamed.as_deref().unwrap_or(field.key), ..*field }))
+        .collect();
+    // By the key as logged, so `key#2` stays next to `key`.
+    if sort { kept.sort_by_key(|(key, _)| *key); }
+    let fields: Vec<Field> = kept.into_iter().map(|(_, field)| field).collect();
+
+    let written = write(&fields);
+    for collision in collisions { self_log(Level::Warn, format_args!("{collision}")); }
+    written
+}
+
+/// ` key=value key=value`, or nothing without fields.
+struct Fields<'a>(&'a [Field<'a>]);
+
+impl fmt::Display for Fields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in self.0 { write!(f, " {}={}", field.key, Shown(field))?; }
+        Ok(())
+    }
+}
+
+impl<'a> Record<'a> {
+    /// A record with just a level and a message; set the other fields with
+    /// struct update syntax.
+    pub fn new(level: Level, message: &'a str) -> Self {
+        Record {
+            level, emphasis: false, category: None, process: None, thread: None, timestamp: None, target: None, depth: 0,
+            message, raw: None, fields: &[], suppressed: 0, causes: &[], destination: Destination::All, seq: 0,
+            event_seq: None,
+        }
+    }
+
+    /// The message exactly as the call formatted it, before anything else
+    /// touched it: markup tags and escape sequences still in, line breaks
+    /// and all, no error added. For sinks that render it themselves.
+    pub fn raw_message(&self) -> &'a str {
+        self.raw.unwrap_or(self.message)
+    }
+}
+
+/// The layout `set_prefix_template` starts with, which draws lines as they
+/// were before templates existed.
+pub const DEFAULT_PREFIX_TEMPLATE: &str = "{level_short} {process} {thread} {ts} {target} {msg}";
+
+/// Why `set_prefix_template` rejected a template.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum TemplateError {
+    /// A `{name}` that is not one of the placeholders
+    UnknownPlaceholder(String),
+    /// A `{` without its `}`
+    Unclosed,
+    /// `{msg}` is missing or given more than once
+    MessageCount(usize),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => write!(
+                f,
+                "unknown placeholder {{{name}}}: expected {{ts}}, {{level}}, {{level_short}}, {{process}}, {{target}}, {{thread}} or {{msg}}"
+            ),
+            TemplateError::Unclosed => write!(f, "unclosed '{{' in prefix template: write '{{{{' for a literal brace"),
+            TemplateError::MessageCount(n) => write!(f, "prefix template must contain {{msg}} once, found {n}"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PrefixField {
+    Timestamp,
+    Level,
+    LevelShort,
+    Process,
+    Target,
+    Thread,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum PrefixPart {
+    Text(String),
+    Field(PrefixField),
+}
+
+/// A template compiled into the parts before and after `{msg}`.
+#[derive(Debug)]
+struct PrefixTemplate {
+    head: Vec<PrefixPart>,
+    tail: Vec<PrefixPart>,
+}
+
+static PREFIX_TEMPLATE: LazyLock<RwLock<Arc<PrefixTemplate>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(compile_prefix_template(DEFAULT_PREFIX_TEMPLATE).unwrap())));
+
+fn compile_prefix_template(template: &str) -> Result<PrefixTemplate, TemplateError> {
+    let mut compiled = PrefixTemplate { head: Vec::new(), tail: Vec::new() };
+    let mut messages = 0;
+    let mut text = String::new();
+    let mut rest = template;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        text.push_str(&rest[..i]);
+        let brace = if rest[i..].starts_with('{') { '{' } else { '}' };
+        rest = &rest[i + 1..];
+        // `{{` and `}}` are literal braces, and so is a lone `}`.
+        if let Some(after) = rest.strip_prefix(brace) {
+            text.push(brace);
+            rest = after;
+            continue;
+        }
+        if brace == '}' {
+            text.push('}');
+            continue;
+        }
+
+        let end = rest.find('}').ok_or(TemplateError::Unclosed)?;
+        let name = &rest[..end];
+        rest = &rest[end + 1..];
+        let parts = if messages == 0 { &mut compiled.head } else { &mut compiled.tail };
+        if !text.is_empty() { parts.push(PrefixPart::Text(std::mem::take(&mut text))); }
+        let field = match name {
+            "ts" => PrefixField::Timestamp,
+            "level" => PrefixField::Level,
+            "level_short" => PrefixField::LevelShort,
+            "process" => PrefixField::Process,
+            "target" => PrefixField::Target,
+            "thread" => PrefixField::Thread,
+            "msg" => {
+                messages += 1;
+                continue;
+            }
+            _ => return Err(TemplateError::UnknownPlaceholder(name.to_string())),
+        };
+        parts.push(PrefixPart::Field(field));
+    }
+
+    text.push_str(rest);
+    if messages != 1 { return Err(TemplateError::MessageCount(messages)); }
+    if !text.is_empty() { compiled.tail.push(PrefixPart::Text(text)); }
+    Ok(compiled)
+}
+
+/// Sets the layout of pretty and plain lines, e.g. `"{ts} {level} {msg}"`.
+/// The template is checked and compiled here, not on each record.
+pub fn set_prefix_template(template: &str) -> Result<(), TemplateError> {
+    let compiled = compile_prefix_template(template)?;
+    note_config("prefix_template", ConfigSource::Api("set_prefix_template"), format_args!("{template}"));
+    *PREFIX_TEMPLATE.write().unwrap() = Arc::new(compiled);
+    Ok(())
+}
+
+fn prefix_template() -> Arc<PrefixTemplate> {
+    PREFIX_TEMPLATE.read().unwrap().clone()
+}
+
+/// Writes template parts, drawing placeholders with `field`, which returns
+/// whether the record had anything to draw. The text right after an empty
+/// placeholder is left out with it, so `{thread} ` leaves no double space.
+fn write_prefix_parts(
+    out: &mut dyn fmt::Write,
+    parts: &[PrefixPart],
+    mut field: impl FnMut(&mut dyn fmt::Write, PrefixField) -> Result<bool, fmt::Error>,
+) -> fmt::Result {
+    let mut drawn = true;
+    for part in parts {
+        match part {
+            PrefixPart::Text(text) if drawn => out.write_str(text)?,
+            PrefixPart::Text(_) => drawn = true,
+            PrefixPart::Field(f) => drawn = field(out, *f)?,
+        }
+    }
+    Ok(())
+}
+
+/// `DEBUG`, `INFO`, ... for the `{level}` placeholder.
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Debug => "DEBUG",
+        Level::Info => "INFO",
+        Level::Warn => "WARN",
+        Level::Error => "ERROR",
+        Level::None => "NONE",
+    }
+}
+
+/// The pretty console format as a value, so a custom destination can render
+/// records with its own theme and settings, independent of the console's.
+#[derive(Clone, Debug)]
+pub struct PrettyFormatter {
+    theme: Theme,
+    colors: bool,
+    timestamps: bool,
+}
+
+impl Default for PrettyFormatter {
+    fn default() -> Self {
+        PrettyFormatter { theme: Theme::default(), colors: true, timestamps: true }
+    }
+}
+
+impl PrettyFormatter {
+    pub fn new(theme: Theme) -> Self {
+        PrettyFormatter { theme, ..PrettyFormatter::default() }
+    }
+
+    /// Without colors no escape sequences are written at all.
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Writes the line without the trailing newline.
+    pub fn format(&self, record: &Record, out: &mut dyn fmt::Write) -> fmt::Result {
+        let record = Record { timestamp: record.timestamp.filter(|_| self.timestamps), ..*record };
+        format_pretty(&self.theme, self.colors, FieldsLayout::Inline, &record, out)
+    }
+}
+
+fn format_pretty(theme: &Theme, colors: bool, layout: FieldsLayout, record: &Record, out: &mut dyn fmt::Write) -> fmt::Result {
+    if !colors {
+        let mut line = String::new();
+        with_emphasis_marks(record, |record| format_pretty(theme, true, layout, record, &mut line))?;
+        return out.write_str(&strip_ansi(&line));
+    }
+
+    let template = prefix_template();
+    let layout = if record.fields.is_empty() { FieldsLayout::Inline } else { layout };
+    if layout == FieldsLayout::AlignedColumn {
+        // Measured before the fields are placed.
+        let mut line = String::new();
+        write_pretty_message(theme, &template, record, &mut line)?;
+        out.write_str(&line)?;
+        let width = display_width(line.rsplit('\n').next().unwrap_or_default());
+        write!(out, "{:1$}\x1b[2m", "", fields_column().saturating_sub(width).max(1))?;
+        write_fields(out, record.fields)?;
+        out.write_str("\x1b[22m")?;
+    } else {
+        write_pretty_message(theme, &template, record, out)?;
+        if layout == FieldsLayout::Inline && !record.fields.is_empty() {
+            out.write_str(" \x1b[2m")?;
+            write_fields(out, record.fields)?;
+            out.write_str("\x1b[22m")?;
+        }
+    }
+    write_prefix_parts(out, &template.tail, |out, field| write_pretty_field(theme, record, out, field))?;
+    out.write_str(" \x1b[0m")?;
+
+    if layout == FieldsLayout::NextLine {
+        write!(out, "\n{:1$}", "", pretty_message_column(theme, record)?)?;
+        for (i, field) in record.fields.iter().enumerate() {
+            let value = Shown(field).to_string();
+            let color = match value.as_str() {
+                "true" | "false" => "35",
+                _ if is_number(&value) => "36",
+                _ => "39",
+            };
+            write!(out, "{}\x1b[2m{}=\x1b[22;{color}m{value}\x1b[39m", if i > 0 { " " } else { "" }, field.key)?;
+        }
+        out.write_str(" \x1b[0m")?;
+    }
+
+    if !record.causes.is_empty() {
+        let column = pretty_message_column(theme, record)?;
+        for cause in record.causes {
+            out.write_str("\n")?;
+            write!(out, "{:column$}", "")?;
+            render::write_sgr(out, &[&theme.style(Level::Error).msg_color])?;
+            write!(out, "\x1b[2mcaused by: {cause} \x1b[0m")?;
+        }
+    }
+    Ok(())
+}
+
+/// The head, the category, the message and the suppressed count of a pretty
+/// line: everything before the fields.
+fn write_pretty_message(theme: &Theme, template: &PrefixTemplate, record: &Record, out: &mut dyn fmt::Write) -> fmt::Result {
+    let msg_color = if record.emphasis { &theme.alert } else { &theme.style(record.level).msg_color };
+    write_pretty_head(theme, template, record, out)?;
+    if let Some(category) = record.category {
+        write!(out, "\x1b[1m[{category}]\x1b[22m ")?;
+    }
+    let message = apply_markup(record.message.trim_end_matches(['\n', '\r']), msg_color);
+    if message.contains('\n') {
+        write_message_lines(out, &message, pretty_message_column(theme, record)?, msg_color)?;
+    } else {
+        out.write_str(&message)?;
+    }
+    if record.suppressed > 0 {
+        write!(out, " \x1b[2m{}\x1b[22m", Suppressed(record.suppressed))?;
+    }
+    Ok(())
+}
+
+/// `k=v k2=v2`, without the space in front that `Fields` writes.
+fn write_fields(out: &mut dyn fmt::Write, fields: &[Field]) -> fmt::Result {
+    let [first, rest @ ..] = fields else { return Ok(()) };
+    write!(out, "{}={}{}", first.key, Shown(first), Fields(rest))
+}
+
+/// Whether a field value reads as a number, for `FieldsLayout::NextLine`.
+fn is_number(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    digits.starts_with(|c: char| c.is_ascii_digit()) && value.parse::<f64>().is_ok()
+}
+
+/// The prefix template parts before `{msg}`, then the group indent, leaving
+/// the message color set.
+fn write_pretty_head(theme: &Theme, template: &PrefixTemplate, record: &Record, out: &mut dyn fmt::Write) -> fmt::Result {
+    write_prefix_parts(out, &template.head, |out, field| write_pretty_field(theme, record, out, field))?;
+
+    let msg_color = if record.emphasis { &theme.alert } else { &theme.style(record.level).msg_color };
+    let depth = record.depth.min(MAX_GROUP_INDENT);
+    if group_indent() == GroupIndent::Dots && depth > 0 {
+        write!(out, "\x1b[0;2m{}\x1b[22m", indent_dot().repeat(depth))?;
+        render::write_sgr(out, &[msg_color])
+    } else {
+        render::write_sgr(out, &[msg_color])?;
+        for _ in 0..depth { out.write_str("  ")?; }
+        Ok(())
+    }
+}
+
+fn write_pretty_field(theme: &Theme, record: &Record, out: &mut dyn fmt::Write, field: PrefixField) -> Result<bool, fmt::Error> {
+    let style = theme.style(record.level).as_style();
+    match field {
+        PrefixField::Level | PrefixField::LevelShort => {
+            render::write_sgr(out, &["0", style.bg, style.fg])?;
+            if field == PrefixField::Level {
+                write!(out, " {:<5} \x1b[0m", level_name(record.level))?;
+            } else {
+                write!(out, " {} \x1b[0m", style.label)?;
+            }
+        }
+        PrefixField::Process => {
+            let Some(tag) = record.process else { return Ok(false) };
+            write!(out, "\x1b[38;5;{}m{tag}\x1b[0m", hash_color(tag))?;
+        }
+        PrefixField::Thread => {
+            let Some(thread) = record.thread else { return Ok(false) };
+            if THREAD_COLORS.load(Ordering::Relaxed) {
+                write!(out, "\x1b[0;38;5;{}m[{thread}]\x1b[0m", hash_color(thread))?;
+            } else {
+                write!(out, "\x1b[0;2m[{thread}]\x1b[22m")?;
+            }
+        }
+        PrefixField::Timestamp => {
+            let Some(ts) = record.timestamp else { return Ok(false) };
+            render::write_sgr(out, &[if record.emphasis { &theme.alert } else { style.ts_color }])?;
+            write!(out, "[{ts}]")?;
+        }
+        PrefixField::Target => {
+            let Some(target) = record.target else { return Ok(false) };
+            let (shown, pad) = target_column(target);
+            let (counter, pad) = event_seq_column(record, pad);
+            match target_color(target) {
+                Some(color) => write!(out, "\x1b[0;{}m{shown}{counter}:\x1b[0m{:pad$}", color.sgr(false), "")?,
+                None => write!(out, "\x1b[0;2m{shown}{counter}:\x1b[22m{:pad$}", "")?,
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Width of what `format_pretty` draws before the message, group indent
+/// included, so lines under the first can start at the same column.
+fn pretty_message_column(theme: &Theme, record: &Record) -> Result<usize, fmt::Error> {
+    let mut head = String::new();
+    write_pretty_head(theme, &prefix_template(), record, &mut head)?;
+    Ok(strip_ansi(&head).chars().count())
+}
+
+/// Writes a rendered message and moves each line after the first to
+/// `column`, re-opening the markup styles still open at the line break.
+fn write_message_lines(out: &mut dyn fmt::Write, message: &str, column: usize, msg_color: &str) -> fmt::Result {
+    let gutter = multiline() == Multiline::Gutter;
+    // Escape sequences written since the last reset.
+    let mut open = String::new();
+
+    for (i, line) in message.split('\n').enumerate() {
+        if i > 0 {
+            write!(out, " \x1b[0m\n{:column$}", "")?;
+            if gutter { write!(out, "\x1b[2m{} \x1b[22m", glyphs().gutter)?; }
+            render::write_sgr(out, &[msg_color])?;
+            out.write_str(&open)?;
+        }
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        out.write_str(line)?;
+
+        let mut rest = line;
+        while let Some(start) = rest.find("\x1b[") {
+            let Some(len) = rest[start..].find('m') else { break };
+            let sequence = &rest[start..=start + len];
+            if sequence == "\x1b[0m" || sequence == "\x1b[m" { open.clear(); } else { open.push_str(sequence); }
+            rest = &rest[start + len + 1..];
+        }
+    }
+    Ok(())
+}
+
+/// Without colors there is no alert style, so an emphasized record gets
+/// `!!! message !!!` instead.
+fn with_emphasis_marks<T>(record: &Record, f: impl FnOnce(&Record) -> T) -> T {
+    if !record.emphasis { return f(record); }
+    let marked = format!("!!! {} !!!", record.message.trim_end_matches(['\n', '\r']));
+    f(&Record { emphasis: false, message: &marked, ..*record })
+}
+
+/// The console line for `ColorChoice::Never`: the label in brackets and no
+/// escape sequences at all.
+fn format_plain(
+    label: &str, layout: FieldsLayout, continuation: ContinuationStyle, record: &Record, out: &mut dyn fmt::Write,
+) -> fmt::Result {
+    if record.emphasis {
+        return with_emphasis_marks(record, |record| format_plain(label, layout, continuation, record, out));
+    }
+    let template = prefix_template();
+    let lines = PlainLines { label, template: &template, continuation, record };
+    let layout = if record.fields.is_empty() { FieldsLayout::Inline } else { layout };
+    if layout == FieldsLayout::AlignedColumn {
+        let mut line = String::new();
+        let column = lines.write_message(&mut line)?;
+        out.write_str(&line)?;
+        let width = display_width(line.rsplit('\n').next().unwrap_or_default());
+        write!(out, "{:1$}", "", fields_column().saturating_sub(width).max(1))?;
+        write_fields(out, record.fields)?;
+        return lines.write_tail(column, out);
+    }
+    let column = lines.write_message(out)?;
+    if layout == FieldsLayout::NextLine {
+        PlainLines { record: &Record { causes: &[], ..*record }, ..lines }.write_tail(column, out)?;
+        let column = if column > 0 { column } else { plain_message_column(label, &template, record)? };
+        write!(out, "\n{:column$}", "")?;
+        write_fields(out, record.fields)?;
+        for cause in record.causes { write!(out, "\n{:column$}caused by: {cause}", "")?; }
+        return Ok(());
+    }
+    write!(out, "{}", Fields(record.fields))?;
+    lines.write_tail(column, out)
+}
+
+/// Width of what `format_plain` draws before the message.
+fn plain_message_column(label: &str, template: &PrefixTemplate, record: &Record) -> Result<usize, fmt::Error> {
+    let mut head = String::new();
+    write_plain_head(label, template, record, &mut head)?;
+    Ok(head.chars().count())
+}
+
+/// What `format_plain` draws a record's lines with.
+#[derive(Clone, Copy)]
+struct PlainLines<'a> {
+    label: &'a str,
+    template: &'a PrefixTemplate,
+    continuation: ContinuationStyle,
+    record: &'a Record<'a>,
+}
+
+impl PlainLines<'_> {
+    /// Everything `format_plain` draws before the fields. Returns the
+    /// message column when the lines under the first need it, 0 otherwise.
+    fn write_message(&self, out: &mut dyn fmt::Write) -> Result<usize, fmt::Error> {
+        let PlainLines { label, template, continuation, record } = *self;
+        write_plain_head(label, template, record, out)?;
+        let message = strip_ansi(&apply_markup(record.message.trim_end_matches(['\n', '\r']), ""));
+        let spans_lines = message.contains('\n') || !record.causes.is_empty();
+        if spans_lines && continuation == ContinuationStyle::RepeatPrefix { write!(out, "#{} ", record.seq)?; }
+        if let Some(category) = record.category { write!(out, "[{category}] ")?; }
+        let indented = matches!(continuation, ContinuationStyle::Gutter | ContinuationStyle::Indent);
+        let column = if spans_lines && indented { plain_message_column(label, template, record)? } else { 0 };
+        for (i, line) in message.split('\n').enumerate() {
+            if i > 0 { self.write_break(continuation, column, out)?; }
+            out.write_str(line.strip_suffix('\r').unwrap_or(line))?;
+        }
+        if record.suppressed > 0 { write!(out, " {}", Suppressed(record.suppressed))?; }
+        Ok(column)
+    }
+
+    /// The prefix template parts after the message, then the causes at
+    /// `column`.
+    fn write_tail(&self, column: usize, out: &mut dyn fmt::Write) -> fmt::Result {
+        let PlainLines { label, template, record, .. } = *self;
+        write_prefix_parts(out, &template.tail, |out, field| write_plain_field(label, record, out, field))?;
+        let continuation = match self.continuation {
+            ContinuationStyle::Gutter => ContinuationStyle::Indent,
+            style => style,
+        };
+        for cause in record.causes {
+            self.write_break(continuation, column, out)?;
+            write!(out, "caused by: {cause}")?;
+        }
+        Ok(())
+    }
+
+    /// A line break and what starts the next line in `continuation`.
+    fn write_break(&self, continuation: ContinuationStyle, column: usize, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_char('\n')?;
+        match continuation {
+            ContinuationStyle::Gutter => write!(out, "{:column$}{} ", "", glyphs().gutter),
+            ContinuationStyle::Indent => write!(out, "{:column$}", ""),
+            ContinuationStyle::RepeatPrefix => {
+                write_plain_head(self.label, self.template, self.record, out)?;
+                write!(out, "#{} ", self.record.seq)
+            }
+            ContinuationStyle::None => Ok(()),
+        }
+    }
+}
+
+fn write_plain_head(label: &str, template: &PrefixTemplate, record: &Record, out: &mut dyn fmt::Write) -> fmt::Result {
+    write_prefix_parts(out, &template.head, |out, field| write_plain_field(label, record, out, field))?;
+    let indent = if group_indent() == GroupIndent::Dots { indent_dot() } else { "  " };
+    for _ in 0..record.depth.min(MAX_GROUP_INDENT) { out.write_str(indent)?; }
+    Ok(())
+}
+
+fn write_plain_field(label: &str, record: &Record, out: &mut dyn fmt::Write, field: PrefixField) -> Result<bool, fmt::Error> {
+    match field {
+        PrefixField::Level => write!(out, "[{:<5}]", level_name(record.level))?,
+        PrefixField::LevelShort => write!(out, "[{label}]")?,
+        PrefixField::Process => {
+            let Some(tag) = record.process else { return Ok(false) };
+            out.write_str(tag)?;
+        }
+        PrefixField::Thread => {
+            let Some(thread) = record.thread else { return Ok(false) };
+            write!(out, "[{thread}]")?;
+        }
+        PrefixField::Timestamp => {
+            let Some(ts) = record.timestamp else { return Ok(false) };
+            write!(out, "[{ts}]")?;
+        }
+        PrefixField::Target => {
+            let Some(target) = record.target else { return Ok(false) };
+            let (shown, pad) = target_column(target);
+            let (counter, pad) = event_seq_column(record, pad);
+            write!(out, "{shown}{counter}:{:pad$}", "")?;
+        }
+    }
+    Ok(true)
+}
+
+/// `(suppressed 3 similar messages)`.
+struct Suppressed(u64);
+
+impl fmt::Display for Suppressed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = if self.0 == 1 { "" } else { "s" };
+        write!(f, "(suppressed {} similar message{plural})", self.0)
+    }
+}
+
+/// Line format of the console output.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// Colored label, timestamp and message (default)
+    Pretty,
+    /// One JSON object per line
+    Json,
+    /// `key=value` pairs, one record per line
+    Logfmt,
+    /// The `Pretty` layout without any escape sequence, whatever the color
+    /// choice
+    Plain,
+}
+
+impl Format {
+    /// Parses `pretty`, `json`, `logfmt` or `plain`, as in `LOG_FORMAT`.
+    pub fn parse(name: &str) -> Result<Format, ConfigError> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "pretty" => Ok(Format::Pretty),
+            "json" => Ok(Format::Json),
+            "logfmt" => Ok(Format::Logfmt),
+            "plain" => Ok(Format::Plain),
+            _ => Err(ConfigError::UnknownFormat(name.to_string())),
+        }
+    }
+}
+
+/// 0 until `set_format` or `LOG_FORMAT` decided, then the format plus one.
+static FORMAT: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_format(format: Format) {
+    note_config("format", ConfigSource::Api("set_format"), format_args!("{format:?}"));
+    FORMAT.store(format as u8 + 1, Ordering::Relaxed);
+}
+
+/// The format set with `set_format`, or else read from `LOG_FORMAT` by the
+/// first record (or `Builder::init`); `Pretty` without either.
+pub fn output_format() -> Format {
+    let mut stored = FORMAT.load(Ordering::Relaxed);
+    if stored == 0 {
+        let (format, error) = format_from_env();
+        stored = match FORMAT.compare_exchange(0, format as u8 + 1, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => {
+                // Stored first: the warning is a record, which reads the format.
+                if let Some(e) = error { self_log(Level::Warn, format_args!("using the pretty format: {e}")); }
+                format as u8 + 1
+            }
+            Err(current) => current,
+        };
+    }
+    match stored {
+        2 => Format::Json,
+        3 => Format::Logfmt,
+        4 => Format::Plain,
+        _ => Format::Pretty,
+    }
+}
+
+fn format_from_env() -> (Format, Option<ConfigError>) {
+    let Ok(name) = std::env::var("LOG_FORMAT") else { return (Format::Pretty, None) };
+
+    match Format::parse(&name) {
+        Ok(format) => {
+            note_config("format", ConfigSource::Env("LOG_FORMAT"), format_args!("{format:?}"));
+            (format, None)
+        }
+        Err(e) => {
+            note_config("format", ConfigSource::Ignored("LOG_FORMAT"), format!("{name} (unknown format)"));
+            (Format::Pretty, Some(e))
+        }
+    }
+}
+
+/// Member layout of JSON records.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JsonFlavor {
+    /// `level` as a name, fields as top-level members (default)
+    Default,
+    /// OpenTelemetry log data model: `severity_number` and `severity_text`
+    /// (see `Level::otel_severity`) in place of `level`, a nanosecond
+    /// `timestamp`, and the fields nested under `attributes`
+    Otel,
+}
+
+static JSON_FLAVOR: AtomicU8 = AtomicU8::new(JsonFlavor::Default as u8);
+
+/// Picks the member layout of every JSON record: the console, log files,
+/// sinks and `JsonFormatter`.
+pub fn set_json_flavor(flavor: JsonFlavor) {
+    note_config("json_flavor", ConfigSource::Api("set_json_flavor"), format_args!("{flavor:?}"));
+    JSON_FLAVOR.store(flavor as u8, Ordering::Relaxed);
+}
+
+pub fn json_flavor() -> JsonFlavor {
+    match JSON_FLAVOR.load(Ordering::Relaxed) {
+        1 => JsonFlavor::Otel,
+        _ => JsonFlavor::Default,
+    }
+}
+
+/// One JSON object per record, for log shippers:
+/// `{"timestamp":"2024-05-01T12:00:00.000Z","level":"warn","message":"disk full"}`.
+/// Markup tags are removed, not rendered. `process` and `category` are added
+/// when set, and so is `thread` with `console::show_thread`; a `throttle:`
+/// count follows `message` as the number `suppressed`, an `err =` chain as
+/// the string array `causes`, then fields as string members. See
+/// `set_json_flavor` for the OpenTelemetry layout.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct JsonFormatter;
+
+impl JsonFormatter {
+    /// Writes the object without the trailing newline.
+    pub fn format(&self, record: &Record, out: &mut dyn fmt::Write) -> fmt::Result {
+        let names = field_names();
+        let message = strip_ansi(&apply_markup(record.message, ""));
+        let otel = json_flavor() == JsonFlavor::Otel;
+
+        out.write_char('{')?;
+        let mut first = true;
+        for (key, value) in standard_pairs(record, &message, names.as_deref()) {
+            if !std::mem::take(&mut first) { out.write_char(',')?; }
+            if otel && key == StandardKey::Level {
+                let (number, text) = record.level.otel_severity();
+                write!(out, r#""severity_number":{number},"severity_text":"{text}""#)?;
+                continue;
+            }
+            write_json_string(out, &key_name(names.as_deref(), key, true))?;
+            out.write_char(':')?;
+            match value {
+                StandardValue::Text(text) => write_json_string(out, text)?,
+                StandardValue::Count(count) => write!(out, "{count}")?,
+                StandardValue::List(items) => {
+                    out.write_char('[')?;
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 { out.write_char(',')?; }
+                        write_json_string(out, item)?;
+                    }
+                    out.write_char(']')?;
+                }
+            }
+        }
+        if otel && !record.fields.is_empty() { out.write_str(r#","attributes":{"#)?; }
+        for (i, field) in record.fields.iter().enumerate() {
+            if !otel || i > 0 { out.write_char(',')?; }
+            write_json_string(out, field_name(names.as_deref(), field.key))?;
+            out.write_char(':')?;
+            write_json_field(out, field)?;
+        }
+        if otel && !record.fields.is_empty() { out.write_char('}')?; }
+        out.write_char('}')
+    }
+}
+
+/// One line of `key=value` pairs per record, for logfmt-based tools:
+/// `ts=2024-05-01T12:00:00.000Z level=warn target=my_app msg="disk full"`.
+/// The keys follow `JsonFormatter`'s members, with `ts` and `msg` for
+/// `timestamp` and `message`, and the `err =` chain joined by `: ` in
+/// `causes`. Values are quoted when they are empty or hold a space, `=`,
+/// `"` or a control character.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LogfmtFormatter;
+
+impl LogfmtFormatter {
+    /// Writes the pairs without the trailing newline.
+    pub fn format(&self, record: &Record, out: &mut dyn fmt::Write) -> fmt::Result {
+        let names = field_names();
+        let message = strip_ansi(&apply_markup(record.message, ""));
+
+        let mut first = true;
+        for (key, value) in standard_pairs(record, &message, names.as_deref()) {
+            if !std::mem::take(&mut first) { out.write_char(' ')?; }
+            write!(out, "{}=", key_name(names.as_deref(), key, false))?;
+            match value {
+                // The level is always a bare word.
+                StandardValue::Text(text) if key == StandardKey::Level => out.write_str(text)?,
+                StandardValue::Text(text) => write_logfmt_value(out, text)?,
+                StandardValue::Count(count) => write!(out, "{count}")?,
+                StandardValue::List(items) => write_logfmt_value(out, &items.join(": "))?,
+            }
+        }
+        for field in record.fields {
+            write!(out, " {}=", field_name(names.as_deref(), field.key))?;
+            write_logfmt_value(out, &Shown(field).to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// The members JSON and logfmt records have besides the fields, in their
+/// default order.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StandardKey {
+    /// `timestamp` in JSON, `ts` in logfmt
+    Timestamp,
+    Level,
+    Process,
+    Thread,
+    Target,
+    /// `event_seq`, from `set_event_counter`
+    EventSeq,
+    Category,
+    /// `message` in JSON, `msg` in logfmt
+    Message,
+    Suppressed,
+    Causes,
+}
+
+impl StandardKey {
+    const ALL: [StandardKey; 10] = [
+        StandardKey::Timestamp, StandardKey::Level, StandardKey::Process, StandardKey::Thread, StandardKey::Target,
+        StandardKey::EventSeq, StandardKey::Category, StandardKey::Message, StandardKey::Suppressed, StandardKey::Causes,
+    ];
+
+    fn default_name(self, json: bool) -> &'static str {
+        match self {
+            StandardKey::Timestamp if json => "timestamp",
+            StandardKey::Timestamp => "ts",
+            StandardKey::Level => "level",
+            StandardKey::Process => "process",
+            StandardKey::Thread => "thread",
+            StandardKey::Target => "target",
+            StandardKey::EventSeq => "event_seq",
+            StandardKey::Category => "category",
+            StandardKey::Message if json => "message",
+            StandardKey::Message => "msg",
+            StandardKey::Suppressed => "suppressed",
+            StandardKey::Causes => "causes",
+        }
+    }
+}
+
+/// Key names and order for JSON and logfmt records, for collectors that
+/// expect their own: `FieldNames::default().rename(StandardKey::Level,
+/// "severity").order(&[StandardKey::Timestamp])`.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct FieldNames {
+    standard: Vec<(StandardKey, String)>,
+    fields: Vec<(String, String)>,
+    order: Vec<StandardKey>,
+}
+
+impl FieldNames {
+    /// Writes `key` as `name` in both formats.
+    pub fn rename(mut self, key: StandardKey, name: &str) -> Self {
+        self.standard.retain(|(existing, _)| *existing != key);
+        self.standard.push((key, name.to_string()));
+        self
+    }
+
+    /// Writes the fields named `key` as `name`, whether a call or a context
+    /// added them.
+    pub fn rename_field(mut self, key: &str, name: &str) -> Self {
+        self.fields.retain(|(existing, _)| existing != key);
+        self.fields.push((key.to_string(), name.to_string()));
+        self
+    }
+
+    /// Writes `keys` first, in this order, then the other standard keys in
+    /// the default order. The fields always come last.
+    pub fn order(mut self, keys: &[StandardKey]) -> Self {
+        self.order.clear();
+        for key in keys {
+            if !self.order.contains(key) { self.order.push(*key); }
+        }
+        self
+    }
+}
+
+static FIELD_NAMES: RwLock<Option<Arc<FieldNames>>> = RwLock::new(None);
+static HAS_FIELD_NAMES: AtomicBool = AtomicBool::new(false);
+
+/// Applies `names` to every JSON and logfmt record: the console, log
+/// files, sinks and `JsonFormatter`/`LogfmtFormatter`.
+/// `FieldNames::default()` restores the default names and order.
+pub fn set_field_names(names: FieldNames) {
+    note_config("field_names", ConfigSource::Api("set_field_names"), format_args!("{names:?}"));
+    let custom = names != FieldNames::default();
+    *FIELD_NAMES.write().unwrap() = custom.then(|| Arc::new(names));
+    HAS_FIELD_NAMES.store(custom, Ordering::Relaxed);
+}
+
+pub fn field_names() -> Option<Arc<FieldNames>> {
+    if !HAS_FIELD_NAMES.load(Ordering::Relaxed) { return None; }
+    FIELD_NAMES.read().unwrap().clone()
+}
+
+fn key_name(names: Option<&FieldNames>, key: StandardKey, json: bool) -> Cow<'static, str> {
+    match names.and_then(|names| names.standard.iter().find(|(existing, _)| *existing == key)) {
+        Some((_, name)) => Cow::Owned(name.clone()),
+        None => Cow::Borrowed(key.default_name(json)),
+    }
+}
+
+fn field_name<'a>(names: Option<&'a FieldNames>, key: &'a str) -> &'a str {
+    names.and_then(|names| names.fields.iter().find(|(existing, _)| existing == key)).map_or(key, |(_, name)| name)
+}
+
+enum StandardValue<'a> {
+    Text(&'a str),
+    Count(u64),
+    List(&'a [&'a str]),
+}
+
+/// The standard keys in the configured order.
+fn standard_order(names: Option<&FieldNames>) -> [StandardKey; 10] {
+    let mut keys = StandardKey::ALL;
+    let Some(names) = names.filter(|names| !names.order.is_empty()) else { return keys };
+    let rest = StandardKey::ALL.into_iter().filter(|key| !names.order.contains(key));
+    for (slot, key) in keys.iter_mut().zip(names.order.iter().copied().chain(rest)) { *slot = key; }
+    keys
+}
+
+/// The standard members `record` has, in the configured order.
+fn standard_pairs<'a>(
+    record: &'a Record, message: &'a str, names: Option<&FieldNames>,
+) -> impl Iterator<Item = (StandardKey, StandardValue<'a>)> + 'a {
+    let level = match record.level {
+        Level::Debug => "debug",
+        Level::Info => "info",
+        Level::Warn => "warn",
+        Level::Error => "error",
+        Level::None => "none",
+    };
+    standard_order(names).into_iter().filter_map(move |key| {
+        let value = match key {
+            StandardKey::Timestamp => record.timestamp.map(StandardValue::Text),
+            StandardKey::Level => Some(StandardValue::Text(level)),
+            StandardKey::Process => record.process.map(StandardValue::Text),
+            StandardKey::Thread => record.thread.map(StandardValue::Text),
+            StandardKey::Target => record.target.map(StandardValue::Text),
+            StandardKey::EventSeq => record.event_seq.map(StandardValue::Count),
+            StandardKey::Category => record.category.map(StandardValue::Text),
+            StandardKey::Message => Some(StandardValue::Text(message)),
+            StandardKey::Suppressed => (record.suppressed > 0).then_some(StandardValue::Count(record.suppressed)),
+            StandardKey::Causes => (!record.causes.is_empty()).then_some(StandardValue::List(record.causes)),
+        };
+        value.map(|value| (key, value))
+    })
+}
+
+/// A field value in a JSON record: a string, or what the field formatter
+/// for its type returns.
+fn write_json_field(out: &mut dyn fmt::Write, field: &Field) -> fmt::Result {
+    let json = match field.value {
+        FieldValue::Typed { value, .. } => field_formatter(value.type_id()).map(|formatter| (formatter.json)(value)),
+        _ => None,
+    };
+    match json {
+        None => write_json_string(out, &Shown(field).to_string()),
+        Some(JsonValue::String(text)) => write_json_string(out, &Shown(&Field::display(field.key, &text)).to_string()),
+        Some(JsonValue::Null) => out.write_str("null"),
+        Some(JsonValue::Bool(value)) => write!(out, "{value}"),
+        Some(JsonValue::Integer(value)) => write!(out, "{value}"),
+        Some(JsonValue::Number(value)) if value.is_finite() => write!(out, "{value}"),
+        Some(JsonValue::Number(_)) => out.write_str("null"),
+    }
+}
+
+fn write_logfmt_value(out: &mut dyn fmt::Write, s: &str) -> fmt::Result {
+    let bare = !s.is_empty() && !s.chars().any(|c| c == ' ' || c == '=' || c == '"' || c.is_control());
+    if bare { return out.write_str(s); }
+    // The same escapes as JSON strings, which logfmt readers accept.
+    write_json_string(out, s)
+}
+
+fn write_json_string(out: &mut dyn fmt::Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for ch in s.chars() {
+        match ch {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 || c == '\u{7f}' => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+static SLOW_CALL_BUDGET_NANOS: AtomicU64 = AtomicU64::new(0);
+/// Set after a call went over the budget: the next ones time every
+/// destination, until one is fast again.
+static SLOW_CALL_DETAIL: AtomicBool = AtomicBool::new(false);
+static LAST_SLOW_CALL_WARNING: Mutex<Option<Instant>> = Mutex::new(None);
+
+const SLOW_CALL_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Warns (as a `[logger]` diagnostic, at most every 10 seconds) when writing
+/// a record to the console, the file sinks and the sinks takes longer than
+/// `budget`, with the time each of them took. `None` (the default) turns it
+/// off. Costs one `Instant::now()` pair per record; the destinations are
+/// timed one by one only once a call was over the budget.
+pub fn warn_if_log_call_exceeds(budget: Option<Duration>) {
+    note_config("slow_call_budget", ConfigSource::Api("warn_if_log_call_exceeds"), format_args!("{budget:?}"));
+    let nanos = budget.map_or(0, |budget| u64::try_from(budget.as_nanos()).unwrap_or(u64::MAX).max(1));
+    SLOW_CALL_BUDGET_NANOS.store(nanos, Ordering::Relaxed);
+    SLOW_CALL_DETAIL.store(false, Ordering::Relaxed);
+}
+
+/// Time per destination, collected only in the detail phase.
+struct Timings(Option<Vec<(String, Duration)>>);
+
+impl Timings {
+    fn start(&self) -> Option<Instant> {
+        self.0.as_ref().map(|_| Instant::now())
+    }
+
+    fn stop(&mut self, started: Option<Instant>, name: impl FnOnce() -> String) {
+        if let (Some(times), Some(started)) = (self.0.as_mut(), started) { times.push((name(), started.elapsed())); }
+    }
+}
+
+/// Formats and writes one record; the group depth is filled in here. Prints
+/// it on the console (when `to_console`) and the file sinks, and hands it to
+/// the sinks that take its level.
+fn print_line(record: Record, to_console: bool) {
+    let budget = SLOW_CALL_BUDGET_NANOS.load(Ordering::Relaxed);
+    if budget == 0 {
+        write_record(record, to_console, &mut Timings(None));
+        return;
+    }
+
+    let mut timings = Timings(SLOW_CALL_DETAIL.load(Ordering::Relaxed).then(Vec::new));
+    let started = Instant::now();
+    write_record(record, to_console, &mut timings);
+    let took = started.elapsed();
+
+    if took.as_nanos() <= u128::from(budget) {
+        SLOW_CALL_DETAIL.store(false, Ordering::Relaxed);
+        return;
+    }
+    match timings.0 {
+        None => SLOW_CALL_DETAIL.store(true, Ordering::Relaxed),
+        Some(mut times) => {
+            let now = Instant::now();
+            {
+                let mut last = LAST_SLOW_CALL_WARNING.lock().unwrap();
+                if last.is_some_and(|at| now.duration_since(at) < SLOW_CALL_WARNING_INTERVAL) { return; }
+                *last = Some(now);
+            }
+
+            times.sort_by_key(|(_, took)| std::cmp::Reverse(*took));
+            let listed: Vec<String> = times.iter().map(|(name, took)| format!("{name} {took:.1?}")).collect();
+            self_log(Level::Warn, format_args!(
+                "log call took {took:.1?}, over the {:.1?} budget: {}", Duration::from_nanos(budget), listed.join(", "),
+            ));
+        }
+    }
+}
+
+/// The last `Record::seq` handed out.
+static RECORD_SEQ: AtomicU64 = AtomicU64::new(0);
+
+fn write_record(record: Record, to_console: bool, timings: &mut Timings) {
+    apply_theme_schedule();
+    let level = record.level;
+    let thread = match record.thread {
+        None if SHOW_THREAD.load(Ordering::Relaxed) => thread_label(),
+        _ => None,
+    };
+    let destination = match record.destination {
+        Destination::All => DESTINATION.try_with(Cell::get).unwrap_or(Destination::All),
+        chosen => chosen,
+    };
+    let seq = if record.seq == 0 { RECORD_SEQ.fetch_add(1, Ordering::Relaxed) + 1 } else { record.seq };
+    let record = Record { depth: current_depth(), thread: record.thread.or(thread.as_deref()), destination, seq, ..record };
+    #[cfg(all(unix, feature = "signals"))]
+    note_for_dump(level);
+
+    // Muted, it only gets here for the black box.
+    let muted = muted(level);
+    let seq = if muted { None } else { keep_for_replay(&record, to_console) };
+    let mut plain = PlainText::default();
+    if SINK_LEVEL.load(Ordering::Relaxed) != Level::None as u8 && !muted {
+        write_sinks(&record, seq, &mut plain, timings);
+    }
+    // The log files follow the console level unless they have their own.
+    let console = to_console && destination.console();
+    let files = destination.files() && HAS_FILE_SINKS.load(Ordering::Relaxed)
+        && (to_console || level as u8 >= FILE_SINK_LEVEL.load(Ordering::Relaxed));
+    let black_box = (to_console || muted) && destination.files();
+    if !console && !files && !black_box { return; }
+
+    let mut line = String::new();
+    match output_format() {
+        Format::Pretty if stable_test_format() => {
+            let _ = format_pretty(&THEME.read().unwrap(), false, fields_layout(), &record, &mut line);
+        }
+        Format::Plain => {
+            let _ = format_pretty(&THEME.read().unwrap(), false, fields_layout(), &record, &mut line);
+        }
+        Format::Pretty if colors_enabled() => {
+            let _ = format_pretty(&THEME.read().unwrap(), true, fields_layout(), &record, &mut line);
+            line.push_str(bell_for(level));
+        }
+        Format::Pretty => {
+            let theme = THEME.read().unwrap();
+            let _ = format_plain(&theme.style(level).label, fields_layout(), ContinuationStyle::of_console(), &record, &mut line);
+        }
+        Format::Json => {
+            let _ = JsonFormatter.format(&record, &mut line);
+        }
+        Format::Logfmt => {
+            let _ = LogfmtFormatter.format(&record, &mut line);
+        }
+    }
+    line.push('\n');
+
+    #[cfg(unix)]
+    if let Some(black_box) = BLACK_BOX.get().filter(|_| black_box) {
+        let started = timings.start();
+        black_box.record(plain.get(&record, ContinuationStyle::of_console()));
+        timings.stop(started, || String::from("black box"));
+    }
+
+    if files {
+        let structured = matches!(output_format(), Format::Json | Format::Logfmt);
+        let mut texts: [String; 4] = Default::default();
+        if !structured {
+            for style in file_continuations() {
+                let mut text = plain.take(&record, style);
+                text.push('\n');
+                texts[style as usize] = text;
+            }
+        }
+        let same = if structured { line.as_str() } else { "" };
+        // A batch is written to the files that follow the console.
+        if !(to_console && batched_files(same, &texts)) { write_file_sinks(same, &texts, level, to_console, seq, timings); }
+    }
+
+    if !console { return; }
+    let width = match wrap() {
+        Wrap::Off => 0,
+        Wrap::Terminal => terminal_width(),
+        Wrap::Columns(columns) => columns,
+    };
+    if width > 0 && matches!(output_format(), Format::Pretty | Format::Plain) {
+        let column = pretty_message_column(&THEME.read().unwrap(), &record).unwrap_or(0);
+        let colors = output_format() == Format::Pretty && colors_enabled() && !stable_test_format();
+        if let Some(wrapped) = wrap_lines(&line, width, column, colors) { line = wrapped; }
+    }
+    LOGGED.store(true, Ordering::Relaxed);
+    if batched(&line) { return; }
+    let started = timings.start();
+    emit(&line);
+    timings.stop(started, || String::from("console"));
+}
+
+/// A blank line on the console, in the log files and in the sinks that get
+/// text. A blank line is not a JSON or logfmt record, so structured output
+/// skips it. With a `level`, sinks and files that would not take a record at
+/// that level skip it too; without one every output gets it.
+pub fn print_new_line(level: Option<Level>) {
+    let structured = matches!(output_format(), Format::Json | Format::Logfmt);
+    if SINK_LEVEL.load(Ordering::Relaxed) != Level::None as u8 && !IN_SINK_WRITE.with(Cell::get) {
+        let sinks: Vec<Arc<SinkEntry>> = SINKS
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|entry| matches!(entry.format, Format::Pretty | Format::Plain) && entry.route.matches(None))
+            .filter(|entry| level.is_none_or(|level| level >= entry.level) && !sink_muted(SinkKey::Sink(entry.id)))
+            .cloned()
+            .collect();
+        let record = Record::new(level.unwrap_or(Level::Info), "");
+        for entry in &sinks { write_sink(entry, &record, ""); }
+    }
+    if structured { return; }
+
+    if HAS_FILE_SINKS.load(Ordering::Relaxed) && !batched_files("", &[(); 4].map(|()| String::from("\n"))) {
+        let mut sinks = FILE_SINKS.lock().unwrap();
+        let takes = |sink: &FileSink| level.is_none_or(|level| sink.takes(level, true)) && !sink_muted(SinkKey::File(sink.id));
+        for sink in sinks.iter_mut().filter(|sink| takes(sink)) {
+            if let Err(e) = sink.write("\n", Level::Info) {
+                if !std::mem::replace(&mut sink.failed, true) {
+                    self_log_to(SelfLog::Stderr, Level::Error, format_args!("file sink {} failed: {e}", sink.path.display()));
+                }
+            }
+        }
+    }
+    if !batched("\n") { emit("\n"); }
+}
+
+/// Where console lines are broken to fit a width.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Wrap {
+    /// Lines are as long as the record (default)
+    Off,
+    /// At `terminal_width()`
+    Terminal,
+    /// At this many columns
+    Columns(usize),
+}
+
+/// 0 while off, `usize::MAX` for the terminal width.
+static WRAP: AtomicUsize = AtomicUsize::new(0);
+
+/// Breaks console lines longer than the width at the last space that fits,
+/// and continues them under the message. A word longer than the room left
+/// is cut at the width and ends with `display::glyphs().wrap`. Styles open
+/// at a break are opened again on the next line. Only the pretty console
+/// output is wrapped; log files, sinks, JSON and logfmt keep one line per
+/// record line.
+pub fn set_wrap(wrap: Wrap) {
+    note_config("wrap", ConfigSource::Api("set_wrap"), format_args!("{wrap:?}"));
+    WRAP.store(match wrap { Wrap::Off => 0, Wrap::Terminal => usize::MAX, Wrap::Columns(columns) => columns.max(1) }, Ordering::Relaxed);
+}
+
+pub fn wrap() -> Wrap {
+    match WRAP.load(Ordering::Relaxed) {
+        0 => Wrap::Off,
+        usize::MAX => Wrap::Terminal,
+        columns => Wrap::Columns(columns),
+    }
+}
+
+/// Columns a message needs after `column` besides the wrap glyph, or the
+/// wrapping is left off.
+const MIN_WRAP_ROOM: usize = 8;
+
+/// `text` with its lines broken to `width` columns, each new line starting
+/// at `column`; `None` if every line fits. One pass over `text`: a break
+/// only moves the part of the line after it.
+fn wrap_lines(text: &str, width: usize, column: usize, colors: bool) -> Option<String> {
+    // A line's bytes are at least its columns.
+    if text.split('\n').all(|line| line.len() <= width) { return None; }
+    let marker = glyphs().wrap;
+    let marker_width = display_width(marker);
+    if width < column + marker_width + MIN_WRAP_ROOM { return None; }
+
+    let mut out = String::with_capacity(text.len() + text.len() / width * (column + 8));
+    // Escape sequences written since the last reset, as at the
+    // continuation lines of `write_message_lines`.
+    let mut open = String::new();
+    let mut col = 0;
+    // Where the line can be broken: after the last space, else where the
+    // wrap glyph fits; with the column there and the styles open there.
+    let mut space: Option<(usize, usize, String)> = None;
+    let mut hard: Option<(usize, usize, String)> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((at, ch)) = chars.next() {
+        if ch == '\x1b' && text[at + 1..].starts_with('[') {
+            let len = text[at + 2..].find(|c| ('@'..='~').contains(&c)).map_or(text.len() - at, |end| end + 3);
+            let sequence = &text[at..at + len];
+            if sequence.ends_with('m') {
+                if sequence == "\x1b[0m" || sequence == "\x1b[m" { open.clear(); } else { open.push_str(sequence); }
+            }
+            out.push_str(sequence);
+            while chars.next_if(|&(next, _)| next < at + len).is_some() {}
+            continue;
+        }
+        if ch == '\n' {
+            out.push(ch);
+            (col, space, hard) = (0, None, None);
+            continue;
+        }
+        let ch_width = char_width(ch);
+        while col + ch_width > width && col > column {
+            let (break_at, break_col, reopen, glyph) = match space.take() {
+                Some((at, col, open)) => (at, col, open, ""),
+                None => match hard.take() {
+                    Some((at, col, open)) => (at, col, open, marker),
+                    // Only the prefix is this wide.
+                    None => (out.len(), col, open.clone(), ""),
+                },
+            };
+            let tail = out.split_off(break_at);
+            out.push_str(glyph);
+            if colors { out.push_str("\x1b[0m"); }
+            out.push('\n');
+            out.extend(std::iter::repeat_n(' ', column));
+            if colors { out.push_str(&reopen); }
+            out.push_str(&tail);
+            col = column + (col - break_col);
+            hard = None;
+        }
+        if col > column && hard.is_none() && col + ch_width + marker_width > width {
+            hard = Some((out.len(), col, open.clone()));
+        }
+        out.push(ch);
+        col += ch_width;
+        if ch == ' ' && col > column + 1 {
+            space = Some((out.len(), col, open.clone()));
+            hard = None;
+        }
+    }
+    Some(out)
+}
+
+/// Console width in columns; 0 until detected.
+static TERMINAL_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Columns `separator!` and `section!` fill, detected once: the terminal's
+/// width when stdout is one, 80 otherwise, and for a writer from
+/// `set_writer`.
+pub fn terminal_width() -> usize {
+    match TERMINAL_WIDTH.load(Ordering::Relaxed) {
+        0 => refresh_terminal_width(),
+        width => width,
+    }
+}
+
+/// Detects the width again, e.g. after the window was resized.
+pub fn refresh_terminal_width() -> usize {
+    let detected = if WRITER_SET.load(Ordering::Relaxed) || !io::stdout().is_terminal() { None } else { stdout_columns() };
+    let width = detected.filter(|&columns| columns > 0).unwrap_or(80);
+    TERMINAL_WIDTH.store(width, Ordering::Relaxed);
+    width
+}
+
+#[cfg(unix)]
+fn stdout_columns() -> Option<usize> {
+    use std::os::raw::{c_int, c_ulong};
+
+    #[repr(C)]
+    struct WinSize {
+        rows: u16,
+        columns: u16,
+        x_pixels: u16,
+        y_pixels: u16,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+    #[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd")))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+
+    let mut size = WinSize { rows: 0, columns: 0, x_pixels: 0, y_pixels: 0 };
+    // SAFETY: TIOCGWINSZ fills the winsize struct `size` points to.
+    let result = unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut WinSize) };
+    (result == 0).then_some(usize::from(size.columns))
+}
+
+#[cfg(not(unix))]
+fn stdout_columns() -> Option<usize> {
+    None
+}
+
+/// A `separator!` rule, or a `section!` banner with the title centered in
+/// it, as one record that ends at the terminal width.
+pub fn print_rule(level: Level, location: Location, title: Option<fmt::Arguments>) {
+    let target = location_text(&location);
+    let thread = if SHOW_THREAD.load(Ordering::Relaxed) { thread_label() } else { None };
+    let column = {
+        let tag = PROCESS_TAG.read().unwrap();
+        let ts = record_timestamp();
+        let probe = Record {
+            process: tag.as_deref(), thread: thread.as_deref(), timestamp: ts.as_deref(), target: target.as_deref(),
+            depth: current_depth(), ..Record::new(level, "")
+        };
+        pretty_message_column(&THEME.read().unwrap(), &probe).unwrap_or(0)
+    };
+    // The trailing space of the line is part of the width.
+    let room = terminal_width().saturating_sub(column + 1).max(3);
+    let rule = glyphs().rule;
+    let styled = |style: &str, text: &str| if cfg!(feature = "markup") { format!("<{style}>{text}</>") } else { text.to_string() };
+
+    let message = match title {
+        None => styled("dim", &rule.repeat(room)),
+        Some(title) => {
+            let title = title.to_string();
+            let shown = strip_ansi(&apply_markup(&title, "")).chars().count();
+            let sides = room.saturating_sub(shown + 2).max(2);
+            let (left, right) = (rule.repeat(sides / 2), rule.repeat(sides - sides / 2));
+            format!("{} {} {}", styled("dim", &left), styled("bold", &title), styled("dim", &right))
+        }
+    };
+    print_with_prefix(level, location, format_args!("{message}"));
+}
+
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+/// How long `shutdown` waits for the console queue to be written.
+const SHUTDOWN_QUEUE_WAIT: Duration = Duration::from_secs(1);
+
+/// Flushes stdout and switches the logger to its shutdown mode: every later
+/// record (e.g. from `Drop` impls of statics) is written straight to stderr
+/// as plain text, without touching the logger's locks, themes or aliases.
+/// Groups still open on the calling thread are reported there too, since
+/// the main thread's thread-locals are never destroyed. Never panics or
+/// blocks on logger state, except that it waits up to a second for the
+/// lines of `set_console_queue` to be written; calling it again is a
+/// no-op.
+pub fn shutdown() {
+    if SHUT_DOWN.load(Ordering::Acquire) { return; }
+
+    drain_console_queue(Some(Instant::now() + SHUTDOWN_QUEUE_WAIT));
+    if let Ok(mut writer) = WRITER.try_lock() {
+        if let Some(writer) = writer.as_mut() { let _ = writer.flush(); }
+    }
+    if let Ok(mut sinks) = FILE_SINKS.try_lock() {
+        for sink in sinks.iter_mut() {
+            let _ = if sink.sync == SyncPolicy::Never { sink.file.flush() } else { sink.sync() };
+        }
+    }
+    if let Ok(sinks) = SINKS.try_read() {
+        for entry in sinks.iter() {
+            if let Ok(mut sink) = entry.sink.try_lock() {
+                if let Some(sink) = sink.as_mut() { sink.flush(); }
+            }
+        }
+    }
+    let _ = io::stdout().flush();
+
+    SHUT_DOWN.store(true, Ordering::Release);
+
+    let open = current_depth();
+    if open > 0 && !UNBALANCED_WARNED.swap(true, Ordering::Relaxed) && is_enabled(Level::Warn) {
+        self_log(Level::Warn, format_args!("{open} group(s) still open at shutdown"));
+    }
+}
+
+/// Calls `shutdown` when dropped, e.g. at the end of `main`.
+#[must_use = "the logger shuts down when the guard is dropped"]
+pub struct ShutdownGuard;
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        shutdown();
+    }
+}
+
+pub fn shutdown_guard() -> ShutdownGuard {
+    ShutdownGuard
+}
+
+/// The shutdown path: a plain line straight to stderr, with the default
+/// label. Never waits for one of the logger's locks or panics on a poisoned
+/// one; see `plain_stderr_timestamp`.
+fn print_plain_stderr(level: Level, args: fmt::Arguments) {
+    let Some(style) = render::default_style(level) else { return };
+
+    let mut message = String::new();
+    let _ = render::write_markup(&mut message, &args.to_string(), "", &render::NoAliases);
+    let message = strip_ansi(&message);
+
+    let line = match plain_stderr_timestamp() {
+        Some(ts) => format!("[{}] [{ts}] {message}\n", style.label),
+        None => format!("[{}] {message}\n", style.label),
+    };
+    let _ = io::stderr().write_all(line.as_bytes());
+}
+
+/// Where the logger reports its own problems: unbalanced groups, throttling,
+/// a stalled stdout. Each report is a record with the target `[logger]`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SelfLog {
+    /// As normal records on the console (default); a stalled stdout is still
+    /// reported on stderr.
+    Console,
+    /// As plain `[LBL] [timestamp] [logger]: message` lines on stderr.
+    Stderr,
+    /// To this sink only, in its format. Reports raised while a sink is
+    /// writing, or after the sink was removed, go to stderr.
+    Sink(SinkId),
+    Silent,
+}
+
+/// Target of the logger's own records.
+pub const SELF_LOG_TARGET: &str = "[logger]";
+
+const SELF_LOG_MAX_PER_SECOND: u32 = 10;
+
+static SELF_LOG: RwLock<SelfLog> = RwLock::new(SelfLog::Console);
+static SELF_LOG_WINDOW: AtomicU64 = AtomicU64::new(0);
+static SELF_LOG_COUNT: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    static IN_SELF_LOG: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set_self_log(mode: SelfLog) {
+    note_config("self_log", ConfigSource::Api("set_self_log"), format!("{mode:?}"));
+    *SELF_LOG.write().unwrap() = mode;
+}
+
+/// Reports a problem of the logger itself, as a `[logger]` record.
+pub(crate) fn self_log(level: Level, args: fmt::Arguments) {
+    self_log_to(SelfLog::Console, level, args);
+}
+
+/// `self_log` that uses `mode` instead when the configured channel is
+/// `Console`. Messages raised while reporting one are dropped, and at most
+/// `SELF_LOG_MAX_PER_SECOND` get through.
+fn self_log_to(mode: SelfLog, level: Level, args: fmt::Arguments) {
+    // try_read: also called on the shutdown path, which never waits.
+    let mode = match SELF_LOG.try_read().map(|configured| *configured) {
+        Ok(SelfLog::Console) | Err(_) => mode,
+        Ok(configured) => configured,
+    };
+    if mode == SelfLog::Silent { return; }
+    if IN_SELF_LOG.with(|inside| inside.replace(true)) { return; }
+
+    let window = START.elapsed().as_secs();
+    let seen = SELF_LOG_WINDOW.load(Ordering::Relaxed);
+    if seen != window && SELF_LOG_WINDOW.compare_exchange(seen, window, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+        SELF_LOG_COUNT.store(0, Ordering::Relaxed);
+    }
+
+    if SELF_LOG_COUNT.fetch_add(1, Ordering::Relaxed) < SELF_LOG_MAX_PER_SECOND {
+        write_self_log(mode, level, args);
+    }
+
+    IN_SELF_LOG.with(|inside| inside.set(false));
+}
+
+/// Writes one self-log record where `mode` says, falling back to stderr.
+fn write_self_log(mode: SelfLog, level: Level, args: fmt::Arguments) {
+    if SHUT_DOWN.load(Ordering::Acquire) || mode == SelfLog::Stderr {
+        print_plain_stderr(level, format_args!("{SELF_LOG_TARGET}: {args}"));
+        return;
+    }
+
+    let message = args.to_string();
+    let tag = PROCESS_TAG.read().unwrap();
+    let ts = record_timestamp();
+    let record = Record {
+        process: tag.as_deref(),
+        timestamp: ts.as_deref(),
+        target: Some(SELF_LOG_TARGET),
+        ..Record::new(level, &message)
+    };
+    let written = match mode {
+        SelfLog::Console => {
+            print_line(record, true);
+            true
+        }
+        SelfLog::Sink(id) => write_one_sink(id, &record),
+        SelfLog::Stderr | SelfLog::Silent => false,
+    };
+    if !written {
+        print_plain_stderr(level, format_args!("{SELF_LOG_TARGET}: {message}"));
+    }
+}
+
+static CONSOLE_BROKEN: AtomicBool = AtomicBool::new(false);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static NONBLOCKING_STDOUT: Mutex<Option<NonblockingStdout>> = Mutex::new(None);
+
+/// How `set_console_nonblocking` writes to fd 1.
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NonblockingStdout {
+    /// `send` with `MSG_DONTWAIT`
+    Socket,
+    /// `write` once `poll` says there is room
+    Polled,
+}
+static LAST_DROP_COMPLAINT: Mutex<Option<Instant>> = Mutex::new(None);
+
+const DROP_COMPLAINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of records dropped because the console would have blocked.
+pub fn dropped_records() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Whether fd 1 is a socket (systemd socket activation, journald's stream);
+/// an error if there is no fd 1.
+#[cfg(unix)]
+fn nonblocking_stdout() -> io::Result<NonblockingStdout> {
+    Ok(if raw_stdout::is_socket()? { NonblockingStdout::Socket } else { NonblockingStdout::Polled })
+}
+
+#[cfg(not(unix))]
+fn nonblocking_stdout() -> io::Result<NonblockingStdout> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "non-blocking console is only supported on unix"))
+}
+
+#[cfg(unix)]
+fn write_nonblocking(stdout: NonblockingStdout, bytes: &[u8]) -> io::Result<()> {
+    raw_stdout::write_all(stdout == NonblockingStdout::Socket, bytes)
+}
+
+#[cfg(not(unix))]
+fn write_nonblocking(_stdout: NonblockingStdout, _bytes: &[u8]) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Writes to fd 1 that never block, without touching its file status
+/// flags: those belong to the open file description, which fd 1 shares
+/// with `println!` and with the parent shell's terminal.
+#[cfg(unix)]
+mod raw_stdout {
+    use std::io;
+    use std::os::raw::{c_int, c_short, c_void};
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    type NFds = std::os::raw::c_ulong;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    type NFds = std::os::raw::c_uint;
+
+    extern "C" {
+        fn poll(fds: *mut PollFd, nfds: NFds, timeout: c_int) -> c_int;
+        fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+        fn send(socket: c_int, buf: *const c_void, len: usize, flags: c_int) -> isize;
+        fn getsockopt(socket: c_int, level: c_int, name: c_int, value: *mut c_void, len: *mut u32) -> c_int;
+    }
+
+    const POLLOUT: c_short = 0x4;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mod consts {
+        use std::os::raw::c_int;
+        pub const MSG_DONTWAIT: c_int = 0x40;
+        pub const MSG_NOSIGNAL: c_int = 0x4000;
+        pub const SOL_SOCKET: c_int = 1;
+        pub const SO_TYPE: c_int = 3;
+        pub const PIPE_BUF: usize = 4096;
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    mod consts {
+        use std::os::raw::c_int;
+        pub const MSG_DONTWAIT: c_int = 0x80;
+        // SIGPIPE is ignored in Rust programs anyway.
+        pub const MSG_NOSIGNAL: c_int = 0;
+        pub const SOL_SOCKET: c_int = 0xffff;
+        pub const SO_TYPE: c_int = 0x1008;
+        pub const PIPE_BUF: usize = 512;
+    }
+    use consts::*;
+
+    /// How long a write that got its first bytes out waits for room for the
+    /// rest, rather than leaving a torn line.
+    const REST_WAIT_MS: c_int = 50;
+
+    pub(super) fn is_socket() -> io::Result<bool> {
+        const EBADF: i32 = 9;
+
+        let mut kind: c_int = 0;
+        let mut len = std::mem::size_of::<c_int>() as u32;
+        // SAFETY: SO_TYPE stores one c_int in `kind`; it fails on anything
+        // but a socket.
+        if unsafe { getsockopt(1, SOL_SOCKET, SO_TYPE, (&mut kind as *mut c_int).cast(), &mut len) } == 0 { return Ok(true); }
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() == Some(EBADF) { Err(e) } else { Ok(false) }
+    }
+
+    /// `WouldBlock` when fd 1 has no room for `bytes`. Sockets get them
+    /// with `MSG_DONTWAIT`; anything else in pieces no larger than
+    /// `PIPE_BUF`, each once `poll` says there is room, which a pipe
+    /// guarantees for that much.
+    pub(super) fn write_all(socket: bool, bytes: &[u8]) -> io::Result<()> {
+        let mut written = 0;
+        while written < bytes.len() {
+            let mut fd = PollFd { fd: 1, events: POLLOUT, revents: 0 };
+            let wait = if written == 0 { 0 } else { REST_WAIT_MS };
+            // SAFETY: `fd` is one valid pollfd.
+            let ready = unsafe { poll(&mut fd, 1, wait) };
+            if ready == 0 { return Err(io::ErrorKind::WouldBlock.into()); }
+
+            let rest = &bytes[written..];
+            let sent = if ready < 0 {
+                ready as isize
+            } else if socket {
+                // SAFETY: `rest` is valid for `rest.len()` bytes.
+                unsafe { send(1, rest.as_ptr().cast(), rest.len(), MSG_DONTWAIT | MSG_NOSIGNAL) }
+            } else {
+                // SAFETY: as above, for the shorter length.
+                unsafe { write(1, rest.as_ptr().cast(), rest.len().min(PIPE_BUF)) }
+            };
+            if sent < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted { continue; }
+                return Err(e);
+            }
+            written += sent as usize;
+        }
+        Ok(())
+    }
+}
+
+/// Writes that would block on a full stdout pipe are dropped (and counted,
+/// see `dropped_records`) instead of stalling the caller. Off by default.
+pub fn set_console_nonblocking(enabled: bool) -> io::Result<()> {
+    let mut stdout = NONBLOCKING_STDOUT.lock().unwrap();
+
+    *stdout = if enabled { Some(nonblocking_stdout()?) } else { None };
+    note_config("console_nonblocking", ConfigSource::Api("set_console_nonblocking"), enabled);
+    Ok(())
+}
+
+fn complain_dropped() {
+    let mut last = LAST_DROP_COMPLAINT.lock().unwrap();
+    let now = Instant::now();
+
+    if last.is_none_or(|at| now.duration_since(at) >= DROP_COMPLAINT_INTERVAL) {
+        *last = Some(now);
+        // The console is what is failing here, so never report it there.
+        self_log_to(SelfLog::Stderr, Level::Error, format_args!("stdout is not accepting output, {} record(s) dropped so far", dropped_records()));
+    }
+}
+
+/// Something else drawing on the terminal, e.g. a progress bar. `suspend`
+/// must clear its drawing, run `f`, then redraw, so log lines are never
+/// printed into the middle of a draw.
+pub trait DrawCoordinator: Send + Sync {
+    fn suspend(&self, f: &mut dyn FnMut());
+}
+
+static DRAW_GUARD: RwLock<Option<Arc<dyn DrawCoordinator>>> = RwLock::new(None);
+
+/// Routes every console write through `coordinator.suspend`.
+pub fn set_external_draw_guard(coordinator: Arc<dyn DrawCoordinator>) {
+    *DRAW_GUARD.write().unwrap() = Some(coordinator);
+}
+
+pub fn clear_external_draw_guard() {
+    *DRAW_GUARD.write().unwrap() = None;
+}
+
+static WRITER: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+static WRITER_SET: AtomicBool = AtomicBool::new(false);
+static WRITER_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Sends every line to `writer` instead of stdout, e.g. a `Vec<u8>` in tests
+/// or a file. The bell and the draw guard only apply to stdout. A failing
+/// writer is reported once through the self-log; its errors never panic.
+pub fn set_writer(writer: Box<dyn Write + Send>) {
+    // Lines logged before go where the console was then.
+    flush_console_queue();
+    let mut current = WRITER.lock().unwrap();
+    note_config("writer", ConfigSource::Api("set_writer"), "custom");
+    *current = Some(writer);
+    WRITER_SET.store(true, Ordering::Relaxed);
+    WRITER_FAILED.store(false, Ordering::Relaxed);
+}
+
+/// Goes back to stdout; returns the writer that was set, if any.
+pub fn reset_writer() -> Option<Box<dyn Write + Send>> {
+    flush_console_queue();
+    let mut current = WRITER.lock().unwrap();
+    note_config("writer", ConfigSource::Api("reset_writer"), "stdout");
+    WRITER_SET.store(false, Ordering::Relaxed);
+    current.take()
+}
+
+/// In-memory writer for `set_writer` that can still be read after it was
+/// handed over: clones share one buffer.
+#[derive(Clone, Default, Debug)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        SharedBuffer::default()
+    }
+
+    /// Copy of every byte written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// `contents` as text, invalid UTF-8 replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes to the writer from `set_writer`; `false` if there is none.
+fn write_custom(text: &str) -> bool {
+    if !WRITER_SET.load(Ordering::Relaxed) { return false; }
+
+    let mut writer = WRITER.lock().unwrap();
+    let Some(writer) = writer.as_mut() else { return false };
+
+    if let Err(e) = writer.write_all(text.as_bytes()) {
+        if !WRITER_FAILED.swap(true, Ordering::Relaxed) {
+            self_log_to(SelfLog::Stderr, Level::Error, format_args!("custom writer failed, its output is lost: {e}"));
+        }
+    }
+    true
+}
+
+/// The one place console bytes are written: records, blank lines and any
+/// other decoration go to stdout (or the writer from `set_writer`) through
+/// here. A closed pipe disables console output instead of panicking like
+/// `println!` would; after `shutdown` the text goes straight to stderr.
+///
+/// `text` is a whole line with its newline (or several, for a record
+/// group) and is written with one `write_all` while holding the stdout or
+/// writer lock, so lines of different threads never tear into each other.
+fn emit(text: &str) {
+    if SHUT_DOWN.load(Ordering::Acquire) {
+        let _ = io::stderr().write_all(text.as_bytes());
+        return;
+    }
+    if PROGRESS_SHOWN.load(Ordering::Acquire) {
+        // Clear the progress line, write the text and draw the line again, all in one write.
+        let progress = PROGRESS_LINE.lock().unwrap();
+        if let Some((_, line)) = progress.as_ref() {
+            write_console(&format!("\r\x1b[2K{text}{line}"));
+            return;
+        }
+    }
+    write_console(text);
+}
+
+fn write_console(text: &str) {
+    if queue_console(text) { return; }
+    write_console_now(text);
+}
+
+fn write_console_now(text: &str) {
+    if write_custom(text) { return; }
+    if CONSOLE_BROKEN.load(Ordering::Relaxed) { return; }
+
+    // Cloned so the coordinator may itself replace the guard without deadlocking.
+    let guard = DRAW_GUARD.read().unwrap().clone();
+    match guard {
+        Some(coordinator) => coordinator.suspend(&mut || emit_now(text)),
+        None => emit_now(text),
+    }
+}
+
+fn emit_now(text: &str) {
+    let result = match *NONBLOCKING_STDOUT.lock().unwrap() {
+        Some(stdout) => write_nonblocking(stdout, text.as_bytes()),
+        None => io::stdout().lock().write_all(text.as_bytes()),
+    };
+
+    match result {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => CONSOLE_BROKEN.store(true, Ordering::Relaxed),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            complain_dropped();
+        }
+        Err(_) => {}
+    }
+}
+
+/// Console lines waiting for the writer thread of `set_console_queue`.
+struct ConsoleQueue {
+    lines: VecDeque<String>,
+    /// Lines the queue holds at most; 0 while it is off
+    capacity: usize,
+    overflow: QueueOverflow,
+    /// Where lines go past a full queue, under `QueueOverflow::Spill`
+    spill: Option<ConsoleSpill>,
+    /// The writer thread took lines and has not written them yet
+    writing: bool,
+    running: bool,
+}
+
+impl ConsoleQueue {
+    fn full(&self) -> bool {
+        self.capacity > 0 && self.lines.len() >= self.capacity
+    }
+
+    fn spilled(&self) -> bool {
+        self.spill.as_ref().is_some_and(ConsoleSpill::pending)
+    }
+}
+
+static CONSOLE_QUEUE: Mutex<ConsoleQueue> = Mutex::new(ConsoleQueue {
+    lines: VecDeque::new(), capacity: 0, overflow: QueueOverflow::Block, spill: None, writing: false, running: false,
+});
+/// Lines were queued.
+static CONSOLE_QUEUED: Condvar = Condvar::new();
+/// The writer thread took lines or wrote them.
+static CONSOLE_WRITTEN: Condvar = Condvar::new();
+static HAS_CONSOLE_QUEUE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static ON_CONSOLE_WRITER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Hands console lines to one writer thread through a queue of up to
+/// `lines` lines, so logging threads only wait for a push instead of the
+/// stdout lock and the write: the thread writes what queued up in one
+/// `write_all`. Lines keep the order they were logged in, per thread and
+/// across threads. A full queue makes the logging thread wait for room.
+/// Lines the writer thread logs itself, and every line after `shutdown`,
+/// are written directly. `0` (the default) turns the queue off and waits
+/// until it is written.
+///
+/// Queued lines are lost if the process exits before they are written:
+/// call `shutdown` (or keep a `shutdown_guard`), which waits up to a
+/// second for them, or `flush_console_queue` first.
+pub fn set_console_queue(lines: usize) -> io::Result<()> {
+    let mut queue = CONSOLE_QUEUE.lock().unwrap();
+    if lines > 0 && !queue.running {
+        std::thread::Builder::new().name(String::from("rust_logger-console")).spawn(run_console_writer)?;
+        queue.running = true;
+    }
+    note_config("console_queue", ConfigSource::Api("set_console_queue"), lines);
+    queue.capacity = lines;
+    HAS_CONSOLE_QUEUE.store(lines > 0, Ordering::Relaxed);
+    drop(queue);
+    if lines == 0 { flush_console_queue(); }
+    Ok(())
+}
+
+pub fn console_queue() -> usize {
+    CONSOLE_QUEUE.lock().unwrap().capacity
+}
+
+/// Waits until the writer thread of `set_console_queue` has written every
+/// line queued so far. `shutdown`, `set_writer` and `reset_writer` call it.
+pub fn flush_console_queue() {
+    drain_console_queue(None);
+}
+
+/// `flush_console_queue`, giving up at `deadline`.
+fn drain_console_queue(deadline: Option<Instant>) {
+    if ON_CONSOLE_WRITER.try_with(Cell::get).unwrap_or(false) { return; }
+    let mut queue = CONSOLE_QUEUE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    while queue.running && (!queue.lines.is_empty() || queue.writing || queue.spilled()) {
+        queue = match deadline {
+            None => CONSOLE_WRITTEN.wait(queue).unwrap_or_else(|poisoned| poisoned.into_inner()),
+            Some(deadline) => {
+                let Some(left) = deadline.checked_duration_since(Instant::now()) else { return };
+                CONSOLE_WRITTEN.wait_timeout(queue, left).unwrap_or_else(|poisoned| poisoned.into_inner()).0
+            }
+        };
+    }
+}
+
+/// Queues `text` for the writer thread; `false` if the caller writes it.
+fn queue_console(text: &str) -> bool {
+    if !HAS_CONSOLE_QUEUE.load(Ordering::Relaxed) || ON_CONSOLE_WRITER.try_with(Cell::get).unwrap_or(true) { return false; }
+    let text = text.to_string();
+    let Ok(mut queue) = CONSOLE_QUEUE.lock() else { return false };
+    while queue.overflow == QueueOverflow::Block && queue.full() {
+        let Ok(waited) = CONSOLE_WRITTEN.wait(queue) else { return false };
+        queue = waited;
+    }
+    // Turned off meanwhile: still behind the lines queued before, if any.
+    if !queue.running || (queue.capacity == 0 && queue.lines.is_empty() && !queue.spilled()) { return false; }
+    if queue.spill.is_some() && (queue.full() || queue.spilled()) {
+        // Behind the lines spilled before, so they keep their order.
+        let spilled = queue.spill.as_mut().is_some_and(|spill| spill.push(&text));
+        let counter = if spilled { &SPILLED_RECORDS } else { &QUEUE_DROPPED_RECORDS };
+        counter.fetch_add(1, Ordering::Relaxed);
+    } else {
+        if queue.overflow == QueueOverflow::DropOldest && queue.full() {
+            queue.lines.pop_front();
+            QUEUE_DROPPED_RECORDS.fetch_add(1, Ordering::Relaxed);
+        }
+        queue.lines.push_back(text);
+    }
+    CONSOLE_QUEUED.notify_one();
+    true
+}
+
+/// What a logging thread does when the queue of `set_console_queue` is
+/// full.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QueueOverflow {
+    /// Waits for room (default)
+    Block,
+    /// Drops the oldest queued line to make room, counted by
+    /// `queue_dropped_records`
+    DropOldest,
+    /// Appends the line to a temporary file, from which the writer thread
+    /// writes it, in order, once the queue is empty. A burst then costs
+    /// neither lines nor memory. Lines that would make the file larger
+    /// than `max_bytes` are dropped and counted instead.
+    Spill { max_bytes: u64 },
+}
+
+static SPILLED_RECORDS: AtomicU64 = AtomicU64::new(0);
+static QUEUE_DROPPED_RECORDS: AtomicU64 = AtomicU64::new(0);
+
+/// Sets what happens to lines logged while the queue of
+/// `set_console_queue` is full, once the lines queued so far are written.
+/// The file of `QueueOverflow::Spill` is created in the temporary
+/// directory under a fresh name, readable by this process only, and gone
+/// once the policy changes or the process ends; lines still in it after a
+/// crash are not recovered.
+pub fn set_queue_overflow(overflow: QueueOverflow) -> io::Result<()> {
+    let spill = match overflow {
+        QueueOverflow::Spill { max_bytes } => Some(ConsoleSpill::open(max_bytes)?),
+        _ => None,
+    };
+    loop {
+        drain_console_queue(None);
+        let mut queue = CONSOLE_QUEUE.lock().unwrap();
+        if queue.running && (!queue.lines.is_empty() || queue.writing || queue.spilled()) { continue; }
+        note_config("queue_overflow", ConfigSource::Api("set_queue_overflow"), format_args!("{overflow:?}"));
+        queue.overflow = overflow;
+        queue.spill = spill;
+        return Ok(());
+    }
+}
+
+pub fn queue_overflow() -> QueueOverflow {
+    CONSOLE_QUEUE.lock().unwrap().overflow
+}
+
+/// Number of lines `QueueOverflow::Spill` wrote to its file.
+pub fn spilled_records() -> u64 {
+    SPILLED_RECORDS.load(Ordering::Relaxed)
+}
+
+/// Number of lines the console queue dropped: the oldest ones under
+/// `QueueOverflow::DropOldest`, and the ones past `max_bytes` under
+/// `QueueOverflow::Spill`.
+pub fn queue_dropped_records() -> u64 {
+    QUEUE_DROPPED_RECORDS.load(Ordering::Relaxed)
+}
+
+/// The file of `QueueOverflow::Spill`: each line as its length in 4
+/// little-endian bytes and its text. It starts over whenever every line in
+/// it was read back.
+struct ConsoleSpill {
+    file: File,
+    /// Set while the file still has a name
+    path: Option<PathBuf>,
+    /// Bytes written to the file, and read back from it
+    written: u64,
+    read: u64,
+    max_bytes: u64,
+}
+
+/// Spilled bytes read back at a time.
+const SPILL_READ_CHUNK: u64 = 256 * 1024;
+
+impl ConsoleSpill {
+    /// Creates the file under a name nobody else has: the temporary
+    /// directory is shared, and a file or symlink planted there must not
+    /// be written through. On unix only this process can read it, and it
+    /// is unlinked at once.
+    fn open(max_bytes: u64) -> io::Result<ConsoleSpill> {
+        use std::hash::{BuildHasher, Hasher};
+
+        let mut name = format!("rust_logger-console-spill-{}.bin", std::process::id());
+        let mut attempts = 0;
+        loop {
+            let path = std::env::temp_dir().join(&name);
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(file) => {
+                    // Still removed on drop where an open file cannot be.
+                    let path = if cfg!(unix) && fs::remove_file(&path).is_ok() { None } else { Some(path) };
+                    return Ok(ConsoleSpill { file, path, written: 0, read: 0, max_bytes });
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempts < 16 => {
+                    attempts += 1;
+                    let mut random = std::collections::hash_map::RandomState::new().build_hasher();
+                    random.write_u32(attempts);
+                    name = format!("rust_logger-console-spill-{}-{:016x}.bin", std::process::id(), random.finish());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn pending(&self) -> bool {
+        self.read < self.written
+    }
+
+    /// Appends `line`; `false` if the file would pass `max_bytes` or the
+    /// write failed.
+    fn push(&mut self, line: &str) -> bool {
+        use std::io::{Seek, SeekFrom};
+
+        let Ok(len) = u32::try_from(line.len()) else { return false };
+        if self.written + 4 + u64::from(len) > self.max_bytes { return false; }
+        let mut entry = Vec::with_capacity(4 + line.len());
+        entry.extend_from_slice(&len.to_le_bytes());
+        entry.extend_from_slice(line.as_bytes());
+        let appended = self.file.seek(SeekFrom::Start(self.written)).and_then(|_| self.file.write_all(&entry));
+        if appended.is_err() { return false; }
+        self.written += entry.len() as u64;
+        true
+    }
+
+    /// Moves up to `max` of the oldest lines into `into`. Lines that cannot
+    /// be read back are dropped and counted.
+    fn take(&mut self, max: usize, into: &mut VecDeque<String>) {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let want = (self.written - self.read).min(SPILL_READ_CHUNK);
+        let mut bytes = Vec::new();
+        let read = self.file.seek(SeekFrom::Start(self.read)).and_then(|_| (&mut self.file).take(want).read_to_end(&mut bytes));
+        let mut at = 0;
+        while read.is_ok() && into.len() < max && at + 4 <= bytes.len() {
+            let len = u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as usize;
+            let Some(line) = bytes.get(at + 4..at + 4 + len) else {
+                // Longer than the chunk: read it on its own.
+                if at > 0 { break; }
+                let mut line = vec![0; len];
+                let whole = self.file.seek(SeekFrom::Start(self.read + 4)).and_then(|_| self.file.read_exact(&mut line));
+                if whole.is_err() { break; }
+                into.push_back(String::from_utf8_lossy(&line).into_owned());
+                at = 4 + len;
+                break;
+            };
+            into.push_back(String::from_utf8_lossy(line).into_owned());
+            at += 4 + len;
+        }
+        self.read += at as u64;
+        if read.is_err() || (at == 0 && self.pending()) {
+            // Unreadable: what is left is lost.
+            QUEUE_DROPPED_RECORDS.fetch_add(1, Ordering::Relaxed);
+            self.read = self.written;
+        }
+        if !self.pending() {
+            let _ = self.file.set_len(0);
+            (self.written, self.read) = (0, 0);
+        }
+    }
+}
+
+impl Drop for ConsoleSpill {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path { let _ = fs::remove_file(path); }
+    }
+}
+
+fn run_console_writer() {
+    ON_CONSOLE_WRITER.with(|on| on.set(true));
+    let mut taken = VecDeque::new();
+    let mut text = String::new();
+    loop {
+        {
+            let mut queue = CONSOLE_QUEUE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            queue.writing = false;
+            CONSOLE_WRITTEN.notify_all();
+            while queue.lines.is_empty() && !queue.spilled() {
+                queue = CONSOLE_QUEUED.wait(queue).unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+            queue.writing = true;
+            // The spilled lines came after the queued ones.
+            if queue.lines.is_empty() {
+                let max = queue.capacity.max(1);
+                if let Some(spill) = queue.spill.as_mut() { spill.take(max, &mut taken); }
+            } else {
+                std::mem::swap(&mut queue.lines, &mut taken);
+            }
+        }
+        // Room for the threads waiting on a full queue.
+        CONSOLE_WRITTEN.notify_all();
+        text.clear();
+        for line in taken.drain(..) {
+            text.push_str(&line);
+        }
+        write_console_now(&text);
+    }
+}
+
+/// The line `Progress` draws at the bottom of the console, with its owner.
+static PROGRESS_LINE: Mutex<Option<(u64, String)>> = Mutex::new(None);
+static PROGRESS_SHOWN: AtomicBool = AtomicBool::new(false);
+static NEXT_PROGRESS_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Records between plain progress lines when the console is not a terminal.
+const PROGRESS_PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+/// Records between the `progress` records the other destinations get while
+/// the line is drawn in place.
+const PROGRESS_RECORD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A status line like `processing 420/1000 (42%)` that redraws itself in
+/// place under the log records, which are printed above it. On a console
+/// that is not a terminal it is logged as a plain Info record every few
+/// seconds instead. Dropping it clears the line.
+///
+/// The line is drawn on the console only. Meanwhile the log files and the
+/// `SinkKind::File` sinks get its state as Debug records in the category
+/// `progress`: the first one right away, then at most one per
+/// `record_interval`. The state it ends in is always recorded, by
+/// `finish_with` or when it is dropped.
+pub struct Progress {
+    id: u64,
+    total: u64,
+    label: String,
+    location: Location,
+    /// Drawn in place; off for pipes, files and JSON output
+    in_place: bool,
+    shown: bool,
+    plain_interval: Duration,
+    record_interval: Duration,
+    state: Mutex<ProgressState>,
+}
+
+struct ProgressState {
+    done: u64,
+    drawn_percent: Option<u64>,
+    drawn_at: Instant,
+    /// The count of the last record the state was logged in
+    recorded: Option<u64>,
+    recorded_at: Instant,
+}
+
+pub fn start_progress(total: u64, location: Location, label: fmt::Arguments) -> Progress {
+    let in_place = output_format() == Format::Pretty && (WRITER_SET.load(Ordering::Relaxed) || io::stdout().is_terminal());
+    let progress = Progress {
+        id: NEXT_PROGRESS_ID.fetch_add(1, Ordering::Relaxed),
+        total,
+        label: label.to_string(),
+        location,
+        in_place,
+        shown: record_enabled(Level::Info, location.module),
+        plain_interval: PROGRESS_PLAIN_INTERVAL,
+        record_interval: PROGRESS_RECORD_INTERVAL,
+        state: Mutex::new(ProgressState {
+            done: 0, drawn_percent: None, drawn_at: Instant::now(), recorded: None, recorded_at: Instant::now(),
+        }),
+    };
+    progress.set(0);
+    progress
+}
+
+impl Progress {
+    /// A progress line without a label, logged under the target `progress`;
+    /// `progress!` names the caller's module and takes a label.
+    #[track_caller]
+    pub fn new(total: u64) -> Self {
+        let caller = std::panic::Location::caller();
+        start_progress(total, Location { module: "progress", file: caller.file(), line: caller.line() }, format_args!(""))
+    }
+
+    /// How often the plain record is logged when the console is not a
+    /// terminal. 5 seconds by default.
+    pub fn plain_interval(mut self, interval: Duration) -> Self {
+        self.plain_interval = interval;
+        self
+    }
+
+    /// How often the other destinations get a `progress` record while the
+    /// line is drawn in place. 10 seconds by default.
+    pub fn record_interval(mut self, interval: Duration) -> Self {
+        self.record_interval = interval;
+        self
+    }
+
+    /// Moves the count to `done`, at most `total`. The line is redrawn when
+    /// the percentage changes, and at least every 100ms while it moves.
+    pub fn set(&self, done: u64) {
+        if !self.shown { return; }
+
+        let mut state = self.state.lock().unwrap();
+        let done = done.min(self.total);
+        let percent = self.percent(done);
+        state.done = done;
+        let now = Instant::now();
+
+        if self.in_place {
+            let due = state.recorded.is_none_or(|recorded| recorded != done && now.duration_since(state.recorded_at) >= self.record_interval);
+            if due {
+                state.recorded = Some(done);
+                state.recorded_at = now;
+                self.record(done);
+            }
+            if state.drawn_percent == Some(percent) && now.duration_since(state.drawn_at) < Duration::from_millis(100) { return; }
+            let mut line = PROGRESS_LINE.lock().unwrap();
+            let text = self.text(done);
+            write_console(&format!("\r\x1b[2K{text}"));
+            *line = Some((self.id, text));
+            PROGRESS_SHOWN.store(true, Ordering::Release);
+        } else {
+            let due = state.drawn_percent.is_none_or(|drawn| drawn != percent && now.duration_since(state.drawn_at) >= self.plain_interval);
+            if !due { return; }
+            state.recorded = Some(done);
+            print_with_prefix(Level::Info, self.location, format_args!("{}", self.text(done)));
+        }
+        state.drawn_percent = Some(percent);
+        state.drawn_at = now;
+    }
+
+    /// Removes the line and logs its last state with `message` as an Info
+    /// record: `processing 1000/1000 (100%) done`.
+    pub fn finish_with(self, message: impl fmt::Display) {
+        self.clear();
+        if self.shown && record_enabled(Level::Info, self.location.module) {
+            let mut state = self.state.lock().unwrap();
+            state.recorded = Some(state.done);
+            print_with_prefix(Level::Info, self.location, format_args!("{} {message}", self.text(state.done)));
+        }
+    }
+
+    /// Logs the count for the other destinations than the console.
+    fn record(&self, done: u64) {
+        if !record_enabled(Level::Debug, self.location.module) { return; }
+        let previous = DESTINATION.with(|current| current.replace(Destination::File));
+        print_in_category(Level::Debug, "progress", self.location, format_args!("{}", self.text(done)));
+        DESTINATION.with(|current| current.set(previous));
+    }
+
+    /// Logs the count the progress ends at, unless it was logged already.
+    fn record_end(&self) {
+        if !self.shown { return; }
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if state.recorded == Some(state.done) { return; }
+        state.recorded = Some(state.done);
+        if self.in_place {
+            self.record(state.done);
+        } else {
+            print_with_prefix(Level::Info, self.location, format_args!("{}", self.text(state.done)));
+        }
+    }
+
+    fn percent(&self, done: u64) -> u64 {
+        if self.total == 0 { 100 } else { (u128::from(done) * 100 / u128::from(self.total)) as u64 }
+    }
+
+    fn text(&self, done: u64) -> String {
+        let space = if self.label.is_empty() { "" } else { " " };
+        format!("{}{space}{done}/{} ({}%)", self.label, self.total, self.percent(done))
+    }
+
+    fn clear(&self) {
+        let mut line = PROGRESS_LINE.lock().unwrap();
+        if line.as_ref().is_some_and(|(id, _)| *id == self.id) {
+            *line = None;
+            PROGRESS_SHOWN.store(false, Ordering::Release);
+            write_console("\r\x1b[2K");
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+        self.record_end();
+    }
+}
+
+/// When a file sink starts a new file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RotationPolicy {
+    /// One file that grows forever
+    Never,
+    /// Once the file would exceed `max_bytes` it is renamed to `<path>.1`
+    /// (older ones shift to `.2`, `.3`, ...) and a new file is started;
+    /// files beyond `.keep` are deleted.
+    Size { max_bytes: u64, keep: usize },
+}
+
+/// When a file sink asks the OS to put its records on disk with
+/// `File::sync_data`, so they survive a power loss and not just a crash.
+/// Each sync waits for the disk, typically from tens of microseconds on an
+/// SSD to milliseconds on spinning disks or network storage.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyncPolicy {
+    /// The OS writes the file back when it sees fit (default)
+    Never,
+    /// After each record at Error level
+    OnError,
+    /// After every record. The logging thread waits for the disk each time,
+    /// so this costs the most.
+    EveryRecord,
+    /// At most this often, from a background thread, when records were
+    /// written since the last sync. The logging threads never wait for it.
+    Interval(Duration),
+}
+
+/// The file under a file sink; a trait so tests can count the syncs.
+trait LogFile: Send + Sync {
+    fn write(&self, buf: &[u8]) -> io::Result<usize>;
+    fn sync_data(&self) -> io::Result<()>;
+}
+
+impl LogFile for File {
+    fn write(&self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buf)
+    }
+
+    fn sync_data(&self) -> io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+/// Shared, so the background sync needs no lock.
+struct FileHandle(Arc<dyn LogFile>);
+
+/// Device and inode of an open file, to tell whether its path still leads
+/// to it; `None` where the platform has no stable way to get them.
+type FileId = Option<(u64, u64)>;
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> FileId {
+    None
+}
+
+impl FileHandle {
+    /// The file, its size and its id.
+    fn open(path: &Path) -> io::Result<(FileHandle, u64, FileId)> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        Ok((FileHandle(Arc::new(file)), metadata.len(), file_id(&metadata)))
+    }
+}
+
+impl Write for FileHandle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct FileSink {
+    id: u64,
+    path: PathBuf,
+    policy: RotationPolicy,
+    session_header: bool,
+    sync: SyncPolicy,
+    reopen_check: Option<Duration>,
+    file: BufWriter<FileHandle>,
+    file_id: FileId,
+    size: u64,
+    /// Records were written since the last sync.
+    unsynced: bool,
+    synced_at: Instant,
+    checked_at: Instant,
+    /// The last startup record replayed into the file when it was opened.
+    replayed_through: u64,
+    /// Set by `FileSinkBuilder::level`; otherwise `file_level()` applies.
+    level: Option<Level>,
+    /// Set by `FileSinkBuilder::continuation`; otherwise the console's applies.
+    continuation: Option<ContinuationStyle>,
+    failed: bool,
+}
+
+impl FileSink {
+    /// Whether the file takes a record at `level`. Without a level of its
+    /// own or a `file_level()`, it takes what the console shows.
+    fn takes(&self, level: Level, to_console: bool) -> bool {
+        match self.level.or_else(file_level) {
+            None => to_console,
+            Some(threshold) => threshold != Level::None && level != Level::None && level >= threshold && !muted(level),
+        }
+    }
+
+    fn continuation(&self) -> ContinuationStyle {
+        self.continuation.unwrap_or_else(ContinuationStyle::of_console)
+    }
+}
+
+static FILE_SINKS: Mutex<Vec<FileSink>> = Mutex::new(Vec::new());
+static HAS_FILE_SINKS: AtomicBool = AtomicBool::new(false);
+/// The lowest level a file sink takes on its own, apart from the console;
+/// `Level::None` while they all follow it.
+static FILE_SINK_LEVEL: AtomicU8 = AtomicU8::new(Level::None as u8);
+static NEXT_FILE_SINK_ID: AtomicU64 = AtomicU64::new(0);
+/// Bit `style as u8` for each `ContinuationStyle` a file sink has, and
+/// `FOLLOWS_CONSOLE` if one has none.
+static FILE_CONTINUATIONS: AtomicU8 = AtomicU8::new(0);
+const FOLLOWS_CONSOLE: u8 = 1 << 4;
+
+/// The continuation styles the plain text of a record is needed in.
+fn file_continuations() -> impl Iterator<Item = ContinuationStyle> {
+    let bits = FILE_CONTINUATIONS.load(Ordering::Relaxed);
+    let console = (bits & FOLLOWS_CONSOLE != 0).then(ContinuationStyle::of_console);
+    ContinuationStyle::ALL.into_iter().filter(move |&style| bits & (1 << style as u8) != 0 || console == Some(style))
+}
+
+/// Keeps a file sink open; dropping it flushes and closes the file.
+#[must_use = "the file sink is removed as soon as the guard is dropped"]
+pub struct FileSinkGuard {
+    id: u64,
+}
+
+impl Drop for FileSinkGuard {
+    fn drop(&mut self) {
+        let mut sinks = FILE_SINKS.lock().unwrap();
+        if let Some(i) = sinks.iter().position(|sink| sink.id == self.id) {
+            let mut sink = sinks.remove(i);
+            let _ = if sink.sync == SyncPolicy::Never { sink.file.flush() } else { sink.sync() };
+        }
+        store_file_sinks(&sinks);
+    }
+}
+
+/// Updates what the record path reads without the lock after `sinks` (the
+/// locked list) or `file_level()` changed.
+fn store_file_sinks(sinks: &[FileSink]) {
+    HAS_FILE_SINKS.store(!sinks.is_empty(), Ordering::Relaxed);
+    let lowest = sinks.iter().filter_map(|sink| sink.level.or_else(file_level)).min().unwrap_or(Level::None);
+    FILE_SINK_LEVEL.store(lowest as u8, Ordering::Relaxed);
+    let continuations = sinks.iter().map(|sink| sink.continuation.map_or(FOLLOWS_CONSOLE, |style| 1 << style as u8));
+    FILE_CONTINUATIONS.store(continuations.fold(0, |bits, bit| bits | bit), Ordering::Relaxed);
+    let paths: Vec<String> = sinks.iter().map(|sink| sink.path.display().to_string()).collect();
+    note_config("file_sinks", ConfigSource::Api("add_file_sink"), if paths.is_empty() { String::from("(none)") } else { paths.join(", ") });
+}
+
+/// Appends every record to `path` as plain text (never with escape codes,
+/// whatever the console does), or as JSON or logfmt lines in `Format::Json`
+/// and `Format::Logfmt`. Writes are buffered: call `flush`, or keep the
+/// guard until the end of `main`. Short for
+/// `file_sink(path).rotation(policy).open()`.
+pub fn add_file_sink(path: impl AsRef<Path>, policy: RotationPolicy) -> io::Result<FileSinkGuard> {
+    file_sink(path).rotation(policy).open()
+}
+
+/// Options of a file sink, applied by `open`; see `add_file_sink`.
+#[must_use = "the file is not opened until `open` is called"]
+pub struct FileSinkBuilder {
+    path: PathBuf,
+    policy: RotationPolicy,
+    session_header: bool,
+    sync: SyncPolicy,
+    reopen_check: Option<Duration>,
+    level: Option<Level>,
+    continuation: Option<ContinuationStyle>,
+}
+
+/// A file sink at `path` that never rotates and writes session headers,
+/// until the builder says otherwise.
+pub fn file_sink(path: impl AsRef<Path>) -> FileSinkBuilder {
+    FileSinkBuilder {
+        path: path.as_ref().to_path_buf(), policy: RotationPolicy::Never, session_header: true, sync: SyncPolicy::Never,
+        reopen_check: None, level: None, continuation: None,
+    }
+}
+
+impl FileSinkBuilder {
+    pub fn rotation(mut self, policy: RotationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether a header starts each session in the file (default on): a
+    /// rule and a `[logger]` record with the binary, its pid, the level and
+    /// the logger version when the file is opened, and a `continued from`
+    /// record at the top of each file started by rotation.
+    pub fn session_header(mut self, enabled: bool) -> Self {
+        self.session_header = enabled;
+        self
+    }
+
+    /// Default `SyncPolicy::Never`; see `SyncPolicy` for what each costs.
+    pub fn sync_policy(mut self, policy: SyncPolicy) -> Self {
+        self.sync = policy;
+        self
+    }
+
+    /// Checks, on the first record after each `every` (`Duration::ZERO`:
+    /// every record), whether `path` still leads to the open file, and
+    /// reopens it if the file was moved away or deleted, as logrotate's
+    /// default move-and-recreate does. The check is a `stat` of the path;
+    /// off by default. Without inode numbers (outside unix), only a
+    /// missing path is noticed.
+    pub fn reopen_check(mut self, every: Duration) -> Self {
+        self.reopen_check = Some(every);
+        self
+    }
+
+    /// The lowest level written to this file, whatever the console and
+    /// `file_level()` let through: `Level::Debug` keeps every detail in the
+    /// file while the console shows only warnings. Module levels and
+    /// `mute` do not apply to it.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// How the plain text lines after the first of a record start, e.g.
+    /// `ContinuationStyle::RepeatPrefix` for a collector that takes each
+    /// line as a record of its own. Without it, the console's
+    /// `set_multiline` applies. JSON and logfmt records are one line anyway.
+    pub fn continuation(mut self, style: ContinuationStyle) -> Self {
+        self.continuation = Some(style);
+        self
+    }
+
+    pub fn open(self) -> io::Result<FileSinkGuard> {
+        let FileSinkBuilder { path, policy, session_header, sync, reopen_check, level, continuation } = self;
+        let (file, size, file_id) = FileHandle::open(&path)?;
+        let id = NEXT_FILE_SINK_ID.fetch_add(1, Ordering::Relaxed);
+        // Read before the lock: an unknown LOG_FORMAT is reported as a record.
+        let format = output_format();
+        let header = session_header.then(|| session_header_text(format, None));
+
+        // Held until the sink is in the list, so each record is either
+        // replayed or written to the file live.
+        let startup = open_startup_log();
+        let mut sinks = FILE_SINKS.lock().unwrap();
+        let now = Instant::now();
+        let mut sink = FileSink {
+            id, path, policy, session_header, sync, reopen_check, file: BufWriter::new(file), file_id, size, unsynced: false,
+            synced_at: now, checked_at: now, replayed_through: 0, level, continuation, failed: false,
+        };
+        if let Some(header) = header { sink.write_raw(&header)?; }
+        if let Some(startup) = startup.as_deref().and_then(Option::as_ref) {
+            for kept in startup.records.iter().filter(|kept| kept.destination.files()) {
+                sink.replayed_through = kept.seq;
+                if !sink.takes(kept.level, kept.to_console) { continue; }
+                let mut line = kept.with_record(|record| match format {
+                    Format::Json => json_line(record),
+                    Format::Logfmt => logfmt_line(record),
+                    Format::Pretty | Format::Plain => plain_line(record, sink.continuation()),
+                });
+                line.push('\n');
+                sink.write(&line, kept.level)?;
+            }
+        }
+        // Under the lock, so the thread cannot end before the sink is added.
+        if matches!(sync, SyncPolicy::Interval(_)) && !FILE_SYNC_RUNNING.swap(true, Ordering::Relaxed) {
+            let spawned = std::thread::Builder::new().name(String::from("rust_logger-file-sync")).spawn(run_file_sync);
+            if let Err(e) = spawned {
+                FILE_SYNC_RUNNING.store(false, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+        sinks.push(sink);
+        store_file_sinks(&sinks);
+
+        Ok(FileSinkGuard { id })
+    }
+}
+
+/// The lines that start a session in a file, in `format`: a rule (in the
+/// text formats) and a `[logger]` record; after a rotation, just the
+/// `continued` record.
+fn session_header_text(format: Format, continued: Option<String>) -> String {
+    let rule = continued.is_none();
+    let message = match continued {
+        Some(continued) => continued,
+        None => {
+            let binary = std::env::current_exe().ok().and_then(|exe| exe.file_name().map(|name| name.to_string_lossy().into_owned()));
+            format!(
+                "session started: {} (pid {}), level {:?}, rust_logger {}",
+                binary.as_deref().unwrap_or("?"), std::process::id(), level(), env!("CARGO_PKG_VERSION"),
+            )
+        }
+    };
+    let ts = record_timestamp();
+    let tag = PROCESS_TAG.read().unwrap();
+    let record = Record {
+        process: tag.as_deref(), timestamp: ts.as_deref(), target: Some(SELF_LOG_TARGET), ..Record::new(Level::Info, &message)
+    };
+
+    let mut text = String::new();
+    match format {
+        Format::Json => text.push_str(&json_line(&record)),
+        Format::Logfmt => text.push_str(&logfmt_line(&record)),
+        Format::Pretty | Format::Plain => {
+            if rule {
+                text.push_str(&glyphs().rule.repeat(72));
+                text.push('\n');
+            }
+            text.push_str(&plain_line(&record, ContinuationStyle::of_console()));
+        }
+    }
+    text.push('\n');
+    text
+}
+
+/// Writes out what the file sinks have buffered.
+pub fn flush() {
+    for sink in FILE_SINKS.lock().unwrap().iter_mut() {
+        let _ = sink.file.flush();
+    }
+}
+
+/// Reopens the file of every file sink at its path, e.g. after logrotate
+/// moved the files away; for a SIGHUP handler to call (from a thread, not
+/// the signal handler itself). What was buffered goes to the old file
+/// first. Returns the first error; the sinks that failed are reported once
+/// each and keep their old file.
+pub fn reopen() -> io::Result<()> {
+    let mut result = Ok(());
+    for sink in FILE_SINKS.lock().unwrap().iter_mut() {
+        if let Err(e) = sink.reopen() {
+            if !std::mem::replace(&mut sink.failed, true) {
+                self_log_to(SelfLog::Stderr, Level::Error, format_args!("file sink {} failed: {e}", sink.path.display()));
+            }
+            if result.is_ok() { result = Err(e); }
+        }
+    }
+    result
+}
+
+/// `<path>.<n>`, e.g. `app.log.2`.
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+impl FileSink {
+    fn rotate(&mut self, keep: usize) -> io::Result<()> {
+        // What went to the old file is made durable before it is renamed.
+        if self.sync == SyncPolicy::Never { self.file.flush()?; } else { self.sync()?; }
+        if keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(rotated_path(&self.path, keep));
+            for n in (1..keep).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() { fs::rename(from, rotated_path(&self.path, n + 1))?; }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        let (file, _, file_id) = FileHandle::open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.file_id = file_id;
+        self.size = 0;
+        if self.session_header {
+            let continued = if keep == 0 {
+                String::from("continued after rotation; earlier records were deleted")
+            } else {
+                let previous = rotated_path(&self.path, 1);
+                format!("continued from {}", previous.file_name().unwrap_or(previous.as_os_str()).to_string_lossy())
+            };
+            self.write_raw(&session_header_text(output_format(), Some(continued)))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the path no longer leads to the open file.
+    fn moved(&self) -> bool {
+        match fs::metadata(&self.path) {
+            Ok(metadata) => file_id(&metadata) != self.file_id,
+            Err(e) => e.kind() == io::ErrorKind::NotFound,
+        }
+    }
+
+    /// Starts writing to whatever file is at the path now, after finishing
+    /// the old one.
+    fn reopen(&mut self) -> io::Result<()> {
+        if self.sync == SyncPolicy::Never { self.file.flush()?; } else { self.sync()?; }
+        let (file, size, file_id) = FileHandle::open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.file_id = file_id;
+        self.size = size;
+        if self.session_header {
+            let header = session_header_text(output_format(), Some(String::from("reopened: the previous file was moved or deleted")));
+            self.write_raw(&header)?;
+        }
+        Ok(())
+    }
+
+    /// Writes records at `level`, then syncs if the policy says so.
+    fn write(&mut self, text: &str, level: Level) -> io::Result<()> {
+        // Before the write, so the record goes to the new file only. If the
+        // new file cannot be opened, the record still goes to the old one.
+        let mut reopened = Ok(());
+        if let Some(every) = self.reopen_check {
+            let now = Instant::now();
+            if now.duration_since(self.checked_at) >= every {
+                self.checked_at = now;
+                if self.moved() { reopened = self.reopen(); }
+            }
+        }
+        if let RotationPolicy::Size { max_bytes, keep } = self.policy {
+            if self.size > 0 && self.size + text.len() as u64 > max_bytes { self.rotate(keep)?; }
+        }
+        self.write_raw(text)?;
+        self.unsynced = true;
+        let synced = match self.sync {
+            SyncPolicy::EveryRecord => self.sync(),
+            SyncPolicy::OnError if level >= Level::Error => self.sync(),
+            _ => Ok(()),
+        };
+        reopened.and(synced)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().0.sync_data()?;
+        self.unsynced = false;
+        self.synced_at = Instant::now();
+        Ok(())
+    }
+
+    /// Writes `text` without the rotation check; it still counts toward
+    /// the size.
+    fn write_raw(&mut self, text: &str) -> io::Result<()> {
+        self.file.write_all(text.as_bytes())?;
+        self.size += text.len() as u64;
+        Ok(())
+    }
+}
+
+static FILE_SYNC_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// The background thread of `SyncPolicy::Interval`: flushes the sinks that
+/// are due under the lock, then syncs them without it. Ends when no sink
+/// has an interval any more.
+fn run_file_sync() {
+    loop {
+        let (tick, due) = {
+            let mut sinks = FILE_SINKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let tick = sinks.iter().filter_map(|sink| match sink.sync { SyncPolicy::Interval(every) => Some(every), _ => None }).min();
+            let Some(tick) = tick else {
+                FILE_SYNC_RUNNING.store(false, Ordering::Relaxed);
+                return;
+            };
+            (tick, due_syncs(&mut sinks, Instant::now()))
+        };
+
+        let failed: Vec<(u64, io::Error)> = due.into_iter().filter_map(|(id, file)| file.sync_data().err().map(|e| (id, e))).collect();
+        for (id, e) in failed {
+            let mut sinks = FILE_SINKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Some(sink) = sinks.iter_mut().find(|sink| sink.id == id) {
+                if !std::mem::replace(&mut sink.failed, true) {
+                    self_log_to(SelfLog::Stderr, Level::Error, format_args!("file sink {} failed: {e}", sink.path.display()));
+                }
+            }
+        }
+        std::thread::sleep(tick.max(Duration::from_millis(1)));
+    }
+}
+
+/// Flushes the `Interval` sinks with records older than their interval and
+/// hands out their files to sync.
+fn due_syncs(sinks: &mut [FileSink], now: Instant) -> Vec<(u64, Arc<dyn LogFile>)> {
+    let mut due = Vec::new();
+    for sink in sinks.iter_mut() {
+        let SyncPolicy::Interval(every) = sink.sync else { continue };
+        if !sink.unsynced || now.duration_since(sink.synced_at) < every || sink.file.flush().is_err() { continue; }
+        sink.unsynced = false;
+        sink.synced_at = now;
+        due.push((sink.id, sink.file.get_ref().0.clone()));
+    }
+    due
+}
+
+/// Writes `same`, JSON or logfmt text, to every file that takes `level`,
+/// then the text in `plain` for the file's continuation style.
+/// `seq` is the record's place in the startup log, so a sink that had it
+/// replayed does not get it twice.
+fn write_file_sinks(same: &str, plain: &[String; 4], level: Level, to_console: bool, seq: Option<u64>, timings: &mut Timings) {
+    let mut sinks = FILE_SINKS.lock().unwrap();
+    let takes = |sink: &FileSink| {
+        sink.takes(level, to_console) && seq.is_none_or(|seq| seq > sink.replayed_through) && !sink_muted(SinkKey::File(sink.id))
+    };
+    for sink in sinks.iter_mut().filter(|sink| takes(sink)) {
+        let started = timings.start();
+        let styled = &plain[sink.continuation() as usize];
+        let written = match (same.is_empty(), styled.is_empty()) {
+            (false, true) => sink.write(same, level),
+            (true, false) => sink.write(styled, level),
+            _ => sink.write(&format!("{same}{styled}"), level),
+        };
+        timings.stop(started, || format!("file {}", sink.path.display()));
+        if let Err(e) = written {
+            if !std::mem::replace(&mut sink.failed, true) {
+                self_log_to(SelfLog::Stderr, Level::Error, format_args!("file sink {} failed: {e}", sink.path.display()));
+            }
+        }
+    }
+}
+
+/// The flight recorder file: a header, then `capacity` bytes used as a
+/// circular buffer of records.
+#[cfg(unix)]
+struct BlackBox {
+    file: File,
+    path: PathBuf,
+    capacity: u64,
+    /// Bytes handed out to writers so far; a record's position is its
+    /// reservation modulo `capacity`.
+    reserved: AtomicU64,
+    /// End of the furthest record written, stored as the header's head.
+    head: AtomicU64,
+    failed: AtomicBool,
+}
+
+#[cfg(unix)]
+static BLACK_BOX: std::sync::OnceLock<BlackBox> = std::sync::OnceLock::new();
+
+/// `RLBBOX01`, then the capacity and the head as little-endian `u64`s.
+#[cfg(unix)]
+const BLACK_BOX_MAGIC: &[u8; 8] = b"RLBBOX01";
+#[cfg(unix)]
+const BLACK_BOX_HEADER_LEN: u64 = 24;
+
+/// Starts the flight recorder: every record that reaches the console is
+/// also written, as plain text, to `path`, a file of `capacity` bytes plus a
+/// small header that is overwritten from the start once full. Each record is
+/// written with one positioned write and then the head is updated, so the
+/// file is complete up to the last record even after `kill -9`; read it back
+/// with `read_black_box`. One per process; the file is truncated.
+#[cfg(unix)]
+pub fn start_black_box(path: impl AsRef<Path>, capacity: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+
+    if BLACK_BOX.get().is_some() { return Err(io::Error::new(io::ErrorKind::AlreadyExists, "a black box is already recording")); }
+    if capacity < 64 { return Err(io::Error::new(io::ErrorKind::InvalidInput, "black box capacity must be at least 64 bytes")); }
+
+    let path = path.as_ref().to_path_buf();
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+    file.set_len(BLACK_BOX_HEADER_LEN + capacity)?;
+    let mut header = Vec::with_capacity(BLACK_BOX_HEADER_LEN as usize);
+    header.extend_from_slice(BLACK_BOX_MAGIC);
+    header.extend_from_slice(&capacity.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    file.write_all_at(&header, 0)?;
+
+    note_config("black_box", ConfigSource::Api("start_black_box"), path.display());
+    let black_box = BlackBox {
+        file, path, capacity, reserved: AtomicU64::new(0), head: AtomicU64::new(0), failed: AtomicBool::new(false),
+    };
+    BLACK_BOX.set(black_box).map_err(|_| io::Error::new(io::ErrorKind::AlreadyExists, "a black box is already recording"))
+}
+
+#[cfg(unix)]
+impl BlackBox {
+    /// Frames `text` as `len, bytes, len` so the reader can walk back from
+    /// the head; records longer than the buffer are left out.
+    fn record(&self, text: &str) {
+        let framed_len = text.len() as u64 + 8;
+        if framed_len > self.capacity || text.len() > u32::MAX as usize { return; }
+
+        let len = (text.len() as u32).to_le_bytes();
+        let mut framed = Vec::with_capacity(framed_len as usize);
+        framed.extend_from_slice(&len);
+        framed.extend_from_slice(text.as_bytes());
+        framed.extend_from_slice(&len);
+
+        let start = self.reserved.fetch_add(framed_len, Ordering::Relaxed);
+        let end = start + framed_len;
+        let written = self.write_wrapped(start, &framed).and_then(|()| self.advance_head(end));
+        if let Err(e) = written {
+            if !self.failed.swap(true, Ordering::Relaxed) {
+                self_log_to(SelfLog::Stderr, Level::Error, format_args!("black box {} failed: {e}", self.path.display()));
+            }
+        }
+    }
+
+    /// Stores the furthest end so far in the header without a lock. A writer
+    /// that raced with a larger end may write a stale head, so each one
+    /// checks again after writing and repeats until the header holds the
+    /// maximum it last saw; the last header write is then never a smaller one.
+    fn advance_head(&self, end: u64) -> io::Result<()> {
+        use std::os::unix::fs::FileExt;
+
+        if self.head.fetch_max(end, Ordering::AcqRel) >= end { return Ok(()); }
+        let mut written = 0;
+        loop {
+            let head = self.head.load(Ordering::Acquire);
+            if head == written { return Ok(()); }
+            self.file.write_all_at(&head.to_le_bytes(), 16)?;
+            written = head;
+        }
+    }
+
+    fn write_wrapped(&self, start: u64, bytes: &[u8]) -> io::Result<()> {
+        use std::os::unix::fs::FileExt;
+
+        let at = start % self.capacity;
+        let first = bytes.len().min((self.capacity - at) as usize);
+        self.file.write_all_at(&bytes[..first], BLACK_BOX_HEADER_LEN + at)?;
+        if first < bytes.len() { self.file.write_all_at(&bytes[first..], BLACK_BOX_HEADER_LEN)?; }
+        Ok(())
+    }
+}
+
+/// The records in a black box file, oldest first: walks back from the head
+/// until a record was overwritten, or is incomplete because another thread
+/// was still writing it when the process died.
+#[cfg(unix)]
+pub fn read_black_box(path: impl AsRef<Path>) -> io::Result<Vec<String>> {
+    let file = fs::read(path)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a black box file");
+    if file.len() < BLACK_BOX_HEADER_LEN as usize || &file[..8] != BLACK_BOX_MAGIC { return Err(invalid()); }
+
+    let u64_at = |at: usize| u64::from_le_bytes(file[at..at + 8].try_into().unwrap());
+    let capacity = u64_at(8);
+    let head = u64_at(16);
+    let data = &file[BLACK_BOX_HEADER_LEN as usize..];
+    if capacity == 0 || data.len() as u64 != capacity { return Err(invalid()); }
+
+    let read = |start: u64, len: u64| -> Vec<u8> { (start..start + len).map(|i| data[(i % capacity) as usize]).collect() };
+    let len_at = |start: u64| u64::from(u32::from_le_bytes(read(start, 4).try_into().unwrap()));
+
+    let oldest = head.saturating_sub(capacity);
+    let mut records = Vec::new();
+    let mut end = head;
+    while end >= oldest + 8 {
+        let len = len_at(end - 4);
+        let Some(start) = end.checked_sub(len + 8).filter(|start| *start >= oldest) else { break };
+        if len_at(start) != len { break; }
+        records.push(String::from_utf8_lossy(&read(start + 4, len)).into_owned());
+        end = start;
+    }
+    records.reverse();
+    Ok(records)
+}
+
+/// A record kept for sinks added later, owning what `Record` borrows.
+#[derive(Clone)]
+struct KeptRecord {
+    seq: u64,
+    /// `Record::seq`
+    record_seq: u64,
+    to_console: bool,
+    level: Level,
+    emphasis: bool,
+    category: Option<Cow<'static, str>>,
+    process: Option<Cow<'static, str>>,
+    thread: Option<Cow<'static, str>>,
+    timestamp: Option<String>,
+    target: Option<Cow<'static, str>>,
+    depth: usize,
+    message: String,
+    raw: Option<String>,
+    event_seq: Option<u64>,
+    /// Formatted, as they would be when written
+    fields: Vec<(Cow<'static, str>, String)>,
+    suppressed: u64,
+    causes: Vec<String>,
+    destination: Destination,
+}
+
+impl KeptRecord {
+    fn new(seq: u64, to_console: bool, record: &Record) -> KeptRecord {
+        let owned = |text: Option<&str>| text.map(String::from);
+        let interned = |text: Option<&str>| text.map(|text| INTERNER.cow(text));
+        KeptRecord {
+            seq, record_seq: record.seq, to_console, level: record.level, emphasis: record.emphasis, category: interned(record.category),
+            process: interned(record.process), thread: interned(record.thread), timestamp: owned(record.timestamp),
+            target: interned(record.target), depth: record.depth, message: record.message.to_string(), raw: owned(record.raw),
+            event_seq: record.event_seq,
+            fields: record.fields.iter().map(|field| (INTERNER.cow(field.key), Shown(field).to_string())).collect(),
+            suppressed: record.suppressed, causes: record.causes.iter().map(|cause| cause.to_string()).collect(),
+            destination: record.destination,
+        }
+    }
+
+    fn with_record<T>(&self, f: impl FnOnce(&Record) -> T) -> T {
+        let fields: Vec<Field> = self.fields.iter().map(|(key, value)| Field { key, value: FieldValue::Display(value) }).collect();
+        let causes: Vec<&str> = self.causes.iter().map(String::as_str).collect();
+        f(&Record {
+            emphasis: self.emphasis, category: self.category.as_deref(), process: self.process.as_deref(),
+            thread: self.thread.as_deref(), timestamp: self.timestamp.as_deref(), target: self.target.as_deref(),
+            depth: self.depth, fields: &fields, suppressed: self.suppressed, causes: &causes, destination: self.destination,
+            raw: self.raw.as_deref(), seq: self.record_seq, event_seq: self.event_seq,
+            ..Record::new(self.level, &self.message)
+        })
+    }
+}
+
+/// The first records of the run, for `add_sink` and `add_file_sink` calls
+/// that come after them.
+struct StartupLog {
+    records: Vec<KeptRecord>,
+    capacity: usize,
+    window: Duration,
+    /// When the first record was kept; the window starts there.
+    started: Option<Instant>,
+    /// Numbers records from 1, so sinks can tell which ones they were
+    /// replayed.
+    next_seq: u64,
+}
+
+const DEFAULT_STARTUP_RECORDS: usize = 100;
+const DEFAULT_STARTUP_WINDOW: Duration = Duration::from_secs(10);
+
+/// `None` once the window is over: the records are dropped then.
+static STARTUP_LOG: Mutex<Option<StartupLog>> = Mutex::new(Some(StartupLog {
+    records: Vec::new(), capacity: DEFAULT_STARTUP_RECORDS, window: DEFAULT_STARTUP_WINDOW, started: None, next_seq: 1,
+}));
+/// Keeps logging a single load once the startup log is gone.
+static STARTUP_LOG_OPEN: AtomicBool = AtomicBool::new(true);
+
+/// The startup log, locked, or `None` inside if its window is over (or was
+/// already).
+fn open_startup_log() -> Option<MutexGuard<'static, Option<StartupLog>>> {
+    if !STARTUP_LOG_OPEN.load(Ordering::Relaxed) { return None; }
+    let mut startup = STARTUP_LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if startup.as_ref().is_some_and(|log| log.started.is_some_and(|started| started.elapsed() >= log.window)) {
+        close_startup_log(&mut startup);
+    }
+    Some(startup)
+}
+
+fn close_startup_log(startup: &mut Option<StartupLog>) {
+    *startup = None;
+    STARTUP_LOG_OPEN.store(false, Ordering::Relaxed);
+}
+
+/// Keeps `record` in the startup log while there is room, and returns its
+/// number there.
+fn keep_for_replay(record: &Record, to_console: bool) -> Option<u64> {
+    // What a sink logs never reaches the sinks, so it is never replayed.
+    if IN_SINK_WRITE.with(Cell::get) { return None; }
+    let mut guard = open_startup_log()?;
+    let startup = guard.as_mut()?;
+    if startup.records.len() >= startup.capacity { return None; }
+    startup.started.get_or_insert_with(Instant::now);
+    let seq = startup.next_seq;
+    startup.next_seq += 1;
+    startup.records.push(KeptRecord::new(seq, to_console, record));
+    Some(seq)
+}
+
+/// How many of the first records are kept for sinks added later, and for
+/// how long after the first one (default 100 records, 10 seconds). A sink
+/// or file sink added in that window first gets the kept records it would
+/// have taken, with their original timestamps; after it, or after
+/// `finish_initialization`, they are dropped. `0` records turns it off.
+/// Has no effect once the window is over.
+pub fn set_startup_replay(records: usize, window: Duration) {
+    note_config("startup_replay", ConfigSource::Api("set_startup_replay"), format_args!("{records} records, {window:?}"));
+    let Some(mut guard) = open_startup_log() else { return };
+    let Some(startup) = guard.as_mut() else { return };
+    if records == 0 {
+        close_startup_log(&mut guard);
+        return;
+    }
+    startup.capacity = records;
+    startup.records.truncate(records);
+    startup.window = window;
+}
+
+/// Ends the startup replay window: sinks added from now on only get new
+/// records, and the kept ones are dropped.
+pub fn finish_initialization() {
+    if let Some(mut startup) = open_startup_log() { close_startup_log(&mut startup); }
+}
+
+/// A destination for records next to the console, registered with
+/// `add_sink`.
+pub trait Sink: Send {
+    /// Called for every record at or above the sink's level. `line` is the
+    /// record in the sink's format, without the trailing newline: plain text
+    /// without escape codes for `Format::Pretty`, a JSON object for
+    /// `Format::Json`, `key=value` pairs for `Format::Logfmt`, and the
+    /// console layout without escape codes for `Format::Plain`. `new_line!`
+    /// reaches `Format::Pretty` and `Format::Plain` sinks as an Info record
+    /// (or one at its level) with an empty message and an empty `line`.
+    ///
+    /// No registry lock is held during the call, so `write` may log: records
+    /// logged from inside it reach the console and file sinks but not the
+    /// sinks, which would otherwise recurse.
+    fn write(&mut self, record: &Record, line: &str);
+
+    /// Called by `console::shutdown`.
+    fn flush(&mut self) {}
+
+    /// Names the sink in diagnostics such as `warn_if_log_call_exceeds`;
+    /// the type name by default.
+    fn name(&self) -> String {
+        String::from(std::any::type_name::<Self>())
+    }
+
+    /// Which `dest:` records the sink takes besides `dest: all`, read once
+    /// when it is added; `SinkKind::File` by default.
+    fn kind(&self) -> SinkKind {
+        SinkKind::File
+    }
+}
+
+/// What a sink stands for, to pick the records logged with `dest: console`
+/// or `dest: file`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SinkKind {
+    /// A view next to the console, e.g. a pane in a terminal UI
+    Console,
+    /// A record kept for later: a file, a collector, a database
+    File,
+}
+
+impl SinkKind {
+    /// `console` or `file`, as in `LOG_LEVEL=console:warn,file:debug`.
+    pub fn parse(name: &str) -> Result<SinkKind, ConfigError> {
+        [("console", SinkKind::Console), ("file", SinkKind::File)]
+            .into_iter()
+            .find(|(known, _)| name.eq_ignore_ascii_case(known))
+            .map(|(_, kind)| kind)
+            .ok_or_else(|| ConfigError::UnknownSinkKind(name.to_string()))
+    }
+}
+
+/// Which records a sink takes, on top of its level.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Route {
+    /// Every record (`add_sink`)
+    All,
+    /// Records logged with this category, e.g. `"audit"` for `audit!`
+    Category(&'static str),
+    /// Records without a category: everything but business events
+    Uncategorized,
+}
+
+impl Route {
+    fn matches(&self, category: Option<&str>) -> bool {
+        match self {
+            Route::All => true,
+            Route::Category(name) => category == Some(*name),
+            Route::Uncategorized => category.is_none(),
+        }
+    }
+}
+
+/// Identifies a sink for `remove_sink`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SinkId(u64);
+
+/// A sink `mute_sink` can mute: a `SinkId`, or a `&FileSinkGuard` for a
+/// log file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SinkRef(SinkKey);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SinkKey {
+    Sink(SinkId),
+    File(u64),
+}
+
+impl From<SinkId> for SinkRef {
+    fn from(id: SinkId) -> SinkRef {
+        SinkRef(SinkKey::Sink(id))
+    }
+}
+
+impl From<&FileSinkGuard> for SinkRef {
+    fn from(guard: &FileSinkGuard) -> SinkRef {
+        SinkRef(SinkKey::File(guard.id))
+    }
+}
+
+thread_local! {
+    /// Sinks muted on this thread, once per `mute_sink` guard alive.
+    static MUTED_SINKS: RefCell<Vec<SinkKey>> = const { RefCell::new(Vec::new()) };
+}
+
+fn sink_muted(key: SinkKey) -> bool {
+    MUTED_SINKS.try_with(|muted| muted.borrow().contains(&key)).unwrap_or(false)
+}
+
+/// Returned by `mute_sink!`; unmutes the sink when dropped.
+#[must_use = "the sink is muted only while the guard is alive"]
+pub struct SinkMuteGuard {
+    key: SinkKey,
+    // Restores a thread-local, so it must be dropped on the thread that created it.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+/// Stops records logged on the current thread from reaching `sink` until
+/// the guard is dropped; the console, the other sinks and the black box
+/// still get them. Mutes of the same sink nest: it stays muted until the
+/// last guard is dropped.
+pub fn mute_sink(sink: impl Into<SinkRef>) -> SinkMuteGuard {
+    let SinkRef(key) = sink.into();
+    let _ = MUTED_SINKS.try_with(|muted| muted.borrow_mut().push(key));
+    SinkMuteGuard { key, _not_send: std::marker::PhantomData }
+}
+
+impl Drop for SinkMuteGuard {
+    fn drop(&mut self) {
+        let _ = MUTED_SINKS.try_with(|muted| {
+            let mut muted = muted.borrow_mut();
+            if let Some(i) = muted.iter().rposition(|key| *key == self.key) { muted.remove(i); }
+        });
+    }
+}
+
+struct SinkEntry {
+    id: SinkId,
+    level: Level,
+    format: Format,
+    route: Route,
+    kind: SinkKind,
+    /// `None` once `remove_sink` took it while a record was on its way.
+    sink: Mutex<Option<Box<dyn Sink>>>,
+    /// Set when `write` panicked; the sink gets no more records.
+    broken: AtomicBool,
+    /// The last startup record replayed into the sink when it was added.
+    replayed_through: u64,
+    /// `ContinuationStyle` plus one, 0 to follow the console
+    continuation: AtomicU8,
+}
+
+impl SinkEntry {
+    fn continuation(&self) -> ContinuationStyle {
+        ContinuationStyle::stored(self.continuation.load(Ordering::Relaxed))
+    }
+}
+
+/// Copied on write: `write_sinks` clones the `Arc` and calls the sinks after
+/// releasing the lock, so adding or removing a sink never waits on a slow
+/// `write`.
+static SINKS: RwLock<Vec<Arc<SinkEntry>>> = RwLock::new(Vec::new());
+/// Lowest level any sink takes, `Level::None` without sinks.
+static SINK_LEVEL: AtomicU8 = AtomicU8::new(Level::None as u8);
+static NEXT_SINK_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Set while this thread is inside `Sink::write`.
+    static IN_SINK_WRITE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Clears `IN_SINK_WRITE` on drop, also when `write` panics.
+struct InSinkWrite;
+
+impl InSinkWrite {
+    fn enter() -> InSinkWrite {
+        IN_SINK_WRITE.with(|inside| inside.set(true));
+        InSinkWrite
+    }
+}
+
+impl Drop for InSinkWrite {
+    fn drop(&mut self) {
+        IN_SINK_WRITE.with(|inside| inside.set(false));
+    }
+}
+
+fn store_sinks(sinks: &[Arc<SinkEntry>]) {
+    let lowest = sinks.iter().map(|entry| entry.level).min().unwrap_or(Level::None);
+    SINK_LEVEL.store(lowest as u8, Ordering::Relaxed);
+
+    let listed: Vec<String> = sinks
+        .iter()
+        .map(|entry| match &entry.route {
+            Route::All => format!("{:?}/{:?}", entry.level, entry.format),
+            route => format!("{:?}/{:?}/{route:?}", entry.level, entry.format),
+        })
+        .collect();
+    note_config("sinks", ConfigSource::Api("add_sink"), if listed.is_empty() { String::from("(none)") } else { listed.join(", ") });
+}
+
+/// Sends every record at `level` or above to `sink`, formatted as `format`,
+/// in addition to the console. The sink's level is independent of the
+/// console's: a Debug sink gets Debug records while the console stays at
+/// Info. Only `mute!` applies to both.
+pub fn add_sink(sink: Box<dyn Sink>, level: Level, format: Format) -> SinkId {
+    add_routed_sink(sink, level, format, Route::All)
+}
+
+/// `add_sink` for the records `route` picks, e.g. a separate destination
+/// for `audit!` events with `Route::Category("audit")`.
+/// Added while the startup log is kept (see `set_startup_replay`), the sink
+/// first gets the records it would have taken from the start of the run,
+/// with their original timestamps.
+pub fn add_routed_sink(sink: Box<dyn Sink>, level: Level, format: Format, route: Route) -> SinkId {
+    let id = SinkId(NEXT_SINK_ID.fetch_add(1, Ordering::Relaxed));
+    // Held until the sink is registered, so each record is either replayed
+    // or written to it live. A sink added from inside a sink's `write`
+    // would not get the replay, which would reach the sinks.
+    let startup = if IN_SINK_WRITE.with(Cell::get) { None } else { open_startup_log() };
+    let kept = startup.as_deref().and_then(Option::as_ref);
+    let kind = sink.kind();
+    let takes = |r: &KeptRecord| r.level >= level && route.matches(r.category.as_deref()) && r.destination.takes(kind);
+    let replay: Vec<KeptRecord> = kept.map(|kept| kept.records.iter().filter(|r| takes(r)).cloned().collect()).unwrap_or_default();
+    let replayed_through = kept.map_or(0, |kept| kept.next_seq - 1);
+    let entry = Arc::new(SinkEntry {
+        id, level, format, route, kind, sink: Mutex::new(Some(sink)), broken: AtomicBool::new(false), replayed_through,
+        continuation: AtomicU8::new(0),
+    });
+    // Live records wait for the replay on this lock.
+    let mut held = entry.sink.lock().unwrap();
+    {
+        let mut sinks = SINKS.write().unwrap();
+        sinks.push(entry.clone());
+        store_sinks(&sinks);
+    }
+    drop(startup);
+
+    for kept in &replay {
+        kept.with_record(|record| write_locked_sink(&entry, &mut held, record, &sink_line(&entry, record)));
+    }
+    id
+}
+
+/// How the lines after the first of a record start in the plain text the
+/// sink `id` gets as `Format::Pretty`; without it, the console's
+/// `set_multiline` applies. False if there is no such sink.
+pub fn set_sink_continuation(id: SinkId, style: ContinuationStyle) -> bool {
+    let Some(entry) = SINKS.read().unwrap().iter().find(|entry| entry.id == id).cloned() else { return false };
+    entry.continuation.store(style as u8 + 1, Ordering::Relaxed);
+    true
+}
+
+/// Unregisters a sink and hands it back, flushed; `None` if it was already
+/// removed.
+pub fn remove_sink(id: SinkId) -> Option<Box<dyn Sink>> {
+    let entry = {
+        let mut sinks = SINKS.write().unwrap();
+        let i = sinks.iter().position(|entry| entry.id == id)?;
+        let entry = sinks.remove(i);
+        store_sinks(&sinks);
+        entry
+    };
+    let mut sink = entry.sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take()?;
+    sink.flush();
+    Some(sink)
+}
+
+fn has_category_sink(category: &'static str) -> bool {
+    SINKS.read().unwrap().iter().any(|entry| entry.route == Route::Category(category))
+}
+
+fn sinks_accept(level: Level) -> bool {
+    let lowest = SINK_LEVEL.load(Ordering::Relaxed).min(FILE_SINK_LEVEL.load(Ordering::Relaxed));
+    lowest != Level::None as u8 && level != Level::None && level as u8 >= lowest && !muted(level)
+}
+
+/// Whether a record goes anywhere: to the console (`is_enabled_for`), to a
+/// sink, or, muted, to the black box only.
+pub fn record_enabled(level: Level, target: &str) -> bool {
+    is_enabled_for(level, target) || sinks_accept(level) || black_box_takes_muted(level, target)
+}
+
+#[cfg(unix)]
+fn black_box_takes_muted(level: Level, target: &str) -> bool {
+    BLACK_BOX.get().is_some() && muted(level) && passes_level_for(level, target)
+}
+
+#[cfg(not(unix))]
+fn black_box_takes_muted(_level: Level, _target: &str) -> bool {
+    false
+}
+
+/// Formats `record` at most once per format and hands it to every sink that
+/// takes its level.
+/// `seq` is the record's place in the startup log, so a sink that had it
+/// replayed does not get it twice.
+fn write_sinks(record: &Record, seq: Option<u64>, plain: &mut PlainText, timings: &mut Timings) {
+    if IN_SINK_WRITE.with(Cell::get) { return; }
+    let sinks: Vec<Arc<SinkEntry>> = SINKS
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|entry| record.level >= entry.level && entry.route.matches(record.category))
+        .filter(|entry| record.destination.takes(entry.kind) && seq.is_none_or(|seq| seq > entry.replayed_through))
+        .filter(|entry| !sink_muted(SinkKey::Sink(entry.id)))
+        .cloned()
+        .collect();
+    let (mut json, mut logfmt, mut pretty) = (None, None, None);
+    for entry in &sinks {
+        let line = match entry.format {
+            Format::Pretty => plain.get(record, entry.continuation()),
+            Format::Json => json.get_or_insert_with(|| json_line(record)),
+            Format::Logfmt => logfmt.get_or_insert_with(|| logfmt_line(record)),
+            Format::Plain => pretty.get_or_insert_with(|| uncolored_pretty_line(record)),
+        };
+        let started = timings.start();
+        let name = write_sink(entry, record, line);
+        timings.stop(started, || name.unwrap_or_default());
+    }
+}
+
+/// Hands `line` to one sink, catching a panic in `write`: the sink is then
+/// skipped from now on, which is reported once. Returns the sink's name, or
+/// `None` if it was skipped.
+fn write_sink(entry: &SinkEntry, record: &Record, line: &str) -> Option<String> {
+    if entry.broken.load(Ordering::Relaxed) { return None; }
+    let mut sink = entry.sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    write_locked_sink(entry, &mut sink, record, line)
+}
+
+/// `write_sink` with the sink's lock already held.
+fn write_locked_sink(entry: &SinkEntry, sink: &mut Option<Box<dyn Sink>>, record: &Record, line: &str) -> Option<String> {
+    if entry.broken.load(Ordering::Relaxed) { return None; }
+    let (name, written) = {
+        let sink = sink.as_mut()?;
+        let _inside = InSinkWrite::enter();
+        (sink.name(), std::panic::catch_unwind(AssertUnwindSafe(|| sink.write(record, line))))
+    };
+    if let Err(payload) = written {
+        if !entry.broken.swap(true, Ordering::Relaxed) {
+            self_log(Level::Error, format_args!("sink {name} panicked and gets no more records: {}", panic_message(&*payload)));
+        }
+    }
+    Some(name)
+}
+
+/// Writes a `[logger]` record to the sink `id` only; false if there is no
+/// such sink or this thread is inside a sink's `write` already.
+fn write_one_sink(id: SinkId, record: &Record) -> bool {
+    if IN_SINK_WRITE.with(Cell::get) || sink_muted(SinkKey::Sink(id)) { return false; }
+    let Some(entry) = SINKS.read().unwrap().iter().find(|entry| entry.id == id).cloned() else { return false };
+    write_sink(&entry, record, &sink_line(&entry, record)).is_some()
+}
+
+/// `record` as the sink gets it.
+fn sink_line(entry: &SinkEntry, record: &Record) -> String {
+    match entry.format {
+        Format::Pretty => plain_line(record, entry.continuation()),
+        Format::Json => json_line(record),
+        Format::Logfmt => logfmt_line(record),
+        Format::Plain => uncolored_pretty_line(record),
+    }
+}
+
+fn json_line(record: &Record) -> String {
+    let mut line = String::new();
+    let _ = JsonFormatter.format(record, &mut line);
+    line
+}
+
+fn logfmt_line(record: &Record) -> String {
+    let mut line = String::new();
+    let _ = LogfmtFormatter.format(record, &mut line);
+    line
+}
+
+fn uncolored_pretty_line(record: &Record) -> String {
+    let mut line = String::new();
+    let _ = format_pretty(&THEME.read().unwrap(), false, FieldsLayout::Inline, record, &mut line);
+    line
+}
+
+fn plain_line(record: &Record, continuation: ContinuationStyle) -> String {
+    let mut line = String::new();
+    let theme = THEME.read().unwrap();
+    let _ = format_plain(&theme.style(record.level).label, FieldsLayout::Inline, continuation, record, &mut line);
+    line
+}
+
+/// A record's `plain_line`, formatted once for each continuation style it
+/// is asked in.
+#[derive(Default)]
+struct PlainText([Option<String>; 4]);
+
+impl PlainText {
+    fn get(&mut self, record: &Record, continuation: ContinuationStyle) -> &str {
+        self.0[continuation as usize].get_or_insert_with(|| plain_line(record, continuation))
+    }
+
+    fn take(&mut self, record: &Record, continuation: ContinuationStyle) -> String {
+        self.0[continuation as usize].take().unwrap_or_else(|| plain_line(record, continuation))
+    }
+}
+
+/// Which glyphs decorations are drawn with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Charset {
+    Unicode,
+    Ascii,
+    /// ASCII unless the locale (`LC_ALL`, `LC_CTYPE`, `LANG`) says UTF-8;
+    /// without a locale, ASCII only when `CI` is set.
+    Auto,
+}
+
+impl Charset {
+    /// Parses `unicode`, `ascii` or `auto`, as in `LOG_CHARSET`.
+    pub fn parse(name: &str) -> Result<Charset, ConfigError> {
+        [("unicode", Charset::Unicode), ("ascii", Charset::Ascii), ("auto", Charset::Auto)]
+            .into_iter()
+            .find(|(known, _)| name.trim().eq_ignore_ascii_case(known))
+            .map(|(_, charset)| charset)
+            .ok_or_else(|| ConfigError::UnknownCharset(name.to_string()))
+    }
+
+    fn is_ascii(self) -> bool {
+        match self {
+            Charset::Unicode => false,
+            Charset::Ascii => true,
+            Charset::Auto => *AUTO_CHARSET_ASCII,
+        }
+    }
+}
+
+static AUTO_CHARSET_ASCII: LazyLock<bool> = LazyLock::new(|| {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_ascii_lowercase();
+            !(locale.contains("utf-8") || locale.contains("utf8"))
+        }
+        None => std::env::var_os("CI").is_some(),
+    }
+});
+
+/// 0 = not decided yet, 1 = Unicode, 2 = ASCII
+static ASCII_GLYPHS: AtomicU8 = AtomicU8::new(0);
+
+/// Default `Unicode`, or `LOG_CHARSET=unicode|ascii|auto`.
+pub fn set_charset(charset: Charset) {
+    note_config("charset", ConfigSource::Api("set_charset"), format_args!("{charset:?}"));
+    ASCII_GLYPHS.store(if charset.is_ascii() { 2 } else { 1 }, Ordering::Relaxed);
+}
+
+fn charset_from_env() -> Charset {
+    let Ok(name) = std::env::var("LOG_CHARSET") else { return Charset::Unicode };
+
+    match Charset::parse(&name) {
+        Ok(charset) => {
+            note_config("charset", ConfigSource::Env("LOG_CHARSET"), format_args!("{charset:?}"));
+            charset
+        }
+        Err(_) => {
+            note_config("charset", ConfigSource::Ignored("LOG_CHARSET"), format!("{name} (unknown charset)"));
+            Charset::Unicode
+        }
+    }
+}
+
+/// `set_charset` with a name as in `LOG_CHARSET`.
+pub fn set_charset_by_name(name: &str) -> Result<(), ConfigError> {
+    set_charset(Charset::parse(name)?);
+    Ok(())
+}
+
+/// `set_charset_by_name` that warns and uses `Unicode` on a bad name.
+pub fn set_charset_by_name_or_default(name: &str) {
+    if let Err(e) = set_charset_by_name(name) {
+        self_log(Level::Warn, format_args!("using the unicode charset: {e}"));
+        set_charset(Charset::Unicode);
+    }
+}
+
+/// The glyph table for the current charset.
+pub fn glyphs() -> &'static Glyphs {
+    let mut mode = ASCII_GLYPHS.load(Ordering::Relaxed);
+    if mode == 0 {
+        let decided = if charset_from_env().is_ascii() { 2 } else { 1 };
+        mode = match ASCII_GLYPHS.compare_exchange(0, decided, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => decided,
+            Err(current) => current,
+        };
+    }
+
+    if mode == 2 { &render::ASCII_GLYPHS } else { &render::UNICODE_GLYPHS }
+}
+
+fn write_dim_replacement(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "\x1b[2m{}\x1b[22m", glyphs().replacement)
+}
+
+/// Displays bytes as UTF-8, replacing invalid sequences with a dimmed U+FFFD
+/// so corruption is visible without breaking the line.
+pub struct Lossy<'a>(pub &'a [u8]);
+
+impl fmt::Display for Lossy<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() { write_dim_replacement(f)?; }
+        }
+
+        Ok(())
+    }
+}
+
+/// Displays a path without `{:?}` quoting; see `Lossy` for non-UTF-8 parts.
+pub struct P<'a>(pub &'a std::path::Path);
+
+impl fmt::Display for P<'_> {
+    #[cfg(unix)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use std::os::unix::ffi::OsStrExt;
+        Lossy(self.0.as_os_str().as_bytes()).fmt(f)
+    }
+
+    #[cfg(not(unix))]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.to_str() {
+            Some(path) => f.write_str(path),
+            None => {
+                for (i, part) in self.0.to_string_lossy().split('\u{FFFD}').enumerate() {
+                    if i > 0 { write_dim_replacement(f)?; }
+                    f.write_str(part)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A record recovered from a line of this crate's pretty output.
+#[cfg(feature = "timestamps")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParsedRecord {
+    pub level: Level,
+    /// Process tag printed after the label, if any
+    pub process: Option<String>,
+    /// Thread name printed in brackets before the timestamp, if any
+    pub thread: Option<String>,
+    /// Timestamp as printed, e.g. `2024.05.01 12:00:00.123`, with its
+    /// offset (`Z`, `+02:00`) when `set_timezone` was called. Recognized in
+    /// the current timestamp format and the standard one; lines without a
+    /// timestamp are not parsed
+    pub timestamp: String,
+    /// Module path or `file:line` after the timestamp; recognized unless
+    /// the current location mode is `None`
+    pub target: Option<String>,
+    /// Message with all ANSI sequences removed
+    pub message: String,
+}
+
+#[cfg(feature = "timestamps")]
+impl ParsedRecord {
+    /// Prints the record again with the current theme, keeping its original timestamp.
+    pub fn emit(&self) {
+        if is_enabled(self.level) {
+            print_line(Record {
+                process: self.process.as_deref(), thread: self.thread.as_deref(), timestamp: Some(&self.timestamp),
+                target: self.target.as_deref(),
+                ..Record::new(self.level, &self.message)
+            }, true);
+        }
+    }
+}
+
+fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x1b' => {
+                if chars.peek() == Some(&'[') {
+                    chars.next();
+                    // CSI: parameter and intermediate bytes, then one final byte in '@'..='~'
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) { break; }
+                    }
+                }
+            }
+            '\x07' => {}
+            _ => out.push(ch),
+        }
+    }
+
+    out
+}
+
+/// Terminal cells taken by `text`: escape sequences take none, combining
+/// marks and zero-width characters none, East Asian wide characters and
+/// emoji two.
+fn display_width(text: &str) -> usize {
+    strip_ansi(text).chars().map(char_width).sum()
+}
+
+fn char_width(ch: char) -> usize {
+    match u32::from(ch) {
+        0..=0x1f | 0x7f..=0x9f => 0,
+        0x300..=0x36f | 0x483..=0x489 | 0x591..=0x5bd | 0x610..=0x61a | 0x64b..=0x65f | 0x1ab0..=0x1aff
+        | 0x1dc0..=0x1dff | 0x200b..=0x200f | 0x2028..=0x202e | 0x2060..=0x2064 | 0x20d0..=0x20ff | 0xfe00..=0xfe0f
+        | 0xfe20..=0xfe2f | 0xfeff | 0xe0100..=0xe01ef => 0,
+        0x1100..=0x115f | 0x231a..=0x231b | 0x2329..=0x232a | 0x23e9..=0x23ec | 0x23f0 | 0x23f3 | 0x25fd..=0x25fe
+        | 0x2614..=0x2615 | 0x2648..=0x2653 | 0x267f | 0x2693 | 0x26a1 | 0x26aa..=0x26ab | 0x26bd..=0x26be
+        | 0x26c4..=0x26c5 | 0x26ce | 0x26d4 | 0x26ea | 0x26f2..=0x26f3 | 0x26f5 | 0x26fa | 0x26fd | 0x2705
+        | 0x270a..=0x270b | 0x2728 | 0x274c | 0x274e | 0x2753..=0x2755 | 0x2757 | 0x2795..=0x2797 | 0x27b0 | 0x27bf
+        | 0x2b1b..=0x2b1c | 0x2b50 | 0x2b55 | 0x2e80..=0x303e | 0x3041..=0x33ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf | 0xa960..=0xa97f | 0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xfe10..=0xfe19 | 0xfe30..=0xfe6f
+        | 0xff00..=0xff60 | 0xffe0..=0xffe6 | 0x16fe0..=0x16fe4 | 0x17000..=0x18cff | 0x1b000..=0x1b2ff
+        | 0x1f004 | 0x1f0cf | 0x1f18e | 0x1f191..=0x1f19a | 0x1f200..=0x1f251 | 0x1f300..=0x1f64f | 0x1f680..=0x1f6ff
+        | 0x1f7e0..=0x1f7eb | 0x1f90c..=0x1f9ff | 0x1fa70..=0x1faff | 0x20000..=0x2fffd | 0x30000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// A timestamp in the current format or the standard one, with or without
+/// an offset.
+#[cfg(feature = "timestamps")]
+fn is_timestamp(s: &str) -> bool {
+    TIMESTAMP_FORMAT.read().unwrap().matches(s) || TimestampFormat::Standard.matches(s)
+}
+
+/// Splits the text before the timestamp into the level and the optional process tag.
+#[cfg(feature = "timestamps")]
+fn level_from_label(prefix: &str) -> Option<(Level, Option<String>)> {
+    let current = THEME.read().unwrap().clone();
+
+    for theme in [current, Theme::default(), Theme::colorblind(), Theme::high_contrast()] {
+        for level in [Level::Debug, Level::Info, Level::Warn, Level::Error] {
+            let label = theme.style(level).label.trim();
+            // Colored lines pad the label with spaces, plain ones put it in brackets.
+            let bracketed = prefix.strip_prefix('[').and_then(|p| p.strip_prefix(label)).and_then(|p| p.strip_prefix(']'));
+            let Some(rest) = bracketed.or_else(|| prefix.strip_prefix(label)) else { continue };
+
+            if rest.is_empty() { return Some((level, None)); }
+            if rest.starts_with(' ') { return Some((level, Some(rest.trim().to_string()))); }
+        }
+    }
+
+    None
+}
+
+/// Splits a trailing `[thread]` off the text before the timestamp. A plain
+/// `[LBL]` label alone is not a thread.
+#[cfg(feature = "timestamps")]
+fn split_thread(prefix: &str) -> (&str, Option<&str>) {
+    let Some(open) = prefix.strip_suffix(']').and_then(|p| p.rfind('[')) else { return (prefix, None) };
+    if !prefix[..open].ends_with(' ') { return (prefix, None); }
+    (prefix[..open].trim_end(), Some(&prefix[open + 1..prefix.len() - 1]))
+}
+
+/// Splits a leading `target: ` off the message. Skipped when locations are
+/// not printed, so a message like `error: ...` stays whole.
+#[cfg(feature = "timestamps")]
+fn split_target(message: &str) -> (Option<&str>, &str) {
+    if location_mode() == LocationMode::None { return (None, message); }
+
+    let Some((target, rest)) = message.split_once(": ") else { return (None, message) };
+    let is_path = !target.is_empty() && target.chars().all(|c| c.is_alphanumeric() || "_:./\\-".contains(c));
+    if is_path { (Some(target), rest) } else { (None, message) }
+}
+
+/// Parses one line of this crate's pretty output, colored or not.
+/// Returns `None` for lines that were not produced by the logger.
+#[cfg(feature = "timestamps")]
+pub fn parse_pretty_line(line: &str) -> Option<ParsedRecord> {
+    let plain = strip_ansi(line.trim_end_matches(['\r', '\n']));
+
+    let mut search_from = 0;
+    while let Some(rel) = plain[search_from..].find('[') {
+        let open = search_from + rel;
+        let ts_end = plain[open..].find(']').map_or(open, |close| open + close);
+
+        if ts_end > open && is_timestamp(&plain[open + 1..ts_end]) {
+            let (prefix, thread) = split_thread(plain[..open].trim());
+            let (level, process) = level_from_label(prefix)?;
+            let rest = &plain[ts_end + 1..];
+            let message = rest.strip_prefix(' ').unwrap_or(rest);
+            let message = message.strip_suffix(' ').unwrap_or(message);
+            let (target, message) = split_target(message);
+
+            return Some(ParsedRecord {
+                level,
+                process,
+                thread: thread.map(str::to_string),
+                timestamp: plain[open + 1..ts_end].to_string(),
+                target: target.map(str::to_string),
+                message: message.to_string(),
+            });
+        }
+
+        search_from = open + 1;
+    }
+
+    None
+}
+
+/// A signal that can trigger `dump_on_signal`.
+#[cfg(all(unix, feature = "signals"))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Signal {
+    Quit,
+    Usr1,
+    Usr2,
+}
+
+#[cfg(all(unix, feature = "signals"))]
+impl Signal {
+    /// `"SIGQUIT"`, `"quit"`, `"SIGUSR1"`, ... case-insensitively.
+    pub fn parse(name: &str) -> Option<Signal> {
+        let name = name.trim().to_ascii_uppercase();
+        match name.strip_prefix("SIG").unwrap_or(&name) {
+            "QUIT" => Some(Signal::Quit),
+            "USR1" => Some(Signal::Usr1),
+            "USR2" => Some(Signal::Usr2),
+            _ => None,
+        }
+    }
+
+    fn number(self) -> std::ffi::c_int {
+        match self {
+            Signal::Quit => 3,
+            #[cfg(target_os = "linux")]
+            Signal::Usr1 => 10,
+            #[cfg(target_os = "linux")]
+            Signal::Usr2 => 12,
+            #[cfg(not(target_os = "linux"))]
+            Signal::Usr1 => 30,
+            #[cfg(not(target_os = "linux"))]
+            Signal::Usr2 => 31,
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "signals"))]
+mod dump_signal {
+    use std::ffi::c_int;
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::unix::io::FromRawFd;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::OnceLock;
+
+    use super::Signal;
+
+    extern "C" {
+        fn pipe(fds: *mut c_int) -> c_int;
+        fn write(fd: c_int, buf: *const std::ffi::c_void, count: usize) -> isize;
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    const SIG_ERR: usize = usize::MAX;
+
+    /// Write end of the pipe the handler pokes; -1 until set up.
+    static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+    static THREAD: OnceLock<Result<(), io::ErrorKind>> = OnceLock::new();
+
+    /// Only async-signal-safe work here: one `write` to the pipe.
+    extern "C" fn on_signal(_: c_int) {
+        let fd = PIPE_WRITE.load(Ordering::Relaxed);
+        // SAFETY: writing one byte from a static buffer; errors are ignored.
+        if fd >= 0 { unsafe { write(fd, b"d".as_ptr().cast(), 1); } }
+    }
+
+    /// Starts the thread that waits on the pipe and runs `on_poke` once per
+    /// signal, then points the signal at the handler.
+    pub(super) fn install(signal_kind: Signal, on_poke: fn()) -> io::Result<()> {
+        let started = THREAD.get_or_init(|| {
+            let mut fds = [0; 2];
+            // SAFETY: `fds` has room for the two descriptors.
+            if unsafe { pipe(fds.as_mut_ptr()) } != 0 { return Err(io::Error::last_os_error().kind()); }
+            // SAFETY: the read end is ours alone from here on.
+            let mut read_end = unsafe { File::from_raw_fd(fds[0]) };
+            PIPE_WRITE.store(fds[1], Ordering::Relaxed);
+
+            std::thread::Builder::new()
+                .name(String::from("rust_logger-dump"))
+                .spawn(move || {
+                    let mut byte = [0];
+                    while read_end.read_exact(&mut byte).is_ok() { on_poke(); }
+                })
+                .map(|_| ())
+                .map_err(|e| e.kind())
+        });
+        started.map_err(io::Error::from)?;
+
+        // SAFETY: `on_signal` only calls `write`, which is async-signal-safe.
+        if unsafe { signal(signal_kind.number(), on_signal) } == SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, feature = "signals"))]
+static RECORDS_BY_LEVEL: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+#[cfg(all(unix, feature = "signals"))]
+static LOGGING_THREADS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[cfg(all(unix, feature = "signals"))]
+thread_local! {
+    static THREAD_LISTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Counts the record for the dump and lists its thread the first time.
+#[cfg(all(unix, feature = "signals"))]
+fn note_for_dump(level: Level) {
+    if let Some(count) = RECORDS_BY_LEVEL.get(level as usize) { count.fetch_add(1, Ordering::Relaxed); }
+    if THREAD_LISTED.try_with(|listed| listed.replace(true)) == Ok(false) {
+        if let Some(label) = thread_label() { LOGGING_THREADS.lock().unwrap().push(label.to_string()); }
+    }
+}
+
+/// On `signal`, writes a dump of the configuration report, records per
+/// level, the threads that have logged and the last records of the black
+/// box to
+/// `<temp dir>/rust_logger-dump-<pid>-<unix seconds>.txt`, and logs the
+/// same lines at Error. The handler only wakes a helper thread, which does
+/// the work; the level and other settings stay as they are. `LOG_DUMP_ON`
+/// (e.g. `SIGQUIT`) does the same from `init`.
+#[cfg(all(unix, feature = "signals"))]
+pub fn dump_on_signal(signal: Signal) -> io::Result<()> {
+    dump_signal::install(signal, write_dump)?;
+    note_config("dump_on", ConfigSource::Api("dump_on_signal"), format_args!("{signal:?}"));
+    Ok(())
+}
+
+#[cfg(all(unix, feature = "signals"))]
+fn dump_on_signal_from_env() {
+    let Ok(value) = std::env::var("LOG_DUMP_ON") else { return };
+    let installed = match Signal::parse(&value) {
+        Some(signal) => dump_signal::install(signal, write_dump).map_err(|e| e.to_string()),
+        None => Err(String::from("expected SIGQUIT, SIGUSR1 or SIGUSR2")),
+    };
+    match installed {
+        Ok(()) => note_config("dump_on", ConfigSource::Env("LOG_DUMP_ON"), value),
+        Err(e) => {
+            note_config("dump_on", ConfigSource::Ignored("LOG_DUMP_ON"), format_args!("{value} ({e})"));
+            self_log(Level::Warn, format_args!("ignoring LOG_DUMP_ON: {e}"));
+        }
+    }
+}
+
+/// The text `dump_on_signal` writes.
+#[cfg(all(unix, feature = "signals"))]
+fn dump_report(unix_secs: u64) -> String {
+    let mut dump = format!("rust_logger dump of process {} at unix time {unix_secs}\n\n", std::process::id());
+    dump.push_str(&config_report());
+
+    dump.push_str("\nrecords by level:\n");
+    for (name, count) in ["debug", "info", "warn", "error"].iter().zip(&RECORDS_BY_LEVEL) {
+        dump.push_str(&format!("  {name} = {}\n", count.load(Ordering::Relaxed)));
+    }
+    dump.push_str(&format!("  dropped = {}\n  throttled = {}\n", dropped_records(), throttled_records()));
+
+    dump.push_str("\nthreads that have logged:\n");
+    for thread in LOGGING_THREADS.lock().unwrap().iter() { dump.push_str(&format!("  {thread}\n")); }
+
+    // Only the black box keeps records once they are written.
+    dump.push_str("\nrecent records:\n");
+    match BLACK_BOX.get().map(|black_box| read_black_box(&black_box.path)) {
+        Some(Ok(records)) => {
+            for record in &records[records.len().saturating_sub(DUMP_RECENT_RECORDS)..] { dump.push_str(&format!("  {record}\n")); }
+        }
+        Some(Err(e)) => dump.push_str(&format!("  (the black box could not be read: {e})\n")),
+        None => dump.push_str("  (none kept; file::start_black_box keeps them)\n"),
+    }
+    dump
+}
+
+/// How many black box records the dump ends with.
+#[cfg(all(unix, feature = "signals"))]
+const DUMP_RECENT_RECORDS: usize = 20;
+
+#[cfg(all(unix, feature = "signals"))]
+fn write_dump() {
+    let unix_secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let dump = dump_report(unix_secs);
+    let path = std::env::temp_dir().join(format!("rust_logger-dump-{}-{unix_secs}.txt", std::process::id()));
+
+    let location = Location { module: module_path!(), file: file!(), line: line!() };
+    for line in dump.lines().filter(|line| !line.is_empty()) {
+        print_with_prefix(Level::Error, location, format_args!("{line}"));
+    }
+    match fs::write(&path, &dump) {
+        Ok(()) => print_with_prefix(Level::Error, location, format_args!("dump written to {}", P(&path))),
+        Err(e) => self_log(Level::Error, format_args!("could not write the dump to {}: {e}", P(&path))),
+    }
+}
+
+/// Where a configuration value came from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ConfigSource {
+    Api(&'static str),
+    Env(&'static str),
+    /// Present in the environment but not usable
+    Ignored(&'static str),
+}
+
+impl ConfigSource {
+    fn rank(self) -> u8 {
+        match self {
+            ConfigSource::Ignored(_) => 0,
+            ConfigSource::Env(_) => 1,
+            ConfigSource::Api(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Api(name) => write!(f, "{name}()"),
+            ConfigSource::Env(name) => write!(f, "env {name}"),
+            ConfigSource::Ignored(name) => write!(f, "env {name}, ignored"),
+        }
+    }
+}
+
+struct Applied {
+    setting: &'static str,
+    source: ConfigSource,
+    value: String,
+}
+
+static APPLIED: Mutex<Vec<Applied>> = Mutex::new(Vec::new());
+
+/// Records that `setting` was set to `value` by `source`, for `config_report`.
+/// A source setting it again replaces its earlier entry, so the
+/// list stays bounded however often a setting changes.
+pub(crate) fn note_config(setting: &'static str, source: ConfigSource, value: impl fmt::Display) {
+    let mut applied = APPLIED.lock().unwrap();
+    // Moved to the end: within one rank the latest call wins.
+    applied.retain(|a| a.setting != setting || a.source != source);
+    applied.push(Applied { setting, source, value: value.to_string() });
+}
+
+/// Every setting with its default, in report order.
+const SETTINGS: [(&str, &str); 32] = [
+    ("level", ""),
+    ("module_levels", "(none)"),
+    ("theme", "default"),
+    ("bell", "Off"),
+    ("bell_style", "Audible"),
+    ("bell_cooldown", "2s"),
+    ("process_tag", "(none)"),
+    ("console_nonblocking", "false"),
+    ("max_records_per_second", "0"),
+    ("exempt_errors", "false"),
+    ("suppressed_accounting", "false"),
+    ("suppressed_summary_interval", "None"),
+    ("stable_test_format", "false"),
+    ("self_log", "Console"),
+    ("charset", "Unicode"),
+    ("audit_bypasses_level", "true"),
+    ("writer", "stdout"),
+    ("format", "Pretty"),
+    ("color", "Auto"),
+    ("file_sinks", "(none)"),
+    ("sinks", "(none)"),
+    ("catch_format_panics", "false"),
+    ("slow_call_budget", "None"),
+    ("location", "Module"),
+    ("multiline", "Align"),
+    ("group_indent", "Spaces"),
+    ("prefix_template", DEFAULT_PREFIX_TEMPLATE),
+    ("show_thread", "false"),
+    ("dump_on", "(none)"),
+    ("timezone", "Utc"),
+    ("timestamp_format", "Standard"),
+    ("black_box", "(none)"),
+];
+
+/// Effective value and source of every setting. Values that were applied but
+/// lost to a higher-precedence source are listed underneath as overridden.
+/// Precedence: API calls, then environment variables, then defaults;
+/// `LOG_BELL=0` beats `set_bell`.
+pub fn config_report() -> String {
+    // Environment-backed settings are read lazily; force them so they show up.
+    LazyLock::force(&THEME);
+    LazyLock::force(&PROCESS_TAG);
+    LazyLock::force(&BELL_ENV_DISABLED);
+    LazyLock::force(&STABLE_FORMAT);
+    LazyLock::force(&PROCESS_COLOR_ENV);
+    glyphs();
+
+    let applied = APPLIED.lock().unwrap();
+    let mut report = String::from("rust_logger configuration:\n");
+
+    for (setting, default) in SETTINGS {
+        let (default, default_source) = if setting == "level" {
+            (format!("{:?}", build_level()), format!("build-time LOG_LEVEL, {BUILD_LOG_LEVEL_SOURCE}"))
+        } else {
+            (default.to_string(), String::from("default"))
+        };
+
+        let mut entries: Vec<(usize, &Applied)> = applied.iter().enumerate().filter(|(_, a)| a.setting == setting).collect();
+        // Highest precedence first; within one source the latest call wins.
+        entries.sort_by(|(ia, a), (ib, b)| {
+            let rank = |a: &Applied| if a.setting == "bell" && a.source == ConfigSource::Env("LOG_BELL") { 3 } else { a.source.rank() };
+            rank(b).cmp(&rank(a)).then(ib.cmp(ia))
+        });
+
+        let winner = entries.iter().position(|(_, a)| a.source.rank() > 0);
+        match winner {
+            Some(i) => {
+                let (_, a) = entries[i];
+                report.push_str(&format!("  {setting} = {}  [{}]\n", a.value, a.source));
+            }
+            None => report.push_str(&format!("  {setting} = {default}  [{default_source}]\n")),
+        }
+
+        for (i, (_, a)) in entries.iter().enumerate() {
+            if Some(i) != winner {
+                report.push_str(&format!("      overridden: {}  [{}]\n", a.value, a.source));
+            }
+        }
+        if winner.is_some() {
+            report.push_str(&format!("      overridden: {default}  [{default_source}]\n"));
+        }
+    }
+
+    report
+}
+
+/// Logs `config_report` at Debug, one record per line.
+pub fn dump_config(location: Location) {
+    if !record_enabled(Level::Debug, location.module) { return; }
+
+    for line in config_report().lines() {
+        print_with_prefix(Level::Debug, location, format_args!("{line}"));
+    }
+}
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+static LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Why `Builder::init` refused to apply a configuration.
+#[derive(Debug)]
+pub enum InitError {
+    /// `init` already ran once.
+    AlreadyInitialized,
+    /// A record was printed before `init`, with the old configuration.
+    AlreadyLogging,
+    /// The console could not be switched to non-blocking mode.
+    Console(io::Error),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::AlreadyInitialized => f.write_str("the logger was already initialized"),
+            InitError::AlreadyLogging => f.write_str("records were logged before the logger was initialized"),
+            InitError::Console(e) => write!(f, "cannot make the console non-blocking: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for InitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InitError::Console(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Collects the whole configuration and applies it at once in `init`. Options
+/// left unset keep their current value, including values from the
+/// environment (`LOG_THEME`, `LOG_BELL`, `LOG_FORMAT`, `LOG_PROCESS_TAG`,
+/// `LOG_STABLE_FORMAT`), which are read when first needed. Without `level`,
+/// the build-time `LOG_LEVEL` stays in effect.
+#[derive(Default)]
+#[must_use = "nothing is applied until `init` is called"]
+pub struct Builder {
+    level: Option<Level>,
+    module_levels: Vec<(String, Level)>,
+    format: Option<Format>,
+    theme: Option<Theme>,
+    bell: Option<BellMode>,
+    bell_style: Option<BellStyle>,
+    bell_cooldown: Option<Duration>,
+    process_tag: Option<String>,
+    console_nonblocking: Option<bool>,
+    max_records_per_second: Option<u32>,
+    exempt_errors: Option<bool>,
+    suppressed_accounting: Option<bool>,
+    suppressed_summary_interval: Option<Option<Duration>>,
+    stable_test_format: Option<bool>,
+    self_log: Option<SelfLog>,
+    charset: Option<Charset>,
+    color_choice: Option<ColorChoice>,
+    location_mode: Option<LocationMode>,
+    multiline: Option<Multiline>,
+    group_indent: Option<GroupIndent>,
+    show_thread: Option<bool>,
+    #[cfg(feature = "timestamps")]
+    timezone: Option<TimeZoneMode>,
+    #[cfg(feature = "timestamps")]
+    timestamp_format: Option<TimestampFormat>,
+    draw_guard: Option<Arc<dyn DrawCoordinator>>,
+    writer: Option<Box<dyn Write + Send>>,
+    force: bool,
+}
+
+pub fn builder() -> Builder {
+    Builder::default()
+}
+
+impl Builder {
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// See `set_module_level`; may be called for several modules.
+    pub fn module_level(mut self, module: &str, level: Level) -> Self {
+        self.module_levels.push((module.to_string(), level));
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    pub fn bell(mut self, mode: BellMode) -> Self {
+        self.bell = Some(mode);
+        self
+    }
+
+    pub fn bell_style(mut self, style: BellStyle) -> Self {
+        self.bell_style = Some(style);
+        self
+    }
+
+    pub fn bell_cooldown(mut self, cooldown: Duration) -> Self {
+        self.bell_cooldown = Some(cooldown);
+        self
+    }
+
+    pub fn process_tag(mut self, tag: &str) -> Self {
+        self.process_tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn console_nonblocking(mut self, enabled: bool) -> Self {
+        self.console_nonblocking = Some(enabled);
+        self
+    }
+
+    pub fn max_records_per_second(mut self, max: u32) -> Self {
+        self.max_records_per_second = Some(max);
+        self
+    }
+
+    pub fn exempt_errors(mut self, exempt: bool) -> Self {
+        self.exempt_errors = Some(exempt);
+        self
+    }
+
+    pub fn suppressed_accounting(mut self, enabled: bool) -> Self {
+        self.suppressed_accounting = Some(enabled);
+        self
+    }
+
+    pub fn suppressed_summary_interval(mut self, interval: Option<Duration>) -> Self {
+        self.suppressed_summary_interval = Some(interval);
+        self
+    }
+
+    pub fn stable_test_format(mut self, enabled: bool) -> Self {
+        self.stable_test_format = Some(enabled);
+        self
+    }
+
+    pub fn self_log(mut self, mode: SelfLog) -> Self {
+        self.self_log = Some(mode);
+        self
+    }
+
+    pub fn charset(mut self, charset: Charset) -> Self {
+        self.charset = Some(charset);
+        self
+    }
+
+    pub fn color_choice(mut self, choice: ColorChoice) -> Self {
+        self.color_choice = Some(choice);
+        self
+    }
+
+    pub fn location_mode(mut self, mode: LocationMode) -> Self {
+        self.location_mode = Some(mode);
+        self
+    }
+
+    pub fn multiline(mut self, mode: Multiline) -> Self {
+        self.multiline = Some(mode);
+        self
+    }
+
+    pub fn group_indent(mut self, indent: GroupIndent) -> Self {
+        self.group_indent = Some(indent);
+        self
+    }
+
+    pub fn show_thread(mut self, show: bool) -> Self {
+        self.show_thread = Some(show);
+        self
+    }
+
+    #[cfg(feature = "timestamps")]
+    pub fn timezone(mut self, mode: TimeZoneMode) -> Self {
+        self.timezone = Some(mode);
+        self
+    }
+
+    #[cfg(feature = "timestamps")]
+    pub fn timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = Some(format);
+        self
+    }
+
+    pub fn draw_guard(mut self, coordinator: Arc<dyn DrawCoordinator>) -> Self {
+        self.draw_guard = Some(coordinator);
+        self
+    }
+
+    pub fn writer(mut self, writer: Box<dyn Write + Send>) -> Self {
+        self.writer = Some(writer);
+        self
+    }
+
+    /// Applies the configuration even if `init` already ran or records were
+    /// already printed.
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    /// Applies every option that was set. Fails without changing anything if
+    /// the logger was already initialized or has already printed a record,
+    /// unless `force` was called. Keep the returned guard alive until the end
+    /// of `main` so output is flushed on exit.
+    pub fn init(self) -> Result<ShutdownGuard, InitError> {
+        if !self.force {
+            if INITIALIZED.load(Ordering::Acquire) { return Err(InitError::AlreadyInitialized); }
+            if LOGGED.load(Ordering::Relaxed) { return Err(InitError::AlreadyLogging); }
+        }
+
+        // Claimed first, so of two racing calls only one touches the console.
+        let was_initialized = INITIALIZED.swap(true, Ordering::AcqRel);
+        if was_initialized && !self.force {
+            return Err(InitError::AlreadyInitialized);
+        }
+
+        // The only option that can fail goes next; an error hands the claim
+        // back and leaves everything else untouched.
+        if let Some(enabled) = self.console_nonblocking {
+            if let Err(e) = set_console_nonblocking(enabled) {
+                INITIALIZED.store(was_initialized, Ordering::Release);
+                return Err(InitError::Console(e));
+            }
+        }
+
+        if let Some(level) = self.level { set_level(level); }
+        for (module, level) in &self.module_levels { set_module_level(module, *level); }
+        if let Some(format) = self.format { set_format(format); }
+        if let Some(theme) = self.theme { set_theme(theme); }
+        if let Some(mode) = self.bell { set_bell(mode); }
+        if let Some(style) = self.bell_style { set_bell_style(style); }
+        if let Some(cooldown) = self.bell_cooldown { set_bell_cooldown(cooldown); }
+        if let Some(tag) = &self.process_tag { set_process_tag(tag); }
+        if let Some(max) = self.max_records_per_second { set_max_records_per_second(max); }
+        if let Some(exempt) = self.exempt_errors { exempt_errors(exempt); }
+        if let Some(enabled) = self.suppressed_accounting { set_suppressed_accounting(enabled); }
+        if let Some(interval) = self.suppressed_summary_interval { set_suppressed_summary_interval(interval); }
+        if let Some(enabled) = self.stable_test_format { set_stable_test_format(enabled); }
+        if let Some(mode) = self.self_log { set_self_log(mode); }
+        if let Some(charset) = self.charset { set_charset(charset); }
+        if let Some(choice) = self.color_choice { set_color_choice(choice); }
+        if let Some(mode) = self.location_mode { set_location_mode(mode); }
+        if let Some(mode) = self.multiline { set_multiline(mode); }
+        if let Some(indent) = self.group_indent { set_group_indent(indent); }
+        if let Some(show) = self.show_thread { show_thread(show); }
+        #[cfg(feature = "timestamps")]
+        if let Some(mode) = self.timezone { set_timezone(mode); }
+        #[cfg(feature = "timestamps")]
+        if let Some(format) = self.timestamp_format { set_timestamp_format(format); }
+        if let Some(coordinator) = self.draw_guard { set_external_draw_guard(coordinator); }
+        if let Some(writer) = self.writer { set_writer(writer); }
+        // Without `.format(..)`, reads `LOG_FORMAT` now, so a bad value is
+        // reported at startup.
+        let _ = output_format();
+        #[cfg(all(unix, feature = "signals"))]
+        dump_on_signal_from_env();
+
+        Ok(ShutdownGuard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_config_keeps_one_entry_per_setting_and_source() {
+        for i in 0..1000 {
+            note_config("test_setting", ConfigSource::Api("set_test"), i);
+            note_config("test_setting", ConfigSource::Env("LOG_TEST"), i);
+        }
+        note_config("test_setting", ConfigSource::Api("set_test"), "last");
+
+        let applied = APPLIED.lock().unwrap();
+        let entries: Vec<(ConfigSource, &str)> =
+            applied.iter().filter(|a| a.setting == "test_setting").map(|a| (a.source, a.value.as_str())).collect();
+        assert_eq!(entries, [(ConfigSource::Env("LOG_TEST"), "999"), (ConfigSource::Api("set_test"), "last")]);
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn the_shutdown_path_does_not_wait_for_a_held_timestamp_lock() {
+        let held = TIMESTAMP_FORMAT.write().unwrap();
+        let zone = TIMEZONE.write().unwrap();
+        // The read locks would deadlock on this thread.
+        let ts = plain_stderr_timestamp().unwrap();
+        drop((held, zone));
+        assert!(ts == "TS" || (ts.len() == 23 && ts.as_bytes()[4] == b'.'), "{ts}");
+    }
+
+    struct ManualClock(Cell<Instant>);
+
+    impl ManualClock {
+        fn advance(&self, by: Duration) {
+            self.0.set(self.0.get() + by);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn heartbeats_tick_once_per_interval_on_a_manual_clock() {
+        let clock = ManualClock(Cell::new(Instant::now()));
+        let started = clock.now();
+        let location = Location { module: "unit", file: "unit.rs", line: 1 };
+        let interval = Duration::from_secs(10);
+        let mut heartbeats = vec![HeartbeatEntry {
+            id: 1, level: Level::Error, location, interval, message: String::from("compacting"), started, next: started + interval,
+        }];
+        let mut tick_after = |by: u64| {
+            clock.advance(Duration::from_secs(by));
+            due_ticks(&mut heartbeats, clock.now()).into_iter().map(|tick| tick.line).collect::<Vec<_>>()
+        };
+
+        assert!(tick_after(5).is_empty());
+        assert_eq!(tick_after(5), ["compacting (still running, 10.0s)"]);
+        // Two intervals slept through give one tick, then the schedule resumes.
+        assert_eq!(tick_after(25), ["compacting (still running, 35.0s)"]);
+        assert!(tick_after(4).is_empty());
+        assert_eq!(tick_after(1), ["compacting (still running, 40.0s)"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn black_box_head_ends_at_the_last_record_under_contention() {
+        let path = std::env::temp_dir().join(format!("rust_logger-unit-bbox-{}", std::process::id()));
+        let capacity = 1 << 20;
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        file.set_len(BLACK_BOX_HEADER_LEN + capacity).unwrap();
+        let mut header = BLACK_BOX_MAGIC.to_vec();
+        header.extend_from_slice(&capacity.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+        std::os::unix::fs::FileExt::write_all_at(&file, &header, 0).unwrap();
+        let black_box = BlackBox {
+            file, path: path.clone(), capacity, reserved: AtomicU64::new(0), head: AtomicU64::new(0), failed: AtomicBool::new(false),
+        };
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let black_box = &black_box;
+                scope.spawn(move || {
+                    for i in 0..500 { black_box.record(&format!("thread {thread} record {i}")); }
+                });
+            }
+        });
+
+        let records = read_black_box(&path).unwrap();
+        let header = fs::read(&path).unwrap()[16..24].to_vec();
+        let _ = fs::remove_file(&path);
+        let reserved = black_box.reserved.load(Ordering::Relaxed);
+        assert_eq!(black_box.head.load(Ordering::Relaxed), reserved);
+        assert_eq!(header, reserved.to_le_bytes());
+        assert_eq!(records.len(), 8 * 500);
+    }
+
+    #[test]
+    fn bell_modes_pick_their_levels() {
+        let levels = [Level::Debug, Level::Info, Level::Warn, Level::Error];
+        let wanted = |mode| levels.map(|level| bell_wanted(mode, level));
+        assert_eq!(wanted(BellMode::Off), [false, false, false, false]);
+        assert_eq!(wanted(BellMode::OnError), [false, false, false, true]);
+        assert_eq!(wanted(BellMode::OnWarnAndError), [false, false, true, true]);
+    }
+
+    #[test]
+    fn bell_bytes_per_style_only_on_a_terminal() {
+        let now = Instant::now();
+        let mut bell = BellState::new();
+        assert_eq!(ring_bell(&mut bell, false, now), "");
+        // Not ringing off a terminal does not start the cooldown.
+        assert!(bell.last.is_none());
+        assert_eq!(ring_bell(&mut bell, true, now), "\x07");
+
+        let mut bell = BellState { style: BellStyle::Visual, ..BellState::new() };
+        assert_eq!(ring_bell(&mut bell, false, now), "");
+        assert_eq!(ring_bell(&mut bell, true, now), "\x1b[?5h\x1b[?5l");
+    }
+
+    #[test]
+    fn bell_stays_silent_inside_the_cooldown() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut bell = BellState { cooldown: Duration::from_millis(500), ..BellState::new() };
+
+        assert_eq!(ring_bell(&mut bell, true, at(0)), "\x07");
+        assert_eq!(ring_bell(&mut bell, true, at(499)), "");
+        // A silent record does not push the window back.
+        assert_eq!(ring_bell(&mut bell, true, at(500)), "\x07");
+        assert_eq!(ring_bell(&mut bell, true, at(900)), "");
+        assert_eq!(ring_bell(&mut bell, true, at(1000)), "\x07");
+    }
+
+    #[test]
+    fn bell_mode_round_trips_through_the_atomic() {
+        for mode in [BellMode::OnError, BellMode::OnWarnAndError, BellMode::Off] {
+            set_bell(mode);
+            assert_eq!(bell_mode(), mode);
+        }
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn civil_dates_across_known_days() {
+        // (days since the epoch, date, weekday, day of the year), from Python's datetime.
+        let known = [
+            (0, (1970, 1, 1), "Thu", 1),
+            (-1, (1969, 12, 31), "Wed", 365),
+            (789, (1972, 2, 29), "Tue", 60),
+            (-25_509, (1900, 2, 28), "Wed", 59),
+            (-25_508, (1900, 3, 1), "Thu", 60),
+            (11_016, (2000, 2, 29), "Tue", 60),
+            (11_017, (2000, 3, 1), "Wed", 61),
+            (11_322, (2000, 12, 31), "Sun", 366),
+            (47_541, (2100, 3, 1), "Mon", 60),
+            (-135_081, (1600, 2, 29), "Tue", 60),
+            (20_088, (2024, 12, 31), "Tue", 366),
+            (-329_893, (1066, 10, 14), "Sun", 287),
+        ];
+        for (days, ymd, weekday, ordinal) in known {
+            let date = CivilDate::from_days(days);
+            assert_eq!((date.year, date.month, date.day), ymd, "{days}");
+            assert_eq!(date.weekday_abbr(), weekday, "{ymd:?}");
+            assert_eq!(date.ordinal(), ordinal, "{ymd:?}");
+        }
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn weekday_and_ordinal_tokens() {
+        let format = TimestampFormat::parse("%a %j %Y-%m-%d").unwrap();
+        let friday_noon = Duration::from_secs(20_742 * 86_400 + 12 * 3_600);
+        let rendered = format.render(friday_noon, None).unwrap();
+        assert_eq!(rendered, "Fri 289 2026-10-16");
+        assert!(format.matches(&rendered));
+        assert!(!format.matches("Fry 289 2026-10-16"));
+        assert_eq!(format.render(Duration::ZERO, None).unwrap(), "Thu 001 1970-01-01");
+        assert_eq!(TimestampFormat::Standard.render(friday_noon, None).unwrap(), "2026.10.16 12:00:00.000");
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn otel_timestamps_have_nanoseconds() {
+        let at = Duration::new(20_742 * 86_400 + 12 * 3_600, 123_456_789);
+        let rendered = RFC3339_NANOS.render(at, None).unwrap();
+        assert_eq!(rendered, "2026-10-16T12:00:00.123456789Z");
+        assert!(RFC3339_NANOS.matches(&rendered));
+        assert_eq!(RFC3339_NANOS.render(at, Some(2 * 3_600)).unwrap(), "2026-10-16T14:00:00.123456789+02:00");
+        assert_eq!(TimestampFormat::parse("%S.%9f").unwrap().render(at, None).unwrap(), "00.123456789");
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn offsets_move_timestamps_across_the_day_boundary() {
+        const DAY: u64 = 86_400;
+        const HOUR: u64 = 3_600;
+        let at = |days: u64, secs: u64, millis: u64| Duration::from_millis((days * DAY + secs) * 1_000 + millis);
+        let minus_11 = Some(-11 * 3_600);
+        let plus_14 = Some(14 * 3_600);
+        let rfc = |since_epoch, offset| TimestampFormat::Rfc3339.render(since_epoch, offset).unwrap();
+
+        // Back across a leap day, and before the epoch.
+        assert_eq!(rfc(at(19_783, 5 * HOUR, 0), minus_11), "2024-02-29T18:00:00.000-11:00");
+        assert_eq!(rfc(at(0, 3 * HOUR, 0), minus_11), "1969-12-31T16:00:00.000-11:00");
+        assert_eq!(rfc(at(19_889, 11 * HOUR, 0), minus_11), "2024-06-15T00:00:00.000-11:00");
+        assert_eq!(rfc(at(19_889, 11 * HOUR - 1, 999), minus_11), "2024-06-14T23:59:59.999-11:00");
+
+        // Forward into the next year.
+        assert_eq!(rfc(at(20_088, 10 * HOUR, 0), plus_14), "2025-01-01T00:00:00.000+14:00");
+        assert_eq!(rfc(at(20_088, 10 * HOUR - 1, 999), plus_14), "2024-12-31T23:59:59.999+14:00");
+        assert_eq!(rfc(at(20_088, 23 * HOUR, 0), plus_14), "2025-01-01T13:00:00.000+14:00");
+
+        assert_eq!(TimestampFormat::Standard.render(at(0, 0, 0), minus_11).unwrap(), "1969.12.31 13:00:00.000-11:00");
+        assert_eq!(rfc(at(0, 0, 0), None), "1970-01-01T00:00:00.000Z");
+    }
+
+    /// Counts what a file sink does to its file.
+    #[derive(Default)]
+    struct CountingFile {
+        bytes: AtomicUsize,
+        syncs: AtomicUsize,
+    }
+
+    impl LogFile for CountingFile {
+        fn write(&self, buf: &[u8]) -> io::Result<usize> {
+            self.bytes.fetch_add(buf.len(), Ordering::Relaxed);
+            Ok(buf.len())
+        }
+
+        fn sync_data(&self) -> io::Result<()> {
+            self.syncs.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    fn counting_sink(sync: SyncPolicy) -> (FileSink, Arc<CountingFile>) {
+        let file = Arc::new(CountingFile::default());
+        let sink = FileSink {
+            id: u64::MAX, path: PathBuf::from("counting.log"), policy: RotationPolicy::Never, session_header: false, sync,
+            reopen_check: None, file: BufWriter::new(FileHandle(file.clone())), file_id: None, size: 0, unsynced: false,
+            synced_at: Instant::now(), checked_at: Instant::now(), replayed_through: 0, level: None, continuation: None,
+            failed: false,
+        };
+        (sink, file)
+    }
+
+    #[test]
+    fn sync_policies_sync_after_the_right_records() {
+        let levels = [Level::Debug, Level::Info, Level::Warn, Level::Error, Level::Info, Level::Error];
+        for (policy, expected) in [(SyncPolicy::Never, 0), (SyncPolicy::OnError, 2), (SyncPolicy::EveryRecord, 6)] {
+            let (mut sink, file) = counting_sink(policy);
+            for level in levels {
+                sink.write("record\n", level).unwrap();
+            }
+            assert_eq!(file.syncs.load(Ordering::Relaxed), expected, "{policy:?}");
+            if policy != SyncPolicy::Never {
+                // Nothing is left in the buffer after the last sync.
+                assert_eq!(file.bytes.load(Ordering::Relaxed), 6 * 7, "{policy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn interval_syncs_are_due_once_per_interval_with_records() {
+        let every = Duration::from_secs(5);
+        let (sink, file) = counting_sink(SyncPolicy::Interval(every));
+        let mut sinks = [sink];
+        let start = sinks[0].synced_at;
+
+        sinks[0].write("record\n", Level::Error).unwrap();
+        assert_eq!(file.syncs.load(Ordering::Relaxed), 0, "the writer synced");
+        assert!(due_syncs(&mut sinks, start + every / 2).is_empty());
+
+        let due = due_syncs(&mut sinks, start + every);
+        assert_eq!(due.len(), 1);
+        assert_eq!(file.bytes.load(Ordering::Relaxed), 7, "not flushed before the sync");
+        due[0].1.sync_data().unwrap();
+        assert_eq!(file.syncs.load(Ordering::Relaxed), 1);
+
+        // Nothing new to sync, however long it has been.
+        assert!(due_syncs(&mut sinks, start + every * 10).is_empty());
+        // A record after a quiet spell is synced on the next tick.
+        sinks[0].write("record\n", Level::Info).unwrap();
+        assert_eq!(due_syncs(&mut sinks, start + every * 10).len(), 1);
+        assert!(due_syncs(&mut sinks, start + every * 10 + every / 2).is_empty());
+    }
+
+    /// What each alias of `defs` expands to, resolved as registering does.
+    #[cfg(all(feature = "aliases", feature = "markup"))]
+    fn expanded(defs: &[(&str, &str)]) -> HashMap<String, String> {
+        let mut aliases: HashMap<String, AliasDef> = defs
+            .iter()
+            .map(|(name, tokens)| (name.to_string(), AliasDef { tokens: tokens.to_string(), resolved: String::new() }))
+            .collect();
+        resolve_aliases(&mut aliases);
+        aliases.into_iter().map(|(name, def)| (name, def.resolved)).collect()
+    }
+
+    #[cfg(all(feature = "aliases", feature = "markup"))]
+    #[test]
+    fn aliases_expand_through_a_chain() {
+        let resolved = expanded(&[("em", "bold, underline"), ("critical", "em, red"), ("page", "critical, reverse")]);
+        assert_eq!(resolved["em"], "bold, underline");
+        assert_eq!(resolved["critical"], "bold, underline, red");
+        assert_eq!(resolved["page"], "bold, underline, red, reverse");
+
+        let mut out = Vec::new();
+        let aliases: HashMap<String, AliasDef> = [("em", "bold"), ("critical", "em, red")]
+            .map(|(name, tokens)| (name.to_string(), AliasDef { tokens: tokens.to_string(), resolved: String::new() }))
+            .into_iter()
+            .collect();
+        let mut expanding = Vec::new();
+        expand_alias("critical", &aliases, &mut expanding, &mut out);
+        assert_eq!(out, ["bold", "red"]);
+        assert!(expanding.is_empty(), "every alias entered is left again");
+    }
+
+    #[cfg(all(feature = "aliases", feature = "markup"))]
+    #[test]
+    fn a_diamond_keeps_each_style_once() {
+        let resolved = expanded(&[("base", "bold"), ("left", "base, red"), ("right", "base, underline"), ("both", "left, right")]);
+        assert_eq!(resolved["left"], "bold, red");
+        assert_eq!(resolved["right"], "bold, underline");
+        assert_eq!(resolved["both"], "bold, red, underline");
+    }
+
+    #[cfg(all(feature = "aliases", feature = "markup"))]
+    #[test]
+    fn cycles_are_cut_where_they_close() {
+        let resolved = expanded(&[("loop", "loop, bold")]);
+        assert_eq!(resolved["loop"], "bold");
+
+        let resolved = expanded(&[("ping", "pong, underline"), ("pong", "ping, red")]);
+        assert_eq!(resolved["ping"], "red, underline");
+        assert_eq!(resolved["pong"], "underline, red");
+
+        let resolved = expanded(&[("a", "b, bold"), ("b", "c, red"), ("c", "a, italic")]);
+        assert_eq!(resolved["a"], "italic, red, bold");
+        assert_eq!(resolved["c"], "red, bold, italic");
+
+        // A removed alias among the tokens is skipped.
+        assert_eq!(expanded(&[("left", "gone, red")])["left"], "red");
+    }
+
+    #[test]
+    fn elapsed_times_round_before_picking_the_unit() {
+        let ms = |ms: f64| Duration::from_secs_f64(ms / 1000.0);
+        for (elapsed, shown) in [
+            (Duration::from_micros(999), "999\u{b5}s"),
+            (Duration::from_micros(1000), "1.0ms"),
+            (ms(1.25), "1.3ms"),
+            (ms(999.94), "999.9ms"),
+            (ms(999.96), "1.0s"),
+            (Duration::from_secs(1), "1.0s"),
+            (ms(59_940.0), "59.9s"),
+            (ms(59_960.0), "1m 00s"),
+            (Duration::from_secs(60), "1m 00s"),
+            (ms(61_400.0), "1m 01s"),
+            (ms(61_600.0), "1m 02s"),
+            (ms(3_599_400.0), "59m 59s"),
+            (ms(3_599_600.0), "1h 00m"),
+            (Duration::from_secs(2 * 3600 + 59 * 60 + 59), "2h 59m"),
+        ] {
+            assert_eq!(Elapsed(elapsed).to_string(), shown, "{elapsed:?}");
+        }
+    }
+
+    #[test]
+    fn globs_match_the_whole_text() {
+        for (pattern, text, matches) in [
+            ("*", "", true),
+            ("*", "a::b", true),
+            ("a*", "abc", true),
+            ("*c", "abc", true),
+            ("a*c", "ac", true),
+            ("a*c", "abcbc", true),
+            ("a*c", "abcb", false),
+            ("a*b*b", "abb", true),
+            ("a*b*b", "ab", false),
+            ("**", "x", true),
+            ("abc", "abc", true),
+            ("abc", "abcd", false),
+            ("*.rs", "main.rs", true),
+            ("?", "a", false),
+        ] {
+            assert_eq!(glob_matches(pattern, text), matches, "{pattern} against {text}");
+        }
+    }
+
+    #[test]
+    fn module_patterns_end_at_segment_boundaries() {
+        for (pattern, target, matches) in [
+            ("a::b", "a::b", true),
+            ("a::b", "a::b::c", true),
+            ("a::b", "a::bc", false),
+            ("a::*", "a::b::c", true),
+            ("a::*", "a", false),
+            ("*::db", "x::db::pool", true),
+            ("*::db", "x::dbx", false),
+            ("*db", "x::mydb::pool", true),
+            ("a*", "ab::c", true),
+            ("*", "", true),
+        ] {
+            assert_eq!(module_matches(pattern, target), matches, "{pattern} against {target}");
+        }
+        assert!(pattern_specificity("app::*::db") == pattern_specificity("*::metrics"));
+        assert_eq!(pattern_specificity("*"), 0);
+    }
+
+    #[test]
+    fn targets_are_shortened_at_segment_boundaries() {
+        use TargetTruncation::{LastSegments, Middle};
+        let deep = "my_app::services::billing::reconciliation::worker";
+        for (target, width, strategy, shown) in [
+            ("my_app::db", 20, Middle, "my_app::db"),
+            ("my_app::db", 10, Middle, "my_app::db"),
+            ("my_app::db", 10, LastSegments, "my_app::db"),
+            (deep, 49, Middle, deep),
+            (deep, 48, Middle, "my_app::…::billing::reconciliation::worker"),
+            (deep, 20, Middle, "my_app::…::worker"),
+            (deep, 17, Middle, "my_app::…::worker"),
+            (deep, 16, Middle, "…::worker"),
+            (deep, 30, LastSegments, "…::reconciliation::worker"),
+            (deep, 24, LastSegments, "…::worker"),
+            (deep, 6, Middle, "…orker"),
+            (deep, 1, LastSegments, "…"),
+            ("my_app::db", 9, Middle, "…::db"),
+            ("src/services/billing.rs:42", 12, Middle, "…lling.rs:42"),
+            ("crate::ünïcödé::wörker", 12, Middle, "…::wörker"),
+            ("crate::ünïcödé::wörker", 4, Middle, "…ker"),
+        ] {
+            let got = shorten_target(target, width, strategy, "…");
+            assert_eq!(got, shown, "{target} in {width}, {strategy:?}");
+            assert!(got.chars().count() <= width, "{got} is wider than {width}");
+        }
+        for (width, strategy, shown) in [
+            (20, Middle, "my_app::...::worker"),
+            (18, Middle, "...::worker"),
+            (8, Middle, "...orker"),
+            (2, LastSegments, ".."),
+        ] {
+            let got = shorten_target(deep, width, strategy, "...");
+            assert_eq!(got, shown, "{deep} in {width}, {strategy:?}");
+            assert!(got.chars().count() <= width, "{got} is wider than {width}");
+        }
+    }
+
+    /// Red and yellow in the 256-color cube have more red than blue.
+    fn warm(index: u8) -> bool {
+        let (red, blue) = match index {
+            0..=15 => return matches!(index, 1 | 3 | 9 | 11),
+            16..=231 => ((index - 16) / 36, (index - 16) % 6),
+            _ => return false,
+        };
+        red > blue
+    }
+
+    #[test]
+    fn hashed_colors_stay_clear_of_the_level_colors() {
+        assert!(HASH_PALETTE.iter().all(|&index| !warm(index)), "{HASH_PALETTE:?}");
+        for n in 0..2000 {
+            let color = hash_color(&format!("app::module_{n}"));
+            assert!(HASH_PALETTE.contains(&color) && !warm(color), "{color}");
+        }
+        // Red, yellow and orange are warm.
+        assert!([1, 3, 9, 11, 160, 196, 178, 214, 220, 226].into_iter().all(warm));
+    }
+
+    #[test]
+    fn display_width_skips_escapes_and_counts_wide_characters() {
+        assert_eq!(display_width("plain"), 5);
+        assert_eq!(display_width("\x1b[1;31mred\x1b[0m"), 3);
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("e\u{301}"), 1, "a combining accent adds nothing");
+        assert_eq!(display_width("ok 🎉"), 5);
+        assert_eq!(display_width("漢\x1b[2mx\x1b[22m"), 3);
+    }
+
+    #[test]
+    fn numbers_are_told_from_other_values() {
+        for number in ["3", "-0.5", "1e9", "42.0"] {
+            assert!(is_number(number), "{number}");
+        }
+        for other in ["inf", "NaN", "-", "", "x1", "true", "\"3\""] {
+            assert!(!is_number(other), "{other}");
+        }
+    }
+
+    #[test]
+    fn interned_strings_are_shared_and_never_mixed_up() {
+        let interner = Interner::new(4096);
+        let texts: Vec<String> = (0..100).map(|n| format!("app::module_{n}")).chain(["a".into(), "ab".into(), "".into()]).collect();
+        let kept: Vec<&str> = texts.iter().map(|text| interner.get(text).unwrap()).collect();
+        for (text, kept) in texts.iter().zip(&kept) {
+            assert_eq!(text, kept);
+            assert!(std::ptr::eq(*kept, interner.get(&text.clone()).unwrap()), "the same copy every time");
+        }
+        assert!(matches!(interner.cow("a"), Cow::Borrowed(kept) if std::ptr::eq(kept, interner.get("a").unwrap())));
+    }
+
+    #[test]
+    fn a_full_interner_hands_out_owned_copies() {
+        let interner = Interner::new(10);
+        assert!(interner.get("12345678").is_some());
+        assert_eq!(interner.get("abc"), None, "past the limit");
+        assert!(matches!(interner.cow("abc"), Cow::Owned(text) if text == "abc"));
+        assert!(interner.get("12345678").is_some(), "what is kept stays");
+        assert!(interner.get("ab").is_some(), "up to the limit");
+    }
+
+    #[test]
+    fn full_locations_are_interned() {
+        let location = Location { module: "app", file: "src/app.rs", line: 42 };
+        match (full_location(&location), full_location(&location)) {
+            (Cow::Borrowed(first), Cow::Borrowed(second)) => {
+                assert_eq!(first, "src/app.rs:42");
+                assert!(std::ptr::eq(first, second));
+            }
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn wrapping_opens_the_styles_again_after_a_break() {
+        let text = "head \x1b[1mbold words \x1b[31mthat wrap\x1b[0m tail \x1b[4mxxxxxxxxxxxxxxxxxxxxxxxxxxx\x1b[0m \n";
+        let wrapped = wrap_lines(text, 20, 5, true).unwrap();
+        assert_eq!(
+            wrapped.split('\n').collect::<Vec<_>>(),
+            [
+                "head \x1b[1mbold words \x1b[0m",
+                "     \x1b[1m\x1b[31mthat wrap\x1b[0m tail \x1b[0m",
+                "     \x1b[4mxxxxxxxxxxxxxx\u{21A9}\x1b[0m",
+                "     \x1b[4mxxxxxxxxxxxxx\x1b[0m ",
+                "",
+            ]
+        );
+        for line in wrapped.lines() {
+            assert!(display_width(line) <= 20, "{line:?}");
+        }
+        assert_eq!(strip_ansi(&wrap_lines(text, 20, 5, false).unwrap()).replace(['\n', '\u{21A9}'], "").split_whitespace().collect::<String>(),
+            strip_ansi(text).split_whitespace().collect::<String>(), "nothing is lost");
+    }
+
+    #[test]
+    fn wrapping_leaves_lines_that_fit_and_narrow_widths_alone() {
+        assert_eq!(wrap_lines("head short \n", 20, 5, false), None);
+        assert_eq!(wrap_lines("head a message too long for it \n", 12, 5, false), None);
+    }
+}
@@ -1,303 +1,773 @@
-use std::fmt;
-use std::collections::HashMap;
-use std::sync::{Mutex, LazyLock};
-
-static ALIASES: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
-
-pub mod internal {
-    use super::*;
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    pub fn register_alias(alias: &str, tokens: &str) {
-        let mut aliases = ALIASES.lock().unwrap();
-        aliases.insert(alias.to_string(), tokens.to_string());
-    }
-
-    pub fn get_alias(alias: &str) -> Option<String> {
-        let aliases = ALIASES.lock().unwrap();
-        aliases.get(alias).cloned()
-    }
-
-    pub fn clear_aliases() {
-        let mut aliases = ALIASES.lock().unwrap();
-        aliases.clear();
-    }
-
-    #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
-    pub enum Level {
-        Debug,
-        Info,
-        Warn,
-        Error,
-        None,
-    }
-
-    fn parse_level(s: &str) -> Level {
-        match s.to_ascii_lowercase().as_str() {
-            "debug" => Level::Debug,
-            "info" => Level::Info,
-            "warn" => Level::Warn,
-            "error" => Level::Error,
-            "none" => Level::None,
-            _ => Level::Debug,
-        }
-    }
-
-    const BUILD_LOG_LEVEL: &str = env!("LOG_LEVEL");
-
-    fn current_level() -> Level {
-      parse_level(BUILD_LOG_LEVEL)
-    }
-
-    pub fn is_enabled(level: Level) -> bool {
-        match (current_level(), level) {
-            (Level::None, _) => false,
-            (Level::Error, Level::Error) => true,
-            (Level::Error, _) => false,
-            (Level::Warn, Level::Error | Level::Warn) => true,
-            (Level::Warn, _) => false,
-            (Level::Info, Level::Error | Level::Warn | Level::Info) => true,
-            (Level::Info, _) => false,
-            (Level::Debug, _) => true,
-        }
-    }
-
-    fn level_styles(level: Level) -> (&'static str, &'static str, &'static str, &'static str) {
-        match level {
-            Level::Debug => ("100", "DBG", "90", "90"),  // bg bright black (gray), fg gray
-            Level::Info => ("44", "LOG", "34", "37"),    // bg blue, fg blue
-            Level::Warn => ("43", "WRN", "33", "33"),    // bg yellow, fg yellow
-            Level::Error => ("41", "ERR", "31", "31"),   // bg red, fg red
-            Level::None => ("0", "", "0", "0"),
-        }
-    }
-
-    /// Returns (fg_code, bright_bold)
-    fn color_name_to_fg_code(name: &str) -> Option<(&'static str, bool)> {
-        match name.to_ascii_lowercase().as_str() {
-            "black" => Some(("30", false)),
-            "red" => Some(("31", false)),
-            "green" => Some(("32", false)),
-            "orange" | "yellow" => Some(("33", false)),
-            "blue" => Some(("34", false)),
-            "purple" | "magenta" => Some(("35", false)),
-            "cyan" => Some(("36", false)),
-            "white" => Some(("37", false)),
-            "gray" => Some(("90", false)),
-            _ => None,
-        }
-    }
-
-    fn apply_markup(input: &str, default_seq: &str) -> String {
-        let mut out = String::with_capacity(input.len() + 16);
-        let bytes = input.as_bytes();
-
-        let mut i: usize = 0;
-
-        while i < bytes.len() {
-            if bytes[i] == b'<' {
-                // find closing '>'
-                if let Some(gt) = input[i..].find('>') {
-                    let tag_inner = &input[i + 1..i + gt];
-
-                    // ensure closing marker exists
-                    if let Some(close_pos_rel) = input[i + gt + 1..].find("</>") {
-                        let content_start = i + gt + 1;
-                        let content_end = content_start + close_pos_rel;
-                        let content = &input[content_start..content_end];
-
-                        // Check if this is an alias first
-                        let tokens_to_process: Vec<String> = if let Some(alias_tokens) = get_alias(tag_inner) {
-                            alias_tokens.split(',').map(|s| s.trim().to_string()).collect()
-                        } else {
-                            // parse tag: flexible tokens: comma-separated any of [color, italic, bold, underline, dim, strikethrough, reverse]
-                            tag_inner.split(',').map(|s| s.trim().to_string()).collect()
-                        };
-
-                        let mut italic_on = false;
-                        let mut bold_on = false;
-                        let mut underline_on = false;
-                        let mut dim_on = false;
-                        let mut strikethrough_on = false;
-                        let mut reverse_on = false;
-                        let mut color_fg: Option<&str> = None;
-                        let mut color_bright_bold = false;
-
-                        for token in tokens_to_process.into_iter().filter(|s| !s.is_empty()) {
-                            let lower = token.to_ascii_lowercase();
-                            match lower.as_str() {
-                                "italic" | "i" => { italic_on = true; }
-                                "bold" | "b" => { bold_on = true; }
-                                "underline" | "u" => { underline_on = true; }
-                                "dim" | "d" => { dim_on = true; }
-                                "strikethrough" | "s" => { strikethrough_on = true; }
-                                "reverse" | "r" => { reverse_on = true; }
-                                _ => {
-                                    if color_fg.is_none() {
-                                        if let Some((fg, bright)) = color_name_to_fg_code(&lower) {
-                                            color_fg = Some(fg);
-                                            color_bright_bold = bright;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        if color_bright_bold { bold_on = true; }
-
-                        // Build opening sequence
-                        let mut seq = String::new();
-
-                        if bold_on { seq.push_str(";1"); }
-                        if italic_on { seq.push_str(";3"); }
-                        if underline_on { seq.push_str(";4"); }
-                        if dim_on { seq.push_str(";2"); }
-                        if strikethrough_on { seq.push_str(";9"); }
-                        if reverse_on { seq.push_str(";7"); }
-
-                        if let Some(c) = color_fg {
-                          seq.push_str(";"); seq.push_str(c);
-                        }
-
-                        if seq.is_empty() {
-                            // no styling -> just append content and markers removed
-                            out.push_str(content);
-                        } else {
-                            out.push_str("\x1b[");
-                            out.push_str(&seq[1..]); // skip leading ';'
-                            out.push('m');
-                            out.push_str(content);
-                            out.push_str("\x1b[0m");
-
-                            if !default_seq.is_empty() {
-                                out.push_str(default_seq);
-                            }
-                        }
-
-                        i = content_end + 3; // skip "</>"
-
-                        continue;
-                    }
-                }
-            }
+//! Colored, leveled console logging with inline markup.
+//!
+//! The macros (`debug!`, `log!`, `warn!`, `error!`, ...) and the public
+//! modules below are the stable API. `internal` and `__private` are
+//! implementation details and may change in any release.
+//!
+//! The default pretty output format is versioned by [`OUTPUT_FORMAT_VERSION`]:
+//! the bytes produced with the default theme change only together with it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[doc(inline)]
+pub use rust_logger_core as render;
+
+#[cfg(feature = "macros")]
+pub use rust_logger_macros::instrument;
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub mod internal;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+pub use render::Level;
+
+/// Version of the default pretty line layout
+/// (`label, [timestamp], target:, message` with the default theme). Bumped
+/// whenever those bytes change.
+pub const OUTPUT_FORMAT_VERSION: u32 = 3;
+
+/// The level (build-time default, runtime override, per-module levels) and
+/// the level check used by the macros.
+#[cfg(feature = "std")]
+pub mod level {
+    pub use crate::internal::{
+        clear_module_levels, init_from_env, is_enabled, is_enabled_for, level, mute, parse_level_spec,
+        set_audit_bypasses_level, set_level, set_level_by_name, set_level_by_name_or_default, set_level_spec,
+        set_module_level, static_enabled, try_parse_level, LevelSpec, MuteGuard, BUILD_LEVEL, MAX_LEVEL,
+    };
+    pub use crate::render::{parse_level, Level};
+}
 
-            let ch = input[i..].chars().next().unwrap();
-            out.push(ch);
-            i += ch.len_utf8();
-        }
-
-        out
-    }
-
-    fn days_to_ymd(days_since_epoch: i64) -> (i32, i32, i32) {
-        let z = days_since_epoch + 719_468;
-        let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
-        let doe = z - era * 146_097; // [0, 146096]
-        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365; // [0, 399]
-        let y = (yoe as i32) + (era as i32) * 400;
-        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100 + yoe / 400); // [0, 365]
-        let mp = (5 * doy + 2) / 153; // [0, 11]
-        let d = (doy - (153 * mp + 2) / 5 + 1) as i32; // [1, 31]
-        let m = (mp + if mp < 10 { 3 } else { -9 }) as i32; // [1, 12]
-        let y = y + if m <= 2 { 1 } else { 0 };
-
-        (y, m, d)
-    }
-
-    fn format_timestamp_utc() -> String {
-        let now = SystemTime::now();
-        let dur = now.duration_since(UNIX_EPOCH).unwrap_or_default();
-        let total_secs = dur.as_secs() as i64;
-        let millis = dur.subsec_millis() as i32;
-        
-        let days = total_secs / 86_400;
-        let sod = (total_secs % 86_400) as i64;
-        let hour = (sod / 3_600) as i32;
-        let min = ((sod % 3_600) / 60) as i32;
-        let sec = (sod % 60) as i32;
-
-        let (y, m, d) = days_to_ymd(days);
-
-        format!("{:04}.{:02}.{:02} {:02}:{:02}:{:02}.{:03}", y, m, d, hour, min, sec, millis)
-    }
-
-    pub fn print_with_prefix(level: Level, args: fmt::Arguments) {
-        let (bg, label, date, font) = level_styles(level);
-
-        let ts = format_timestamp_utc();
-        let prefix_label = format!("\x1b[0;{bg};38;2;0;0;0m {label} \x1b[0m ");
-        let default_date_seq = if date != "0" { format!("\x1b[{date}m") } else { String::new() };
-        let default_font_seq = if font != "0" { format!("\x1b[{font}m") } else { String::new() };
-        let message_raw = format!("{}", args);
-        let message_colored = apply_markup(&message_raw, &default_font_seq);
-
-        let ts_block = if default_date_seq.is_empty() {
-            format!("[{ts}] ")
-        } else {
-            format!("{default_date_seq}[{ts}] ")
-        };
+/// One-shot configuration of every option, applied by `Builder::init`.
+#[cfg(feature = "std")]
+pub mod init {
+    pub use crate::internal::{builder, Builder, InitError};
+}
 
-        let msg_block = if default_font_seq.is_empty() {
-            format!("{message_colored} ")
-        } else {
-            format!("{default_font_seq}{message_colored} ")
-        };
+#[cfg(feature = "std")]
+pub use init::builder;
+
+/// Level colors and label text.
+#[cfg(feature = "std")]
+pub mod theme {
+    pub use crate::internal::{
+        clear_target_colors, hash_color, reset_level_styles, set_level_style, set_target_color, set_target_colors, set_theme,
+        set_theme_by_name, set_theme_by_name_or_default, set_theme_schedule, target_color, theme, toggle_theme, Color,
+        LevelStyle, Theme, TimeOfDay,
+    };
+}
+
+/// Output format selection, and the formats as standalone formatters.
+#[cfg(feature = "std")]
+pub mod format {
+    pub use crate::internal::{
+        clear_field_value_limits, field_names, json_flavor, message_limit, output_format, register_field_formatter, set_field_names,
+        set_field_value_limit, set_field_value_limit_for, set_format, set_json_flavor, set_message_limit, set_prefix_template, Destination, Field,
+        FieldNames, FieldValue, Format, JsonFlavor, JsonFormatter, JsonValue, LogfmtFormatter, PrettyFormatter, Record, StandardKey, TemplateError, DEFAULT_PREFIX_TEMPLATE,
+    };
+}
+
+/// Fields added to every record of the process, a thread or a task, and
+/// what happens when two have the same key.
+#[cfg(feature = "std")]
+pub mod context {
+    pub use crate::internal::{
+        clear_global_fields, duplicate_field_policy, push_field, remove_global_field, set_duplicate_field_policy,
+        set_global_field, set_sort_fields, with_task_fields, DuplicateFieldPolicy, FieldGuard, TaskFields,
+    };
+}
+
+/// Named markup styles used as `<name>text</>`.
+#[cfg(feature = "std")]
+pub mod alias {
+    pub use crate::internal::{clear_aliases, get_alias, register_alias, remove_alias, AliasError};
+}
+
+/// Console output settings: destination, colors, call-site location, bell,
+/// process tag, blocking behavior, rate limit, progress-bar coordination and
+/// shutdown.
+#[cfg(feature = "std")]
+pub mod console {
+    pub use crate::internal::{
+        catch_format_panics, clear_external_draw_guard, color_choice, console_queue, dropped_records, event_counter, exempt_errors, fields_column,
+        fields_layout, flush_console_queue, location_mode, multiline, process_tag, queue_dropped_records, queue_overflow, refresh_terminal_width, reset_counters, reset_writer, set_bell, set_bell_cooldown, set_bell_style,
+        set_color_choice, set_console_nonblocking, set_console_queue, set_event_counter, set_external_draw_guard, set_fields_column, set_fields_layout, set_location_mode, set_max_records_per_second,
+        set_multiline, set_process_tag, set_queue_overflow, set_self_log, set_target_truncation, set_target_width, set_thread_colors, set_writer,
+        set_wrap, show_thread, shutdown, shutdown_guard, spilled_records, target_truncation, target_width, terminal_width, throttled_records, wrap, BellMode,
+        BellStyle, ColorChoice, ColorEnv, DrawCoordinator, EventCounter, FieldsLayout, LocationMode, Multiline, QueueOverflow, SelfLog, SharedBuffer, ShutdownGuard,
+        TargetTruncation, Wrap, SELF_LOG_TARGET,
+    };
+}
+
+/// Extra destinations, each with its own level and format, and a time budget
+/// for writing a record to all of them.
+#[cfg(feature = "std")]
+pub mod sink {
+    pub use crate::internal::{
+        add_routed_sink, add_sink, finish_initialization, mute_sink, remove_sink, set_sink_continuation, set_startup_replay,
+        warn_if_log_call_exceeds, ContinuationStyle, Route, Sink, SinkId, SinkKind, SinkMuteGuard, SinkRef,
+    };
+}
+
+/// Log files with optional size-based rotation, and a fixed-size flight
+/// recorder file that survives the process being killed (unix).
+#[cfg(feature = "std")]
+pub mod file {
+    pub use crate::internal::{
+        add_file_sink, file_level, file_sink, flush, reopen, set_file_level, FileSinkBuilder, FileSinkGuard, RotationPolicy,
+        SyncPolicy,
+    };
+    #[cfg(unix)]
+    pub use crate::internal::{read_black_box, start_black_box};
+}
+
+/// A diagnostic dump on a signal (unix, `signals` feature).
+#[cfg(all(unix, feature = "signals"))]
+pub mod dump {
+    pub use crate::internal::{dump_on_signal, Signal};
+}
+
+/// Effective configuration and where each value came from, configuration
+/// errors, and the stable output mode for snapshot tests.
+#[cfg(feature = "std")]
+pub mod config {
+    pub use crate::internal::{config_report, set_stable_test_format, stable_test_format, ConfigError};
+}
+
+/// Thread-local groups opened by `group_begin!`, and record groups from
+/// `record_group!` that are written out in one piece.
+#[cfg(feature = "std")]
+pub mod group {
+    pub use crate::internal::{
+        current_depth, group_indent, set_group_indent, GroupGuard, GroupIndent, RecordGroup, RECORD_GROUP_CAP,
+    };
+}
 
-        let final_line = format!("{prefix_label}{ts_block}{msg_block}\x1b[0m");
-        println!("{}", final_line);
-    }
+/// "Still working" lines for long operations, started by `heartbeat!`, and
+/// the scope guards of `time_scope!`.
+#[cfg(feature = "std")]
+pub mod heartbeat {
+    pub use crate::internal::{Heartbeat, TimeScope};
+}
+
+/// A status line redrawn in place under the log records, from `progress!`.
+#[cfg(feature = "std")]
+pub mod progress {
+    pub use crate::internal::Progress;
+}
+
+/// Counters for records filtered out by the level check.
+#[cfg(feature = "std")]
+pub mod stats {
+    pub use crate::internal::{
+        dropped_records, queue_dropped_records, set_suppressed_accounting, set_suppressed_summary_interval,
+        spilled_records, suppressed_summary, throttled_records,
+    };
+}
+
+/// `Display` helpers for values that are not valid UTF-8, and the charset
+/// decorations are drawn with.
+#[cfg(feature = "std")]
+pub mod display {
+    pub use crate::internal::{
+        glyphs, set_charset, set_charset_by_name, set_charset_by_name_or_default, Charset, Lossy, P,
+    };
+    pub use crate::render::Glyphs;
+}
 
-    pub fn print_new_line() {
-        println!("");
-    }
+/// The time zone and format of timestamps.
+#[cfg(all(feature = "std", feature = "timestamps"))]
+pub mod time {
+    pub use crate::internal::{
+        format_timestamp, reset_uptime, set_timestamp_format, set_timezone, timestamp_format, timezone, TimeZoneMode, TimestampFormat,
+        TimestampPattern,
+    };
 }
 
+/// Reading this crate's own output back.
+#[cfg(all(feature = "std", feature = "timestamps"))]
+pub mod replay {
+    pub use crate::internal::{parse_pretty_line, ParsedRecord};
+}
+
+/// What the exported macros call. Not part of the public API.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::internal::{
+        audit_enabled, dump_config, group_begin, group_end, group_guard, hexdump, instrument_call, instrument_enter,
+        instrument_future, is_enabled_for, print_audit, print_dump, print_emphasized, print_in_category, print_new_line,
+        print_rule, print_with_fields, print_with_prefix, record_enabled, record_group, register_alias, report_bad_alias,
+        start_heartbeat, start_progress, static_enabled, suppressed_accounting_enabled, time_scope, with_destination, with_error_chain,
+        with_suppressed_count,
+        AnyError, CallCount, InstrumentSpan, Location, MaybeDebug, ShowDebug, SuppressedSite, Throttle, ViaBoxedError, ViaDebug,
+        ViaError, ViaNoDebug,
+    };
+    pub use crate::render::Level;
+}
+
+/// Logs at Debug; takes the same arguments as [`log!`].
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! debug {
+    (once: $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Debug) || __RL_CALLS.first() {
+            $crate::debug!($($arg)*);
+        }
+    }};
+    (every: $n:expr, $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Debug) || __RL_CALLS.every(($n) as u64) {
+            $crate::debug!($($arg)*);
+        }
+    }};
+    (throttle: $window:expr, $($arg:tt)*) => {{
+        static __RL_THROTTLE: $crate::__private::Throttle = $crate::__private::Throttle::new();
+        if !$crate::__enabled!(Debug) {
+            $crate::debug!($($arg)*);
+        } else if let ::core::option::Option::Some(suppressed) = __RL_THROTTLE.pass($window) {
+            $crate::__private::with_suppressed_count(suppressed, || $crate::debug!($($arg)*));
+        }
+    }};
+    (dest: $dest:ident, $($arg:tt)*) => {{
+        $crate::__private::with_destination(::core::stringify!($dest), || $crate::debug!($($arg)*));
+    }};
+    (category: $category:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Debug) {
+            $crate::__private::print_in_category($crate::__private::Level::Debug, $category, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Debug);
+        }
+    }};
+    (emphasis: $emphasis:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Debug) {
+            $crate::__private::print_emphasized($crate::__private::Level::Debug, $emphasis, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Debug);
+        }
+    }};
+    ($fmt:literal $(, $arg:expr)* ; $($fields:tt)+) => {{
+        if $crate::__enabled!(Debug) {
+            $crate::__private::print_with_fields(
+                $crate::__private::Level::Debug, $crate::__location!(), &$crate::__fields!($($fields)+), format_args!($fmt $(, $arg)*),
+            );
+        } else {
+            $crate::__note_suppressed!(Debug);
+        }
+    }};
     ($($arg:tt)*) => {{
-        if $crate::internal::is_enabled($crate::internal::Level::Debug) {
-            $crate::internal::print_with_prefix($crate::internal::Level::Debug, format_args!($($arg)*));
+        if $crate::__enabled!(Debug) {
+            $crate::__private::print_with_prefix($crate::__private::Level::Debug, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Debug);
         }
     }};
 }
 
+/// Logs at Info. The message takes `format!` arguments and markup such as
+/// `<red,bold>text</>`; write `"\\<"` (or `r"\<"`) for a literal `<` that would
+/// otherwise start a tag. Brackets around anything but style names, like
+/// `Vec<String>`, are printed as is. Fields go after a `;`:
+/// `log!("done in {}ms", t; user_id = uid, query = ?q)`, with `?` for `Debug`
+/// and `%` for the formatter registered for the type.
+/// `once:` or `every: n,` before the rest limits the call site to its first
+/// enabled call, or to the 1st, n+1th, 2n+1th, ...: `warn!(once: "fallback")`.
+/// `throttle: window,` lets one record through per window and counts the rest.
+/// `category: "billing",` tags the record for `sink::Route::Category`.
+/// `dest: console,` or `dest: file,` sends it only there (default `dest: all`).
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! log {
+    (once: $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Info) || __RL_CALLS.first() {
+            $crate::log!($($arg)*);
+        }
+    }};
+    (every: $n:expr, $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Info) || __RL_CALLS.every(($n) as u64) {
+            $crate::log!($($arg)*);
+        }
+    }};
+    (throttle: $window:expr, $($arg:tt)*) => {{
+        static __RL_THROTTLE: $crate::__private::Throttle = $crate::__private::Throttle::new();
+        if !$crate::__enabled!(Info) {
+            $crate::log!($($arg)*);
+        } else if let ::core::option::Option::Some(suppressed) = __RL_THROTTLE.pass($window) {
+            $crate::__private::with_suppressed_count(suppressed, || $crate::log!($($arg)*));
+        }
+    }};
+    (dest: $dest:ident, $($arg:tt)*) => {{
+        $crate::__private::with_destination(::core::stringify!($dest), || $crate::log!($($arg)*));
+    }};
+    (category: $category:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Info) {
+            $crate::__private::print_in_category($crate::__private::Level::Info, $category, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Info);
+        }
+    }};
+    (emphasis: $emphasis:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Info) {
+            $crate::__private::print_emphasized($crate::__private::Level::Info, $emphasis, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Info);
+        }
+    }};
+    ($fmt:literal $(, $arg:expr)* ; $($fields:tt)+) => {{
+        if $crate::__enabled!(Info) {
+            $crate::__private::print_with_fields(
+                $crate::__private::Level::Info, $crate::__location!(), &$crate::__fields!($($fields)+), format_args!($fmt $(, $arg)*),
+            );
+        } else {
+            $crate::__note_suppressed!(Info);
+        }
+    }};
     ($($arg:tt)*) => {{
-        if $crate::internal::is_enabled($crate::internal::Level::Info) {
-            $crate::internal::print_with_prefix($crate::internal::Level::Info, format_args!($($arg)*));
+        if $crate::__enabled!(Info) {
+            $crate::__private::print_with_prefix($crate::__private::Level::Info, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Info);
         }
     }};
 }
 
+/// Logs at Warn; takes the same arguments as [`log!`].
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! warn {
+    (once: $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Warn) || __RL_CALLS.first() {
+            $crate::warn!($($arg)*);
+        }
+    }};
+    (every: $n:expr, $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Warn) || __RL_CALLS.every(($n) as u64) {
+            $crate::warn!($($arg)*);
+        }
+    }};
+    (throttle: $window:expr, $($arg:tt)*) => {{
+        static __RL_THROTTLE: $crate::__private::Throttle = $crate::__private::Throttle::new();
+        if !$crate::__enabled!(Warn) {
+            $crate::warn!($($arg)*);
+        } else if let ::core::option::Option::Some(suppressed) = __RL_THROTTLE.pass($window) {
+            $crate::__private::with_suppressed_count(suppressed, || $crate::warn!($($arg)*));
+        }
+    }};
+    (dest: $dest:ident, $($arg:tt)*) => {{
+        $crate::__private::with_destination(::core::stringify!($dest), || $crate::warn!($($arg)*));
+    }};
+    (category: $category:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Warn) {
+            $crate::__private::print_in_category($crate::__private::Level::Warn, $category, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Warn);
+        }
+    }};
+    (emphasis: $emphasis:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Warn) {
+            $crate::__private::print_emphasized($crate::__private::Level::Warn, $emphasis, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Warn);
+        }
+    }};
+    ($fmt:literal $(, $arg:expr)* ; $($fields:tt)+) => {{
+        if $crate::__enabled!(Warn) {
+            $crate::__private::print_with_fields(
+                $crate::__private::Level::Warn, $crate::__location!(), &$crate::__fields!($($fields)+), format_args!($fmt $(, $arg)*),
+            );
+        } else {
+            $crate::__note_suppressed!(Warn);
+        }
+    }};
     ($($arg:tt)*) => {{
-        if $crate::internal::is_enabled($crate::internal::Level::Warn) {
-            $crate::internal::print_with_prefix($crate::internal::Level::Warn, format_args!($($arg)*));
+        if $crate::__enabled!(Warn) {
+            $crate::__private::print_with_prefix($crate::__private::Level::Warn, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Warn);
         }
     }};
 }
 
+/// Logs at Error; takes the same arguments as [`log!`], and `err = e,`
+/// prints `e` and its `source()` chain under the message, one `caused by:`
+/// line each: `error!(err = e, "db connection failed")`.
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! error {
+    (once: $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Error) || __RL_CALLS.first() {
+            $crate::error!($($arg)*);
+        }
+    }};
+    (every: $n:expr, $($arg:tt)*) => {{
+        static __RL_CALLS: $crate::__private::CallCount = $crate::__private::CallCount::new();
+        if !$crate::__enabled!(Error) || __RL_CALLS.every(($n) as u64) {
+            $crate::error!($($arg)*);
+        }
+    }};
+    (throttle: $window:expr, $($arg:tt)*) => {{
+        static __RL_THROTTLE: $crate::__private::Throttle = $crate::__private::Throttle::new();
+        if !$crate::__enabled!(Error) {
+            $crate::error!($($arg)*);
+        } else if let ::core::option::Option::Some(suppressed) = __RL_THROTTLE.pass($window) {
+            $crate::__private::with_suppressed_count(suppressed, || $crate::error!($($arg)*));
+        }
+    }};
+    (err = $err:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Error) {
+            #[allow(unused_imports)]
+            use $crate::__private::{ViaBoxedError as _, ViaError as _};
+            $crate::__private::with_error_chain((&$crate::__private::AnyError(&$err)).as_error(), || $crate::error!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Error);
+        }
+    }};
+    (dest: $dest:ident, $($arg:tt)*) => {{
+        $crate::__private::with_destination(::core::stringify!($dest), || $crate::error!($($arg)*));
+    }};
+    (category: $category:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Error) {
+            $crate::__private::print_in_category($crate::__private::Level::Error, $category, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Error);
+        }
+    }};
+    (emphasis: $emphasis:expr, $($arg:tt)*) => {{
+        if $crate::__enabled!(Error) {
+            $crate::__private::print_emphasized($crate::__private::Level::Error, $emphasis, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Error);
+        }
+    }};
+    ($fmt:literal $(, $arg:expr)* ; $($fields:tt)+) => {{
+        if $crate::__enabled!(Error) {
+            $crate::__private::print_with_fields(
+                $crate::__private::Level::Error, $crate::__location!(), &$crate::__fields!($($fields)+), format_args!($fmt $(, $arg)*),
+            );
+        } else {
+            $crate::__note_suppressed!(Error);
+        }
+    }};
+    ($($arg:tt)*) => {{
+        if $crate::__enabled!(Error) {
+            $crate::__private::print_with_prefix($crate::__private::Level::Error, $crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Error);
+        }
+    }};
+}
+
+/// Business or audit event: printed at Info in the `audit` category, with an
+/// `[audit]` badge, even when the level filters Info out (see
+/// `level::set_audit_bypasses_level`), and never dropped by the rate limit.
+/// Route it to its own sink with `sink::Route::Category("audit")`; the first
+/// `audit!` warns when no sink is.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! audit {
+    ($($arg:tt)*) => {{
+        if $crate::__private::audit_enabled(module_path!()) {
+            $crate::__private::print_audit($crate::__location!(), format_args!($($arg)*));
+        } else {
+            $crate::__note_suppressed!(Info);
+        }
+    }};
+}
+
+/// Mutes records below the level on the current thread while the returned
+/// guard lives: `let _mute = mute!(Level::Warn);` around a noisy call.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! mute {
+    ($level:expr) => {
+        $crate::level::mute($level)
+    };
+}
+
+/// Stops records logged on the current thread from reaching one sink while
+/// the returned guard lives, leaving the console and the other sinks alone:
+/// `let _quiet = mute_sink!(id);` for a `SinkId`, `mute_sink!(&log_file)`
+/// for a `FileSinkGuard`.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! mute_sink {
+    ($sink:expr) => {
+        $crate::sink::mute_sink($sink)
+    };
+}
+
+/// Logs the message with the elapsed time every `interval` until the
+/// returned guard is dropped, then once more with the total duration:
+/// `let _hb = heartbeat!(Level::Info, Duration::from_secs(30), "compacting shard {}", id);`
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! heartbeat {
+    ($level:expr, $interval:expr, $($arg:tt)*) => {
+        $crate::__private::start_heartbeat($level, $interval, $crate::__location!(), format_args!($($arg)*))
+    };
+}
+
+/// Times the rest of the enclosing scope and logs the label with the elapsed
+/// time at Debug when the returned guard is dropped:
+/// `let _t = time_scope!("load config");` logs `load config — done in 12.3ms`.
+/// When Debug is disabled the guard does nothing, not even read the clock.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! time_scope {
+    ($($arg:tt)*) => {
+        $crate::__private::time_scope($crate::__location!(), format_args!($($arg)*))
+    };
+}
+
+/// Evaluates the expression inside a `time_scope!` and returns its value:
+/// `let cfg = time!("parse", parse_config(path));`
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! time {
+    ($label:expr, $value:expr $(,)?) => {{
+        let __rl_scope = $crate::time_scope!("{}", $label);
+        $value
+    }};
+}
+
+/// Logs each expression with its `{:#?}` value, one record per expression,
+/// and returns the value like `dbg!` (a tuple for several):
+/// `let r = dump!(fetch(id));` logs `fetch(id) = Response { .. }` at Debug,
+/// `dump!(level: Warn, a, b)` at another level. A disabled level leaves just
+/// the expression.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! dump {
+    (level: $level:ident, $value:expr $(,)?) => {
+        match $value {
+            __rl_value => {
+                if $crate::__enabled!($level) {
+                    $crate::__private::print_dump(
+                        $crate::__private::Level::$level, $crate::__location!(), stringify!($value), &__rl_value,
+                    );
+                } else {
+                    $crate::__note_suppressed!($level);
+                }
+                __rl_value
+            }
+        }
+    };
+    (level: $level:ident, $($value:expr),+ $(,)?) => {
+        ($($crate::dump!(level: $level, $value)),+,)
+    };
+    ($($value:expr),+ $(,)?) => {
+        $crate::dump!(level: Debug, $($value),+)
+    };
+}
+
+/// Logs a byte buffer at Debug in the 16-bytes-per-row layout of
+/// `hexdump -C`: `hexdump!("rx frame", &buf[..n])`. `max: n,` in front shows
+/// at most `n` bytes and ends with `(+432 more bytes)`.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! hexdump {
+    (max: $max:expr, $label:expr, $bytes:expr $(,)?) => {
+        $crate::hexdump!(@limit ::core::option::Option::Some($max), $label, $bytes)
+    };
+    (@limit $max:expr, $label:expr, $bytes:expr) => {{
+        if $crate::__enabled!(Debug) {
+            $crate::__private::hexdump(
+                $crate::__private::Level::Debug, $crate::__location!(), &$label,
+                ::core::convert::AsRef::<[u8]>::as_ref(&$bytes), $max,
+            );
+        } else {
+            $crate::__note_suppressed!(Debug);
+        }
+    }};
+    ($label:expr, $bytes:expr $(,)?) => {
+        $crate::hexdump!(@limit ::core::option::Option::None, $label, $bytes)
+    };
+}
+
+/// Starts a `progress::Progress` line for `total` items with a label:
+/// `let p = progress!(files.len(), "processing");` draws
+/// `processing 420/1000 (42%)` as `p.set(420)` is called.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! progress {
+    ($total:expr, $($arg:tt)*) => {
+        $crate::__private::start_progress(($total) as u64, $crate::__location!(), format_args!($($arg)*))
+    };
+}
+
+/// Logs a dimmed rule across the terminal at Info: `separator!()`.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! separator {
+    () => {{
+        if $crate::__enabled!(Info) {
+            $crate::__private::print_rule($crate::__private::Level::Info, $crate::__location!(), ::core::option::Option::None);
+        } else {
+            $crate::__note_suppressed!(Info);
+        }
+    }};
+}
+
+/// Logs a banner at Info with the title centered in a rule across the
+/// terminal: `section!("Phase {}: migration", 2)`.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! section {
     ($($arg:tt)*) => {{
-        if $crate::internal::is_enabled($crate::internal::Level::Error) {
-            $crate::internal::print_with_prefix($crate::internal::Level::Error, format_args!($($arg)*));
+        if $crate::__enabled!(Info) {
+            $crate::__private::print_rule(
+                $crate::__private::Level::Info, $crate::__location!(), ::core::option::Option::Some(format_args!($($arg)*)),
+            );
+        } else {
+            $crate::__note_suppressed!(Info);
         }
     }};
 }
 
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! new_line {
     () => {{
-        $crate::internal::print_new_line();
+        $crate::__private::print_new_line(::core::option::Option::None);
+    }};
+    ($level:expr) => {{
+        let level = $level;
+        if $crate::__private::is_enabled_for(level, module_path!()) {
+            $crate::__private::print_new_line(::core::option::Option::Some(level));
+        }
     }};
 }
 
+/// `key = value, key = ?value, key = %value` to an array of `format::Field`s.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fields {
+    (@ [$($done:expr,)*]) => {
+        [$($done,)*]
+    };
+    (@ [$($done:expr,)*] $key:ident = %$value:expr $(, $($rest:tt)*)?) => {
+        $crate::__fields!(@ [$($done,)* $crate::format::Field::typed(stringify!($key), &$value),] $($($rest)*)?)
+    };
+    (@ [$($done:expr,)*] $key:ident = ?$value:expr $(, $($rest:tt)*)?) => {
+        $crate::__fields!(@ [$($done,)* $crate::format::Field::debug(stringify!($key), &$value),] $($($rest)*)?)
+    };
+    (@ [$($done:expr,)*] $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__fields!(@ [$($done,)* $crate::format::Field::display(stringify!($key), &$value),] $($($rest)*)?)
+    };
+    ($($fields:tt)+) => {
+        $crate::__fields!(@ [] $($fields)+)
+    };
+}
+
+/// Registers a style alias: `alias!("hot", "red, bold")`, then
+/// `<hot>text</>`. A rejected alias (see `alias::AliasError`) panics in
+/// debug builds and is reported as a `[logger]` warning in release builds.
+#[cfg(feature = "std")]
 #[macro_export]
 macro_rules! alias {
     ($alias:expr, $tokens:expr) => {{
-        $crate::internal::register_alias($alias, $tokens);
+        if let ::core::result::Result::Err(error) = $crate::__private::register_alias($alias, $tokens) {
+            if ::core::cfg!(debug_assertions) { ::core::panic!("alias!: {}", error); }
+            $crate::__private::report_bad_alias(&error);
+        }
+    }};
+}
+
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! group_begin {
+    (level: $level:expr, $($arg:tt)*) => {{
+        $crate::__private::group_begin($level, $crate::__location!(), format_args!($($arg)*));
+    }};
+    ($($arg:tt)*) => {{
+        $crate::__private::group_begin($crate::__private::Level::Info, $crate::__location!(), format_args!($($arg)*));
+    }};
+}
+
+/// Like `group_begin!`, but returns a `group::GroupGuard` that ends the
+/// group when it is dropped: `let _g = group!("resolving {}", name);`.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! group {
+    (level: $level:expr, $($arg:tt)*) => {
+        $crate::__private::group_guard($level, $crate::__location!(), format_args!($($arg)*))
+    };
+    ($($arg:tt)*) => {
+        $crate::__private::group_guard($crate::__private::Level::Info, $crate::__location!(), format_args!($($arg)*))
+    };
+}
+
+/// Starts a `group::RecordGroup` at the level: records added with its `log`
+/// method are written out together, without lines of other threads in
+/// between, when it is dropped.
+/// `let report = record_group!(Level::Info); report.log("3 errors"); ...`
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! record_group {
+    ($level:expr) => {
+        $crate::__private::record_group($level, $crate::__location!())
+    };
+}
+
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! group_end {
+    () => {{
+        $crate::__private::group_end($crate::__location!(), None);
+    }};
+    ($($arg:tt)*) => {{
+        $crate::__private::group_end($crate::__location!(), Some(format_args!($($arg)*)));
+    }};
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __location {
+    () => {
+        $crate::__private::Location { module: module_path!(), file: file!(), line: line!() }
+    };
+}
+
+/// The level check of the macros: the `max_level_*` and `static_level`
+/// bounds, evaluated at compile time, then the runtime level for the calling
+/// module.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __enabled {
+    ($level:ident) => {
+        (const { $crate::__private::static_enabled($crate::__private::Level::$level) }
+            && $crate::__private::record_enabled($crate::__private::Level::$level, module_path!()))
+    };
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __note_suppressed {
+    ($level:ident) => {{
+        if $crate::__private::static_enabled($crate::__private::Level::$level) && $crate::__private::suppressed_accounting_enabled() {
+            static SITE: $crate::__private::SuppressedSite = $crate::__private::SuppressedSite::new(module_path!());
+            SITE.hit();
+        }
+    }};
+}
+
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! dump_config {
+    () => {{
+        $crate::__private::dump_config($crate::__location!());
     }};
 }
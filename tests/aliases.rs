@@ -0,0 +1,127 @@
+mod common;
+
+use rust_logger::alias::{self, AliasError};
+use rust_logger::format::{PrettyFormatter, Record};
+use rust_logger::theme::Theme;
+use rust_logger::{log, Level};
+
+/// `message` as a colored pretty line without a timestamp.
+fn styled(message: &str) -> String {
+    let mut out = String::new();
+    let record = Record::new(Level::Info, message);
+    PrettyFormatter::new(Theme::default()).colors(true).timestamps(false).format(&record, &mut out).unwrap();
+    out
+}
+
+#[test]
+fn unknown_tokens_are_listed() {
+    let (_guard, _buffer) = common::capture();
+    let error = alias::register_alias("warn-ish", "chartreuse, bold, wiggly").unwrap_err();
+    assert_eq!(
+        error,
+        AliasError::UnknownTokens { alias: String::from("warn-ish"), tokens: vec![String::from("chartreuse"), String::from("wiggly")] }
+    );
+    assert_eq!(
+        error.to_string(),
+        "alias \"warn-ish\": unknown tokens \"chartreuse\", \"wiggly\": expected colors, bg- colors, bold, italic, \
+         underline, dim, strikethrough, reverse or other aliases"
+    );
+    assert_eq!(alias::get_alias("warn-ish"), None, "a rejected alias is not stored");
+
+    // Every kind of style is accepted, and so are other aliases.
+    alias::register_alias("hot", "red, bold, bg-c52, #ff8800, bg-blue, italic, underline, dim, strikethrough, reverse").unwrap();
+    alias::register_alias("alert", "hot, underline").unwrap();
+    alias::clear_aliases();
+    assert!(matches!(alias::register_alias("alert", "hot"), Err(AliasError::UnknownTokens { .. })), "cleared aliases are unknown");
+}
+
+#[test]
+fn bad_names_and_empty_token_lists() {
+    let (_guard, _buffer) = common::capture();
+    for name in ["", "a<b", "a>b", "a,b"] {
+        assert_eq!(alias::register_alias(name, "red"), Err(AliasError::BadName(name.to_string())), "{name:?}");
+    }
+    for tokens in ["", " ", " , ,"] {
+        assert_eq!(alias::register_alias("empty", tokens), Err(AliasError::NoTokens(String::from("empty"))), "{tokens:?}");
+    }
+    assert_eq!(alias::get_alias("empty"), None);
+    assert_eq!(AliasError::BadName(String::from("a<b")).to_string(), "bad alias name \"a<b\": must be non-empty, without '<', '>' or ','");
+}
+
+#[test]
+fn redefining_replaces_the_tokens() {
+    let (_guard, _buffer) = common::capture();
+    alias::register_alias("hot", "red").unwrap();
+    assert!(styled("<hot>x</>").contains("\x1b[31mx"));
+
+    alias::register_alias("hot", "blue, bold").unwrap();
+    assert_eq!(alias::get_alias("hot").as_deref(), Some("blue, bold"));
+    assert!(styled("<hot>x</>").contains("\x1b[1;34mx"));
+
+    // A rejected redefinition keeps the one before.
+    assert!(alias::register_alias("hot", "wiggly").is_err());
+    assert!(alias::register_alias("hot", "").is_err());
+    assert_eq!(alias::get_alias("hot").as_deref(), Some("blue, bold"));
+    assert!(styled("<hot>x</>").contains("\x1b[1;34mx"));
+    alias::clear_aliases();
+}
+
+#[test]
+fn removed_aliases_print_as_text() {
+    let (_guard, buffer) = common::capture();
+    alias::register_alias("hot", "red, bold").unwrap();
+    assert_eq!(alias::remove_alias("hot").as_deref(), Some("red, bold"));
+    assert_eq!(alias::remove_alias("hot"), None, "only once");
+    assert_eq!(alias::remove_alias("never-registered"), None);
+    assert_eq!(alias::get_alias("hot"), None);
+
+    log!("<hot>gone</>");
+    assert_eq!(buffer.text(), " LOG  [TS] aliases: <hot>gone</> \n");
+    assert!(styled("<hot>gone</>").contains("<hot>gone</>"));
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn the_macro_panics_on_a_bad_alias_in_debug_builds() {
+    let (_guard, _buffer) = common::capture();
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let caught = std::panic::catch_unwind(|| rust_logger::alias!("warn-ish", "chartreuse, wiggly"));
+    std::panic::set_hook(hook);
+    let payload = caught.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("alias!: alias \"warn-ish\": unknown tokens \"chartreuse\", \"wiggly\""), "{message}");
+    assert_eq!(alias::get_alias("warn-ish"), None);
+}
+
+#[test]
+fn aliases_built_on_aliases() {
+    let (_guard, _buffer) = common::capture();
+    // A chain, expanded when registered and again when a link changes.
+    alias::register_alias("em", "bold, underline").unwrap();
+    alias::register_alias("critical", "em, red").unwrap();
+    assert!(styled("<critical>x</>").contains("\x1b[1;4;31mx"));
+    alias::register_alias("em", "italic").unwrap();
+    assert!(styled("<critical>x</>").contains("\x1b[3;31mx"), "redefining `em` updates `critical`");
+    assert_eq!(alias::get_alias("critical").as_deref(), Some("em, red"), "the tokens stay as registered");
+    alias::remove_alias("em");
+    assert!(styled("<critical>x</>").contains("\x1b[31mx"), "a removed alias drops out of the ones using it");
+
+    // A diamond: `base` reached twice counts once.
+    alias::register_alias("base", "bold").unwrap();
+    alias::register_alias("left", "base, red").unwrap();
+    alias::register_alias("right", "base, underline").unwrap();
+    alias::register_alias("both", "left, right").unwrap();
+    assert!(styled("<both>x</>").contains("\x1b[1;4;31mx"));
+
+    // Cycles are dropped, direct or through another alias.
+    alias::register_alias("loop", "italic").unwrap();
+    alias::register_alias("loop", "loop, bold").unwrap();
+    assert!(styled("<loop>x</>").contains("\x1b[1mx"));
+    alias::register_alias("ping", "bold").unwrap();
+    alias::register_alias("pong", "ping, red").unwrap();
+    alias::register_alias("ping", "pong, underline").unwrap();
+    assert!(styled("<ping>x</>").contains("\x1b[4;31mx"));
+    assert!(styled("<pong>x</>").contains("\x1b[4;31mx"));
+    alias::clear_aliases();
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Route, Sink};
+use rust_logger::{audit, level, log, warn, Level};
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+impl Collect {
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[test]
+fn audit_bypasses_the_level_unless_told_not_to() {
+    let (_guard, buffer) = common::capture();
+    level::set_level(Level::Error);
+    let audit_trail = Collect::default();
+    let id = sink::add_routed_sink(Box::new(audit_trail.clone()), Level::Info, Format::Pretty, Route::Category("audit"));
+
+    audit!("exported {} rows", 3);
+    level::set_audit_bypasses_level(false);
+    audit!("filtered");
+    level::set_audit_bypasses_level(true);
+    sink::remove_sink(id);
+
+    assert_eq!(buffer.text(), " LOG  [TS] audit: [audit] exported 3 rows \n");
+    // The sink still takes Info, so the filtered record reaches it.
+    assert_eq!(audit_trail.lines(), ["[LOG] [TS] audit: [audit] exported 3 rows", "[LOG] [TS] audit: [audit] filtered"]);
+}
+
+#[test]
+fn sinks_take_the_categories_they_are_routed() {
+    let (_guard, buffer) = common::capture();
+
+    let audit_trail = Collect::default();
+    let billing = Collect::default();
+    let operational = Collect::default();
+    let ids = [
+        sink::add_routed_sink(Box::new(audit_trail.clone()), Level::Info, Format::Pretty, Route::Category("audit")),
+        sink::add_routed_sink(Box::new(billing.clone()), Level::Debug, Format::Json, Route::Category("billing")),
+        sink::add_routed_sink(Box::new(operational.clone()), Level::Debug, Format::Pretty, Route::Uncategorized),
+    ];
+    audit!("user 7 exported data");
+    log!(category: "billing", "invoice {} sent", 12);
+    warn!("disk at 91%");
+    for id in ids {
+        sink::remove_sink(id);
+    }
+
+    assert_eq!(audit_trail.lines(), ["[LOG] [TS] audit: [audit] user 7 exported data"]);
+    let billing = billing.lines();
+    assert_eq!(billing.len(), 1);
+    assert!(billing[0].contains(r#""category":"billing","message":"invoice 12 sent""#), "{billing:?}");
+    assert_eq!(operational.lines(), ["[WRN] [TS] audit: disk at 91%"]);
+    assert!(buffer.text().contains(" LOG  [TS] audit: [billing] invoice 12 sent \n"), "{}", buffer.text());
+}
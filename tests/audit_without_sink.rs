@@ -0,0 +1,30 @@
+//! Its own test binary: the missing-sink warning is given once per process.
+mod common;
+
+use rust_logger::audit;
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Route, Sink};
+use rust_logger::Level;
+
+struct Discard;
+
+impl Sink for Discard {
+    fn write(&mut self, _record: &Record, _line: &str) {}
+}
+
+#[test]
+fn the_first_audit_without_an_audit_sink_warns_once() {
+    let (_guard, buffer) = common::capture();
+
+    // A sink for another category doesn't count.
+    let other = sink::add_routed_sink(Box::new(Discard), Level::Debug, Format::Pretty, Route::Category("billing"));
+    audit!("first");
+    audit!("second");
+    sink::remove_sink(other);
+
+    let text = buffer.text();
+    let warnings: Vec<&str> = text.lines().filter(|line| line.contains("[logger]")).collect();
+    assert_eq!(warnings.len(), 1, "{text}");
+    assert!(warnings[0].starts_with(" WRN  [TS] [logger]: audit! used without a sink routed to the audit category"), "{text}");
+    assert!(text.ends_with("[audit] first \n LOG  [TS] audit_without_sink: [audit] second \n"), "{text}");
+}
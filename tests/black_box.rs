@@ -0,0 +1,87 @@
+//! There is one black box per process; the `kill -9` case runs in a child.
+#![cfg(unix)]
+
+mod common;
+
+use std::io::BufRead;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, Stdio};
+
+use rust_logger::file;
+use rust_logger::log;
+
+const CHILD: &str = "RUST_LOGGER_BLACK_BOX_CHILD";
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("rust_logger-black-box-{}-{name}", std::process::id()))
+}
+
+fn numbers(records: &[String]) -> Vec<u32> {
+    records.iter().map(|record| record.rsplit(' ').next().unwrap().parse().unwrap()).collect()
+}
+
+/// In the child: fills a black box, says so and waits to be killed.
+#[test]
+fn child() {
+    let Some(path) = std::env::var_os(CHILD) else { return };
+    let (_guard, _buffer) = common::capture();
+    file::start_black_box(&path, 4096).unwrap();
+    for i in 0..1000 { log!("child record {i}"); }
+    println!("ready");
+    loop { std::thread::sleep(std::time::Duration::from_secs(1)); }
+}
+
+#[test]
+fn records_are_read_back_across_the_wrap_point() {
+    if std::env::var_os(CHILD).is_some() { return; }
+    let (_guard, _buffer) = common::capture();
+    let path = temp_path("wrap");
+    file::start_black_box(&path, 1024).unwrap();
+    assert!(file::start_black_box(&path, 1024).is_err());
+    // Records of 40 to 50 bytes go round the 1024-byte buffer several times,
+    // so some of them are split across its end.
+    for i in 0..100 { log!("record {i}"); }
+    let records = file::read_black_box(&path).unwrap();
+    let len = std::fs::metadata(&path).unwrap().len();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(len, 24 + 1024);
+    assert_eq!(records.last().unwrap(), "[LOG] [TS] black_box: record 99");
+    let numbers = numbers(&records);
+    assert!(numbers.len() > 10 && numbers[0] > 0, "{numbers:?}");
+    assert!(numbers.windows(2).all(|pair| pair[1] == pair[0] + 1), "{numbers:?}");
+}
+
+#[test]
+fn only_black_box_files_are_read() {
+    let path = temp_path("not-a-black-box");
+    std::fs::write(&path, "[LOG] [TS] app: a log file, not a black box\n").unwrap();
+    let read = file::read_black_box(&path);
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(read.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn records_survive_kill_9() {
+    if std::env::var_os(CHILD).is_some() { return; }
+    let path = temp_path("killed");
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, &path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // libtest's own text comes first, on the same line.
+    let stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    assert!(stdout.lines().any(|line| line.unwrap().ends_with("ready")));
+    child.kill().unwrap();
+    let status = child.wait().unwrap();
+    let records = file::read_black_box(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(status.signal(), Some(9));
+    assert_eq!(records.last().unwrap(), "[LOG] [TS] black_box: child record 999");
+    assert!(records.len() > 40 && records.len() < 1000, "{}", records.len());
+    assert!(numbers(&records).windows(2).all(|pair| pair[1] == pair[0] + 1));
+}
@@ -0,0 +1,58 @@
+//! The level resolution `build.rs` runs, on `.env` files made up here.
+
+#[path = "../build/level.rs"]
+mod level;
+
+use std::fs;
+use std::path::PathBuf;
+
+/// A fresh directory for one test's `.env`.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust_logger-build-level-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn the_dotenv_key_wins() {
+    let dir = scratch("dotenv");
+    let dotenv = dir.join(".env");
+    fs::write(&dotenv, "# comment\n\nOTHER=1\n  LOG_LEVEL= warn \nLOG_LEVEL=error\n").unwrap();
+
+    let (value, source) = level::resolve_level(&dotenv, Some(String::from("info")));
+    assert_eq!(value, "warn");
+    assert_eq!(source, format!(".env:{}", fs::canonicalize(&dotenv).unwrap().display()));
+    assert_eq!(level::default_warning(&source), None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn the_environment_without_a_dotenv_key() {
+    let dir = scratch("env");
+    let dotenv = dir.join(".env");
+    fs::write(&dotenv, "OTHER=1\n# LOG_LEVEL=warn\n").unwrap();
+
+    assert_eq!(level::resolve_level(&dotenv, Some(String::from("info"))), (String::from("info"), String::from("env")));
+    assert_eq!(level::resolve_level(&dir.join("missing"), Some(String::from("error"))).1, "env");
+    assert_eq!(level::default_warning("env"), None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn the_default_warns() {
+    let dir = scratch("default");
+
+    let (value, source) = level::resolve_level(&dir.join(".env"), None);
+    assert_eq!((value.as_str(), source.as_str()), ("debug", "default"));
+    assert_eq!(level::default_warning(&source), Some(level::DEFAULT_WARNING));
+    assert!(level::DEFAULT_WARNING.starts_with("rust_logger: LOG_LEVEL not set, defaulting to debug"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cfg_values() {
+    assert_eq!(level::level_cfg("WARN"), "warn");
+    assert_eq!(level::level_cfg("none"), "none");
+    assert_eq!(level::level_cfg("loud"), "debug");
+}
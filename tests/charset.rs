@@ -0,0 +1,86 @@
+mod common;
+
+use rust_logger::console::{self, Multiline, SharedBuffer};
+use rust_logger::display::{glyphs, set_charset, Charset};
+use rust_logger::format::{set_field_value_limit, set_message_limit};
+use rust_logger::{hexdump, log, section, separator};
+
+/// The console output of `draw` in the Unicode and in the ASCII charset.
+fn in_both_charsets(buffer: &SharedBuffer, draw: impl Fn()) -> [String; 2] {
+    [Charset::Unicode, Charset::Ascii].map(|charset| {
+        set_charset(charset);
+        buffer.clear();
+        draw();
+        set_charset(Charset::Unicode);
+        buffer.text()
+    })
+}
+
+#[test]
+fn separator_and_section_rules() {
+    let (_guard, buffer) = common::capture();
+    // 80 columns for a writer, minus the 20 of the prefix and the trailing space.
+    let [unicode, ascii] = in_both_charsets(&buffer, || separator!());
+    assert_eq!(unicode, format!(" LOG  [TS] charset: {} \n", "\u{2500}".repeat(59)));
+    assert_eq!(ascii, format!(" LOG  [TS] charset: {} \n", "-".repeat(59)));
+
+    let [unicode, ascii] = in_both_charsets(&buffer, || section!("Setup"));
+    let rule = |glyph: &str| format!("{} Setup {}", glyph.repeat(26), glyph.repeat(26));
+    assert_eq!(unicode, format!(" LOG  [TS] charset: {} \n", rule("\u{2500}")));
+    assert_eq!(ascii, format!(" LOG  [TS] charset: {} \n", rule("-")));
+}
+
+#[test]
+fn hexdump_gutters() {
+    let (_guard, buffer) = common::capture();
+    let [unicode, ascii] = in_both_charsets(&buffer, || hexdump!("frame", b"GET /\x00\x01"));
+    // Nine missing bytes, the space between the groups and the one before the gutter.
+    let row = |gutter: &str| {
+        format!(" DBG  [TS] charset: 00000000  47 45 54 20 2f 00 01 {:29}{gutter}GET /..{gutter} \n", "")
+    };
+    let header = " DBG  [TS] charset: frame (7 bytes) \n";
+    assert_eq!(unicode, format!("{header}{}", row("\u{2502}")));
+    assert_eq!(ascii, format!("{header}{}", row("|")));
+}
+
+#[test]
+fn continuation_gutter() {
+    let (_guard, buffer) = common::capture();
+    console::set_multiline(Multiline::Gutter);
+    let [unicode, ascii] = in_both_charsets(&buffer, || log!("one\ntwo"));
+    console::set_multiline(Multiline::Align);
+    assert_eq!(unicode, format!(" LOG  [TS] charset: one \n{:20}\u{2502} two \n", ""));
+    assert_eq!(ascii, format!(" LOG  [TS] charset: one \n{:20}| two \n", ""));
+}
+
+#[test]
+fn truncation_ellipses() {
+    let (_guard, buffer) = common::capture();
+    console::set_target_width(6);
+    let [unicode, ascii] = in_both_charsets(&buffer, || log!("narrow"));
+    console::set_target_width(0);
+    assert_eq!(unicode, " LOG  [TS] \u{2026}arset: narrow \n");
+    assert_eq!(ascii, " LOG  [TS] ...set: narrow \n");
+
+    set_message_limit(5);
+    let [unicode, ascii] = in_both_charsets(&buffer, || log!("abcdefgh"));
+    set_message_limit(0);
+    assert_eq!(unicode, " LOG  [TS] charset: abcde\u{2026}(+3 chars) \n");
+    assert_eq!(ascii, " LOG  [TS] charset: abcde...(+3 chars) \n");
+
+    set_field_value_limit(2);
+    let [unicode, ascii] = in_both_charsets(&buffer, || log!("done"; user = "alice"));
+    set_field_value_limit(0);
+    assert_eq!(unicode, " LOG  [TS] charset: done user=al\u{2026}(+3 chars) \n");
+    assert_eq!(ascii, " LOG  [TS] charset: done user=al...(+3 chars) \n");
+}
+
+#[test]
+fn every_ascii_glyph_is_ascii() {
+    let (_guard, _buffer) = common::capture();
+    set_charset(Charset::Ascii);
+    let ascii = *glyphs();
+    set_charset(Charset::Unicode);
+    let glyphs = [ascii.ellipsis, ascii.gutter, ascii.marker, ascii.rule, ascii.replacement, ascii.wrap];
+    assert!(glyphs.iter().all(|glyph| !glyph.is_empty() && glyph.is_ascii()), "{ascii:?}");
+}
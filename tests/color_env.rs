@@ -0,0 +1,70 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use rust_logger::console::{ColorChoice, ColorEnv};
+
+/// The environment read from `vars` for the executable at `exe`.
+fn env_of(vars: &[(&str, &str)], exe: &str) -> ColorEnv {
+    let var = |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| OsString::from(value));
+    ColorEnv::from_vars(var, Some(Path::new(exe)))
+}
+
+const APP: &str = "/usr/local/bin/app";
+const TEST_EXE: &str = "/work/target/debug/deps/app-0123abcd";
+
+#[test]
+fn variables_are_read_into_the_environment() {
+    let plain = env_of(&[], APP);
+    assert_eq!(plain, ColorEnv { no_color: false, stdout_is_tty: false, writer_set: false, in_test: false, cargo_term_color: None });
+
+    assert!(env_of(&[("NO_COLOR", "1")], APP).no_color);
+    assert!(!env_of(&[("NO_COLOR", "")], APP).no_color, "an empty NO_COLOR is unset");
+
+    assert!(env_of(&[("RUST_TEST_THREADS", "1")], APP).in_test);
+    assert!(env_of(&[("CARGO", "/usr/bin/cargo")], TEST_EXE).in_test);
+    assert!(!env_of(&[], TEST_EXE).in_test, "a deps directory without cargo");
+    assert!(!env_of(&[("CARGO", "/usr/bin/cargo")], APP).in_test, "cargo run");
+    assert!(!ColorEnv::from_vars(|name| (name == "CARGO").then(OsString::new), None).in_test);
+
+    for (value, parsed) in [("always", Some(true)), ("ALWAYS", Some(true)), ("never", Some(false)), ("Never", Some(false)), ("auto", None), ("sometimes", None)] {
+        assert_eq!(env_of(&[("CARGO_TERM_COLOR", value)], APP).cargo_term_color, parsed, "CARGO_TERM_COLOR={value}");
+    }
+}
+
+#[test]
+fn the_decision_over_a_matrix_of_environments() {
+    let tty = ColorEnv { stdout_is_tty: true, ..env_of(&[], APP) };
+    let writer = ColorEnv { writer_set: true, ..env_of(&[], APP) };
+    // (environment, Auto, AlwaysInTests)
+    for (env, auto, in_tests) in [
+        (env_of(&[], APP), false, false),
+        (tty, true, true),
+        (writer, true, true),
+        (ColorEnv { no_color: true, ..tty }, false, false),
+        (ColorEnv { no_color: true, ..writer }, false, false),
+        (env_of(&[("RUST_TEST_THREADS", "4")], APP), false, true),
+        (env_of(&[("CARGO", "cargo")], TEST_EXE), false, true),
+        (env_of(&[("CARGO", "cargo"), ("CARGO_TERM_COLOR", "always")], TEST_EXE), false, true),
+        (env_of(&[("CARGO", "cargo"), ("CARGO_TERM_COLOR", "auto")], TEST_EXE), false, true),
+        (env_of(&[("CARGO", "cargo"), ("CARGO_TERM_COLOR", "never")], TEST_EXE), false, false),
+        (ColorEnv { stdout_is_tty: true, ..env_of(&[("RUST_TEST_THREADS", "1"), ("CARGO_TERM_COLOR", "never")], APP) }, true, false),
+        // NO_COLOR wins over everything but Always.
+        (env_of(&[("CARGO", "cargo"), ("NO_COLOR", "1")], TEST_EXE), false, false),
+        (env_of(&[("CARGO", "cargo"), ("NO_COLOR", "1"), ("CARGO_TERM_COLOR", "always")], TEST_EXE), false, false),
+        // Production binaries ignore CARGO_TERM_COLOR.
+        (env_of(&[("CARGO_TERM_COLOR", "always")], APP), false, false),
+        (ColorEnv { stdout_is_tty: true, ..env_of(&[("CARGO_TERM_COLOR", "never")], APP) }, true, true),
+    ] {
+        assert_eq!(env.colors(ColorChoice::Auto), auto, "Auto in {env:?}");
+        assert_eq!(env.colors(ColorChoice::AlwaysInTests), in_tests, "AlwaysInTests in {env:?}");
+        assert!(env.colors(ColorChoice::Always), "Always in {env:?}");
+        assert!(!env.colors(ColorChoice::Never), "Never in {env:?}");
+    }
+}
+
+#[test]
+fn this_process_is_a_test() {
+    let env = ColorEnv::current();
+    assert!(env.in_test, "{env:?}");
+    assert_eq!(env.no_color, std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()));
+}
@@ -0,0 +1,27 @@
+//! Shared by the integration tests. The logger is global, so every test
+//! that changes its settings or reads its output holds `capture()`'s guard.
+
+#![allow(dead_code)]
+
+use std::sync::{Mutex, MutexGuard};
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice, SharedBuffer};
+use rust_logger::sink;
+use rust_logger::{level, Level};
+
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Stable, uncolored console output into a fresh buffer at level Debug.
+pub fn capture() -> (MutexGuard<'static, ()>, SharedBuffer) {
+    let guard = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // Sinks added by one test would get the records of the ones before.
+    sink::finish_initialization();
+    set_stable_test_format(true);
+    console::set_color_choice(ColorChoice::Never);
+    level::clear_module_levels();
+    level::set_level(Level::Debug);
+    let buffer = SharedBuffer::new();
+    console::set_writer(Box::new(buffer.clone()));
+    (guard, buffer)
+}
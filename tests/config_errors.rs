@@ -0,0 +1,93 @@
+mod common;
+
+use std::error::Error as _;
+use std::io;
+
+use rust_logger::config::ConfigError;
+use rust_logger::display::{self, glyphs, Charset};
+use rust_logger::format::Format;
+use rust_logger::level::{self, try_parse_level};
+use rust_logger::sink::SinkKind;
+use rust_logger::theme::{self, set_level_style, Color, LevelStyle, Theme};
+use rust_logger::{log, Level};
+
+#[test]
+fn each_variant_comes_from_a_bad_input() {
+    assert!(matches!(try_parse_level("verbose"), Err(ConfigError::UnknownLevel(name)) if name == "verbose"));
+    assert!(matches!(Theme::parse("neon"), Err(ConfigError::UnknownTheme(name)) if name == "neon"));
+    assert!(matches!(Charset::parse("ebcdic"), Err(ConfigError::UnknownCharset(name)) if name == "ebcdic"));
+    assert!(matches!(Format::parse("xml"), Err(ConfigError::UnknownFormat(name)) if name == "xml"));
+    assert!(matches!(Color::parse("38;5;x"), Err(ConfigError::BadColor(value)) if value == "38;5;x"));
+    assert!(matches!(LevelStyle::try_new("LOG", "44", "red", "", ""), Err(ConfigError::BadColor(value)) if value == "red"));
+    assert!(matches!(level::parse_level_spec("warn,=debug"), Err(ConfigError::BadLevelSpec(entry)) if entry == "=debug"));
+    assert!(matches!(SinkKind::parse("screen"), Err(ConfigError::UnknownSinkKind(name)) if name == "screen"));
+    assert!(matches!(set_level_style(Level::None, LevelStyle::new("OFF", "", "", "", "")), Err(ConfigError::StyleForNone)));
+    #[cfg(feature = "timestamps")]
+    {
+        use rust_logger::time::TimestampFormat;
+        assert!(matches!(TimestampFormat::parse("iso"), Err(ConfigError::BadTimestampFormat(spec)) if spec == "iso"));
+    }
+
+    let console = ConfigError::from(io::Error::new(io::ErrorKind::Unsupported, "no tty"));
+    assert!(matches!(console, ConfigError::Console(_)));
+    assert_eq!(console.to_string(), "console: no tty");
+    assert_eq!(console.source().unwrap().to_string(), "no tty");
+}
+
+#[test]
+fn messages_name_the_input_and_what_was_expected() {
+    assert_eq!(try_parse_level("verbose").unwrap_err().to_string(), "unknown level \"verbose\": expected debug, info, warn, error or none");
+    assert_eq!(Charset::parse("ebcdic").unwrap_err().to_string(), "unknown charset \"ebcdic\": expected unicode, ascii or auto");
+    assert!(Theme::parse("neon").unwrap_err().source().is_none());
+}
+
+#[test]
+fn strict_setters_leave_the_setting_alone() {
+    let (_guard, buffer) = common::capture();
+    assert!(matches!(level::set_level_by_name("verbose"), Err(ConfigError::UnknownLevel(_))));
+    assert_eq!(level::level(), Level::Debug);
+    assert!(matches!(theme::set_theme_by_name("neon"), Err(ConfigError::UnknownTheme(_))));
+    display::set_charset(Charset::Ascii);
+    assert!(matches!(display::set_charset_by_name("ebcdic"), Err(ConfigError::UnknownCharset(_))));
+    assert_eq!(glyphs().ellipsis, "...");
+    display::set_charset(Charset::Unicode);
+    assert_eq!(buffer.text(), "", "the strict forms do not warn");
+}
+
+#[test]
+fn lenient_setters_warn_and_fall_back() {
+    let (_guard, buffer) = common::capture();
+    level::set_level_by_name_or_default("verbose");
+    assert_eq!(level::level(), Level::Debug);
+
+    theme::set_theme_by_name("colorblind").unwrap();
+    theme::set_theme_by_name_or_default("neon");
+    assert_eq!(theme::theme().preset_name(), "default");
+
+    display::set_charset(Charset::Ascii);
+    display::set_charset_by_name_or_default("ebcdic");
+    assert_eq!(glyphs().ellipsis, "\u{2026}");
+
+    log!("still logging");
+    // The theme warning is still drawn with the colorblind marker.
+    assert_eq!(
+        buffer.text(),
+        " WRN  [TS] [logger]: keeping level Debug: unknown level \"verbose\": expected debug, info, warn, error or none \n\
+         \x20!! WRN  [TS] [logger]: using the default theme: unknown theme \"neon\": expected default, colorblind or high-contrast \n\
+         \x20WRN  [TS] [logger]: using the unicode charset: unknown charset \"ebcdic\": expected unicode, ascii or auto \n\
+         \x20LOG  [TS] config_errors: still logging \n"
+    );
+}
+
+#[test]
+fn lenient_setters_apply_good_names_silently() {
+    let (_guard, buffer) = common::capture();
+    level::set_level_by_name_or_default("warn");
+    assert_eq!(level::level(), Level::Warn);
+    level::set_level(Level::Debug);
+    display::set_charset_by_name_or_default("ascii");
+    assert_eq!(glyphs().ellipsis, "...");
+    display::set_charset(Charset::Unicode);
+    theme::set_theme_by_name_or_default("default");
+    assert_eq!(buffer.text(), "");
+}
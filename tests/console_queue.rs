@@ -0,0 +1,86 @@
+mod common;
+
+use rust_logger::console::{self, SharedBuffer};
+use rust_logger::log;
+
+#[test]
+fn lines_keep_their_order_per_thread() {
+    let (_guard, buffer) = common::capture();
+    // Small, so the threads wait for room.
+    console::set_console_queue(16).unwrap();
+    let threads: Vec<_> = (0..8)
+        .map(|thread| std::thread::spawn(move || for i in 0..1000 { log!("t{thread} {i}") }))
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    console::flush_console_queue();
+    console::set_console_queue(0).unwrap();
+
+    let text = buffer.text();
+    let mut next = [0; 8];
+    for line in text.lines() {
+        let message = line.strip_prefix(" LOG  [TS] console_queue: t").and_then(|rest| rest.strip_suffix(' ')).unwrap();
+        let (thread, i) = message.split_once(' ').unwrap();
+        let thread: usize = thread.parse().unwrap();
+        assert_eq!(i.parse::<i32>().unwrap(), next[thread], "{line}");
+        next[thread] += 1;
+    }
+    assert_eq!(next, [1000; 8]);
+}
+
+#[test]
+fn turning_the_queue_off_writes_what_is_left() {
+    let (_guard, buffer) = common::capture();
+    console::set_console_queue(1024).unwrap();
+    assert_eq!(console::console_queue(), 1024);
+    for i in 0..100 {
+        log!("queued {i}");
+    }
+    console::set_console_queue(0).unwrap();
+    assert_eq!(buffer.text().lines().count(), 100, "written before set_console_queue returns");
+
+    log!("direct");
+    assert!(buffer.text().ends_with(" LOG  [TS] console_queue: direct \n"));
+}
+
+#[test]
+fn lines_go_to_the_writer_set_when_they_were_logged() {
+    let (_guard, first) = common::capture();
+    console::set_console_queue(1024).unwrap();
+    log!("to the first");
+    let second = SharedBuffer::new();
+    console::set_writer(Box::new(second.clone()));
+    log!("to the second");
+    console::set_console_queue(0).unwrap();
+
+    assert_eq!(first.text(), " LOG  [TS] console_queue: to the first \n");
+    assert_eq!(second.text(), " LOG  [TS] console_queue: to the second \n");
+}
+
+const CHILD: &str = "RUST_LOGGER_CONSOLE_QUEUE_CHILD";
+
+/// In the child: queues records, shuts down and prints how many were
+/// written.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return }
+    let (_guard, buffer) = common::capture();
+    console::set_console_queue(4096).unwrap();
+    for i in 0..2000 {
+        log!("record {i}");
+    }
+    console::shutdown();
+    eprintln!("written: {}", buffer.text().lines().count());
+}
+
+#[test]
+fn shutdown_writes_the_queue() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("written: 2000\n"), "{stderr}");
+}
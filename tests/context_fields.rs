@@ -0,0 +1,159 @@
+mod common;
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use rust_logger::context::{self, DuplicateFieldPolicy};
+use rust_logger::log;
+
+/// Polls `future` to completion on this thread.
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut cx) { return value; }
+    }
+}
+
+/// Yields once, so the task is polled twice.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if std::mem::replace(&mut self.0, true) { Poll::Ready(()) } else { Poll::Pending }
+    }
+}
+
+/// The fields of the record logged with `key` set in the two layers, under
+/// `policy`, and the `[logger]` lines after it.
+fn collide(policy: DuplicateFieldPolicy, first: &str, second: &str) -> String {
+    let (_guard, buffer) = common::capture();
+    context::set_duplicate_field_policy(policy);
+    let layers = [first, second];
+    let global = layers.contains(&"global");
+    if global { context::set_global_field("key", "global"); }
+    let _thread = layers.contains(&"thread").then(|| context::push_field("key", "thread"));
+    let log_it = || {
+        if layers.contains(&"call") {
+            log!("m"; key = "call");
+        } else {
+            log!("m");
+        }
+    };
+    if layers.contains(&"task") {
+        block_on(context::with_task_fields(&[("key", &"task")], async { log_it() }));
+    } else {
+        log_it();
+    }
+    drop(_thread);
+    context::clear_global_fields();
+    context::set_duplicate_field_policy(DuplicateFieldPolicy::LastWins);
+    buffer.text().replace(" LOG  [TS] context_fields: m", "")
+}
+
+const PAIRS: [(&str, &str); 6] =
+    [("global", "thread"), ("global", "task"), ("global", "call"), ("thread", "task"), ("thread", "call"), ("task", "call")];
+
+#[test]
+fn last_wins_keeps_the_later_layer() {
+    for (first, second) in PAIRS {
+        assert_eq!(collide(DuplicateFieldPolicy::LastWins, first, second), format!(" key={second} \n"), "{first}/{second}");
+    }
+}
+
+#[test]
+fn first_wins_keeps_the_earlier_layer() {
+    for (first, second) in PAIRS {
+        assert_eq!(collide(DuplicateFieldPolicy::FirstWins, first, second), format!(" key={first} \n"), "{first}/{second}");
+    }
+}
+
+#[test]
+fn keep_both_numbers_the_later_key() {
+    for (first, second) in PAIRS {
+        assert_eq!(
+            collide(DuplicateFieldPolicy::KeepBoth, first, second),
+            format!(" key={first} key#2={second} \n"),
+            "{first}/{second}"
+        );
+    }
+}
+
+#[test]
+fn error_meta_names_the_key_and_both_sources() {
+    for (first, second) in PAIRS {
+        let text = collide(DuplicateFieldPolicy::ErrorMeta, first, second);
+        assert_eq!(
+            text,
+            format!(
+                " key={second} \n WRN  [TS] [logger]: duplicate field \"key\" from {first} and {second}; the {second} value is kept \n"
+            ),
+            "{first}/{second}"
+        );
+    }
+}
+
+#[test]
+fn duplicates_in_one_call_follow_the_policy() {
+    let (_guard, buffer) = common::capture();
+    context::set_duplicate_field_policy(DuplicateFieldPolicy::KeepBoth);
+    log!("m"; a = 1, a = 2, a = 3);
+    context::set_duplicate_field_policy(DuplicateFieldPolicy::FirstWins);
+    log!("m"; a = 1, a = 2);
+    context::set_duplicate_field_policy(DuplicateFieldPolicy::LastWins);
+    log!("m"; a = 1, a = 2);
+    assert_eq!(
+        buffer.text(),
+        " LOG  [TS] context_fields: m a=1 a#2=2 a#3=3 \n LOG  [TS] context_fields: m a=1 \n LOG  [TS] context_fields: m a=2 \n"
+    );
+}
+
+#[test]
+fn layers_add_up_in_order_and_can_be_sorted() {
+    let (_guard, buffer) = common::capture();
+    context::set_global_field("service", "api");
+    context::set_global_field("service", "billing");
+    let guard = context::push_field("request", 17);
+    block_on(context::with_task_fields(&[("task", &"sync")], async {
+        log!("first"; b = 1);
+        YieldOnce(false).await;
+        log!("second"; b = 2);
+    }));
+    log!("outside task");
+    drop(guard);
+    log!("after guard");
+    context::set_sort_fields(true);
+    context::set_duplicate_field_policy(DuplicateFieldPolicy::KeepBoth);
+    log!("sorted"; zeta = 1, service = "call", alpha = 2);
+    context::set_duplicate_field_policy(DuplicateFieldPolicy::LastWins);
+    context::set_sort_fields(false);
+    context::remove_global_field("service");
+    log!("none");
+
+    assert_eq!(
+        buffer.text(),
+        concat!(
+            " LOG  [TS] context_fields: first service=billing request=17 task=sync b=1 \n",
+            " LOG  [TS] context_fields: second service=billing request=17 task=sync b=2 \n",
+            " LOG  [TS] context_fields: outside task service=billing request=17 \n",
+            " LOG  [TS] context_fields: after guard service=billing \n",
+            " LOG  [TS] context_fields: sorted alpha=2 service=billing service#2=call zeta=1 \n",
+            " LOG  [TS] context_fields: none \n",
+        )
+    );
+}
+
+#[test]
+fn thread_fields_stay_on_their_thread() {
+    let (_guard, buffer) = common::capture();
+    let _field = context::push_field("worker", "main");
+    std::thread::spawn(|| log!("other thread")).join().unwrap();
+    log!("this thread");
+    assert_eq!(
+        buffer.text(),
+        " LOG  [TS] context_fields: other thread \n LOG  [TS] context_fields: this thread worker=main \n"
+    );
+}
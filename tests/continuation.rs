@@ -0,0 +1,154 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rust_logger::console::{self, Multiline};
+use rust_logger::file;
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, ContinuationStyle, Sink};
+use rust_logger::{log, record_group, Level};
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+fn temp_log(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rust_logger-continuation-{name}-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[derive(Debug)]
+struct SaveError(std::io::Error);
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cannot write")
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// The `#seq` a `RepeatPrefix` line shows.
+fn seq_of(line: &str) -> &str {
+    line.split(' ').find(|word| word.starts_with('#')).unwrap()
+}
+
+/// The line a sink in `style` gets for a three-line message.
+fn sink_line(style: ContinuationStyle) -> String {
+    let lines = Collect::default();
+    let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+    assert!(sink::set_sink_continuation(id, style));
+    log!("one\ntwo\nthree");
+    sink::remove_sink(id);
+    let lines = lines.0.lock().unwrap();
+    lines[0].clone()
+}
+
+#[test]
+fn each_style_draws_the_lines_after_the_first() {
+    let (_guard, _buffer) = common::capture();
+
+    assert_eq!(sink_line(ContinuationStyle::Indent), "[LOG] [TS] continuation: one\n                         two\n                         three");
+    assert_eq!(sink_line(ContinuationStyle::Gutter), "[LOG] [TS] continuation: one\n                         │ two\n                         │ three");
+    assert_eq!(sink_line(ContinuationStyle::None), "[LOG] [TS] continuation: one\ntwo\nthree");
+
+    let repeated = sink_line(ContinuationStyle::RepeatPrefix);
+    let seq = seq_of(&repeated);
+    assert_eq!(
+        repeated,
+        format!("[LOG] [TS] continuation: {seq} one\n[LOG] [TS] continuation: {seq} two\n[LOG] [TS] continuation: {seq} three")
+    );
+    let next = sink_line(ContinuationStyle::RepeatPrefix);
+    assert_ne!(seq_of(&next), seq, "each record has its own number");
+}
+
+#[test]
+fn single_lines_and_unset_sinks_are_unchanged() {
+    let (_guard, _buffer) = common::capture();
+
+    let lines = Collect::default();
+    let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+    sink::set_sink_continuation(id, ContinuationStyle::RepeatPrefix);
+    log!("just one");
+    sink::remove_sink(id);
+    assert_eq!(lines.0.lock().unwrap()[0], "[LOG] [TS] continuation: just one");
+
+    // Without a style of its own, a sink follows the console.
+    console::set_multiline(Multiline::Gutter);
+    let lines = Collect::default();
+    let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+    log!("one\ntwo");
+    sink::remove_sink(id);
+    console::set_multiline(Multiline::Align);
+    assert_eq!(lines.0.lock().unwrap()[0], "[LOG] [TS] continuation: one\n                         │ two");
+    assert!(!sink::set_sink_continuation(id, ContinuationStyle::None), "the sink is gone");
+}
+
+#[test]
+fn files_and_the_console_have_their_own_styles() {
+    let (_guard, buffer) = common::capture();
+    console::set_multiline(Multiline::Gutter);
+    let (repeated, following) = (temp_log("repeat"), temp_log("follow"));
+
+    let repeat_guard = file::file_sink(&repeated).session_header(false).continuation(ContinuationStyle::RepeatPrefix).open().unwrap();
+    let follow_guard = file::file_sink(&following).session_header(false).open().unwrap();
+    log!("one\ntwo\nthree");
+    drop((repeat_guard, follow_guard));
+    console::set_multiline(Multiline::Align);
+
+    let text = fs::read_to_string(&repeated).unwrap();
+    let seq = seq_of(&text);
+    assert_eq!(
+        text,
+        format!("[LOG] [TS] continuation: {seq} one\n[LOG] [TS] continuation: {seq} two\n[LOG] [TS] continuation: {seq} three\n")
+    );
+    let gutter = "[LOG] [TS] continuation: one\n                         │ two\n                         │ three\n";
+    assert_eq!(fs::read_to_string(&following).unwrap(), gutter);
+    assert_eq!(buffer.text(), " LOG  [TS] continuation: one \n                         │ two \n                         │ three \n");
+    for path in [repeated, following] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[test]
+fn causes_and_record_groups_follow_the_style() {
+    let (_guard, _buffer) = common::capture();
+    let path = temp_log("causes");
+
+    let guard = file::file_sink(&path).session_header(false).continuation(ContinuationStyle::RepeatPrefix).open().unwrap();
+    rust_logger::error!(err = SaveError(std::io::Error::other("disk full")), "save failed");
+    {
+        let group = record_group!(Level::Info);
+        group.log("a\nb");
+        group.log("c");
+    }
+    drop(guard);
+
+    let text = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let (first, second) = (seq_of(lines[0]), seq_of(lines[2]));
+    assert_eq!(
+        lines,
+        [
+            format!("[ERR] [TS] continuation: {first} save failed: cannot write").as_str(),
+            &format!("[ERR] [TS] continuation: {first} caused by: disk full"),
+            &format!("[LOG] [TS] continuation: {second} a"),
+            &format!("[LOG] [TS] continuation: {second} b"),
+            "[LOG] [TS] continuation: c",
+        ]
+    );
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use rust_logger::file::{self, RotationPolicy};
+use rust_logger::format::{Destination, Format, Record};
+use rust_logger::sink::{self, Sink, SinkKind};
+use rust_logger::{debug, error, log, warn, Level};
+
+/// Keeps every message it receives, and says it is of `kind`.
+#[derive(Clone)]
+struct Collect(Arc<Mutex<Vec<String>>>, SinkKind);
+
+impl Collect {
+    fn new(kind: SinkKind) -> Collect {
+        Collect(Arc::default(), kind)
+    }
+
+    fn messages(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Sink for Collect {
+    fn write(&mut self, record: &Record, _line: &str) {
+        self.0.lock().unwrap().push(format!("{} {:?}", record.message, record.destination));
+    }
+
+    fn kind(&self) -> SinkKind {
+        self.1
+    }
+}
+
+#[test]
+fn each_destination_reaches_its_kind() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-destinations-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let console = Collect::new(SinkKind::Console);
+    let files = Collect::new(SinkKind::File);
+    let console_id = sink::add_sink(Box::new(console.clone()), Level::Debug, Format::Pretty);
+    let file_id = sink::add_sink(Box::new(files.clone()), Level::Debug, Format::Pretty);
+    let log_file = file::file_sink(&path).rotation(RotationPolicy::Never).session_header(false).open().unwrap();
+
+    log!("everywhere");
+    debug!(dest: console, "spinner");
+    warn!(dest: file, "for the record");
+    error!(dest: all, "also everywhere");
+    drop(log_file);
+    sink::remove_sink(console_id);
+    sink::remove_sink(file_id);
+
+    assert_eq!(
+        buffer.text(),
+        concat!(
+            " LOG  [TS] destinations: everywhere \n",
+            " DBG  [TS] destinations: spinner \n",
+            " ERR  [TS] destinations: also everywhere \n",
+        )
+    );
+    assert_eq!(console.messages(), ["everywhere All", "spinner Console", "also everywhere All"]);
+    assert_eq!(files.messages(), ["everywhere All", "for the record File", "also everywhere All"]);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "[LOG] [TS] destinations: everywhere\n[WRN] [TS] destinations: for the record\n[ERR] [TS] destinations: also everywhere\n"
+    );
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn an_unknown_destination_drops_the_record_and_warns_once() {
+    let (_guard, buffer) = common::capture();
+
+    log!(dest: printer, "lost");
+    log!(dest: printer, "lost again");
+
+    let text = buffer.text();
+    assert!(!text.contains("lost"), "{text}");
+    let reports: Vec<&str> = text.lines().filter(|line| line.contains("[logger]")).collect();
+    assert_eq!(
+        reports,
+        [" WRN  [TS] [logger]: unknown log destination \"printer\": expected all, console or file; the record is dropped "]
+    );
+}
+
+#[test]
+fn destinations_parse_by_name() {
+    assert_eq!(Destination::parse("console"), Some(Destination::Console));
+    assert_eq!(Destination::parse("FILE"), Some(Destination::File));
+    assert_eq!(Destination::parse("all"), Some(Destination::All));
+    assert_eq!(Destination::parse("stdout"), None);
+}
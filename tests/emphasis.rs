@@ -0,0 +1,86 @@
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+use rust_logger::theme::{self, Theme};
+#[cfg(feature = "timestamps")]
+use rust_logger::time::{self, TimestampFormat};
+use rust_logger::{debug, error, file, log, warn};
+
+#[test]
+fn every_macro_takes_the_flag() {
+    let (_guard, buffer) = common::capture();
+    let detail = "disk 3";
+    debug!(emphasis: true, "debug {detail}");
+    log!(emphasis: true, "info {}", detail);
+    warn!(emphasis: true, "warn");
+    error!(emphasis: true, "DATA LOSS RISK: {}", detail);
+    // Any bool expression, and `false` is a plain record.
+    let count = 2;
+    error!(emphasis: count > 1, "computed");
+    error!(emphasis: false, "not emphasized");
+    // After the call site prefixes.
+    warn!(once: emphasis: true, "once");
+    log!(every: 1, emphasis: true, "every");
+
+    assert_eq!(buffer.text(), [
+        " DBG  [TS] emphasis: !!! debug disk 3 !!! \n",
+        " LOG  [TS] emphasis: !!! info disk 3 !!! \n",
+        " WRN  [TS] emphasis: !!! warn !!! \n",
+        " ERR  [TS] emphasis: !!! DATA LOSS RISK: disk 3 !!! \n",
+        " ERR  [TS] emphasis: !!! computed !!! \n",
+        " ERR  [TS] emphasis: not emphasized \n",
+        " WRN  [TS] emphasis: !!! once !!! \n",
+        " LOG  [TS] emphasis: !!! every !!! \n",
+    ].concat());
+}
+
+#[test]
+fn a_disabled_level_drops_the_record() {
+    let (_guard, buffer) = common::capture();
+    rust_logger::level::set_level(rust_logger::Level::Warn);
+    log!(emphasis: true, "hidden");
+    warn!(emphasis: true, "shown");
+
+    assert_eq!(buffer.text(), " WRN  [TS] emphasis: !!! shown !!! \n");
+}
+
+#[test]
+fn colors_use_the_alert_style_and_keep_the_label() {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    theme::set_theme(Theme::default());
+    error!(emphasis: true, "alert");
+    error!("normal");
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::Standard);
+    set_stable_test_format(true);
+
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    let alert = format!("\x1b[{}m", Theme::default().alert);
+    let normal = format!("\x1b[{}m", Theme::default().error.msg_color);
+    // The same label, then the alert style instead of the level's.
+    let label = |line: &str| line.split(" \x1b[0m").next().unwrap().to_string();
+    assert_eq!(label(lines[0]), label(lines[1]));
+    assert!(lines[0].contains(&format!("{alert}alert")), "{:?}", lines[0]);
+    assert!(lines[1].contains(&format!("{normal}normal")), "{:?}", lines[1]);
+    // No markers when the style shows the emphasis.
+    assert!(!text.contains("!!!"), "{text:?}");
+}
+
+#[test]
+fn files_get_the_markers() {
+    let (_guard, _buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-emphasis-{}.log", std::process::id()));
+    let sink = file::file_sink(&path).session_header(false).open().unwrap();
+    error!(emphasis: true, "in the file");
+    drop(sink);
+    let text = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(text, "[ERR] [TS] emphasis: !!! in the file !!!\n");
+}
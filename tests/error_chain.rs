@@ -0,0 +1,90 @@
+mod common;
+
+use std::error::Error;
+use std::fmt;
+
+use rust_logger::error;
+
+#[derive(Debug)]
+struct Layered(&'static str, Option<Box<Layered>>);
+
+impl fmt::Display for Layered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Error for Layered {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.1.as_deref().map(|source| source as _)
+    }
+}
+
+#[test]
+fn the_error_follows_the_message_and_its_sources_go_under_it() {
+    let (_guard, buffer) = common::capture();
+
+    let chain = Layered("pool exhausted", Some(Box::new(Layered("connect to db:5432", Some(Box::new(Layered("connection refused", None)))))));
+    error!(err = chain, "db connection failed");
+
+    assert_eq!(buffer.text(), concat!(
+        " ERR  [TS] error_chain: db connection failed: pool exhausted \n",
+        "                        caused by: connect to db:5432 \n",
+        "                        caused by: connection refused \n",
+    ));
+}
+
+#[test]
+fn an_error_without_a_source_has_no_cause_lines() {
+    let (_guard, buffer) = common::capture();
+
+    error!(err = Layered("refused", None), "call failed");
+
+    assert_eq!(buffer.text(), " ERR  [TS] error_chain: call failed: refused \n");
+}
+
+#[test]
+fn boxed_errors_are_taken_as_they_are() {
+    let (_guard, buffer) = common::capture();
+
+    let boxed: Box<dyn Error> = Box::new(Layered("pool exhausted", Some(Box::new(Layered("connection refused", None)))));
+    error!(err = boxed, "db connection failed");
+    let shared: Box<dyn Error + Send + Sync> = "timed out".into();
+    error!(err = shared, "call failed");
+    let borrowed: &dyn Error = &*boxed;
+    error!(err = borrowed, "again");
+
+    assert_eq!(buffer.text(), concat!(
+        " ERR  [TS] error_chain: db connection failed: pool exhausted \n",
+        "                        caused by: connection refused \n",
+        " ERR  [TS] error_chain: call failed: timed out \n",
+        " ERR  [TS] error_chain: again: pool exhausted \n",
+        "                        caused by: connection refused \n",
+    ));
+}
+
+struct LogsOnDrop;
+
+impl Drop for LogsOnDrop {
+    fn drop(&mut self) {
+        error!(err = Layered("refused", None), "while the thread goes away");
+    }
+}
+
+thread_local! {
+    static LOGS_ON_DROP: LogsOnDrop = const { LogsOnDrop };
+}
+
+#[test]
+fn an_error_from_a_thread_local_destructor_does_not_panic() {
+    let (_guard, buffer) = common::capture();
+
+    // Destructors run last to first: the error chain's goes before this one.
+    let thread = std::thread::spawn(|| {
+        LOGS_ON_DROP.with(|_| ());
+        error!(err = Layered("refused", None), "before");
+    });
+    thread.join().expect("logging from a destructor panicked");
+
+    assert!(buffer.text().contains("while the thread goes away"), "{}", buffer.text());
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::console::{self, EventCounter};
+use rust_logger::format::{self, Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{log, warn, Level};
+
+/// Keeps the event number of every record.
+#[derive(Clone, Default)]
+struct Numbers(Arc<Mutex<Vec<Option<u64>>>>);
+
+impl Sink for Numbers {
+    fn write(&mut self, record: &Record, _line: &str) {
+        self.0.lock().unwrap().push(record.event_seq);
+    }
+}
+
+mod net {
+    pub fn reconnect() {
+        rust_logger::warn!("reconnecting");
+    }
+}
+
+/// Turns the counter on, from 1, for the rest of a test.
+fn counting(counter: EventCounter) {
+    console::set_event_counter(counter);
+    console::reset_counters();
+}
+
+#[test]
+fn each_target_has_its_own_counter() {
+    let (_guard, buffer) = common::capture();
+    counting(EventCounter::PerTarget);
+    log!("starting");
+    net::reconnect();
+    net::reconnect();
+    log!("ready");
+    console::set_event_counter(EventCounter::Off);
+    log!("uncounted");
+
+    assert_eq!(
+        buffer.text(),
+        concat!(
+            " LOG  [TS] event_counter #1: starting \n",
+            " WRN  [TS] event_counter::net #1: reconnecting \n",
+            " WRN  [TS] event_counter::net #2: reconnecting \n",
+            " LOG  [TS] event_counter #2: ready \n",
+            " LOG  [TS] event_counter: uncounted \n",
+        )
+    );
+}
+
+#[test]
+fn call_sites_can_have_their_own_counters() {
+    let (_guard, buffer) = common::capture();
+    counting(EventCounter::PerCallsite);
+    for _ in 0..2 {
+        log!("first");
+        log!("second");
+    }
+    console::set_event_counter(EventCounter::Off);
+
+    let text = buffer.text();
+    let numbers: Vec<&str> = text.lines().map(|line| line.split(' ').nth(5).unwrap()).collect();
+    assert_eq!(numbers, ["#1:", "#1:", "#2:", "#2:"]);
+}
+
+#[test]
+fn structured_formats_have_event_seq() {
+    let (_guard, buffer) = common::capture();
+    counting(EventCounter::PerTarget);
+    format::set_format(Format::Json);
+    warn!("low"; free = "5%");
+    format::set_format(Format::Logfmt);
+    log!("again");
+    format::set_format(Format::Pretty);
+    console::set_event_counter(EventCounter::Off);
+
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], r#"{"timestamp":"TS","level":"warn","target":"event_counter","event_seq":1,"message":"low","free":"5%"}"#);
+    assert_eq!(lines[1], "ts=TS level=info target=event_counter event_seq=2 msg=again");
+}
+
+#[test]
+fn counters_do_not_lose_records_across_threads() {
+    let (_guard, _buffer) = common::capture();
+    counting(EventCounter::PerTarget);
+    let numbers = Numbers::default();
+    let id = sink::add_sink(Box::new(numbers.clone()), Level::Debug, Format::Pretty);
+
+    let threads: Vec<_> = (0..8).map(|_| std::thread::spawn(|| for _ in 0..500 { log!("tick") })).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    sink::remove_sink(id);
+    console::set_event_counter(EventCounter::Off);
+
+    let mut numbers: Vec<u64> = numbers.0.lock().unwrap().iter().map(|seq| seq.unwrap()).collect();
+    numbers.sort_unstable();
+    assert_eq!(numbers, (1..=4000).collect::<Vec<u64>>(), "every number once, none skipped");
+}
+
+#[test]
+fn reset_starts_again_from_one() {
+    let (_guard, buffer) = common::capture();
+    counting(EventCounter::PerTarget);
+    log!("a");
+    log!("b");
+    console::reset_counters();
+    log!("c");
+    console::set_event_counter(EventCounter::Off);
+
+    assert!(buffer.text().ends_with(" LOG  [TS] event_counter #2: b \n LOG  [TS] event_counter #1: c \n"), "{}", buffer.text());
+    assert_eq!(Record::new(Level::Info, "by hand").event_seq, None);
+}
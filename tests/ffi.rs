@@ -0,0 +1,74 @@
+mod common;
+
+use std::io::{self, Write};
+
+use rust_logger::ffi::{rust_logger_log, rust_logger_set_level};
+use rust_logger::{console, level, Level};
+
+#[test]
+fn records_from_c_reach_the_console() {
+    let (_guard, buffer) = common::capture();
+    // SAFETY: every pointer is null or a NUL-terminated string.
+    unsafe {
+        rust_logger_set_level(2);
+        rust_logger_log(1, c"engine".as_ptr(), c"below the level".as_ptr());
+        rust_logger_log(2, c"engine::io".as_ptr(), c"read \xff failed".as_ptr());
+        rust_logger_log(9, std::ptr::null(), c"no target".as_ptr());
+        rust_logger_log(3, c"engine".as_ptr(), std::ptr::null());
+        rust_logger_log(3, c"bad \xfe target".as_ptr(), c"error".as_ptr());
+        rust_logger_set_level(4);
+        rust_logger_log(3, c"engine".as_ptr(), c"logging is off".as_ptr());
+        rust_logger_set_level(0);
+    }
+    assert_eq!(level::level(), Level::Debug);
+
+    assert_eq!(
+        buffer.text(),
+        concat!(
+            " WRN  [TS] engine::io: read \u{fffd} failed \n",
+            " ERR  [TS] no target \n",
+            " ERR  [TS] bad \u{fffd} target: error \n",
+        )
+    );
+}
+
+/// Panics on every write, poisoning the writer's lock as it goes.
+struct Panicking;
+
+impl Write for Panicking {
+    fn write(&mut self, _bytes: &[u8]) -> io::Result<usize> {
+        panic!("writer failed");
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+const CHILD: &str = "RUST_LOGGER_FFI_CHILD";
+
+/// In the child: a panic while logging from C stays on the Rust side.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return; }
+    let (_guard, _buffer) = common::capture();
+    console::set_writer(Box::new(Panicking));
+    // SAFETY: NUL-terminated strings.
+    unsafe {
+        rust_logger_log(1, c"engine".as_ptr(), c"panics".as_ptr());
+        rust_logger_log(1, c"engine".as_ptr(), c"now the lock is poisoned".as_ptr());
+        rust_logger_set_level(1);
+    }
+    eprintln!("returned to C");
+}
+
+#[test]
+fn a_panic_does_not_unwind_into_c() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success() && stderr.contains("returned to C\n"), "{stderr}");
+}
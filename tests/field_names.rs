@@ -0,0 +1,85 @@
+mod common;
+
+use rust_logger::context;
+use rust_logger::format::{self, Field, FieldNames, Format, JsonFormatter, LogfmtFormatter, Record, StandardKey};
+use rust_logger::{log, Level};
+
+fn record<'a>(fields: &'a [Field<'a>], causes: &'a [&'a str]) -> Record<'a> {
+    Record {
+        timestamp: Some("2024-05-01T12:00:00.000Z"), target: Some("my_app::db"), thread: Some("worker"), fields, causes,
+        suppressed: 2, ..Record::new(Level::Warn, "disk full")
+    }
+}
+
+fn both(record: &Record) -> (String, String) {
+    let (mut json, mut logfmt) = (String::new(), String::new());
+    JsonFormatter.format(record, &mut json).unwrap();
+    LogfmtFormatter.format(record, &mut logfmt).unwrap();
+    (json, logfmt)
+}
+
+/// Time first and `severity` for Splunk, `msg` for Loki.
+fn profile() -> FieldNames {
+    FieldNames::default()
+        .rename(StandardKey::Timestamp, "time")
+        .rename(StandardKey::Level, "severity")
+        .rename(StandardKey::Message, "msg")
+        .rename_field("user_id", "uid")
+        .order(&[StandardKey::Message, StandardKey::Timestamp, StandardKey::Message])
+}
+
+#[test]
+fn the_default_names_and_order_are_unchanged() {
+    let (_guard, _buffer) = common::capture();
+    let fields = [Field::display("user_id", &42)];
+    assert_eq!(
+        both(&record(&fields, &["timeout"])),
+        (
+            String::from(concat!(
+                r#"{"timestamp":"2024-05-01T12:00:00.000Z","level":"warn","thread":"worker","target":"my_app::db","#,
+                r#""message":"disk full","suppressed":2,"causes":["timeout"],"user_id":"42"}"#,
+            )),
+            String::from(
+                r#"ts=2024-05-01T12:00:00.000Z level=warn thread=worker target=my_app::db msg="disk full" suppressed=2 causes=timeout user_id=42"#
+            ),
+        )
+    );
+    assert_eq!(format::field_names(), None);
+}
+
+#[test]
+fn a_profile_renames_and_orders_both_formats() {
+    let (_guard, _buffer) = common::capture();
+    format::set_field_names(profile());
+    let fields = [Field::display("user_id", &42), Field::display("other", &"x")];
+    let lines = both(&record(&fields, &[]));
+    format::set_field_names(FieldNames::default());
+
+    assert_eq!(
+        lines,
+        (
+            String::from(concat!(
+                r#"{"msg":"disk full","time":"2024-05-01T12:00:00.000Z","severity":"warn","thread":"worker","#,
+                r#""target":"my_app::db","suppressed":2,"uid":"42","other":"x"}"#,
+            )),
+            String::from(
+                r#"msg="disk full" time=2024-05-01T12:00:00.000Z severity=warn thread=worker target=my_app::db suppressed=2 uid=42 other=x"#
+            ),
+        )
+    );
+    assert_eq!(format::field_names(), None, "the default profile is no profile");
+}
+
+#[test]
+fn renames_apply_to_context_fields_on_the_console() {
+    let (_guard, buffer) = common::capture();
+    format::set_field_names(profile());
+    format::set_format(Format::Logfmt);
+    context::set_global_field("user_id", 7);
+    log!("hi"; extra = 1);
+    context::clear_global_fields();
+    format::set_format(Format::Pretty);
+    format::set_field_names(FieldNames::default());
+
+    assert_eq!(buffer.text(), "msg=hi time=TS severity=info target=field_names uid=7 extra=1\n");
+}
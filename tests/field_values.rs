@@ -0,0 +1,108 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rust_logger::format::{self, Field, Format, JsonFormatter, JsonValue, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{log, Level};
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+fn json(fields: &[Field]) -> String {
+    let mut line = String::new();
+    JsonFormatter.format(&Record { fields, ..Record::new(Level::Info, "m") }, &mut line).unwrap();
+    line
+}
+
+#[derive(Debug)]
+struct Unregistered {
+    #[allow(dead_code)]
+    id: u32,
+}
+
+/// Registered once: the registry is global and the tests share it.
+fn register_duration() {
+    format::register_field_formatter::<Duration>(
+        |elapsed, out| write!(out, "{:.1}s", elapsed.as_secs_f64()),
+        |elapsed| JsonValue::Integer(elapsed.as_millis() as i64),
+    );
+}
+
+#[test]
+fn long_values_are_cut_to_their_limit() {
+    let (_guard, buffer) = common::capture();
+    let body = "x".repeat(100);
+    let json_lines = Collect::default();
+    let id = sink::add_sink(Box::new(json_lines.clone()), Level::Debug, Format::Json);
+
+    format::set_field_value_limit(8);
+    format::set_field_value_limit_for("query", 3);
+    format::set_field_value_limit_for("id", 0);
+    log!("request"; body = body, query = "select", id = "0123456789", short = "ok", wide = "日本語日本語日本語");
+    format::clear_field_value_limits();
+    format::set_field_value_limit(0);
+    log!("whole"; body = body);
+    sink::remove_sink(id);
+
+    let text = buffer.text();
+    assert_eq!(
+        text.lines().next().unwrap(),
+        " LOG  [TS] field_values: request body=xxxxxxxx…(+92 chars) query=sel…(+3 chars) id=0123456789 short=ok \
+         wide=日本語日本語日本…(+1 chars) "
+    );
+    assert!(text.lines().nth(1).unwrap().contains(&body), "{text}");
+    let json_lines = json_lines.0.lock().unwrap();
+    assert!(json_lines[0].contains(r#""body":"xxxxxxxx…(+92 chars)""#), "{}", json_lines[0]);
+    assert!(json_lines[1].contains(&body));
+}
+
+#[test]
+fn registered_formatters_write_percent_fields() {
+    let (_guard, buffer) = common::capture();
+    register_duration();
+    let elapsed = Duration::from_millis(1234);
+
+    log!("done"; elapsed = %elapsed, plain = ?elapsed);
+    assert_eq!(buffer.text(), " LOG  [TS] field_values: done elapsed=1.2s plain=1.234s \n");
+    assert_eq!(
+        json(&[Field::typed("elapsed", &elapsed), Field::debug("plain", &elapsed)]),
+        format!(r#"{{"level":"info","message":"m","elapsed":1234,"plain":"1.234s"}}"#)
+    );
+}
+
+#[test]
+fn unregistered_types_fall_back_to_debug() {
+    let (_guard, buffer) = common::capture();
+    register_duration();
+    let value = Unregistered { id: 7 };
+
+    log!("other"; value = %value, count = %3u8);
+    assert_eq!(buffer.text(), " LOG  [TS] field_values: other value=Unregistered { id: 7 } count=3 \n");
+    assert!(json(&[Field::typed("value", &value)]).ends_with(r#""value":"Unregistered { id: 7 }"}"#));
+}
+
+#[test]
+fn formatted_values_are_cut_too() {
+    let (_guard, buffer) = common::capture();
+    format::register_field_formatter::<Vec<u8>>(
+        |bytes, out| bytes.iter().try_for_each(|byte| write!(out, "{byte:02x}")),
+        |bytes| JsonValue::String(bytes.iter().map(|byte| format!("{byte:02x}")).collect()),
+    );
+    format::set_field_value_limit_for("payload", 4);
+    let payload: Vec<u8> = vec![0xde, 0xad, 0xbe, 0xef];
+    log!("sent"; payload = %payload);
+    let line = json(&[Field::typed("payload", &payload)]);
+    format::clear_field_value_limits();
+
+    assert_eq!(buffer.text(), " LOG  [TS] field_values: sent payload=dead…(+4 chars) \n");
+    assert!(line.ends_with(r#""payload":"dead…(+4 chars)"}"#), "{line}");
+}
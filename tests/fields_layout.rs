@@ -0,0 +1,151 @@
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice, FieldsLayout};
+use rust_logger::file;
+use rust_logger::log;
+#[cfg(feature = "timestamps")]
+use rust_logger::time::{self, TimestampFormat};
+
+/// Records with short and long messages and zero to three fields.
+fn log_records() {
+    log!("no fields");
+    log!("short"; port = 8080);
+    log!("a somewhat longer message"; user = "ana", ok = true, ratio = -0.5);
+    log!("a message that is long enough to reach past the fields column"; id = 7);
+    log!("wide 日本語 text"; n = 1);
+}
+
+fn lines_in(layout: FieldsLayout, column: usize) -> String {
+    let (_guard, buffer) = common::capture();
+    console::set_target_width(0);
+    console::set_fields_layout(layout);
+    console::set_fields_column(column);
+    log_records();
+    console::set_fields_layout(FieldsLayout::Inline);
+    console::set_fields_column(60);
+    buffer.text()
+}
+
+#[test]
+fn inline_fields_follow_the_message() {
+    assert_eq!(
+        lines_in(FieldsLayout::Inline, 60),
+        concat!(
+            " LOG  [TS] fields_layout: no fields \n",
+            " LOG  [TS] fields_layout: short port=8080 \n",
+            " LOG  [TS] fields_layout: a somewhat longer message user=ana ok=true ratio=-0.5 \n",
+            " LOG  [TS] fields_layout: a message that is long enough to reach past the fields column id=7 \n",
+            " LOG  [TS] fields_layout: wide 日本語 text n=1 \n",
+        )
+    );
+}
+
+#[test]
+fn aligned_fields_start_in_one_column() {
+    // Fields at cell 56; the wide characters take two cells each.
+    assert_eq!(
+        lines_in(FieldsLayout::AlignedColumn, 56),
+        concat!(
+            " LOG  [TS] fields_layout: no fields \n",
+            " LOG  [TS] fields_layout: short                         port=8080 \n",
+            " LOG  [TS] fields_layout: a somewhat longer message     user=ana ok=true ratio=-0.5 \n",
+            " LOG  [TS] fields_layout: a message that is long enough to reach past the fields column id=7 \n",
+            " LOG  [TS] fields_layout: wide 日本語 text              n=1 \n",
+        )
+    );
+}
+
+#[test]
+fn aligned_fields_keep_the_colors() {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    console::set_fields_layout(FieldsLayout::AlignedColumn);
+    console::set_fields_column(40);
+    log!("colored"; k = 1);
+    console::set_fields_layout(FieldsLayout::Inline);
+    console::set_fields_column(60);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::Standard);
+    set_stable_test_format(true);
+
+    let text = buffer.text();
+    let (before, fields) = text.split_once("\x1b[2mk=1").expect("no fields");
+    assert!(fields.starts_with("\x1b[22m"), "{text:?}");
+    // The escape sequences before the fields take no cells.
+    let mut cells = 0;
+    let mut in_escape = false;
+    for c in before.chars() {
+        match c {
+            '\x1b' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if !in_escape => cells += 1,
+            _ => {}
+        }
+    }
+    assert_eq!(cells, 40, "{text:?}");
+}
+
+#[test]
+fn next_line_fields_go_under_the_message() {
+    assert_eq!(
+        lines_in(FieldsLayout::NextLine, 60),
+        concat!(
+            " LOG  [TS] fields_layout: no fields \n",
+            " LOG  [TS] fields_layout: short \n",
+            "                          port=8080 \n",
+            " LOG  [TS] fields_layout: a somewhat longer message \n",
+            "                          user=ana ok=true ratio=-0.5 \n",
+            " LOG  [TS] fields_layout: a message that is long enough to reach past the fields column \n",
+            "                          id=7 \n",
+            " LOG  [TS] fields_layout: wide 日本語 text \n",
+            "                          n=1 \n",
+        )
+    );
+}
+
+#[test]
+fn next_line_values_are_colored_by_type() {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    console::set_fields_layout(FieldsLayout::NextLine);
+    log!("typed"; count = 3, name = "x", ok = false);
+    console::set_fields_layout(FieldsLayout::Inline);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::Standard);
+    set_stable_test_format(true);
+
+    let text = buffer.text();
+    let fields = text.lines().nth(1).expect("no fields line");
+    assert_eq!(
+        fields.trim_start(),
+        concat!(
+            "\x1b[2mcount=\x1b[22;36m3\x1b[39m",
+            " \x1b[2mname=\x1b[22;39mx\x1b[39m",
+            " \x1b[2mok=\x1b[22;35mfalse\x1b[39m",
+            " \x1b[0m",
+        )
+    );
+}
+
+#[test]
+fn log_files_keep_the_fields_inline() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-fields-layout-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    console::set_fields_layout(FieldsLayout::NextLine);
+    let guard = file::file_sink(&path).session_header(false).open().unwrap();
+    log!("to both"; port = 8080);
+    drop(guard);
+    console::set_fields_layout(FieldsLayout::Inline);
+
+    assert_eq!(buffer.text(), " LOG  [TS] fields_layout: to both \n                          port=8080 \n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "[LOG] [TS] fields_layout: to both port=8080\n");
+    let _ = std::fs::remove_file(path);
+}
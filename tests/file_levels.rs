@@ -0,0 +1,146 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use rust_logger::config::{set_stable_test_format, ConfigError};
+use rust_logger::console::{self, ColorChoice, SharedBuffer};
+use rust_logger::file::{self, RotationPolicy};
+use rust_logger::level::{self, LevelSpec};
+use rust_logger::sink::{self, SinkKind};
+use rust_logger::{debug, error, log, warn, Level};
+
+fn log_all() {
+    debug!("d");
+    log!("i");
+    warn!("w");
+    error!("e");
+}
+
+fn temp_log(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rust_logger-file-levels-{name}-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// The messages in a plain log file, one letter per record.
+fn messages(path: &PathBuf) -> String {
+    let text = fs::read_to_string(path).unwrap();
+    text.lines().map(|line| line.rsplit(": ").next().unwrap()).collect()
+}
+
+#[test]
+fn kind_entries_are_parsed_apart_from_modules() {
+    assert_eq!(
+        level::parse_level_spec("info, console:warn,file:debug,my_crate::net=error").unwrap(),
+        LevelSpec {
+            default: Some(Level::Info),
+            modules: vec![(String::from("my_crate::net"), Level::Error)],
+            kinds: vec![(SinkKind::Console, Level::Warn), (SinkKind::File, Level::Debug)],
+        }
+    );
+    let later = level::parse_level_spec("file:warn,FILE:debug").unwrap();
+    assert_eq!(later.kind(SinkKind::File), Some(Level::Debug));
+    assert_eq!(later.kind(SinkKind::Console), None);
+
+    assert!(matches!(level::parse_level_spec("screen:warn"), Err(ConfigError::UnknownSinkKind(kind)) if kind == "screen"));
+    assert!(level::parse_level_spec("file:loud").is_err());
+    assert!(level::parse_level_spec("my_crate::net").is_err(), "a path is not a kind");
+}
+
+#[test]
+fn a_file_level_of_its_own_wins() {
+    let (_guard, buffer) = common::capture();
+    level::set_level(Level::Warn);
+    let (own, shared, following) = (temp_log("own"), temp_log("shared"), temp_log("following"));
+
+    let own_guard = file::file_sink(&own).rotation(RotationPolicy::Never).session_header(false).level(Level::Debug).open().unwrap();
+    let following_guard = file::file_sink(&following).session_header(false).open().unwrap();
+    log_all();
+    file::set_file_level(Some(Level::Error));
+    let shared_guard = file::file_sink(&shared).session_header(false).open().unwrap();
+    log_all();
+    file::set_file_level(None);
+    drop((own_guard, shared_guard, following_guard));
+
+    assert_eq!(messages(&own), "diwediwe");
+    assert_eq!(messages(&shared), "e");
+    assert_eq!(messages(&following), "wee", "the file level applies to the open files too");
+    assert_eq!(buffer.text().lines().count(), 4, "{}", buffer.text());
+    assert_eq!(file::file_level(), None);
+    for path in [own, shared, following] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[test]
+fn specs_set_the_file_level() {
+    let (_guard, buffer) = common::capture();
+    let path = temp_log("spec");
+
+    level::set_level_spec("console:error,file:info").unwrap();
+    assert_eq!((level::level(), file::file_level()), (Level::Error, Some(Level::Info)));
+    let guard = file::file_sink(&path).session_header(false).open().unwrap();
+    log_all();
+    drop(guard);
+    file::set_file_level(None);
+
+    assert_eq!(messages(&path), "iwe");
+    assert_eq!(buffer.text(), " ERR  [TS] file_levels: e \n");
+    let _ = fs::remove_file(path);
+}
+
+const CHILD: &str = "RUST_LOGGER_FILE_LEVELS_CHILD";
+
+/// In the child: applies `LOG_LEVEL`, then logs one record per level to the
+/// console and a file, and prints both.
+#[test]
+fn child() {
+    let Some(name) = std::env::var_os(CHILD) else { return };
+    sink::finish_initialization();
+    set_stable_test_format(true);
+    console::set_color_choice(ColorChoice::Never);
+    let buffer = SharedBuffer::new();
+    console::set_writer(Box::new(buffer.clone()));
+    level::init_from_env();
+
+    let path = temp_log(name.to_str().unwrap());
+    let guard = file::file_sink(&path).session_header(false).open().unwrap();
+    log_all();
+    drop(guard);
+    eprint!("console:\n{}file: {}\n", buffer.text(), messages(&path));
+    let _ = fs::remove_file(path);
+}
+
+fn run_child(log_level: &str) -> String {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, log_level.replace([':', ','], "-"))
+        .env("LOG_LEVEL", log_level)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn log_level_can_split_the_console_and_the_files() {
+    let stderr = run_child("console:warn,file:debug");
+    assert!(stderr.contains(concat!("console:\n", " WRN  [TS] file_levels: w \n", " ERR  [TS] file_levels: e \n", "file: diwe\n")), "{stderr}");
+
+    let stderr = run_child("error,console:info");
+    assert!(stderr.contains("console:\n LOG  [TS] file_levels: i \n WRN  [TS] file_levels: w \n ERR  [TS] file_levels: e \nfile: e\n"), "{stderr}");
+}
+
+#[test]
+fn a_plain_log_level_still_covers_both() {
+    let stderr = run_child("warn");
+    assert!(stderr.contains("console:\n WRN  [TS] file_levels: w \n ERR  [TS] file_levels: e \nfile: we\n"), "{stderr}");
+}
+
+#[test]
+fn an_unknown_kind_is_reported_and_ignored() {
+    let stderr = run_child("screen:warn,file:error");
+    assert!(stderr.contains(r#"ignoring LOG_LEVEL: unknown sink kind "screen": expected console or file"#), "{stderr}");
+    assert!(stderr.contains(" DBG  [TS] file_levels: d \n"), "the console level was kept: {stderr}");
+    assert!(stderr.contains("file: diwe\n"), "the file level was not applied: {stderr}");
+}
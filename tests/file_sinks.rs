@@ -0,0 +1,226 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use rust_logger::file::{self, RotationPolicy, SyncPolicy};
+use rust_logger::format::{self, Format};
+use rust_logger::log;
+
+/// A fresh directory for one test's files.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust_logger-file-sinks-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap()
+}
+
+fn count(text: &str, needle: &str) -> usize {
+    text.lines().filter(|line| line.contains(needle)).count()
+}
+
+#[test]
+fn one_header_per_open() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("open");
+    let path = dir.join("app.log");
+
+    for run in 0..3 {
+        let sink = file::add_file_sink(&path, RotationPolicy::Never).unwrap();
+        log!("run {run}");
+        drop(sink);
+    }
+
+    let text = read(&path);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 9, "{text}");
+    for run in lines.chunks(3) {
+        assert!(run[0].chars().all(|c| c == '─') && run[0].chars().count() == 72, "{text}");
+        assert!(run[1].starts_with("[LOG] [TS] [logger]: session started: file_sinks-"), "{text}");
+        assert!(run[1].contains(&format!("(pid {}), level Debug, rust_logger ", std::process::id())), "{text}");
+    }
+    assert_eq!(lines[2], "[LOG] [TS] file_sinks: run 0");
+    assert_eq!(lines[8], "[LOG] [TS] file_sinks: run 2");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn one_continued_line_per_rotation() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("rotation");
+    let path = dir.join("app.log");
+
+    let sink = file::file_sink(&path).rotation(RotationPolicy::Size { max_bytes: 400, keep: 3 }).open().unwrap();
+    for i in 0..12 {
+        log!("record {i:02} with some padding to fill the file");
+    }
+    drop(sink);
+
+    let (current, first, second) = (read(&path), read(&dir.join("app.log.1")), read(&dir.join("app.log.2")));
+    let oldest = read(&dir.join("app.log.3"));
+    assert_eq!(count(&oldest, "session started"), 1, "{oldest}");
+    for (name, text) in [("app.log.2", &second), ("app.log.1", &first), ("app.log", &current)] {
+        assert_eq!(count(text, "session started"), 0, "{name}: {text}");
+        assert_eq!(text.lines().next().unwrap(), "[LOG] [TS] [logger]: continued from app.log.1", "{name}");
+        assert_eq!(count(text, "[logger]"), 1, "{name}: {text}");
+    }
+    // Every record is in exactly one file.
+    let all = [oldest, second, first, current].concat();
+    for i in 0..12 {
+        assert_eq!(count(&all, &format!("record {i:02} ")), 1, "{all}");
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn the_header_counts_toward_the_size() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("size");
+    let path = dir.join("app.log");
+
+    let sink = file::add_file_sink(&path, RotationPolicy::Never).unwrap();
+    file::flush();
+    let header = fs::metadata(&path).unwrap().len();
+    drop(sink);
+    assert!(header > 0);
+
+    // A record that fits after the header alone does not rotate; the next one does.
+    let record = "[LOG] [TS] file_sinks: 0123456789\n".len() as u64;
+    let sink = file::add_file_sink(&path, RotationPolicy::Size { max_bytes: 2 * header + record, keep: 1 }).unwrap();
+    log!("0123456789");
+    file::flush();
+    assert!(!dir.join("app.log.1").exists());
+    log!("0123456789");
+    drop(sink);
+    assert_eq!(read(&dir.join("app.log.1")).len() as u64, 2 * header + record);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn headers_can_be_turned_off() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("off");
+    let path = dir.join("app.log");
+
+    let sink = file::file_sink(&path).rotation(RotationPolicy::Size { max_bytes: 40, keep: 1 }).session_header(false).open().unwrap();
+    log!("one");
+    log!("two");
+    drop(sink);
+
+    assert_eq!(read(&dir.join("app.log.1")), "[LOG] [TS] file_sinks: one\n");
+    assert_eq!(read(&path), "[LOG] [TS] file_sinks: two\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn json_files_get_a_json_header() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("json");
+    let path = dir.join("app.jsonl");
+
+    format::set_format(Format::Json);
+    let sink = file::add_file_sink(&path, RotationPolicy::Never).unwrap();
+    log!("started");
+    drop(sink);
+    format::set_format(Format::Pretty);
+
+    let text = read(&path);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2, "{text}");
+    assert!(lines[0].starts_with(r#"{"timestamp":"TS","level":"info","target":"[logger]","message":"session started: "#), "{text}");
+    assert_eq!(lines[1], r#"{"timestamp":"TS","level":"info","target":"file_sinks","message":"started"}"#);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn interval_syncs_write_out_the_buffer() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("interval");
+    let path = dir.join("app.log");
+
+    let sink = file::file_sink(&path).session_header(false).sync_policy(SyncPolicy::Interval(Duration::from_millis(10))).open().unwrap();
+    log!("buffered");
+    // No flush: the sync thread writes the buffer out before syncing.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while read(&path).is_empty() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(read(&path), "[LOG] [TS] file_sinks: buffered\n");
+    drop(sink);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn every_record_leaves_nothing_buffered() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("every");
+    let path = dir.join("app.log");
+
+    let sink = file::file_sink(&path).session_header(false).sync_policy(SyncPolicy::EveryRecord).open().unwrap();
+    log!("one");
+    assert_eq!(read(&path), "[LOG] [TS] file_sinks: one\n");
+    log!("two");
+    assert_eq!(read(&path), "[LOG] [TS] file_sinks: one\n[LOG] [TS] file_sinks: two\n");
+    drop(sink);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_moved_file_is_reopened_at_its_path() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("moved");
+    let path = dir.join("app.log");
+
+    let sink = file::file_sink(&path).session_header(false).reopen_check(Duration::ZERO).open().unwrap();
+    log!("one");
+    // Buffered, so the rename happens with "one" still in memory.
+    fs::rename(&path, dir.join("app.log.old")).unwrap();
+    log!("two");
+    log!("three");
+    drop(sink);
+
+    assert_eq!(read(&dir.join("app.log.old")), "[LOG] [TS] file_sinks: one\n");
+    assert_eq!(read(&path), "[LOG] [TS] file_sinks: two\n[LOG] [TS] file_sinks: three\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_deleted_file_is_recreated_with_a_header() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("deleted");
+    let path = dir.join("app.log");
+
+    let sink = file::file_sink(&path).reopen_check(Duration::ZERO).open().unwrap();
+    log!("one");
+    file::flush();
+    fs::remove_file(&path).unwrap();
+    log!("two");
+    drop(sink);
+
+    assert_eq!(read(&path), "[LOG] [TS] [logger]: reopened: the previous file was moved or deleted\n[LOG] [TS] file_sinks: two\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reopen_checks_wait_for_their_interval() {
+    let (_guard, _buffer) = common::capture();
+    let dir = scratch("interval-check");
+    let path = dir.join("app.log");
+
+    let sink = file::file_sink(&path).session_header(false).reopen_check(Duration::from_secs(3600)).open().unwrap();
+    fs::rename(&path, dir.join("app.log.old")).unwrap();
+    log!("not checked yet");
+    // An explicit reopen, as a SIGHUP handler would do.
+    file::reopen().unwrap();
+    log!("reopened");
+    drop(sink);
+
+    assert_eq!(read(&dir.join("app.log.old")), "[LOG] [TS] file_sinks: not checked yet\n");
+    assert_eq!(read(&path), "[LOG] [TS] file_sinks: reopened\n");
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::fmt;
+use std::panic;
+
+use rust_logger::console;
+use rust_logger::{error, log, warn};
+
+struct Boom;
+
+impl fmt::Display for Boom {
+    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        panic!("boom in Display")
+    }
+}
+
+impl fmt::Debug for Boom {
+    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        panic!("boom {} in Debug", 42)
+    }
+}
+
+/// Runs `body` with the panic output silenced.
+fn quietly<T>(body: impl FnOnce() -> T) -> T {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = body();
+    panic::set_hook(hook);
+    result
+}
+
+/// The diagnostic for a panic at `line` of this file.
+fn diagnostic(line: u32, payload: &str) -> String {
+    format!(" ERR  [TS] [logger]: panic while formatting log message at {}:{line}: {payload} \n", file!())
+}
+
+#[test]
+fn panics_propagate_by_default() {
+    let (_guard, buffer) = common::capture();
+    let caught = quietly(|| panic::catch_unwind(|| log!("value {}", Boom)));
+    let payload = caught.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom in Display"));
+    assert_eq!(buffer.text(), "");
+
+    // The logger is not left in a broken state.
+    log!("after");
+    assert_eq!(buffer.text(), " LOG  [TS] format_panics: after \n");
+}
+
+#[test]
+fn caught_panics_replace_the_record() {
+    let (_guard, buffer) = common::capture();
+    console::catch_format_panics(true);
+    let display_line = line!() + 3;
+    let outcome = quietly(|| {
+        panic::catch_unwind(|| {
+            warn!("value {}", Boom);
+            error!("value {:?}", Boom);
+        })
+    });
+    log!("after");
+    console::catch_format_panics(false);
+
+    assert!(outcome.is_ok(), "the panic did not leave the macro");
+    assert_eq!(
+        buffer.text(),
+        format!(
+            "{}{} LOG  [TS] format_panics: after \n",
+            diagnostic(display_line, "boom in Display"),
+            diagnostic(display_line + 1, "boom 42 in Debug")
+        )
+    );
+}
+
+#[test]
+fn messages_that_format_are_unchanged_while_catching() {
+    let (_guard, buffer) = common::capture();
+    console::catch_format_panics(true);
+    log!("value {} and {:?}", 7, "text");
+    console::catch_format_panics(false);
+    assert_eq!(buffer.text(), " LOG  [TS] format_panics: value 7 and \"text\" \n");
+}
+
+#[test]
+fn the_console_queue_survives_a_panicking_display() {
+    let (_guard, buffer) = common::capture();
+    console::set_console_queue(16).unwrap();
+
+    let propagated = quietly(|| {
+        std::thread::spawn(|| log!("value {}", Boom)).join()
+    });
+    assert!(propagated.is_err(), "the logging thread panicked");
+    log!("after propagated");
+
+    console::catch_format_panics(true);
+    let line = line!() + 1;
+    let caught = quietly(|| std::thread::spawn(move || { log!("value {}", Boom); log!("same thread"); }).join());
+    console::catch_format_panics(false);
+    assert!(caught.is_ok());
+
+    console::flush_console_queue();
+    console::set_console_queue(0).unwrap();
+    assert_eq!(
+        buffer.text(),
+        format!(
+            " LOG  [TS] format_panics: after propagated \n{} LOG  [TS] format_panics: same thread \n",
+            diagnostic(line, "boom in Display")
+        )
+    );
+}
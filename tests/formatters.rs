@@ -0,0 +1,103 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::format::{self, Field, Format, LogfmtFormatter, PrettyFormatter, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::theme::Theme;
+use rust_logger::{error, log, warn, Level};
+
+/// Renders every record with its own formatter, ignoring `line`.
+struct Styled {
+    formatter: PrettyFormatter,
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl Sink for Styled {
+    fn write(&mut self, record: &Record, _line: &str) {
+        let mut line = String::new();
+        self.formatter.format(record, &mut line).unwrap();
+        self.lines.lock().unwrap().push(line);
+    }
+}
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+fn logfmt(record: &Record) -> String {
+    let mut line = String::new();
+    LogfmtFormatter.format(record, &mut line).unwrap();
+    line
+}
+
+#[test]
+fn a_sink_formats_with_its_own_settings() {
+    let (_guard, buffer) = common::capture();
+
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let formatter = PrettyFormatter::new(Theme::high_contrast()).timestamps(false);
+    let styled = sink::add_sink(Box::new(Styled { formatter, lines: lines.clone() }), Level::Debug, Format::Pretty);
+    warn!("disk <bold>almost</> full");
+    sink::remove_sink(styled);
+
+    assert_eq!(
+        *lines.lock().unwrap(),
+        ["\x1b[0;103;1;30m WARN  \x1b[0m \x1b[0;2mformatters:\x1b[22m \x1b[93mdisk \x1b[1malmost\x1b[0m\x1b[93m full \x1b[0m"]
+    );
+    assert_eq!(buffer.text(), " WRN  [TS] formatters: disk almost full \n");
+}
+
+#[test]
+fn logfmt_pairs() {
+    let user = 42;
+    let route = "/orders";
+    let fields = [Field::display("user_id", &user), Field::debug("route", &route)];
+    let causes = ["refused", "reset by peer"];
+    let record = Record {
+        timestamp: Some("2024-05-01T12:00:00.000Z"),
+        target: Some("my_app"),
+        fields: &fields,
+        ..Record::new(Level::Warn, "disk <red>almost</> full")
+    };
+    assert_eq!(
+        logfmt(&record),
+        r#"ts=2024-05-01T12:00:00.000Z level=warn target=my_app msg="disk almost full" user_id=42 route="\"/orders\"""#
+    );
+
+    let record = Record { causes: &causes, suppressed: 3, ..Record::new(Level::Error, "failed") };
+    assert_eq!(logfmt(&record), r#"level=error msg=failed suppressed=3 causes="refused: reset by peer""#);
+
+    let record = Record { category: Some("audit"), ..Record::new(Level::Info, "a=b \"c\"\nd\t") };
+    assert_eq!(logfmt(&record), r#"level=info category=audit msg="a=b \"c\"\nd\t""#);
+    assert_eq!(logfmt(&Record::new(Level::Debug, "")), r#"level=debug msg="""#);
+}
+
+#[test]
+fn logfmt_on_the_console_and_in_sinks() {
+    let (_guard, buffer) = common::capture();
+
+    let lines = Collect::default();
+    let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Logfmt);
+    format::set_format(Format::Logfmt);
+    log!("started"; port = 8080);
+    error!("stopped");
+    rust_logger::new_line!();
+    format::set_format(Format::Pretty);
+    sink::remove_sink(id);
+
+    assert_eq!(
+        buffer.text(),
+        "ts=TS level=info target=formatters msg=started port=8080\nts=TS level=error target=formatters msg=stopped\n"
+    );
+    assert_eq!(
+        *lines.0.lock().unwrap(),
+        ["ts=TS level=info target=formatters msg=started port=8080", "ts=TS level=error target=formatters msg=stopped"]
+    );
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use rust_logger::{group_begin, group_end, log};
+
+#[test]
+fn thread_exiting_with_an_open_group_warns_instead_of_aborting() {
+    let (_guard, buffer) = common::capture();
+
+    std::thread::spawn(|| group_begin!("outer")).join().unwrap();
+
+    let text = buffer.text();
+    assert!(text.starts_with(" LOG  [TS] groups: outer \n"), "{text}");
+    assert!(text.contains("[logger]: 1 group(s) still open when the thread exited"), "{text}");
+}
+
+#[test]
+fn groups_are_per_thread() {
+    let (_guard, buffer) = common::capture();
+
+    group_begin!("main");
+    std::thread::spawn(|| {
+        assert_eq!(rust_logger::group::current_depth(), 0);
+        log!("other thread");
+    })
+    .join()
+    .unwrap();
+    assert_eq!(rust_logger::group::current_depth(), 1);
+    group_end!("done");
+    assert_eq!(rust_logger::group::current_depth(), 0);
+
+    assert_eq!(buffer.text(), concat!(
+        " LOG  [TS] groups: main \n",
+        " LOG  [TS] groups: other thread \n",
+        " LOG  [TS] groups: done \n",
+    ));
+}
+
+const CHILD: &str = "RUST_LOGGER_GROUPS_CHILD";
+
+/// In the child: leaves a group open on the main thread and shuts down.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return; }
+    group_begin!("never closed");
+    rust_logger::console::shutdown();
+}
+
+#[test]
+fn shutdown_reports_groups_left_open_on_the_calling_thread() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .env("LOG_STABLE_FORMAT", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("[WRN] [TS] [logger]: 1 group(s) still open at shutdown\n"), "{stderr}");
+}
+
+struct GroupsOnDrop;
+
+impl Drop for GroupsOnDrop {
+    fn drop(&mut self) {
+        let _group = rust_logger::group!("late group");
+        group_end!("late end");
+        log!("still logging");
+    }
+}
+
+thread_local! {
+    static GROUPS_ON_DROP: GroupsOnDrop = const { GroupsOnDrop };
+}
+
+#[test]
+fn groups_from_a_thread_local_destructor_are_ignored() {
+    let (_guard, buffer) = common::capture();
+
+    // Destructors run last to first: the group stack's goes before this one.
+    let thread = std::thread::spawn(|| {
+        GROUPS_ON_DROP.with(|_| ());
+        group_begin!("first");
+        group_end!();
+    });
+    thread.join().expect("a group from a destructor panicked");
+
+    let text = buffer.text();
+    assert!(text.contains(" LOG  [TS] groups: late group \n") && text.ends_with(" LOG  [TS] groups: still logging \n"), "{text}");
+    assert!(!text.contains("without a matching"), "{text}");
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use std::time::Duration;
+
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{heartbeat, Level};
+
+/// Runs a heartbeat of its own for every record it receives.
+struct Nested;
+
+impl Sink for Nested {
+    fn write(&mut self, _record: &Record, _line: &str) {
+        let _inner = heartbeat!(Level::Debug, Duration::from_secs(60), "nested");
+    }
+}
+
+#[test]
+fn sinks_can_start_and_stop_heartbeats_while_ticks_print() {
+    let (_guard, buffer) = common::capture();
+
+    let nested = sink::add_sink(Box::new(Nested), Level::Debug, Format::Pretty);
+    let outer = heartbeat!(Level::Info, Duration::from_millis(2), "work");
+    std::thread::sleep(Duration::from_millis(30));
+    drop(outer);
+    std::thread::sleep(Duration::from_millis(10));
+    sink::remove_sink(nested);
+
+    let text = buffer.text();
+    let work: Vec<&str> = text.lines().filter(|line| line.contains("work")).collect();
+    assert!(work.len() >= 2, "{text}");
+    assert!(work.last().unwrap().contains("work done in"), "a tick followed the completion line:\n{text}");
+    assert!(work[..work.len() - 1].iter().all(|line| line.contains("work (still running, ")), "{text}");
+    assert!(text.contains("nested done in"), "{text}");
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+use rust_logger::display::glyphs;
+use rust_logger::{hexdump, level, Level};
+
+/// A 16-byte request line with markup characters in it, then 4 bytes outside ASCII.
+fn frame() -> Vec<u8> {
+    b"GET /<a>\\ HTTP\r\n".iter().copied().chain(0xfc..=0xff).collect()
+}
+
+/// The hexdump row for the first 16 bytes of `frame`.
+fn first_row() -> String {
+    let gutter = glyphs().gutter;
+    format!(" DBG  [TS] hexdump: 00000000  47 45 54 20 2f 3c 61 3e  5c 20 48 54 54 50 0d 0a  {gutter}GET /<a>\\ HTTP..{gutter} \n")
+}
+
+#[test]
+fn an_empty_buffer_has_only_the_header() {
+    let (_guard, buffer) = common::capture();
+    hexdump!("empty", []);
+    hexdump!("empty vec", Vec::<u8>::new());
+    assert_eq!(buffer.text(), " DBG  [TS] hexdump: empty (0 bytes) \n DBG  [TS] hexdump: empty vec (0 bytes) \n");
+}
+
+#[test]
+fn exactly_one_row() {
+    let (_guard, buffer) = common::capture();
+    hexdump!("exact", &frame()[..16]);
+    assert_eq!(buffer.text(), format!(" DBG  [TS] hexdump: exact (16 bytes) \n{}", first_row()));
+}
+
+#[test]
+fn a_single_byte_pads_the_row() {
+    let (_guard, buffer) = common::capture();
+    hexdump!("one", [0x41]);
+    let gutter = glyphs().gutter;
+    assert_eq!(buffer.text(), format!(" DBG  [TS] hexdump: one (1 byte) \n DBG  [TS] hexdump: 00000000  41 {:47}{gutter}A{gutter} \n", ""));
+}
+
+#[test]
+fn max_ends_with_a_trailer() {
+    let (_guard, buffer) = common::capture();
+    hexdump!(max: 18, "rx frame", frame());
+    let gutter = glyphs().gutter;
+    assert_eq!(
+        buffer.text(),
+        format!(
+            " DBG  [TS] hexdump: rx frame (20 bytes) \n{} DBG  [TS] hexdump: 00000010  fc fd {:44}{gutter}..{gutter} \n \
+             DBG  [TS] hexdump: (+2 more bytes) \n",
+            first_row(),
+            ""
+        )
+    );
+}
+
+#[test]
+fn max_at_or_past_the_length_has_no_trailer() {
+    let (_guard, buffer) = common::capture();
+    hexdump!(max: 20, "all", frame());
+    hexdump!(max: 100, "more", frame());
+    let text = buffer.text();
+    assert!(!text.contains("more bytes"), "{text}");
+    assert_eq!(text.lines().count(), 6, "{text}");
+
+    buffer.clear();
+    hexdump!(max: 0, "none", frame());
+    assert_eq!(buffer.text(), " DBG  [TS] hexdump: none (20 bytes) \n DBG  [TS] hexdump: (+20 more bytes) \n");
+}
+
+#[test]
+fn bytes_outside_ascii_are_gray() {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    rust_logger::time::set_timestamp_format(rust_logger::time::TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    hexdump!("colored", [b'a', 0x00]);
+    console::set_color_choice(ColorChoice::Never);
+    #[cfg(feature = "timestamps")]
+    rust_logger::time::set_timestamp_format(rust_logger::time::TimestampFormat::Standard);
+    set_stable_test_format(true);
+
+    let colored = buffer.text();
+    if cfg!(feature = "markup") {
+        assert!(colored.contains("\x1b[90m00") && !colored.contains("\x1b[90m61"), "{colored:?}");
+    }
+}
+
+#[test]
+fn below_the_level_nothing_is_drawn() {
+    let (_guard, buffer) = common::capture();
+    level::set_level(Level::Info);
+    hexdump!("quiet", [1, 2, 3]);
+    level::set_level(Level::Debug);
+    assert_eq!(buffer.text(), "");
+}
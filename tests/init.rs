@@ -0,0 +1,37 @@
+//! `init` runs once per process, so each case runs in a child process.
+#![cfg(target_os = "linux")]
+
+use std::process::{Command, Stdio};
+
+use rust_logger::init::{builder, InitError};
+
+const CHILD: &str = "RUST_LOGGER_INIT_CHILD";
+
+/// In the child: a failed `init` hands its claim back, so the next one works.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return; }
+
+    extern "C" {
+        fn close(fd: std::os::raw::c_int) -> std::os::raw::c_int;
+    }
+    // With no stdout at all, the console fails.
+    // SAFETY: nothing in the child writes to fd 1 through a handle of its own.
+    unsafe { close(1) };
+    let failed = builder().console_nonblocking(true).init();
+    eprintln!("first: {}", matches!(failed, Err(InitError::Console(_))));
+    eprintln!("second: {}", builder().init().is_ok());
+    eprintln!("third: {}", matches!(builder().init(), Err(InitError::AlreadyInitialized)));
+}
+
+#[test]
+fn a_console_failure_rolls_the_init_back() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("first: true\nsecond: true\nthird: true\n"), "{stderr}");
+}
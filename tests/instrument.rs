@@ -0,0 +1,186 @@
+mod common;
+
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use rust_logger::log;
+
+/// Has no `Debug`, so it is shown as `_`.
+struct Opaque;
+
+struct Counter {
+    count: u32,
+}
+
+impl Counter {
+    #[rust_logger::instrument]
+    fn bump(&mut self, by: u32) -> u32 {
+        self.count += by;
+        self.count
+    }
+
+    #[rust_logger::instrument(name = "Counter::get")]
+    fn get(&self) -> u32 {
+        self.count
+    }
+}
+
+#[rust_logger::instrument]
+fn add(a: i32, b: i32) -> i32 {
+    log!("adding");
+    a + b
+}
+
+#[rust_logger::instrument]
+fn unit(_opaque: Opaque) {}
+
+#[rust_logger::instrument(skip(password), rename(user = "login"))]
+fn login(user: &str, password: &str) -> Result<u32, String> {
+    if password.is_empty() { return Err(format!("no password for {user}")); }
+    Ok(user.len() as u32)
+}
+
+#[rust_logger::instrument(level = "debug")]
+fn first<T: std::fmt::Debug + Clone>(items: &[T]) -> Option<T> {
+    items.first().cloned()
+}
+
+#[rust_logger::instrument]
+fn opaque_generic<T: Clone>(item: &T) -> T {
+    item.clone()
+}
+
+#[rust_logger::instrument(level = "debug")]
+async fn fetch(id: u32) -> String {
+    Pending(2).await;
+    format!("item {id}")
+}
+
+/// Pending the given number of times before it is ready.
+struct Pending(u32);
+
+impl Future for Pending {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 == 0 { return Poll::Ready(()); }
+        self.0 -= 1;
+        Poll::Pending
+    }
+}
+
+/// Polls `future` to completion, sleeping between polls; returns the output
+/// and how many polls it took.
+fn block_on<F: Future>(future: F) -> (F::Output, u32) {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut polls = 0;
+    loop {
+        polls += 1;
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) { return (output, polls); }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// The lines without the elapsed time after ` in `.
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(|line| match line.rfind(" in ") {
+        Some(at) if line.contains(" returned ") || line.contains(" failed: ") => line[..at].to_string(),
+        _ => line.trim_end().to_string(),
+    }).collect()
+}
+
+#[test]
+fn sync_function_logs_arguments_and_return_value() {
+    let (_guard, buffer) = common::capture();
+    assert_eq!(add(2, 3), 5);
+
+    // The body is grouped under the entry line.
+    assert_eq!(lines(&buffer.text()), [
+        " LOG  [TS] instrument: add(a = 2, b = 3)",
+        " LOG  [TS] instrument:   adding",
+        " LOG  [TS] instrument: add returned 5",
+    ]);
+}
+
+#[test]
+fn unit_function_and_values_without_debug() {
+    let (_guard, buffer) = common::capture();
+    unit(Opaque);
+    assert_eq!(opaque_generic(&7).to_string(), "7");
+
+    assert_eq!(lines(&buffer.text()), [
+        " LOG  [TS] instrument: unit(_opaque = _)",
+        " LOG  [TS] instrument: unit returned",
+        // Without a `Debug` bound on `T` the values show as `_`.
+        " LOG  [TS] instrument: opaque_generic(item = _)",
+        " LOG  [TS] instrument: opaque_generic returned _",
+    ]);
+}
+
+#[test]
+fn err_is_logged_at_warn_and_skipped_arguments_are_hidden() {
+    let (_guard, buffer) = common::capture();
+    assert_eq!(login("ada", "secret"), Ok(3));
+    assert!(login("ada", "").is_err());
+
+    let text = buffer.text();
+    assert_eq!(lines(&text), [
+        " LOG  [TS] instrument: login(login = \"ada\")",
+        " LOG  [TS] instrument: login returned Ok(3)",
+        " LOG  [TS] instrument: login(login = \"ada\")",
+        " WRN  [TS] instrument: login failed: \"no password for ada\"",
+    ]);
+    assert!(!text.contains("secret"));
+    assert!(!text.contains("password ="));
+}
+
+#[test]
+fn generics_and_methods() {
+    let (_guard, buffer) = common::capture();
+    assert_eq!(first(&["a", "b"]), Some("a"));
+    let mut counter = Counter { count: 1 };
+    assert_eq!(counter.bump(2), 3);
+    assert_eq!(counter.get(), 3);
+
+    // `self` is not listed among the arguments.
+    assert_eq!(lines(&buffer.text()), [
+        " DBG  [TS] instrument: first(items = [\"a\", \"b\"])",
+        " DBG  [TS] instrument: first returned Some(\"a\")",
+        " LOG  [TS] instrument: bump(by = 2)",
+        " LOG  [TS] instrument: bump returned 3",
+        " LOG  [TS] instrument: Counter::get()",
+        " LOG  [TS] instrument: Counter::get returned 3",
+    ]);
+}
+
+#[test]
+fn level_below_the_threshold_logs_nothing() {
+    let (_guard, buffer) = common::capture();
+    rust_logger::level::set_level(rust_logger::Level::Info);
+    assert_eq!(first(&[1]), Some(1));
+    assert_eq!(block_on(fetch(1)).0, "item 1");
+
+    assert_eq!(buffer.text(), "");
+}
+
+#[test]
+fn async_function_is_timed_across_polls() {
+    let (_guard, buffer) = common::capture();
+    let (output, polls) = block_on(fetch(7));
+    assert_eq!(output, "item 7");
+    assert_eq!(polls, 3);
+
+    // One entry and one exit for the whole future, however often it is polled.
+    let text = buffer.text();
+    assert_eq!(lines(&text), [
+        " DBG  [TS] instrument: fetch(id = 7)",
+        " DBG  [TS] instrument: fetch returned \"item 7\"",
+    ]);
+    // Two sleeps of 10ms between the polls are inside the span.
+    let elapsed = text.lines().nth(1).unwrap().rsplit(" in ").next().unwrap().trim_end();
+    assert!(elapsed.ends_with("ms") && !elapsed.ends_with("µs"), "{elapsed}");
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use rust_logger::console::{self, LocationMode};
+use rust_logger::log;
+
+/// Counts the allocations of the thread that turned counting on.
+struct Counting;
+
+thread_local! {
+    static COUNTED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = COUNTED.try_with(|counted| counted.set(counted.get().map(|n| n + 1)));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Allocations made while logging 100 field-heavy records, after a first
+/// one from the same call site.
+fn allocations_per_100() -> u64 {
+    let record = || log!("request finished"; user_id = 42, route = "/api/items", status = 200, bytes = 5120);
+    record();
+    COUNTED.with(|counted| counted.set(Some(0)));
+    for _ in 0..100 {
+        record();
+    }
+    COUNTED.with(|counted| counted.replace(None)).unwrap()
+}
+
+#[test]
+fn file_and_line_targets_allocate_nothing_per_record() {
+    let (_guard, _buffer) = common::capture();
+    console::set_writer(Box::new(std::io::sink()));
+
+    let module = allocations_per_100();
+    console::set_location_mode(LocationMode::Full);
+    let full = allocations_per_100();
+    console::set_location_mode(LocationMode::Module);
+
+    assert_eq!(full, module, "`file:line` is put together once per call site, not once per record");
+}
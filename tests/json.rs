@@ -0,0 +1,175 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use rust_logger::format::{self, Format};
+use rust_logger::{error, log, warn};
+
+/// Just enough of a JSON value to check what the logger writes.
+#[derive(Debug, PartialEq)]
+enum Json {
+    Str(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+/// A strict parser for the strings, arrays and objects records are made
+/// of; anything else, or trailing text, is an error.
+fn parse(text: &str) -> Result<Json, String> {
+    let mut chars = text.chars().peekable();
+    let value = value(&mut chars)?;
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("trailing {c:?}")),
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn value(chars: &mut Chars) -> Result<Json, String> {
+    match chars.next() {
+        Some('"') => string(chars).map(Json::Str),
+        Some('[') => {
+            let mut items = Vec::new();
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(value(chars)?);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Json::Array(items)),
+                    other => return Err(format!("expected , or ] but got {other:?}")),
+                }
+            }
+        }
+        Some('{') => {
+            let mut members = BTreeMap::new();
+            loop {
+                if chars.next() != Some('"') { return Err(String::from("expected a key")); }
+                let key = string(chars)?;
+                if chars.next() != Some(':') { return Err(format!("no : after {key}")); }
+                if members.insert(key.clone(), value(chars)?).is_some() { return Err(format!("duplicate {key}")); }
+                match chars.next() {
+                    Some(',') => {}
+                    Some('}') => return Ok(Json::Object(members)),
+                    other => return Err(format!("expected , or }} but got {other:?}")),
+                }
+            }
+        }
+        other => Err(format!("unexpected {other:?}")),
+    }
+}
+
+fn string(chars: &mut Chars) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(out),
+            '\\' => match chars.next().ok_or("unterminated escape")? {
+                '"' => out.push('"'),
+                '\\' => out.push('\\'),
+                '/' => out.push('/'),
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("bad \\u{hex}"))?;
+                    out.push(char::from_u32(code).ok_or(format!("surrogate \\u{hex}"))?);
+                }
+                other => return Err(format!("bad escape {other:?}")),
+            },
+            c if (c as u32) < 0x20 => return Err(format!("raw control character {c:?}")),
+            c => out.push(c),
+        }
+    }
+}
+
+fn records(text: &str) -> Vec<BTreeMap<String, Json>> {
+    text.lines()
+        .map(|line| match parse(line) {
+            Ok(Json::Object(members)) => members,
+            other => panic!("{line}: {other:?}"),
+        })
+        .collect()
+}
+
+fn str(value: &str) -> Json {
+    Json::Str(value.to_string())
+}
+
+#[test]
+fn messages_round_trip() {
+    let (_guard, buffer) = common::capture();
+    format::set_format(Format::Json);
+    let messages = ["say \"hi\"", "first\nsecond\r\nthird", "tab\there \\ back", "controls \u{1} and \u{1f}", "ünïcödé ✓"];
+    for message in messages {
+        log!("{message}");
+    }
+    format::set_format(Format::Pretty);
+
+    let records = records(&buffer.text());
+    assert_eq!(records.len(), messages.len(), "{}", buffer.text());
+    for (record, message) in records.iter().zip(messages) {
+        assert_eq!(record["message"], str(message));
+        assert_eq!(record["level"], str("info"));
+        assert_eq!(record["timestamp"], str("TS"));
+        assert_eq!(record["target"], str("json"));
+    }
+}
+
+#[test]
+fn markup_is_stripped_not_rendered() {
+    let (_guard, buffer) = common::capture();
+    format::set_format(Format::Json);
+    warn!("disk <red>almost</> full, <bold><green>ok</></>");
+    format::set_format(Format::Pretty);
+
+    let text = buffer.text();
+    assert!(!text.contains('\u{1b}'), "{text}");
+    assert_eq!(records(&text)[0]["message"], str("disk almost full, ok"));
+}
+
+#[test]
+fn fields_and_causes_round_trip() {
+    let (_guard, buffer) = common::capture();
+    format::set_format(Format::Json);
+    let inner = std::io::Error::other("reset \"by\" peer\n");
+    warn!("with fields"; user = "a\"b", tag = "<red>", n = 3);
+    error!(err = Layered("refused", Some(inner)), "failed");
+    format::set_format(Format::Pretty);
+
+    let records = records(&buffer.text());
+    assert_eq!(records[0]["user"], str("a\"b"));
+    assert_eq!(records[0]["tag"], str("<red>"));
+    assert_eq!(records[0]["n"], str("3"));
+    assert_eq!(records[1]["message"], str("failed: refused"));
+    assert_eq!(records[1]["causes"], Json::Array(vec![str("reset \"by\" peer\n")]));
+}
+
+#[test]
+fn pretty_stays_the_default() {
+    let (_guard, buffer) = common::capture();
+    assert_eq!(format::output_format(), Format::Pretty);
+    log!("plain");
+    assert_eq!(buffer.text(), " LOG  [TS] json: plain \n");
+}
+
+#[derive(Debug)]
+struct Layered(&'static str, Option<std::io::Error>);
+
+impl std::fmt::Display for Layered {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for Layered {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.1.as_ref().map(|e| e as _)
+    }
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::format::{self, Field, FieldNames, Format, JsonFlavor, JsonFormatter, Record, StandardKey};
+use rust_logger::{log, warn, Level};
+
+fn record<'a>(fields: &'a [Field<'a>]) -> Record<'a> {
+    Record {
+        timestamp: Some("2024-05-01T12:00:00.123456789Z"), target: Some("my_app::db"), fields,
+        ..Record::new(Level::Warn, "disk full")
+    }
+}
+
+fn json(record: &Record) -> String {
+    let mut line = String::new();
+    JsonFormatter.format(record, &mut line).unwrap();
+    line
+}
+
+#[test]
+fn the_default_flavor_is_unchanged() {
+    let (_guard, _buffer) = common::capture();
+    let fields = [Field::display("user_id", &42), Field::display("path", &"/var")];
+    assert_eq!(
+        json(&record(&fields)),
+        concat!(
+            r#"{"timestamp":"2024-05-01T12:00:00.123456789Z","level":"warn","target":"my_app::db","message":"disk full","#,
+            r#""user_id":"42","path":"/var"}"#,
+        )
+    );
+}
+
+#[test]
+fn the_otel_flavor_has_severities_and_attributes() {
+    let (_guard, _buffer) = common::capture();
+    format::set_json_flavor(JsonFlavor::Otel);
+    let fields = [Field::display("user_id", &42), Field::display("path", &"/var")];
+    let with_fields = json(&record(&fields));
+    let without_fields = json(&record(&[]));
+    let info = json(&Record::new(Level::Info, "up"));
+    format::set_json_flavor(JsonFlavor::Default);
+
+    assert_eq!(
+        with_fields,
+        concat!(
+            r#"{"timestamp":"2024-05-01T12:00:00.123456789Z","severity_number":13,"severity_text":"WARN","#,
+            r#""target":"my_app::db","message":"disk full","attributes":{"user_id":"42","path":"/var"}}"#,
+        )
+    );
+    assert_eq!(
+        without_fields,
+        r#"{"timestamp":"2024-05-01T12:00:00.123456789Z","severity_number":13,"severity_text":"WARN","target":"my_app::db","message":"disk full"}"#
+    );
+    assert_eq!(info, r#"{"severity_number":9,"severity_text":"INFO","message":"up"}"#);
+}
+
+#[test]
+fn every_level_has_a_severity() {
+    assert_eq!(Level::Debug.otel_severity(), (5, "DEBUG"));
+    assert_eq!(Level::Info.otel_severity(), (9, "INFO"));
+    assert_eq!(Level::Warn.otel_severity(), (13, "WARN"));
+    assert_eq!(Level::Error.otel_severity(), (17, "ERROR"));
+    assert_eq!(Level::None.otel_severity(), (0, "UNSPECIFIED"));
+}
+
+#[test]
+fn field_names_still_apply() {
+    let (_guard, _buffer) = common::capture();
+    format::set_json_flavor(JsonFlavor::Otel);
+    format::set_field_names(FieldNames::default().rename(StandardKey::Message, "body").rename_field("user_id", "user.id"));
+    let fields = [Field::display("user_id", &42)];
+    let line = json(&record(&fields));
+    format::set_field_names(FieldNames::default());
+    format::set_json_flavor(JsonFlavor::Default);
+
+    assert_eq!(
+        line,
+        concat!(
+            r#"{"timestamp":"2024-05-01T12:00:00.123456789Z","severity_number":13,"severity_text":"WARN","#,
+            r#""target":"my_app::db","body":"disk full","attributes":{"user.id":"42"}}"#,
+        )
+    );
+}
+
+#[test]
+fn the_console_writes_the_flavor() {
+    let (_guard, buffer) = common::capture();
+    format::set_format(Format::Json);
+    format::set_json_flavor(JsonFlavor::Otel);
+    warn!("low"; free = "5%");
+    set_stable_test_format(false);
+    log!("real clock");
+    set_stable_test_format(true);
+    format::set_json_flavor(JsonFlavor::Default);
+    format::set_format(Format::Pretty);
+
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        lines[0],
+        r#"{"timestamp":"TS","severity_number":13,"severity_text":"WARN","target":"json_flavor","message":"low","attributes":{"free":"5%"}}"#
+    );
+    #[cfg(feature = "timestamps")]
+    {
+        // 2024-05-01T12:00:00.123456789Z
+        let timestamp = lines[1].strip_prefix(r#"{"timestamp":""#).unwrap().split('"').next().unwrap();
+        let fraction = timestamp.split_once('.').unwrap().1;
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        assert_eq!(digits, 9, "{timestamp}");
+    }
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use rust_logger::theme::{self, Color, Theme, TimeOfDay};
+use rust_logger::Level;
+
+#[test]
+fn restyling_keeps_the_theme_schedule() {
+    let (_guard, _buffer) = common::capture();
+
+    theme::set_theme_schedule(Theme::default(), Theme::colorblind(), (TimeOfDay::new(7, 0), TimeOfDay::new(19, 0)));
+    let warn = Theme::default().warn.label("WARN").fg(Color::Black);
+    theme::set_level_style(Level::Warn, warn.clone()).unwrap();
+
+    assert_eq!(theme::theme().warn, warn);
+    assert!(theme::toggle_theme(), "the schedule ended");
+    assert_eq!(theme::theme().warn, warn, "the other scheduled theme was not restyled");
+
+    theme::reset_level_styles();
+    assert_eq!(theme::theme().warn, Theme::default().warn);
+    assert!(theme::toggle_theme());
+    assert_eq!(theme::theme().warn, Theme::default().warn);
+    theme::set_theme(Theme::default());
+}
+
+#[test]
+fn concurrent_restyles_all_land() {
+    let (_guard, _buffer) = common::capture();
+    theme::set_theme(Theme::default());
+
+    let levels = [Level::Debug, Level::Info, Level::Warn, Level::Error];
+    std::thread::scope(|scope| {
+        for (i, level) in levels.into_iter().enumerate() {
+            scope.spawn(move || {
+                for _ in 0..200 {
+                    let style = theme::theme().style(level).clone().fg(Color::Indexed(i as u8));
+                    theme::set_level_style(level, style).unwrap();
+                }
+            });
+        }
+    });
+
+    let current = theme::theme();
+    for (i, level) in levels.into_iter().enumerate() {
+        assert_eq!(current.style(level).fg, Color::Indexed(i as u8).sgr(false), "{level:?}");
+    }
+    theme::set_theme(Theme::default());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use rust_logger::{debug, error, level, log, warn, Level};
+
+fn log_all() {
+    debug!("d");
+    log!("i");
+    warn!("w");
+    error!("e");
+}
+
+#[test]
+fn flipping_the_level_changes_which_macros_print() {
+    let (_guard, buffer) = common::capture();
+
+    level::set_level(Level::Warn);
+    assert_eq!(level::level(), Level::Warn);
+    log_all();
+    assert_eq!(buffer.text(), " WRN  [TS] levels: w \n ERR  [TS] levels: e \n");
+
+    buffer.clear();
+    level::set_level(Level::Debug);
+    log_all();
+    assert_eq!(buffer.text(), " DBG  [TS] levels: d \n LOG  [TS] levels: i \n WRN  [TS] levels: w \n ERR  [TS] levels: e \n");
+
+    buffer.clear();
+    level::set_level(Level::None);
+    log_all();
+    assert_eq!(buffer.text(), "");
+}
+
+#[test]
+fn other_threads_see_a_new_level_right_away() {
+    let (_guard, buffer) = common::capture();
+
+    level::set_level(Level::Error);
+    std::thread::scope(|scope| {
+        scope.spawn(|| log!("hidden")).join().unwrap();
+        level::set_level(Level::Info);
+        scope.spawn(|| {
+            assert!(level::is_enabled(Level::Info) && !level::is_enabled(Level::Debug));
+            log!("shown");
+        });
+    });
+
+    assert_eq!(buffer.text(), " LOG  [TS] levels: shown \n");
+}
+
+#[test]
+fn level_names_parse_at_runtime() {
+    let (_guard, _buffer) = common::capture();
+
+    level::set_level_by_name("warn").unwrap();
+    assert_eq!(level::level(), Level::Warn);
+    assert!(level::set_level_by_name("loud").is_err());
+    assert_eq!(level::level(), Level::Warn, "a bad name keeps the level");
+}
+
+const CHILD: &str = "RUST_LOGGER_LEVELS_CHILD";
+
+/// In the child: applies `LOG_LEVEL` before any API call.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return; }
+    eprintln!("applied: {:?}", level::init_from_env());
+    eprintln!("level: {:?}", level::level());
+}
+
+#[test]
+fn init_from_env_reads_the_process_environment() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .env("LOG_LEVEL", "error")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("applied: Some(Error)\nlevel: Error\n"), "{stderr}");
+}
@@ -0,0 +1,53 @@
+//! Local offsets come from the C library, which reads `TZ` once; each case
+//! runs in a child process with its own `TZ`. POSIX rule strings need no
+//! time zone database.
+#![cfg(all(unix, feature = "timestamps"))]
+
+use std::process::Command;
+use std::time::{Duration, UNIX_EPOCH};
+
+use rust_logger::time::{format_timestamp, TimeZoneMode, TimestampFormat};
+
+const CHILD: &str = "RUST_LOGGER_LOCAL_TIME_CHILD";
+
+/// In the child: prints the local RFC 3339 times at the given unix seconds.
+#[test]
+fn child() {
+    let Ok(times) = std::env::var(CHILD) else { return };
+    for secs in times.split(',') {
+        let time = UNIX_EPOCH + Duration::from_secs(secs.parse().unwrap());
+        println!("local={}", format_timestamp(time, TimeZoneMode::Local, &TimestampFormat::Rfc3339));
+    }
+}
+
+fn local_times(tz: &str, times: &str) -> Vec<String> {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, times)
+        .env("TZ", tz)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    // libtest may print `test child ... ` in front of the first line.
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.split_once("local=").map(|(_, time)| time))
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn fixed_offsets_follow_tz() {
+    assert_eq!(local_times("<+0530>-5:30", "0"), ["1970-01-01T05:30:00.000+05:30"]);
+    assert_eq!(local_times("<-11>11", "0"), ["1969-12-31T13:00:00.000-11:00"]);
+}
+
+#[test]
+fn daylight_saving_rules_follow_tz() {
+    // 2024-01-15 and 2024-07-15, 12:00 UTC.
+    assert_eq!(
+        local_times("EST5EDT,M3.2.0,M11.1.0", "1705320000,1721044800"),
+        ["2024-01-15T07:00:00.000-05:00", "2024-07-15T08:00:00.000-04:00"]
+    );
+}
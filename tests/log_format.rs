@@ -0,0 +1,83 @@
+//! `LOG_FORMAT` is read once per process, so each case runs in a child.
+
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+use rust_logger::format::{self, Format};
+#[cfg(feature = "timestamps")]
+use rust_logger::time::{self, TimestampFormat};
+use rust_logger::warn;
+
+const CHILD: &str = "RUST_LOGGER_LOG_FORMAT_CHILD";
+
+/// In the child: logs one known record, with colors on and real escape
+/// sequences, and writes what the console got to stderr.
+#[test]
+fn child() {
+    let Some(case) = std::env::var_os(CHILD) else { return };
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    if case == "api" { format::set_format(Format::Json); }
+
+    warn!("disk <red>almost</> full"; free = "2 GB");
+    warn!("again");
+    eprint!("{}", buffer.text());
+}
+
+fn run(case: &str, log_format: Option<&str>) -> String {
+    let mut command = std::process::Command::new(std::env::current_exe().unwrap());
+    command.args(["--exact", "child", "--nocapture", "--test-threads=1"]).env(CHILD, case).env_remove("LOG_FORMAT");
+    if let Some(value) = log_format { command.env("LOG_FORMAT", value); }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn pretty_without_the_variable() {
+    let out = run("env", None);
+    assert!(out.starts_with("\x1b[0;43;38;2;0;0;0m WRN \x1b[0m "), "{out:?}");
+    assert_eq!(out.lines().count(), 2, "{out:?}");
+}
+
+#[test]
+fn json() {
+    assert_eq!(
+        run("env", Some("json")),
+        "{\"level\":\"warn\",\"target\":\"log_format\",\"message\":\"disk almost full\",\"free\":\"2 GB\"}\n\
+         {\"level\":\"warn\",\"target\":\"log_format\",\"message\":\"again\"}\n"
+    );
+}
+
+#[test]
+fn logfmt() {
+    assert_eq!(
+        run("env", Some(" LOGFMT ")),
+        "level=warn target=log_format msg=\"disk almost full\" free=\"2 GB\"\nlevel=warn target=log_format msg=again\n"
+    );
+}
+
+#[test]
+fn plain_is_the_pretty_layout_without_escapes() {
+    assert_eq!(run("env", Some("plain")), " WRN  log_format: disk almost full free=2 GB \n WRN  log_format: again \n");
+}
+
+#[test]
+fn an_unknown_value_warns_once_and_stays_pretty() {
+    let out = run("env", Some("yaml"));
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3, "{out:?}");
+    assert!(lines[0].contains("[logger]:"), "{out:?}");
+    assert!(lines[0].contains("using the pretty format: unknown format \"yaml\": expected pretty, json, logfmt or plain"), "{out:?}");
+    assert!(lines[1].starts_with("\x1b[0;43;38;2;0;0;0m WRN \x1b[0m "), "{out:?}");
+}
+
+#[test]
+fn set_format_wins() {
+    let out = run("api", Some("logfmt"));
+    assert!(out.starts_with("{\"level\":\"warn\""), "{out:?}");
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::path::Path;
+
+use rust_logger::display::{self, Charset, Lossy, P};
+use rust_logger::format::{set_format, Format};
+use rust_logger::log;
+
+/// The dimmed replacement `Lossy` draws for each invalid sequence.
+const BAD: &str = "\x1b[2m\u{FFFD}\x1b[22m";
+
+#[test]
+fn valid_utf8_is_written_as_is() {
+    assert_eq!(Lossy(b"").to_string(), "");
+    assert_eq!(Lossy("plain, caf\u{e9}, \u{1f980}".as_bytes()).to_string(), "plain, caf\u{e9}, \u{1f980}");
+}
+
+#[test]
+fn each_invalid_sequence_gets_one_replacement() {
+    assert_eq!(Lossy(b"a\xffb").to_string(), format!("a{BAD}b"));
+    // Two invalid bytes in a row are two sequences.
+    assert_eq!(Lossy(b"\xff\xfe").to_string(), format!("{BAD}{BAD}"));
+    // A character cut short at the end is one.
+    assert_eq!(Lossy(b"euro \xe2\x82").to_string(), format!("euro {BAD}"));
+    // Latin-1 text read as UTF-8.
+    assert_eq!(Lossy(b"caf\xe9 cr\xe8me").to_string(), format!("caf{BAD} cr{BAD}me"));
+    // UTF-8 encoded surrogates are invalid.
+    assert_eq!(Lossy(b"\xed\xa0\x80!").to_string(), format!("{BAD}{BAD}{BAD}!"));
+}
+
+#[test]
+fn paths_are_shown_without_quotes() {
+    assert_eq!(P(Path::new("/tmp/my file \u{e9}.txt")).to_string(), "/tmp/my file \u{e9}.txt");
+    assert_eq!(P(Path::new("")).to_string(), "");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths_keep_their_valid_parts() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = Path::new(OsStr::from_bytes(b"/srv/caf\xe9/r\xe9sum\xe9.txt"));
+    assert_eq!(P(path).to_string(), format!("/srv/caf{BAD}/r{BAD}sum{BAD}.txt"));
+    // The same text `Lossy` draws for the raw bytes.
+    assert_eq!(P(path).to_string(), Lossy(path.as_os_str().as_bytes()).to_string());
+}
+
+#[test]
+fn logged_records_show_the_replacement() {
+    let (_guard, buffer) = common::capture();
+    log!("read {} from {}", Lossy(b"ok\xff"), P(Path::new("/tmp/x")));
+    // The stable format drops the dimming, not the character.
+    assert_eq!(buffer.text(), " LOG  [TS] lossy: read ok\u{FFFD} from /tmp/x \n");
+
+    buffer.clear();
+    set_format(Format::Json);
+    log!("{}", Lossy(b"\"q\"\xff"));
+    set_format(Format::Pretty);
+    assert!(buffer.text().contains("\"message\":\"\\\"q\\\"\u{FFFD}\""), "{}", buffer.text());
+}
+
+#[test]
+fn the_ascii_charset_uses_a_question_mark() {
+    let (_guard, buffer) = common::capture();
+    display::set_charset(Charset::Ascii);
+    let shown = Lossy(b"a\xffb").to_string();
+    log!("{}", Lossy(b"a\xffb"));
+    display::set_charset(Charset::Unicode);
+
+    assert_eq!(shown, "a\x1b[2m?\x1b[22mb");
+    assert_eq!(buffer.text(), " LOG  [TS] lossy: a?b \n");
+}
@@ -0,0 +1,30 @@
+//! Built with `max_level_info` and `release_max_level_warn`: the stripped
+//! calls are gone before LOG_LEVEL or the runtime level get a say, so their
+//! arguments never run.
+
+use rust_logger::{debug, error, level, log, warn, Level};
+
+#[test]
+#[allow(unreachable_code, clippy::diverging_sub_expression)]
+fn stripped_arguments_never_run() {
+    level::set_level(Level::Debug);
+    assert!(!level::is_enabled(Level::Debug));
+
+    debug!("{}", panic!("debug! evaluated its arguments"));
+    debug!(once: "{}", panic!("debug! evaluated its arguments"); n = panic!("and its fields"));
+    if !cfg!(debug_assertions) {
+        log!("{}", panic!("log! evaluated its arguments in a release build"));
+    }
+}
+
+#[test]
+fn levels_above_the_cap_still_log() {
+    let expected = if cfg!(debug_assertions) { Level::Info } else { Level::Warn };
+    assert_eq!(level::MAX_LEVEL, expected);
+    assert!(level::is_enabled(Level::Warn));
+
+    let mut ran = 0;
+    warn!("{}", { ran += 1; ran });
+    error!("{}", { ran += 1; ran });
+    assert_eq!(ran, 2);
+}
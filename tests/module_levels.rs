@@ -0,0 +1,180 @@
+mod common;
+
+use rust_logger::level::{self, is_enabled_for, LevelSpec};
+use rust_logger::{debug, log, warn, Level};
+
+mod a {
+    pub mod b {
+        pub fn emit() {
+            rust_logger::debug!("b debug");
+            rust_logger::warn!("b warn");
+        }
+
+        pub mod c {
+            pub fn emit() {
+                rust_logger::debug!("c debug");
+                rust_logger::warn!("c warn");
+            }
+        }
+    }
+
+    pub mod bc {
+        pub fn emit() {
+            rust_logger::debug!("bc debug");
+        }
+    }
+}
+
+#[test]
+fn the_longest_prefix_wins() {
+    let (_guard, buffer) = common::capture();
+    level::set_module_level("module_levels::a::b", Level::Warn);
+    level::set_module_level("module_levels::a::b::c", Level::Debug);
+
+    a::b::emit();
+    a::b::c::emit();
+    level::clear_module_levels();
+
+    assert_eq!(
+        buffer.text(),
+        " WRN  [TS] module_levels::a::b: b warn \n\
+         \x20DBG  [TS] module_levels::a::b::c: c debug \n\
+         \x20WRN  [TS] module_levels::a::b::c: c warn \n"
+    );
+}
+
+#[test]
+fn overlapping_prefixes_in_either_order() {
+    let (_guard, _buffer) = common::capture();
+    level::set_level(Level::Info);
+    // Set the longer one first: the order does not decide.
+    level::set_module_level("a::b::c", Level::Error);
+    level::set_module_level("a::b", Level::Debug);
+
+    assert!(is_enabled_for(Level::Debug, "a::b"));
+    assert!(is_enabled_for(Level::Debug, "a::b::d"));
+    assert!(!is_enabled_for(Level::Warn, "a::b::c"));
+    assert!(!is_enabled_for(Level::Warn, "a::b::c::d"));
+    // Prefixes match whole segments only.
+    assert!(!is_enabled_for(Level::Debug, "a::bc"));
+    assert!(!is_enabled_for(Level::Debug, "a::b_c"));
+    level::clear_module_levels();
+}
+
+#[test]
+fn modules_without_a_level_use_the_default() {
+    let (_guard, buffer) = common::capture();
+    level::set_level(Level::Warn);
+    level::set_module_level("module_levels::a::b", Level::Debug);
+
+    a::bc::emit();
+    debug!("top debug");
+    log!("top log");
+    warn!("top warn");
+    a::b::emit();
+    level::clear_module_levels();
+
+    assert_eq!(
+        buffer.text(),
+        " WRN  [TS] module_levels: top warn \n\
+         \x20DBG  [TS] module_levels::a::b: b debug \n\
+         \x20WRN  [TS] module_levels::a::b: b warn \n"
+    );
+    assert!(is_enabled_for(Level::Warn, "anything"));
+    assert!(!is_enabled_for(Level::Info, "anything"));
+}
+
+#[test]
+fn specs_set_the_default_and_the_modules() {
+    let (_guard, _buffer) = common::capture();
+    assert_eq!(
+        level::parse_level_spec("debug, my_crate::net=warn,hyper=error").unwrap(),
+        LevelSpec {
+            default: Some(Level::Debug),
+            modules: vec![(String::from("my_crate::net"), Level::Warn), (String::from("hyper"), Level::Error)],
+            kinds: vec![],
+        }
+    );
+    assert!(level::parse_level_spec("my_crate=loud").is_err());
+
+    level::set_level_spec("warn,my_crate=debug,my_crate::net=error").unwrap();
+    assert!(is_enabled_for(Level::Debug, "my_crate::db"));
+    assert!(!is_enabled_for(Level::Warn, "my_crate::net::tcp"));
+    assert!(!is_enabled_for(Level::Info, "hyper"));
+    assert!(is_enabled_for(Level::Warn, "hyper"));
+    level::clear_module_levels();
+}
+
+#[test]
+fn glob_patterns_are_anchored_at_segments() {
+    let (_guard, _buffer) = common::capture();
+    level::set_level(Level::Info);
+    level::set_module_level("*::metrics", Level::Error);
+
+    assert!(!is_enabled_for(Level::Warn, "app::metrics"));
+    assert!(!is_enabled_for(Level::Warn, "a::b::metrics"));
+    assert!(!is_enabled_for(Level::Warn, "a::metrics::http"), "a pattern covers the modules below");
+    assert!(is_enabled_for(Level::Info, "app::my_metrics"), "`*::metrics` stops at `::`");
+    assert!(is_enabled_for(Level::Info, "app::metrics_v2"), "and ends at one");
+    assert!(is_enabled_for(Level::Info, "metrics"), "the `::` is literal");
+
+    // `*` inside a segment matches raw text.
+    level::set_module_level("*metrics", Level::Error);
+    assert!(!is_enabled_for(Level::Warn, "app::my_metrics"));
+    assert!(!is_enabled_for(Level::Warn, "metrics"));
+    assert!(is_enabled_for(Level::Info, "app::metrics_v2"));
+    level::clear_module_levels();
+
+    level::set_module_level("app::*::db", Level::Debug);
+    assert!(is_enabled_for(Level::Debug, "app::store::db"));
+    assert!(is_enabled_for(Level::Debug, "app::store::replica::db::pool"), "`*` spans segments");
+    assert!(!is_enabled_for(Level::Debug, "app::store::mydb"));
+    assert!(!is_enabled_for(Level::Debug, "app::db"), "the `::` on both sides are literal");
+    assert!(!is_enabled_for(Level::Debug, "other::app::x::db"), "anchored at the start");
+    level::clear_module_levels();
+}
+
+#[test]
+fn a_lone_star_covers_everything_and_loses_to_every_other_pattern() {
+    let (_guard, _buffer) = common::capture();
+    level::set_level(Level::Info);
+    level::set_module_level("*", Level::None);
+    assert!(!is_enabled_for(Level::Error, "anything"));
+    assert!(!is_enabled_for(Level::Error, "a::b::c"));
+    assert!(!is_enabled_for(Level::Error, ""));
+
+    level::set_module_level("app::*", Level::Warn);
+    assert!(is_enabled_for(Level::Warn, "app::x"));
+    assert!(!is_enabled_for(Level::Error, "application"), "`app::*` needs the `::`");
+    level::clear_module_levels();
+}
+
+#[test]
+fn overlapping_patterns_go_by_literal_length_then_order() {
+    let (_guard, _buffer) = common::capture();
+    level::set_level(Level::Info);
+    level::set_module_level("app::*", Level::Warn);
+    level::set_module_level("app::db", Level::Debug);
+    level::set_module_level("*::db::*", Level::Error);
+
+    // `app::db` (7 literal characters) beats `*::db::*` (6) and `app::*` (5).
+    assert!(is_enabled_for(Level::Debug, "app::db::pool"));
+    assert!(!is_enabled_for(Level::Warn, "web::db::pool"));
+    assert!(!is_enabled_for(Level::Info, "app::cache"));
+    level::clear_module_levels();
+
+    // Equally specific: the one set first wins, whichever order they come in.
+    for (patterns, debug_passes) in [([("*::metrics", Level::Error), ("app::*::db", Level::Debug)], false), ([("app::*::db", Level::Debug), ("*::metrics", Level::Error)], true)] {
+        for (pattern, level) in patterns {
+            level::set_module_level(pattern, level);
+        }
+        assert_eq!(is_enabled_for(Level::Debug, "app::metrics::db"), debug_passes, "{patterns:?}");
+        level::clear_module_levels();
+    }
+
+    // A spec takes patterns too.
+    level::set_level_spec("warn,module_levels::*=error").unwrap();
+    assert!(!is_enabled_for(Level::Warn, "module_levels::a"));
+    assert!(is_enabled_for(Level::Warn, "module_levels"));
+    level::clear_module_levels();
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::file::{self, RotationPolicy};
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{error, log, mute, mute_sink, warn, Level};
+
+/// Keeps the message of every record.
+#[derive(Clone, Default)]
+struct Messages(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Messages {
+    fn write(&mut self, record: &Record, _line: &str) {
+        self.0.lock().unwrap().push(record.message.to_string());
+    }
+}
+
+fn messages(text: &str) -> Vec<&str> {
+    text.lines().map(|line| line.split(": ").nth(1).unwrap().trim_end()).collect()
+}
+
+#[test]
+fn mutes_nest_and_restore() {
+    let (_guard, buffer) = common::capture();
+    {
+        let _outer = mute!(Level::Warn);
+        log!("info under warn");
+        warn!("warn under warn");
+        {
+            let _inner = mute!(Level::Error);
+            warn!("warn under error");
+            error!("error under error");
+            // A weaker mute inside does not unmute anything.
+            let _weaker = mute!(Level::Info);
+            warn!("warn under info inside error");
+        }
+        warn!("warn after the inner mute");
+        log!("info after the inner mute");
+    }
+    log!("info after both");
+
+    assert_eq!(messages(&buffer.text()), [
+        "warn under warn",
+        "error under error",
+        "warn after the inner mute",
+        "info after both",
+    ]);
+}
+
+#[test]
+fn a_mute_is_local_to_its_thread() {
+    let (_guard, buffer) = common::capture();
+    let _mute = mute!(Level::Error);
+    std::thread::spawn(|| log!("from another thread")).join().unwrap();
+    log!("muted here");
+
+    assert_eq!(messages(&buffer.text()), ["from another thread"]);
+}
+
+#[test]
+fn one_sink_can_be_muted_while_the_console_goes_on() {
+    let (_guard, buffer) = common::capture();
+    let (quiet, loud) = (Messages::default(), Messages::default());
+    let quiet_id = sink::add_sink(Box::new(quiet.clone()), Level::Debug, Format::Pretty);
+    let loud_id = sink::add_sink(Box::new(loud.clone()), Level::Debug, Format::Pretty);
+    {
+        let _outer = mute_sink!(quiet_id);
+        log!("first");
+        {
+            let _inner = mute_sink!(quiet_id);
+            log!("second");
+        }
+        // Still muted by the outer guard.
+        log!("third");
+        std::thread::spawn(|| log!("other thread")).join().unwrap();
+    }
+    log!("fourth");
+    sink::remove_sink(quiet_id);
+    sink::remove_sink(loud_id);
+
+    assert_eq!(*quiet.0.lock().unwrap(), ["other thread", "fourth"]);
+    assert_eq!(*loud.0.lock().unwrap(), ["first", "second", "third", "other thread", "fourth"]);
+    assert_eq!(messages(&buffer.text()), ["first", "second", "third", "other thread", "fourth"]);
+}
+
+#[test]
+fn a_log_file_can_be_muted() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-mute-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let log_file = file::add_file_sink(&path, RotationPolicy::Never).unwrap();
+    {
+        let _quiet = mute_sink!(&log_file);
+        log!("console only");
+    }
+    log!("both");
+    drop(log_file);
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(!text.contains("console only") && text.contains("[LOG] [TS] mute: both"), "{text}");
+    assert_eq!(messages(&buffer.text()), ["console only", "both"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn the_black_box_still_records_muted_records() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-mute-{}.blackbox", std::process::id()));
+    file::start_black_box(&path, 64 * 1024).unwrap();
+    let messages_sink = Messages::default();
+    let id = sink::add_sink(Box::new(messages_sink.clone()), Level::Debug, Format::Pretty);
+    {
+        let _mute = mute!(Level::Error);
+        log!("muted but kept");
+        let _quiet = mute_sink!(id);
+        warn!("sink muted, still kept");
+    }
+    rust_logger::level::set_level(Level::Warn);
+    {
+        let _mute = mute!(Level::Error);
+        log!("below the level anyway");
+    }
+    rust_logger::level::set_level(Level::Debug);
+    sink::remove_sink(id);
+    let kept = file::read_black_box(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert!(buffer.text().is_empty(), "{}", buffer.text());
+    assert!(messages_sink.0.lock().unwrap().is_empty());
+    assert!(kept.iter().any(|record| record.ends_with("mute: muted but kept")), "{kept:?}");
+    assert!(kept.iter().any(|record| record.ends_with("mute: sink muted, still kept")), "{kept:?}");
+    assert!(!kept.iter().any(|record| record.contains("below the level anyway")), "{kept:?}");
+}
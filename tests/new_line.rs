@@ -0,0 +1,89 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::file;
+use rust_logger::format::{set_format, Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{level, log, new_line, Level};
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Lines(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Lines {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+impl Lines {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+#[test]
+fn a_blank_line_reaches_the_console_files_and_text_sinks() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-new-line-{}.log", std::process::id()));
+    let file_sink = file::file_sink(&path).session_header(false).open().unwrap();
+    let (text, json) = (Lines::default(), Lines::default());
+    let text_id = sink::add_sink(Box::new(text.clone()), Level::Debug, Format::Pretty);
+    let json_id = sink::add_sink(Box::new(json.clone()), Level::Debug, Format::Json);
+
+    log!("before");
+    new_line!();
+    log!("after");
+    drop(file_sink);
+    sink::remove_sink(text_id);
+    sink::remove_sink(json_id);
+    let file_text = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(buffer.text(), " LOG  [TS] new_line: before \n\n LOG  [TS] new_line: after \n");
+    assert_eq!(file_text, "[LOG] [TS] new_line: before\n\n[LOG] [TS] new_line: after\n");
+    assert_eq!(text.take(), ["[LOG] [TS] new_line: before", "", "[LOG] [TS] new_line: after"]);
+    // A blank line is not a JSON record.
+    let json = json.take();
+    assert_eq!(json.len(), 2, "{json:?}");
+    assert!(json.iter().all(|line| line.starts_with('{')), "{json:?}");
+}
+
+#[test]
+fn the_level_filters_every_output() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-new-line-level-{}.log", std::process::id()));
+    let file_sink = file::file_sink(&path).session_header(false).level(Level::Warn).open().unwrap();
+    let warn_sink = Lines::default();
+    let id = sink::add_sink(Box::new(warn_sink.clone()), Level::Warn, Format::Plain);
+
+    level::set_level(Level::Info);
+    new_line!(Level::Debug);
+    // Shown on the console, but below the file's and the sink's levels.
+    new_line!(Level::Info);
+    new_line!(Level::Error);
+    drop(file_sink);
+    sink::remove_sink(id);
+    let file_text = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(buffer.text(), "\n\n");
+    assert_eq!(file_text, "\n");
+    assert_eq!(warn_sink.take(), [""]);
+}
+
+#[test]
+fn structured_console_output_skips_it() {
+    let (_guard, buffer) = common::capture();
+    let text = Lines::default();
+    let id = sink::add_sink(Box::new(text.clone()), Level::Debug, Format::Pretty);
+    set_format(Format::Json);
+    new_line!();
+    set_format(Format::Pretty);
+    sink::remove_sink(id);
+
+    assert_eq!(buffer.text(), "");
+    // A text sink still gets its blank line.
+    assert_eq!(text.take(), [""]);
+}
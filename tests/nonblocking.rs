@@ -0,0 +1,100 @@
+//! `set_console_nonblocking` with stdout a consumer that never reads.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read};
+use std::os::raw::c_int;
+use std::process::{Command, Stdio};
+
+use rust_logger::{console, log};
+
+extern "C" {
+    fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+}
+
+const F_GETFL: c_int = 3;
+const F_SETPIPE_SZ: c_int = 1031;
+const O_NONBLOCK: c_int = 0o4000;
+
+const CHILD: &str = "RUST_LOGGER_NONBLOCKING_CHILD";
+const RECORDS: u64 = 20_000;
+const PIPE_CAPACITY: usize = 16384;
+const PREFIX: &str = " LOG  [TS] nonblocking: record ";
+
+/// In the child: logs to a stdout nobody reads, then reports on stderr.
+#[test]
+fn child() {
+    let Some(mode) = std::env::var_os(CHILD) else { return };
+    let (_guard, _buffer) = common::capture();
+    console::reset_writer();
+    if mode == "pipe" {
+        // SAFETY: fd 1 is the pipe the parent made.
+        assert_eq!(unsafe { fcntl(1, F_SETPIPE_SZ, PIPE_CAPACITY as c_int) }, PIPE_CAPACITY as c_int);
+    }
+    console::set_console_nonblocking(true).unwrap();
+    for i in 0..RECORDS {
+        log!("record {i:05}");
+    }
+    // SAFETY: F_GETFL only reads the flags.
+    let blocking = unsafe { fcntl(1, F_GETFL) } & O_NONBLOCK == 0;
+    eprintln!("dropped: {} blocking: {blocking}", console::dropped_records());
+}
+
+/// Runs the child with `stdout` and, once it has logged, reads what it
+/// got through. Returns the record numbers written whole, the number
+/// dropped, the bytes written and whether fd 1 stayed blocking.
+fn run(mode: &str, stdout: Stdio, mut output: impl Read) -> (Vec<u64>, u64, usize, bool) {
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, mode)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut report = String::new();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    while !report.starts_with("dropped: ") {
+        report.clear();
+        assert_ne!(stderr.read_line(&mut report).unwrap(), 0, "the child ended without a report: it blocked or failed");
+    }
+    let mut written = Vec::new();
+    output.read_to_end(&mut written).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let mut words = report.split_whitespace();
+    let dropped = words.nth(1).unwrap().parse().unwrap();
+    let blocking = words.nth(1).unwrap() == "true";
+    let text = String::from_utf8_lossy(&written);
+    // The last record on a line: the test harness may have begun it, and a
+    // record cut short leaves its start in front of the next one.
+    let records = text
+        .lines()
+        .filter_map(|line| line[line.rfind(PREFIX)? + PREFIX.len()..].strip_suffix(' ')?.parse().ok())
+        .collect();
+    let bytes = text.lines().filter(|line| line.contains(PREFIX)).map(|line| line.len() + 1).sum();
+    (records, dropped, bytes, blocking)
+}
+
+#[test]
+fn a_full_pipe_drops_whole_records() {
+    let (reader, writer) = std::io::pipe().unwrap();
+    let (records, dropped, bytes, blocking) = run("pipe", writer.into(), reader);
+
+    assert!(bytes <= PIPE_CAPACITY, "no more than the pipe holds: {bytes}");
+    assert!(records.len() > 100, "{}", records.len());
+    assert_eq!(records.len() as u64 + dropped, RECORDS, "every record written whole or counted");
+    assert_eq!(records, (0..records.len() as u64).collect::<Vec<u64>>(), "the first ones, in order");
+    assert!(blocking, "fd 1 keeps its flags");
+}
+
+#[test]
+fn a_full_socket_drops_records_too() {
+    let (reader, writer) = std::os::unix::net::UnixStream::pair().unwrap();
+    let (records, dropped, _, blocking) = run("socket", std::os::fd::OwnedFd::from(writer).into(), reader);
+
+    assert!(dropped > 0 && !records.is_empty(), "{} written, {dropped} dropped", records.len());
+    assert_eq!(records.len() as u64 + dropped, RECORDS);
+    assert!(records.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(blocking);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::sync::Barrier;
+
+use rust_logger::{level, log, warn, Level};
+
+#[test]
+fn once_fires_once_under_contention() {
+    let (_guard, buffer) = common::capture();
+
+    let start = Barrier::new(16);
+    std::thread::scope(|scope| {
+        for _ in 0..16 {
+            scope.spawn(|| {
+                start.wait();
+                for _ in 0..500 {
+                    warn!(once: "fallback path taken");
+                }
+            });
+        }
+    });
+
+    assert_eq!(buffer.text(), " WRN  [TS] once_every: fallback path taken \n");
+}
+
+#[test]
+fn every_fires_on_the_first_and_each_nth_call_after() {
+    let (_guard, buffer) = common::capture();
+
+    for call in 1..=25 {
+        log!(every: 10, "call {call}");
+    }
+
+    assert_eq!(
+        buffer.text(),
+        " LOG  [TS] once_every: call 1 \n LOG  [TS] once_every: call 11 \n LOG  [TS] once_every: call 21 \n"
+    );
+}
+
+#[test]
+fn every_counts_across_threads() {
+    let (_guard, buffer) = common::capture();
+
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for _ in 0..1000 {
+                    log!(every: 100, "tick");
+                }
+            });
+        }
+    });
+
+    assert_eq!(buffer.text().lines().count(), 80);
+}
+
+#[test]
+fn disabled_calls_are_not_counted() {
+    let (_guard, buffer) = common::capture();
+    let site = |call: usize| {
+        warn!(once: "first enabled call {call}");
+        log!(every: 3, "call {call}");
+    };
+
+    level::set_level(Level::Error);
+    (1..=5).for_each(site);
+    level::set_level(Level::Debug);
+    (6..=9).for_each(site);
+
+    assert_eq!(
+        buffer.text(),
+        " WRN  [TS] once_every: first enabled call 6 \n\
+         \x20LOG  [TS] once_every: call 6 \n\
+         \x20LOG  [TS] once_every: call 9 \n"
+    );
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::config::config_report;
+use rust_logger::console::{self, SharedBuffer};
+use rust_logger::format::{self, Field, Format, PrettyFormatter, Record, TemplateError, DEFAULT_PREFIX_TEMPLATE};
+use rust_logger::sink::{self, Sink};
+use rust_logger::theme::Theme;
+use rust_logger::{error, group, log, warn, Level};
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Lines(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Lines {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+#[derive(Debug)]
+struct Layered(&'static str, Option<Box<Layered>>);
+
+impl std::fmt::Display for Layered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for Layered {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.1.as_deref().map(|cause| cause as _)
+    }
+}
+
+/// A record with every part of the prefix, through `PrettyFormatter`.
+fn formatted(colors: bool) -> String {
+    let id = 7;
+    let fields = [Field::display("id", &id)];
+    let record = Record {
+        process: Some("api"), thread: Some("main"), timestamp: Some("12:00:00.000"), target: Some("app"), depth: 1,
+        category: Some("audit"), fields: &fields, suppressed: 3, causes: &["refused"],
+        ..Record::new(Level::Warn, "two\n<bold>lines</>")
+    };
+    let mut out = String::new();
+    PrettyFormatter::new(Theme::default()).colors(colors).format(&record, &mut out).unwrap();
+    out
+}
+
+/// A group, fields and an error chain, logged on a thread named `main`,
+/// through the console and to the lines of a sink.
+fn logged(buffer: &SharedBuffer) -> (String, Vec<String>) {
+    buffer.clear();
+    let lines = Lines::default();
+    let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+    console::show_thread(true);
+    console::set_process_tag("api");
+    let log_all = || {
+        let _group = group!("outer");
+        warn!("two\n<bold>lines</>"; id = 7);
+        error!(err = Layered("refused", Some(Box::new(Layered("reset by peer", None)))), "failed");
+    };
+    std::thread::Builder::new().name(String::from("main")).spawn(log_all).unwrap().join().unwrap();
+    console::set_process_tag("");
+    console::show_thread(false);
+    sink::remove_sink(id);
+    let lines = lines.0.lock().unwrap().clone();
+    (buffer.text(), lines)
+}
+
+/// The bytes before templates existed.
+const COLORED: &str = concat!(
+    "\x1b[0;43;38;2;0;0;0m WRN \x1b[0m \x1b[38;5;183mapi\x1b[0m \x1b[0;2m[main]\x1b[22m \x1b[33m[12:00:00.000] ",
+    "\x1b[0;2mapp:\x1b[22m \x1b[33m  \x1b[1m[audit]\x1b[22m two \x1b[0m\n",
+    "                                       \x1b[33m\x1b[1mlines\x1b[0m\x1b[33m \x1b[2m(suppressed 3 similar messages)\x1b[22m ",
+    "\x1b[2mid=7\x1b[22m \x1b[0m\n",
+    "                                       \x1b[31m\x1b[2mcaused by: refused \x1b[0m",
+);
+const UNCOLORED: &str = concat!(
+    " WRN  api [main] [12:00:00.000] app:   [audit] two \n",
+    "                                       lines (suppressed 3 similar messages) id=7 \n",
+    "                                       caused by: refused ",
+);
+const CONSOLE: &str = concat!(
+    " LOG  api [main] [TS] prefix_template: outer \n",
+    " WRN  api [main] [TS] prefix_template:   two \n",
+    "                                         lines id=7 \n",
+    " ERR  api [main] [TS] prefix_template:   failed: refused \n",
+    "                                         caused by: reset by peer \n",
+);
+const SINK: [&str; 3] = [
+    "[LOG] api [main] [TS] prefix_template: outer",
+    "[WRN] api [main] [TS] prefix_template:   two\n                                         lines id=7",
+    "[ERR] api [main] [TS] prefix_template:   failed: refused\n                                         caused by: reset by peer",
+];
+
+#[test]
+fn the_default_template_draws_todays_bytes() {
+    let (_guard, buffer) = common::capture();
+    for set_explicitly in [false, true] {
+        if set_explicitly { format::set_prefix_template(DEFAULT_PREFIX_TEMPLATE).unwrap(); }
+        assert_eq!(formatted(true), COLORED);
+        assert_eq!(formatted(false), UNCOLORED);
+        let (console, sink) = logged(&buffer);
+        assert_eq!(console, CONSOLE);
+        assert_eq!(sink, SINK);
+    }
+}
+
+#[test]
+fn a_custom_layout() {
+    let (_guard, buffer) = common::capture();
+    let lines = Lines::default();
+    // Timestamp first and full level names; an empty placeholder takes the
+    // text after it along.
+    format::set_prefix_template("{ts} {level} {thread} {target} {msg} {{{level_short}}}").unwrap();
+    let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+    log!("ready");
+    warn!("two\nlines");
+    sink::remove_sink(id);
+    let report = config_report();
+    format::set_prefix_template(DEFAULT_PREFIX_TEMPLATE).unwrap();
+
+    assert_eq!(
+        buffer.text(),
+        concat!(
+            "[TS]  INFO   prefix_template: ready { LOG } \n",
+            "[TS]  WARN   prefix_template: two \n",
+            "                              lines { WRN } \n",
+        )
+    );
+    assert_eq!(
+        *lines.0.lock().unwrap(),
+        ["[TS] [INFO ] prefix_template: ready {[LOG]}", "[TS] [WARN ] prefix_template: two\n                              lines {[WRN]}"]
+    );
+    assert!(report.contains("prefix_template = {ts} {level} {thread} {target} {msg} {{{level_short}}}"), "{report}");
+}
+
+#[test]
+fn bad_templates_are_rejected_when_set() {
+    let (_guard, buffer) = common::capture();
+    assert_eq!(format::set_prefix_template("{ts} {lvl} {msg}"), Err(TemplateError::UnknownPlaceholder(String::from("lvl"))));
+    assert_eq!(format::set_prefix_template("{msg} {Level}"), Err(TemplateError::UnknownPlaceholder(String::from("Level"))));
+    assert_eq!(format::set_prefix_template("{} {msg}"), Err(TemplateError::UnknownPlaceholder(String::new())));
+    assert_eq!(format::set_prefix_template("{ts} {level}"), Err(TemplateError::MessageCount(0)));
+    assert_eq!(format::set_prefix_template("{msg} {msg}"), Err(TemplateError::MessageCount(2)));
+    assert_eq!(format::set_prefix_template("{level} {msg"), Err(TemplateError::Unclosed));
+    assert_eq!(
+        TemplateError::UnknownPlaceholder(String::from("lvl")).to_string(),
+        "unknown placeholder {lvl}: expected {ts}, {level}, {level_short}, {process}, {target}, {thread} or {msg}"
+    );
+    assert!(format::set_prefix_template("{ts").unwrap_err().to_string().contains("'{{' for a literal brace"));
+
+    // The template in use stays.
+    log!("unchanged");
+    assert_eq!(buffer.text(), " LOG  [TS] prefix_template: unchanged \n");
+    assert!(config_report().contains(&format!("prefix_template = {DEFAULT_PREFIX_TEMPLATE}")));
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use std::process::Command;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+use rust_logger::file;
+use rust_logger::format::{set_format, Format};
+use rust_logger::log;
+use rust_logger::theme::hash_color;
+#[cfg(feature = "timestamps")]
+use rust_logger::time::{self, TimestampFormat};
+
+const CHILD: &str = "RUST_LOGGER_PROCESS_TAG_CHILD";
+
+/// In the child: prints the tag read from `LOG_PROCESS_TAG`.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return; }
+    println!("tag={:?}", console::process_tag());
+}
+
+fn tagged<T>(tag: &str, f: impl FnOnce() -> T) -> T {
+    console::set_process_tag(tag);
+    let result = f();
+    console::set_process_tag("");
+    result
+}
+
+#[test]
+fn colors_are_pinned_to_the_fnv_hash() {
+    // 32-bit FNV-1a of the tag, modulo the 12 palette colors.
+    assert_eq!(hash_color("api"), 183);
+    assert_eq!(hash_color("worker-1"), 153);
+    assert_eq!(hash_color("db"), 111);
+    assert_eq!(hash_color(""), 38);
+
+    let palette = [33, 38, 43, 71, 75, 79, 99, 111, 141, 153, 176, 183];
+    for i in 0..200 {
+        let tag = format!("worker-{i}");
+        assert!(palette.contains(&hash_color(&tag)), "{tag}");
+        assert_eq!(hash_color(&tag), hash_color(&tag.clone()));
+    }
+}
+
+#[test]
+fn the_tag_follows_the_label() {
+    let (_guard, buffer) = common::capture();
+    tagged("api", || log!("tagged"));
+    log!("untagged");
+
+    assert_eq!(buffer.text(), " LOG  api [TS] process_tag: tagged \n LOG  [TS] process_tag: untagged \n");
+}
+
+#[test]
+fn colored_tags_use_their_hash_color() {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    tagged("api", || log!("a"));
+    tagged("db", || log!("b"));
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::Standard);
+    set_stable_test_format(true);
+
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].contains("\x1b[38;5;183mapi\x1b[0m"), "{:?}", lines[0]);
+    assert!(lines[1].contains("\x1b[38;5;111mdb\x1b[0m"), "{:?}", lines[1]);
+}
+
+#[test]
+fn files_and_json_carry_the_tag() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-process-tag-{}.log", std::process::id()));
+    let sink = file::file_sink(&path).session_header(false).open().unwrap();
+    tagged("api", || log!("to the file"));
+    drop(sink);
+    let file_text = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(file_text, "[LOG] api [TS] process_tag: to the file\n");
+
+    buffer.clear();
+    set_format(Format::Json);
+    tagged("api", || log!("tagged"));
+    log!("untagged");
+    set_format(Format::Pretty);
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].contains(r#""process":"api""#), "{:?}", lines[0]);
+    assert!(!lines[1].contains(r#""process""#), "{:?}", lines[1]);
+}
+
+#[test]
+fn an_empty_tag_removes_it() {
+    let (_guard, buffer) = common::capture();
+    console::set_process_tag("api");
+    assert_eq!(console::process_tag().as_deref(), Some("api"));
+    console::set_process_tag("");
+    assert_eq!(console::process_tag(), None);
+    log!("untagged");
+
+    assert_eq!(buffer.text(), " LOG  [TS] process_tag: untagged \n");
+}
+
+#[test]
+fn the_environment_sets_the_tag() {
+    let tag = |value: Option<&str>| {
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        command.args(["--exact", "child", "--nocapture", "--test-threads=1"]).env(CHILD, "1");
+        match value {
+            Some(value) => command.env("LOG_PROCESS_TAG", value),
+            None => command.env_remove("LOG_PROCESS_TAG"),
+        };
+        let output = command.output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout.split_once("tag=").map(|(_, tag)| tag.lines().next().unwrap().to_string()).expect(&stdout)
+    };
+
+    assert_eq!(tag(Some("worker-3")), r#"Some("worker-3")"#);
+    assert_eq!(tag(Some("")), "None");
+    assert_eq!(tag(None), "None");
+}
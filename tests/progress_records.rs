@@ -0,0 +1,117 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{progress, Level};
+
+/// Keeps every line it receives, with when it came.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<(Instant, String)>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push((Instant::now(), line.to_string()));
+    }
+}
+
+impl Collect {
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().map(|(_, line)| line.clone()).collect()
+    }
+}
+
+/// Runs `steps` with a sink added for its duration.
+fn with_sink(steps: impl FnOnce()) -> Collect {
+    let lines = Collect::default();
+    let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+    steps();
+    sink::remove_sink(id);
+    lines
+}
+
+#[test]
+fn the_first_and_the_last_state_are_recorded() {
+    let (_guard, buffer) = common::capture();
+
+    let lines = with_sink(|| {
+        let progress = progress!(10, "copying").record_interval(Duration::from_secs(3600));
+        for done in 1..=10 {
+            progress.set(done);
+        }
+    });
+
+    assert_eq!(
+        lines.lines(),
+        [
+            "[DBG] [TS] progress_records: [progress] copying 0/10 (0%)",
+            "[DBG] [TS] progress_records: [progress] copying 10/10 (100%)",
+        ]
+    );
+    let text = buffer.text();
+    assert!(text.contains("\r\x1b[2Kcopying 10/10 (100%)"), "the line is still drawn: {text:?}");
+    assert!(!text.contains("[progress]"), "the console gets no records: {text:?}");
+}
+
+#[test]
+fn changes_are_recorded_at_most_once_per_interval() {
+    let (_guard, _buffer) = common::capture();
+
+    let every = with_sink(|| {
+        let progress = progress!(3, "step").record_interval(Duration::ZERO);
+        progress.set(1);
+        progress.set(1);
+        progress.set(3);
+    });
+    let counts: Vec<String> = every.lines().iter().map(|line| line.rsplit(' ').nth(1).unwrap().to_string()).collect();
+    assert_eq!(counts, ["0/3", "1/3", "3/3"], "an unchanged count is not recorded again");
+
+    let interval = Duration::from_millis(50);
+    let timed = with_sink(|| {
+        let progress = progress!(1000, "timed").record_interval(interval);
+        let started = Instant::now();
+        let mut done = 0;
+        while started.elapsed() < Duration::from_millis(220) {
+            done += 1;
+            progress.set(done);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+    });
+    let records = timed.0.lock().unwrap();
+    // The first record, one per interval at most, and the final one.
+    assert!((3..=7).contains(&records.len()), "{} records", records.len());
+    for pair in records[..records.len() - 1].windows(2) {
+        assert!(pair[1].0 - pair[0].0 >= interval, "{:?} apart", pair[1].0 - pair[0].0);
+    }
+}
+
+#[test]
+fn finish_with_is_the_final_record() {
+    let (_guard, _buffer) = common::capture();
+
+    let lines = with_sink(|| {
+        let progress = progress!(4, "import").record_interval(Duration::from_secs(3600));
+        progress.set(4);
+        progress.finish_with("done");
+    });
+
+    assert_eq!(
+        lines.lines(),
+        ["[DBG] [TS] progress_records: [progress] import 0/4 (0%)", "[LOG] [TS] progress_records: import 4/4 (100%) done"]
+    );
+}
+
+#[test]
+fn without_a_terminal_the_plain_records_end_with_the_last_state() {
+    let (_guard, buffer) = common::capture();
+    rust_logger::format::set_format(Format::Json);
+    let progress = progress!(10, "plain").plain_interval(Duration::from_secs(3600));
+    progress.set(7);
+    drop(progress);
+    rust_logger::format::set_format(Format::Pretty);
+
+    let messages: Vec<String> = buffer.text().lines().map(|line| line.split(r#""message":"#).nth(1).unwrap().to_string()).collect();
+    assert_eq!(messages, [r#""plain 0/10 (0%)"}"#, r#""plain 7/10 (70%)"}"#]);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::io::{self, Write};
+use std::sync::{Arc, Condvar, Mutex};
+
+use rust_logger::console::{self, QueueOverflow, SharedBuffer};
+use rust_logger::log;
+
+/// A console that writes nothing until it is opened, like a terminal that
+/// stopped reading.
+#[derive(Clone, Default)]
+struct Stalled {
+    open: Arc<(Mutex<bool>, Condvar)>,
+    buffer: SharedBuffer,
+}
+
+impl Stalled {
+    fn open(&self) {
+        *self.open.0.lock().unwrap() = true;
+        self.open.1.notify_all();
+    }
+}
+
+impl Write for Stalled {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let mut open = self.open.0.lock().unwrap();
+        while !*open {
+            open = self.open.1.wait(open).unwrap();
+        }
+        drop(open);
+        self.buffer.write(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.buffer.flush()
+    }
+}
+
+/// Logs `records` lines into a stalled console with a queue of 8, then
+/// opens it. Returns the numbers of the lines written, and how many were
+/// spilled and dropped.
+fn burst(overflow: QueueOverflow, records: u64) -> (Vec<u64>, u64, u64) {
+    let (_guard, _buffer) = common::capture();
+    let (spilled, dropped) = (console::spilled_records(), console::queue_dropped_records());
+    let stalled = Stalled::default();
+    console::set_writer(Box::new(stalled.clone()));
+    console::set_console_queue(8).unwrap();
+    console::set_queue_overflow(overflow).unwrap();
+    for i in 0..records {
+        log!("record {i}");
+    }
+    stalled.open();
+    console::flush_console_queue();
+    console::set_queue_overflow(QueueOverflow::Block).unwrap();
+    console::set_console_queue(0).unwrap();
+
+    let text = stalled.buffer.text();
+    let written = text.lines()
+        .map(|line| line.strip_prefix(" LOG  [TS] queue_spill: record ").unwrap().trim_end().parse().unwrap())
+        .collect();
+    (written, console::spilled_records() - spilled, console::queue_dropped_records() - dropped)
+}
+
+#[test]
+fn a_burst_spills_to_disk_and_comes_back_in_order() {
+    let (written, spilled, dropped) = burst(QueueOverflow::Spill { max_bytes: 1 << 20 }, 1000);
+
+    assert_eq!(written, (0..1000).collect::<Vec<u64>>());
+    assert!(spilled > 900, "the queue holds 8 lines, the rest went to the file: {spilled}");
+    assert_eq!(dropped, 0);
+}
+
+#[test]
+fn lines_past_the_disk_cap_are_dropped_and_counted() {
+    // Room for about 100 of the 40-byte entries.
+    let (written, spilled, dropped) = burst(QueueOverflow::Spill { max_bytes: 4096 }, 1000);
+
+    assert!(written.windows(2).all(|pair| pair[0] < pair[1]), "still in order: {written:?}");
+    assert!(spilled < 120, "{spilled}");
+    assert_eq!(written.len() as u64, 1000 - dropped);
+}
+
+#[test]
+fn drop_oldest_keeps_the_newest_lines() {
+    let (written, spilled, dropped) = burst(QueueOverflow::DropOldest, 1000);
+
+    assert_eq!((written.len() as u64, spilled), (1000 - dropped, 0));
+    assert_eq!(written[written.len() - 8..], (992..1000).collect::<Vec<u64>>());
+    assert_eq!(console::queue_overflow(), QueueOverflow::Block);
+}
+
+#[test]
+fn a_file_planted_at_the_spill_path_is_left_alone() {
+    let path = std::env::temp_dir().join(format!("rust_logger-console-spill-{}.bin", std::process::id()));
+    std::fs::write(&path, "not yours\n").unwrap();
+    let (written, spilled, _) = burst(QueueOverflow::Spill { max_bytes: 1 << 20 }, 100);
+    let planted = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(planted.unwrap(), "not yours\n");
+    assert!(spilled > 80, "spilled to a file of its own: {spilled}");
+    assert_eq!(written, (0..100).collect::<Vec<u64>>());
+}
+
+#[cfg(unix)]
+#[test]
+fn the_spill_file_is_private_and_unlinked() {
+    use std::os::unix::fs::symlink;
+
+    let (_guard, _buffer) = common::capture();
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("rust_logger-console-spill-{}.bin", std::process::id()));
+    let target = dir.join(format!("rust_logger-spill-target-{}", std::process::id()));
+    std::fs::write(&target, "keep\n").unwrap();
+    symlink(&target, &path).unwrap();
+    console::set_queue_overflow(QueueOverflow::Spill { max_bytes: 1024 }).unwrap();
+    let spill_files = std::fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&format!("rust_logger-console-spill-{}-", std::process::id())))
+        .count();
+    console::set_queue_overflow(QueueOverflow::Block).unwrap();
+    let kept = std::fs::read_to_string(&target).unwrap();
+    let _ = (std::fs::remove_file(&path), std::fs::remove_file(&target));
+
+    assert_eq!(kept, "keep\n", "not truncated through the symlink");
+    assert_eq!(spill_files, 0, "the file has no name while it is in use");
+}
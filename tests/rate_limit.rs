@@ -0,0 +1,66 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use rust_logger::console::{exempt_errors, set_max_records_per_second};
+use rust_logger::{log, stats, warn};
+
+fn count(text: &str, message: &str) -> usize {
+    text.lines().filter(|line| line.contains(message)).count()
+}
+
+#[test]
+fn a_burst_lets_the_cap_through_and_counts_the_rest() {
+    let (_guard, buffer) = common::capture();
+    // Lets the bucket fill up after the other tests.
+    std::thread::sleep(Duration::from_millis(1100));
+    set_max_records_per_second(100);
+    let throttled = stats::throttled_records();
+    let started = Instant::now();
+    for i in 0..1000 { log!("burst {i}"); }
+    let elapsed = started.elapsed();
+    set_max_records_per_second(0);
+
+    // The whole bucket, plus what refilled while the loop ran.
+    let text = buffer.text();
+    let emitted = count(&text, "burst ");
+    let refilled = (elapsed.as_secs_f64() * 100.0).ceil() as usize;
+    assert!((100..=100 + refilled + 1).contains(&emitted), "{emitted} in {elapsed:?}");
+    assert_eq!(stats::throttled_records() - throttled, 1000 - emitted as u64);
+    assert_eq!(count(&text, "log output throttled: dropping records"), 1, "{text}");
+}
+
+#[test]
+fn the_bucket_refills_at_the_capped_rate() {
+    let (_guard, buffer) = common::capture();
+    set_max_records_per_second(100);
+    for i in 0..200 { log!("drain {i}"); }
+    buffer.clear();
+
+    // A fixed window would let a whole new second's worth through at once;
+    // the bucket only has what refilled, one record per 10ms.
+    let started = Instant::now();
+    std::thread::sleep(Duration::from_millis(100));
+    for i in 0..200 { log!("after {i}"); }
+    let elapsed = started.elapsed();
+    set_max_records_per_second(0);
+
+    let emitted = count(&buffer.text(), "after ");
+    let refilled = (elapsed.as_secs_f64() * 100.0) as usize;
+    assert!(emitted >= 9 && emitted <= refilled + 1, "{emitted} in {elapsed:?}");
+}
+
+#[test]
+fn exempt_errors_bypass_the_cap() {
+    let (_guard, buffer) = common::capture();
+    set_max_records_per_second(10);
+    exempt_errors(true);
+    for i in 0..50 { log!("info {i}"); }
+    for i in 0..50 { warn!("warning {i}"); }
+    exempt_errors(false);
+    set_max_records_per_second(0);
+
+    let text = buffer.text();
+    assert!(count(&text, "info ") <= 11, "{text}");
+    assert_eq!(count(&text, "warning "), 50);
+}
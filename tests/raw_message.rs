@@ -0,0 +1,63 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{alias, error, log, Level};
+
+/// Keeps the raw message, the message and the line of every record.
+#[derive(Clone, Default)]
+struct Raw(Arc<Mutex<Vec<(String, String, String)>>>);
+
+impl Sink for Raw {
+    fn write(&mut self, record: &Record, line: &str) {
+        self.0.lock().unwrap().push((record.raw_message().to_string(), record.message.to_string(), line.to_string()));
+    }
+}
+
+#[derive(Debug)]
+struct SaveError;
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cannot <write>")
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+#[test]
+fn sinks_get_the_message_as_it_was_formatted() {
+    let (_guard, _buffer) = common::capture();
+    alias!("hot", "red, bold");
+
+    let (pretty, json) = (Raw::default(), Raw::default());
+    let pretty_sink = sink::add_sink(Box::new(pretty.clone()), Level::Debug, Format::Pretty);
+    let json_sink = sink::add_sink(Box::new(json.clone()), Level::Debug, Format::Json);
+    let pristine = "<hot>disk</> \x1b[1mfull\x1b[0m on <bold>/var</>\n\tsecond line\r\n\n";
+    error!(err = SaveError, "{}", pristine);
+    log!("plain {}", 42; path = "/var/log/app");
+    sink::remove_sink(pretty_sink);
+    sink::remove_sink(json_sink);
+
+    for lines in [pretty, json] {
+        let records = lines.0.lock().unwrap();
+        let (raw, message, line) = &records[0];
+        assert_eq!(raw, pristine);
+        assert!(message.contains("full\x1b[0m on <bold>/var</>\n\tsecond line\r\n\n: cannot "), "the error is added to the message: {message:?}");
+        assert!(!line.contains('\x1b') && !line.contains("<hot>") && !line.ends_with('\n'), "{line:?}");
+
+        // Without an error the raw message is the message, with no copy made.
+        let (raw, message, _) = &records[1];
+        assert_eq!((raw.as_str(), message.as_str()), ("plain 42", "plain 42"));
+    }
+}
+
+#[test]
+fn records_built_by_hand_have_no_separate_raw_message() {
+    let record = Record::new(Level::Info, "<red>as is</>");
+    assert_eq!(record.raw_message(), "<red>as is</>");
+    let record = Record { raw: Some("original"), ..Record::new(Level::Info, "original: extra") };
+    assert_eq!(record.raw_message(), "original");
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "timestamps")]
+
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+use rust_logger::replay::{parse_pretty_line, ParsedRecord};
+use rust_logger::{debug, error, log, warn, Level};
+
+/// Logs one record per level with real timestamps and returns the lines.
+fn logged(colors: ColorChoice, process: Option<&str>, thread: bool) -> Vec<String> {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    console::set_color_choice(colors);
+    console::set_process_tag(process.unwrap_or(""));
+    console::show_thread(thread);
+    debug!("debug record");
+    log!("info <bold>record</>");
+    warn!("warn: with a colon");
+    error!("error record");
+    console::show_thread(false);
+    console::set_process_tag("");
+    set_stable_test_format(true);
+    buffer.text().lines().map(String::from).collect()
+}
+
+fn parsed(lines: &[String]) -> Vec<ParsedRecord> {
+    lines.iter().map(|line| parse_pretty_line(line).unwrap_or_else(|| panic!("not parsed: {line:?}"))).collect()
+}
+
+fn check(records: &[ParsedRecord], process: Option<&str>, thread: Option<&str>) {
+    let levels = [Level::Debug, Level::Info, Level::Warn, Level::Error];
+    let messages = ["debug record", "info record", "warn: with a colon", "error record"];
+    assert_eq!(records.len(), 4, "{records:?}");
+    for ((record, level), message) in records.iter().zip(levels).zip(messages) {
+        assert_eq!(record.level, level);
+        assert_eq!(record.message, message);
+        assert_eq!(record.target.as_deref(), Some("replay"));
+        assert_eq!(record.process.as_deref(), process);
+        assert_eq!(record.thread.as_deref(), thread);
+        // `2024.05.01 12:00:00.123`
+        assert_eq!(record.timestamp.len(), 23, "{:?}", record.timestamp);
+    }
+}
+
+#[test]
+fn colored_lines_round_trip_at_every_level() {
+    let lines = logged(ColorChoice::Always, None, false);
+    assert!(lines.iter().all(|line| line.contains('\x1b')), "{lines:?}");
+    check(&parsed(&lines), None, None);
+}
+
+#[test]
+fn uncolored_lines_round_trip_at_every_level() {
+    let lines = logged(ColorChoice::Never, None, false);
+    assert!(lines.iter().all(|line| !line.contains('\x1b')), "{lines:?}");
+    check(&parsed(&lines), None, None);
+}
+
+#[test]
+fn process_tag_and_thread_round_trip() {
+    let thread = std::thread::current().name().unwrap().to_string();
+    for colors in [ColorChoice::Always, ColorChoice::Never] {
+        let lines = logged(colors, Some("api"), true);
+        check(&parsed(&lines), Some("api"), Some(&thread));
+    }
+}
+
+#[test]
+fn golden_lines() {
+    let parse = |line| parse_pretty_line(line).unwrap();
+    assert_eq!(parse(" WRN  [2024.05.01 12:00:00.000] app::net: disk full "), ParsedRecord {
+        level: Level::Warn,
+        process: None,
+        thread: None,
+        timestamp: String::from("2024.05.01 12:00:00.000"),
+        target: Some(String::from("app::net")),
+        message: String::from("disk full"),
+    });
+    let colored = parse("\x1b[0;41;97m ERR \x1b[0m api \x1b[0;2m[main]\x1b[22m\x1b[90m[2024.05.01 12:00:00.000]\x1b[0;2m app:\x1b[22m \x1b[31mboom \x1b[0m");
+    assert_eq!((colored.level, colored.process.as_deref(), colored.thread.as_deref()), (Level::Error, Some("api"), Some("main")));
+    assert_eq!((colored.target.as_deref(), colored.message.as_str()), (Some("app"), "boom"));
+    assert_eq!(parse("[DBG] [2024.05.01 12:00:00.000] src/main.rs:7: x").target.as_deref(), Some("src/main.rs:7"));
+}
+
+#[test]
+fn other_lines_are_not_parsed() {
+    for line in [
+        "",
+        "plain text",
+        "Compiling rust_logger v1.0.0",
+        "[2024.05.01 12:00:00.000] no label",
+        " WRN  no timestamp",
+        " WRN  [not a timestamp] app: x",
+        " XYZ  [2024.05.01 12:00:00.000] app: unknown label",
+        "thread 'main' panicked at src/main.rs:1:1:",
+    ] {
+        assert_eq!(parse_pretty_line(line), None, "{line:?}");
+    }
+}
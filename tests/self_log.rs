@@ -0,0 +1,84 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::console::{self, SelfLog};
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{level, log};
+
+/// Counts its calls and panics in every one.
+struct Broken(Arc<Mutex<usize>>);
+
+impl Sink for Broken {
+    fn write(&mut self, _record: &Record, _line: &str) {
+        *self.0.lock().unwrap() += 1;
+        panic!("disk on fire");
+    }
+
+    fn name(&self) -> String {
+        String::from("Broken")
+    }
+}
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+#[test]
+fn a_broken_sink_is_reported_once() {
+    let (_guard, buffer) = common::capture();
+
+    let calls = Arc::new(Mutex::new(0));
+    let broken = sink::add_sink(Box::new(Broken(calls.clone())), rust_logger::Level::Debug, Format::Pretty);
+    for i in 0..50 {
+        log!("record {i}");
+    }
+    sink::remove_sink(broken);
+
+    assert_eq!(*calls.lock().unwrap(), 1);
+    let text = buffer.text();
+    let reports: Vec<&str> = text.lines().filter(|line| line.contains("[logger]")).collect();
+    assert_eq!(reports, [" ERR  [TS] [logger]: sink Broken panicked and gets no more records: disk on fire "]);
+    assert_eq!(text.lines().filter(|line| line.contains(" LOG ")).count(), 50);
+}
+
+#[test]
+fn diagnostics_go_to_the_chosen_sink() {
+    let (_guard, buffer) = common::capture();
+
+    let lines = Collect::default();
+    let id = sink::add_sink(Box::new(lines.clone()), rust_logger::Level::Debug, Format::Json);
+    console::set_self_log(SelfLog::Sink(id));
+    level::set_level_by_name_or_default("loud");
+    console::set_self_log(SelfLog::Console);
+    sink::remove_sink(id);
+
+    let lines = lines.0.lock().unwrap();
+    let report = lines.iter().find(|line| line.contains("keeping level")).expect("no report in the sink");
+    assert!(report.contains(r#""target":"[logger]""#), "{report}");
+    assert!(!buffer.text().contains("[logger]"), "{}", buffer.text());
+}
+
+#[test]
+fn diagnostics_are_rate_limited() {
+    let (_guard, buffer) = common::capture();
+
+    for _ in 0..100 {
+        level::set_level_by_name_or_default("loud");
+    }
+
+    // Ten per one-second window, which the other tests may have used one
+    // each of; the calls may straddle two windows.
+    let reports = buffer.text().lines().filter(|line| line.contains("[logger]")).count();
+    assert!((8..=20).contains(&reports), "{reports} reports");
+
+    // Leaves a fresh window to the next test.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+}
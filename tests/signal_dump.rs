@@ -0,0 +1,68 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::ffi::c_int;
+use std::time::Duration;
+
+use rust_logger::console::SharedBuffer;
+use rust_logger::dump::{dump_on_signal, Signal};
+use rust_logger::{file, log, warn};
+
+extern "C" {
+    fn raise(signal: c_int) -> c_int;
+}
+
+const SIGUSR1: c_int = if cfg!(target_os = "linux") { 10 } else { 30 };
+
+/// Raises SIGUSR1 and returns the dump the helper thread writes.
+fn dump(buffer: &SharedBuffer) -> String {
+    buffer.clear();
+    // SAFETY: the handler installed by `dump_on_signal` only wakes a thread.
+    assert_eq!(unsafe { raise(SIGUSR1) }, 0);
+    let written = (0..200).find_map(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        let text = buffer.text();
+        let at = text.find("dump written to ")?;
+        Some(text[at + 16..].lines().next().unwrap().trim_end().to_string())
+    });
+    let path = written.expect("the dump is written within 2s");
+    let dump = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    dump
+}
+
+#[test]
+fn a_signal_writes_a_dump_ending_with_the_black_box() {
+    let (_guard, buffer) = common::capture();
+    assert_eq!(Signal::parse("sigusr1"), Some(Signal::Usr1));
+    assert_eq!(Signal::parse("SIGHUP"), None);
+    dump_on_signal(Signal::Usr1).unwrap();
+    warn!("before the first dump");
+
+    let first = dump(&buffer);
+    for section in [
+        "rust_logger configuration:",
+        "dump_on = Usr1",
+        "records by level:",
+        "  warn = 1\n",
+        "threads that have logged:\n  a_signal_writes_a_dump_ending_with_the_black_box\n",
+        "recent records:\n  (none kept; file::start_black_box keeps them)\n",
+    ] {
+        assert!(first.contains(section), "missing {section:?} in:\n{first}");
+    }
+    // The same lines are logged at Error.
+    assert!(buffer.text().contains(" ERR  [TS] rust_logger::internal: records by level: "), "{}", buffer.text());
+
+    let path = std::env::temp_dir().join(format!("rust_logger-signal-dump-{}.blackbox", std::process::id()));
+    file::start_black_box(&path, 64 * 1024).unwrap();
+    for i in 0..30 { log!("kept {i}"); }
+    let second = dump(&buffer);
+    let _ = std::fs::remove_file(&path);
+
+    let recent = second.split_once("recent records:\n").expect(&second).1;
+    let recent: Vec<&str> = recent.lines().collect();
+    assert_eq!(recent.len(), 20, "{second}");
+    assert_eq!(recent[0], "  [LOG] [TS] signal_dump: kept 10");
+    assert_eq!(recent[19], "  [LOG] [TS] signal_dump: kept 29");
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{debug, level, log, warn, Level};
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+impl Collect {
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Logs about every record it receives.
+struct Echo(Arc<Mutex<usize>>);
+
+impl Sink for Echo {
+    fn write(&mut self, _record: &Record, _line: &str) {
+        *self.0.lock().unwrap() += 1;
+        warn!("echo");
+    }
+}
+
+#[test]
+fn each_sink_gets_the_records_at_its_level() {
+    let (_guard, buffer) = common::capture();
+    level::set_level(Level::Error);
+
+    let debug_lines = Collect::default();
+    let warn_lines = Collect::default();
+    let debug_sink = sink::add_sink(Box::new(debug_lines.clone()), Level::Debug, Format::Pretty);
+    let warn_sink = sink::add_sink(Box::new(warn_lines.clone()), Level::Warn, Format::Pretty);
+    debug!("one");
+    log!("two");
+    warn!("three");
+    assert!(sink::remove_sink(debug_sink).is_some());
+    assert!(sink::remove_sink(warn_sink).is_some());
+    warn!("after");
+
+    assert_eq!(
+        debug_lines.lines(),
+        ["[DBG] [TS] sinks: one", "[LOG] [TS] sinks: two", "[WRN] [TS] sinks: three"]
+    );
+    assert_eq!(warn_lines.lines(), ["[WRN] [TS] sinks: three"]);
+    assert_eq!(buffer.text(), "");
+}
+
+#[test]
+fn a_sink_that_logs_does_not_deadlock_or_recurse() {
+    let (_guard, buffer) = common::capture();
+
+    let calls = Arc::new(Mutex::new(0));
+    let echo = sink::add_sink(Box::new(Echo(calls.clone())), Level::Debug, Format::Pretty);
+    log!("start");
+    // The registry lock is free again for other threads.
+    std::thread::spawn(|| sink::remove_sink(sink::add_sink(Box::new(Collect::default()), Level::Debug, Format::Json)))
+        .join()
+        .unwrap();
+    assert!(sink::remove_sink(echo).is_some());
+
+    // One call: the echo reaches the console, not the sink. Sinks run
+    // before the console line is written.
+    assert_eq!(*calls.lock().unwrap(), 1);
+    assert_eq!(buffer.text(), " WRN  [TS] sinks: echo \n LOG  [TS] sinks: start \n");
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::time::Duration;
+
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{log, Level};
+
+/// Takes at least 5 ms per record.
+struct Slow;
+
+impl Sink for Slow {
+    fn write(&mut self, _record: &Record, _line: &str) {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    fn name(&self) -> String {
+        String::from("slow-db")
+    }
+}
+
+/// The milliseconds in a `{:.1?}` duration such as `5.2ms` or `1.0s`.
+fn millis(elapsed: &str) -> f64 {
+    match elapsed.strip_suffix("ms") {
+        Some(ms) => ms.parse().unwrap(),
+        None => elapsed.strip_suffix('s').unwrap().parse::<f64>().unwrap() * 1000.0,
+    }
+}
+
+fn warnings(text: &str) -> Vec<&str> {
+    text.lines().filter(|line| line.contains("log call took")).collect()
+}
+
+/// One test: the warnings share one rate limit for the whole process.
+#[test]
+fn a_slow_sink_is_named_once_per_interval() {
+    let (_guard, buffer) = common::capture();
+    let slow = sink::add_sink(Box::new(Slow), Level::Debug, Format::Pretty);
+
+    // Under the budget nothing is said.
+    sink::warn_if_log_call_exceeds(Some(Duration::from_secs(5)));
+    log!("fast enough");
+    assert!(warnings(&buffer.text()).is_empty(), "{}", buffer.text());
+
+    // The first slow call only turns on the timing of each destination; the
+    // second reports them, the ones after are within the 10 second interval.
+    sink::warn_if_log_call_exceeds(Some(Duration::from_millis(1)));
+    for i in 0..4 {
+        log!("slow {i}");
+    }
+    sink::warn_if_log_call_exceeds(None);
+    sink::remove_sink(slow);
+
+    let text = buffer.text();
+    let found = warnings(&text);
+    assert_eq!(found.len(), 1, "{text}");
+    let warning = found[0];
+    assert!(warning.starts_with(" WRN  [TS] [logger]: log call took "), "{warning}");
+
+    // The slowest destination comes first; the sleep is a lower bound only.
+    let (took, listed) = warning.split_once(", over the 1.0ms budget: ").expect(warning);
+    assert!(millis(took.rsplit(' ').next().unwrap()) >= 5.0, "{warning}");
+    let mut destinations = listed.trim_end().split(", ");
+    let (name, elapsed) = destinations.next().unwrap().split_once(' ').unwrap();
+    assert_eq!(name, "slow-db");
+    assert!(millis(elapsed) >= 5.0, "{warning}");
+    assert!(destinations.any(|destination| destination.starts_with("console ")), "{warning}");
+
+    // Every record was still written.
+    for i in 0..4 {
+        assert!(text.contains(&format!(" LOG  [TS] slow_calls: slow {i} \n")), "{text}");
+    }
+}
@@ -0,0 +1,101 @@
+//! `LOG_STABLE_FORMAT` is read once per process, so those cases run in a child.
+
+mod common;
+
+use rust_logger::config::{set_stable_test_format, stable_test_format};
+use rust_logger::console::{self, BellMode, ColorChoice, SharedBuffer};
+use rust_logger::{debug, error, group_begin, group_end, log, warn};
+
+const CHILD: &str = "RUST_LOGGER_STABLE_FORMAT_CHILD";
+
+/// Every kind of record whose bytes the stable format pins.
+fn log_everything() {
+    debug!("debug <bold>bold</> text");
+    log!("info with fields"; id = 7, name = "ada");
+    warn!("warn <red>red</> and <#1e90ff>rgb</>");
+    error!("two\nlines");
+    group_begin!("outer");
+    log!("nested");
+    group_end!();
+    error!(emphasis: true, "alert");
+}
+
+const GOLDEN: &str = concat!(
+    " DBG  [TS] stable_format: debug bold text \n",
+    " LOG  [TS] stable_format: info with fields id=7 name=ada \n",
+    " WRN  [TS] stable_format: warn red and rgb \n",
+    " ERR  [TS] stable_format: two \n",
+    "                          lines \n",
+    " LOG  [TS] stable_format: outer \n",
+    " LOG  [TS] stable_format:   nested \n",
+    " ERR  [TS] stable_format: !!! alert !!! \n",
+);
+
+/// In the child: logs with colors forced on and writes what the console got
+/// to stderr, then whether the stable format was on.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return; }
+    let buffer = SharedBuffer::new();
+    console::set_writer(Box::new(buffer.clone()));
+    console::set_color_choice(ColorChoice::Always);
+    log_everything();
+    eprint!("{}", buffer.text());
+    eprintln!("stable={}", stable_test_format());
+}
+
+fn run(value: Option<&str>) -> String {
+    let mut command = std::process::Command::new(std::env::current_exe().unwrap());
+    command.args(["--exact", "child", "--nocapture", "--test-threads=1"]).env(CHILD, "1").env_remove("LOG_STABLE_FORMAT");
+    if let Some(value) = value { command.env("LOG_STABLE_FORMAT", value); }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn golden_lines() {
+    let (_guard, buffer) = common::capture();
+    // Colors and the bell are asked for but left out.
+    console::set_color_choice(ColorChoice::Always);
+    console::set_bell(BellMode::OnWarnAndError);
+    log_everything();
+    console::set_bell(BellMode::Off);
+
+    assert_eq!(buffer.text(), GOLDEN);
+}
+
+#[test]
+fn the_same_bytes_with_colors_off() {
+    let (_guard, buffer) = common::capture();
+    log_everything();
+    assert_eq!(buffer.text(), GOLDEN);
+}
+
+#[test]
+fn turning_it_off_brings_back_timestamps_and_colors() {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    console::set_color_choice(ColorChoice::Always);
+    log!("colored");
+    set_stable_test_format(true);
+
+    let text = buffer.text();
+    assert!(text.contains('\x1b') && !text.contains("[TS]"), "{text:?}");
+}
+
+#[test]
+fn the_environment_turns_it_on() {
+    for value in ["1", "on", "true"] {
+        assert_eq!(run(Some(value)), format!("{GOLDEN}stable=true\n"), "LOG_STABLE_FORMAT={value}");
+    }
+}
+
+#[test]
+fn other_values_leave_it_off() {
+    for value in [None, Some("0"), Some("yes"), Some("")] {
+        let out = run(value);
+        assert!(out.ends_with("stable=false\n"), "{value:?}: {out:?}");
+        assert!(out.contains('\x1b') && !out.contains("[TS]"), "{value:?}: {out:?}");
+    }
+}
@@ -0,0 +1,157 @@
+//! The startup log is kept once per process, so each case runs in a child.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice, SharedBuffer};
+use rust_logger::format::{Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{debug, file, level, log, Level};
+
+const CHILD: &str = "RUST_LOGGER_STARTUP_REPLAY_CHILD";
+
+/// Keeps every line it receives.
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl Sink for Collect {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.0.lock().unwrap().push(line.to_string());
+    }
+}
+
+impl Collect {
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// In the child: runs one case with the console in a buffer, and writes
+/// the lines the sinks got to stderr.
+#[test]
+fn child() {
+    let Some(case) = std::env::var_os(CHILD) else { return };
+    set_stable_test_format(true);
+    console::set_color_choice(ColorChoice::Never);
+    console::set_writer(Box::new(SharedBuffer::new()));
+    level::set_level(Level::Info);
+
+    let lines = Collect::default();
+    match case.to_str().unwrap() {
+        "five" => {
+            for i in 0..5 { log!("early {i}"); }
+            let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+            log!("late");
+            sink::remove_sink(id);
+        }
+        "levels" => {
+            debug!("sink only");
+            log!("info");
+            let only_warn = Collect::default();
+            sink::remove_sink(sink::add_sink(Box::new(only_warn.clone()), Level::Warn, Format::Pretty));
+            assert!(only_warn.lines().is_empty(), "{:?}", only_warn.lines());
+            // Below the console level, the record went nowhere: no sink took
+            // Debug records when it was logged.
+            sink::remove_sink(sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty));
+        }
+        "finished" => {
+            log!("before");
+            sink::finish_initialization();
+            let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+            log!("after");
+            sink::remove_sink(id);
+        }
+        "capacity" => {
+            sink::set_startup_replay(3, Duration::from_secs(60));
+            for i in 0..5 { log!("early {i}"); }
+            let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+            log!("late");
+            sink::remove_sink(id);
+        }
+        "window" => {
+            sink::set_startup_replay(100, Duration::from_millis(50));
+            log!("too early");
+            std::thread::sleep(Duration::from_millis(100));
+            let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+            log!("late");
+            sink::remove_sink(id);
+        }
+        "concurrent" => {
+            sink::set_startup_replay(10_000, Duration::from_secs(60));
+            let logger = std::thread::spawn(|| for i in 0..2000 { log!("record {i:04}"); });
+            std::thread::sleep(Duration::from_millis(1));
+            let id = sink::add_sink(Box::new(lines.clone()), Level::Debug, Format::Pretty);
+            logger.join().unwrap();
+            sink::remove_sink(id);
+        }
+        "file" => {
+            log!("early");
+            let path = std::env::temp_dir().join(format!("rust_logger-startup-replay-{}.log", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let guard = file::file_sink(&path).session_header(false).open().unwrap();
+            log!("late");
+            drop(guard);
+            eprint!("{}", std::fs::read_to_string(&path).unwrap());
+            std::fs::remove_file(&path).unwrap();
+        }
+        other => panic!("unknown case {other}"),
+    }
+    for line in lines.lines() { eprintln!("{line}"); }
+}
+
+fn run(case: &str) -> Vec<String> {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, case)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stderr).unwrap().lines().map(String::from).collect()
+}
+
+#[test]
+fn a_late_sink_gets_the_early_records_once() {
+    let mut expected: Vec<String> = (0..5).map(|i| format!("[LOG] [TS] startup_replay: early {i}")).collect();
+    expected.push(String::from("[LOG] [TS] startup_replay: late"));
+    assert_eq!(run("five"), expected);
+}
+
+#[test]
+fn replay_follows_the_sink_level() {
+    assert_eq!(run("levels"), ["[LOG] [TS] startup_replay: info"]);
+}
+
+#[test]
+fn nothing_is_replayed_after_initialization() {
+    assert_eq!(run("finished"), ["[LOG] [TS] startup_replay: after"]);
+}
+
+#[test]
+fn only_the_first_records_are_kept() {
+    assert_eq!(
+        run("capacity"),
+        [
+            "[LOG] [TS] startup_replay: early 0",
+            "[LOG] [TS] startup_replay: early 1",
+            "[LOG] [TS] startup_replay: early 2",
+            "[LOG] [TS] startup_replay: late",
+        ]
+    );
+}
+
+#[test]
+fn records_are_dropped_after_the_window() {
+    assert_eq!(run("window"), ["[LOG] [TS] startup_replay: late"]);
+}
+
+#[test]
+fn records_logged_while_the_sink_is_added_arrive_once_in_order() {
+    let expected: Vec<String> = (0..2000).map(|i| format!("[LOG] [TS] startup_replay: record {i:04}")).collect();
+    assert_eq!(run("concurrent"), expected);
+}
+
+#[test]
+fn file_sinks_get_the_early_records() {
+    assert_eq!(run("file"), ["[LOG] [TS] startup_replay: early", "[LOG] [TS] startup_replay: late"]);
+}
@@ -0,0 +1,75 @@
+//! Built with `static_level` and `LOG_LEVEL=error`: calls below Error
+//! compile to nothing, so their arguments never run and `set_level` cannot
+//! bring them back.
+//!
+//! ```text
+//! LOG_LEVEL=error cargo test --manifest-path tests/static-level/Cargo.toml
+//! ```
+
+use std::cell::Cell;
+
+use rust_logger::console::{self, SharedBuffer};
+use rust_logger::{debug, error, hexdump, level, log, separator, warn, Level};
+
+#[test]
+fn the_build_level_is_a_floor() {
+    assert_eq!(level::BUILD_LEVEL, Level::Error, "build with LOG_LEVEL=error");
+    for below in [Level::Debug, Level::Info, Level::Warn] {
+        assert!(!level::static_enabled(below), "{below:?}");
+    }
+    assert!(level::static_enabled(Level::Error));
+
+    level::set_level(Level::Debug);
+    assert!(!level::is_enabled(Level::Warn), "the runtime level cannot go below the build level");
+    assert!(level::is_enabled(Level::Error));
+}
+
+#[test]
+#[allow(unreachable_code, clippy::diverging_sub_expression)]
+fn disabled_arguments_never_run() {
+    level::set_level(Level::Debug);
+    debug!("{}", panic!("debug! evaluated its arguments"));
+    log!("{}", panic!("log! evaluated its arguments"));
+    warn!("{}", panic!("warn! evaluated its arguments"));
+    debug!(once: "{}", panic!("debug! evaluated its arguments"); n = panic!("and its fields"));
+    log!(every: 2, "{}", panic!("log! evaluated its arguments"));
+    warn!("fields"; n = panic!("warn! evaluated its fields"));
+    hexdump!("frame", panic!("hexdump! evaluated its bytes") as [u8; 0]);
+    separator!();
+}
+
+#[test]
+fn enabled_arguments_run_once() {
+    let buffer = SharedBuffer::new();
+    console::set_writer(Box::new(buffer.clone()));
+    let runs = Cell::new(0);
+    let run = || {
+        runs.set(runs.get() + 1);
+        runs.get()
+    };
+    debug!("{}", run());
+    warn!("{}", run(); n = run());
+    error!("still logged: {}", run());
+    error!("with a field"; n = run());
+    assert_eq!(runs.get(), 2);
+    assert_eq!(buffer.text().matches("ERR").count(), 2, "{}", buffer.text());
+}
+
+/// Optimized builds drop the dead calls along with their format strings.
+#[test]
+fn release_builds_leave_out_the_format_strings() {
+    console::set_writer(Box::new(SharedBuffer::new()));
+    debug!("compiled-away-DBG-{}", 1);
+    log!("compiled-away-LOG-{}", 2);
+    warn!("compiled-away-WRN-{}", 3);
+    error!("kept-ERR-{}", 4);
+    if cfg!(debug_assertions) { return; }
+
+    let binary = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let contains = |needle: &str| binary.windows(needle.len()).any(|window| window == needle.as_bytes());
+    for label in ["DBG", "LOG", "WRN"] {
+        let needle = format!("{}-{label}-", "compiled-away");
+        assert!(!contains(&needle), "{needle} is in the binary");
+    }
+    assert!(contains(&format!("{}-ERR-", "kept")));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::time::Duration;
+
+use rust_logger::stats::{set_suppressed_accounting, set_suppressed_summary_interval, suppressed_summary};
+use rust_logger::{debug, level, log, Level};
+
+mod net {
+    pub fn chatter(n: usize) {
+        for _ in 0..n { rust_logger::debug!("net chatter"); }
+    }
+
+    pub fn more_chatter(n: usize) {
+        for _ in 0..n { rust_logger::log!("more net chatter"); }
+    }
+}
+
+mod db {
+    pub fn chatter(n: usize) {
+        for _ in 0..n { rust_logger::debug!("db chatter"); }
+    }
+}
+
+mod cache {
+    pub fn chatter(n: usize) {
+        for _ in 0..n { rust_logger::debug!("cache chatter"); }
+    }
+}
+
+/// Level Warn with accounting on, and nothing counted yet.
+fn counting() -> (std::sync::MutexGuard<'static, ()>, rust_logger::console::SharedBuffer) {
+    let (guard, buffer) = common::capture();
+    level::set_level(Level::Warn);
+    set_suppressed_accounting(true);
+    suppressed_summary(usize::MAX);
+    (guard, buffer)
+}
+
+#[test]
+fn counts_per_target_highest_first() {
+    let (_guard, _buffer) = counting();
+    db::chatter(3);
+    net::chatter(5);
+    // Two call sites in one module add up.
+    net::more_chatter(2);
+    cache::chatter(3);
+    set_suppressed_accounting(false);
+
+    // Ties on the count go by name.
+    assert_eq!(suppressed_summary(10), [
+        ("suppressed_summary::net", 7),
+        ("suppressed_summary::cache", 3),
+        ("suppressed_summary::db", 3),
+    ]);
+}
+
+#[test]
+fn only_the_top_n_are_returned() {
+    let (_guard, _buffer) = counting();
+    db::chatter(1);
+    net::chatter(4);
+    cache::chatter(2);
+    set_suppressed_accounting(false);
+
+    assert_eq!(suppressed_summary(2), [("suppressed_summary::net", 4), ("suppressed_summary::cache", 2)]);
+    // The ones left out were reset too.
+    assert_eq!(suppressed_summary(2), []);
+    assert!(suppressed_summary(0).is_empty());
+}
+
+#[test]
+fn each_call_counts_since_the_previous_one() {
+    let (_guard, _buffer) = counting();
+    net::chatter(5);
+    assert_eq!(suppressed_summary(5), [("suppressed_summary::net", 5)]);
+    assert_eq!(suppressed_summary(5), []);
+    net::chatter(2);
+    db::chatter(3);
+    set_suppressed_accounting(false);
+
+    assert_eq!(suppressed_summary(5), [("suppressed_summary::db", 3), ("suppressed_summary::net", 2)]);
+}
+
+#[test]
+fn nothing_is_counted_while_accounting_is_off() {
+    let (_guard, _buffer) = counting();
+    set_suppressed_accounting(false);
+    net::chatter(5);
+    // Records that pass the level are not suppressed.
+    set_suppressed_accounting(true);
+    level::set_level(Level::Debug);
+    net::chatter(5);
+    set_suppressed_accounting(false);
+
+    assert_eq!(suppressed_summary(5), []);
+}
+
+#[test]
+fn the_summary_is_logged_at_info_on_schedule() {
+    let (_guard, buffer) = counting();
+    debug!("suppressed here");
+    net::chatter(2);
+    net::chatter(2);
+    set_suppressed_summary_interval(Some(Duration::ZERO));
+    level::set_level(Level::Debug);
+    log!("next record");
+    log!("nothing new to report");
+    set_suppressed_summary_interval(None);
+    set_suppressed_accounting(false);
+
+    assert_eq!(buffer.text(), [
+        " LOG  [TS] suppressed records since last summary: suppressed_summary::net=4, suppressed_summary=1 \n",
+        " LOG  [TS] suppressed_summary: next record \n",
+        " LOG  [TS] suppressed_summary: nothing new to report \n",
+    ].concat());
+}
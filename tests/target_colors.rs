@@ -0,0 +1,75 @@
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+use rust_logger::log;
+use rust_logger::theme::{self, hash_color, Color};
+#[cfg(feature = "timestamps")]
+use rust_logger::time::{self, TimestampFormat};
+
+mod db {
+    pub fn query() {
+        rust_logger::log!("query");
+    }
+}
+
+fn colored_lines(body: impl FnOnce()) -> String {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    body();
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::Standard);
+    set_stable_test_format(true);
+    theme::set_target_colors(false);
+    theme::clear_target_colors();
+    buffer.text()
+}
+
+#[test]
+fn explicit_colors_beat_hashing() {
+    let (_guard, _buffer) = common::capture();
+    theme::set_target_colors(true);
+    theme::set_target_color("target_colors::db", "cyan").unwrap();
+    theme::set_target_color("target_colors::db::pool", "c200").unwrap();
+
+    assert_eq!(theme::target_color("target_colors::db"), Some(Color::Cyan));
+    assert_eq!(theme::target_color("target_colors::db::cache"), Some(Color::Cyan));
+    assert_eq!(theme::target_color("target_colors::db::pool"), Some(Color::Indexed(200)), "the longer pattern wins");
+    assert_eq!(theme::target_color("target_colors::dbx"), Some(Color::Indexed(hash_color("target_colors::dbx"))));
+    assert!(theme::set_target_color("x", "mauve").is_err());
+
+    theme::set_target_colors(false);
+    assert_eq!(theme::target_color("target_colors::db"), Some(Color::Cyan), "assignments apply without hashing");
+    assert_eq!(theme::target_color("target_colors::dbx"), None);
+    theme::clear_target_colors();
+    assert_eq!(theme::target_color("target_colors::db"), None);
+}
+
+#[test]
+fn hashed_colors_are_stable() {
+    // FNV-1a of the name, the same on every run and machine.
+    assert_eq!(hash_color("target_colors"), 79);
+    assert_eq!(hash_color("my_app::db"), 38);
+    assert_eq!(hash_color("my_app::net"), 99);
+}
+
+#[test]
+fn the_target_is_drawn_in_its_color() {
+    let text = colored_lines(|| {
+        theme::set_target_color("target_colors::db", "cyan").unwrap();
+        db::query();
+        theme::set_target_colors(true);
+        log!("main");
+        theme::clear_target_colors();
+        theme::set_target_colors(false);
+        db::query();
+    });
+
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines[0].contains("\x1b[0;36mtarget_colors::db:\x1b[0m "), "{:?}", lines[0]);
+    assert!(lines[1].contains(&format!("\x1b[0;38;5;{}mtarget_colors:\x1b[0m ", hash_color("target_colors"))), "{:?}", lines[1]);
+    assert!(lines[2].contains("\x1b[0;2mtarget_colors::db:\x1b[22m "), "{:?}", lines[2]);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use rust_logger::console::{self, TargetTruncation};
+use rust_logger::format::{self, Format};
+use rust_logger::log;
+
+mod services {
+    pub mod billing {
+        pub mod reconciliation {
+            pub fn run() {
+                rust_logger::log!("reconciled");
+            }
+        }
+    }
+}
+
+#[test]
+fn targets_fill_one_column() {
+    let (_guard, buffer) = common::capture();
+
+    console::set_target_width(32);
+    log!("short");
+    services::billing::reconciliation::run();
+    console::set_target_truncation(TargetTruncation::LastSegments);
+    services::billing::reconciliation::run();
+    console::set_target_width(0);
+    console::set_target_truncation(TargetTruncation::Middle);
+    services::billing::reconciliation::run();
+
+    assert_eq!(
+        buffer.text(),
+        concat!(
+            " LOG  [TS] target_width:                     short \n",
+            " LOG  [TS] target_width::…::reconciliation:  reconciled \n",
+            " LOG  [TS] …::billing::reconciliation:       reconciled \n",
+            " LOG  [TS] target_width::services::billing::reconciliation: reconciled \n",
+        )
+    );
+}
+
+#[test]
+fn structured_formats_keep_the_full_target() {
+    let (_guard, buffer) = common::capture();
+
+    console::set_target_width(8);
+    format::set_format(Format::Json);
+    services::billing::reconciliation::run();
+    format::set_format(Format::Pretty);
+    console::set_target_width(0);
+
+    assert!(buffer.text().contains(r#""target":"target_width::services::billing::reconciliation""#), "{}", buffer.text());
+}
@@ -0,0 +1,95 @@
+use rust_logger::format::{PrettyFormatter, Record};
+use rust_logger::theme::{self, Theme};
+use rust_logger::Level;
+
+const LEVELS: [Level; 4] = [Level::Debug, Level::Info, Level::Warn, Level::Error];
+
+fn lines(theme: Theme, colors: bool) -> Vec<String> {
+    let formatter = PrettyFormatter::new(theme).timestamps(false).colors(colors);
+    LEVELS
+        .into_iter()
+        .map(|level| {
+            let mut line = String::new();
+            formatter.format(&Record::new(level, "m"), &mut line).unwrap();
+            line
+        })
+        .collect()
+}
+
+#[test]
+fn default_sequences() {
+    assert_eq!(
+        lines(Theme::default(), true),
+        [
+            "\x1b[0;100;38;2;0;0;0m DBG \x1b[0m \x1b[90mm \x1b[0m",
+            "\x1b[0;44;38;2;0;0;0m LOG \x1b[0m \x1b[37mm \x1b[0m",
+            "\x1b[0;43;38;2;0;0;0m WRN \x1b[0m \x1b[33mm \x1b[0m",
+            "\x1b[0;41;38;2;0;0;0m ERR \x1b[0m \x1b[31mm \x1b[0m",
+        ]
+    );
+}
+
+#[test]
+fn colorblind_sequences() {
+    assert_eq!(
+        lines(Theme::colorblind(), true),
+        [
+            "\x1b[0;48;5;250;38;2;0;0;0m .. DBG \x1b[0m \x1b[38;5;245mm \x1b[0m",
+            "\x1b[0;48;5;74;38;2;0;0;0m -- LOG \x1b[0m \x1b[37mm \x1b[0m",
+            "\x1b[0;48;5;214;38;2;0;0;0m !! WRN \x1b[0m \x1b[38;5;214mm \x1b[0m",
+            "\x1b[0;48;5;175;1;38;2;0;0;0m xx ERR \x1b[0m \x1b[1;38;5;175mm \x1b[0m",
+        ]
+    );
+}
+
+#[test]
+fn high_contrast_sequences() {
+    assert_eq!(
+        lines(Theme::high_contrast(), true),
+        [
+            "\x1b[0;47;30m DEBUG \x1b[0m \x1b[37mm \x1b[0m",
+            "\x1b[0;107;30m INFO  \x1b[0m \x1b[97mm \x1b[0m",
+            "\x1b[0;103;1;30m WARN  \x1b[0m \x1b[93mm \x1b[0m",
+            "\x1b[0;101;1;30m ERROR \x1b[0m \x1b[1;91mm \x1b[0m",
+        ]
+    );
+}
+
+#[test]
+fn labels_tell_levels_apart_without_colors() {
+    assert_eq!(lines(Theme::default(), false), [" DBG  m ", " LOG  m ", " WRN  m ", " ERR  m "]);
+    assert_eq!(lines(Theme::colorblind(), false), [" .. DBG  m ", " -- LOG  m ", " !! WRN  m ", " xx ERR  m "]);
+    assert_eq!(lines(Theme::high_contrast(), false), [" DEBUG  m ", " INFO   m ", " WARN   m ", " ERROR  m "]);
+}
+
+#[test]
+fn presets_by_name() {
+    assert_eq!(Theme::parse("colorblind").unwrap(), Theme::colorblind());
+    assert_eq!(Theme::parse(" High-Contrast ").unwrap(), Theme::high_contrast());
+    assert_eq!(Theme::by_name("default"), Some(Theme::default()));
+    assert!(Theme::parse("neon").is_err());
+    assert_eq!(Theme::colorblind().preset_name(), "colorblind");
+}
+
+const CHILD: &str = "RUST_LOGGER_THEMES_CHILD";
+
+/// In the child: reports the theme `LOG_THEME` selected.
+#[test]
+fn child() {
+    if std::env::var_os(CHILD).is_none() { return; }
+    eprintln!("theme: {}", theme::theme().preset_name());
+}
+
+#[test]
+fn log_theme_selects_a_preset() {
+    for (value, expected) in [("colorblind", "colorblind"), ("high-contrast", "high-contrast"), ("neon", "default")] {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+            .env(CHILD, "1")
+            .env("LOG_THEME", value)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(&format!("theme: {expected}\n")), "{value}: {stderr}");
+    }
+}
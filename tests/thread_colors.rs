@@ -0,0 +1,61 @@
+mod common;
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+use rust_logger::log;
+use rust_logger::theme::hash_color;
+#[cfg(feature = "timestamps")]
+use rust_logger::time::{self, TimestampFormat};
+
+/// The colored line each thread logs, in the order given.
+fn lines_from(names: &[&str], colored: bool) -> Vec<String> {
+    let (_guard, buffer) = common::capture();
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+    console::set_color_choice(ColorChoice::Always);
+    console::show_thread(true);
+    console::set_thread_colors(colored);
+    for name in names {
+        std::thread::Builder::new().name(name.to_string()).spawn(|| log!("hi")).unwrap().join().unwrap();
+    }
+    console::set_thread_colors(false);
+    console::show_thread(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::Standard);
+    set_stable_test_format(true);
+    buffer.text().lines().map(String::from).collect()
+}
+
+#[test]
+fn named_threads_get_pinned_colors() {
+    // FNV-1a of the name into the process tag palette.
+    let pinned = [("worker-0", 176), ("worker-1", 153), ("worker-3", 183), ("io", 38)];
+    for (name, color) in pinned {
+        assert_eq!(hash_color(name), color, "{name}");
+    }
+
+    let names: Vec<&str> = pinned.iter().map(|(name, _)| *name).collect();
+    let lines = lines_from(&names, true);
+    assert_eq!(lines.len(), pinned.len());
+    for (line, (name, color)) in lines.iter().zip(pinned) {
+        assert!(line.contains(&format!("\x1b[0;38;5;{color}m[{name}]\x1b[0m")), "{line:?}");
+    }
+}
+
+#[test]
+fn colliding_names_share_a_color_and_keep_their_names() {
+    let pair = ["worker-3", "worker-10"];
+    assert_eq!(hash_color(pair[0]), hash_color(pair[1]));
+
+    let lines = lines_from(&pair, true);
+    let color = hash_color(pair[0]);
+    assert!(lines[0].contains(&format!("\x1b[0;38;5;{color}m[worker-3]\x1b[0m")), "{:?}", lines[0]);
+    assert!(lines[1].contains(&format!("\x1b[0;38;5;{color}m[worker-10]\x1b[0m")), "{:?}", lines[1]);
+}
+
+#[test]
+fn thread_names_are_dimmed_by_default() {
+    let lines = lines_from(&["worker-0"], false);
+    assert!(lines[0].contains("\x1b[0;2m[worker-0]\x1b[22m"), "{:?}", lines[0]);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use std::fs;
+
+use rust_logger::{file, log};
+
+const THREADS: usize = 8;
+const LINES: usize = 1000;
+/// Lines of each multi-line record.
+const RECORD_LINES: usize = 5;
+
+/// `thread T line I` from a line matching
+/// `^ LOG  \[TS\] torn_lines: thread (\d+) line (\d+) $`.
+fn parse(line: &str) -> Option<(usize, usize)> {
+    let rest = line.strip_prefix(" LOG  [TS] torn_lines: thread ")?.strip_suffix(' ')?;
+    let (thread, index) = rest.split_once(" line ")?;
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    (all_digits(thread) && all_digits(index)).then(|| (thread.parse().unwrap(), index.parse().unwrap()))
+}
+
+#[test]
+fn concurrent_lines_are_never_torn() {
+    let (_guard, buffer) = common::capture();
+
+    std::thread::scope(|scope| {
+        for thread in 0..THREADS {
+            scope.spawn(move || {
+                for index in 0..LINES {
+                    log!("thread {thread} line {index}");
+                }
+            });
+        }
+    });
+
+    let text = buffer.text();
+    assert!(text.ends_with('\n'));
+    let mut next = [0; THREADS];
+    for line in text.lines() {
+        let (thread, index) = parse(line).unwrap_or_else(|| panic!("torn line: {line:?}"));
+        // Each thread's lines arrive whole and in order.
+        assert_eq!(index, next[thread], "thread {thread}");
+        next[thread] += 1;
+    }
+    assert_eq!(next, [LINES; THREADS]);
+}
+
+/// `(thread, record, line)` from the text after the prefix or the
+/// indentation: `thread T record R line L`.
+fn parse_part(line: &str) -> Option<(usize, usize, usize)> {
+    let mut numbers = line.trim().strip_prefix("thread ")?.split(' ').filter_map(|word| word.parse().ok());
+    let parsed = (numbers.next()?, numbers.next()?, numbers.next()?);
+    numbers.next().is_none().then_some(parsed)
+}
+
+/// Checks that the lines of each record follow each other, in order, and
+/// that every thread's records arrived in order.
+fn assert_whole_records(text: &str, prefix: &str) {
+    let mut next = [0; THREADS];
+    let mut lines = text.lines();
+    while let Some(first) = lines.next() {
+        let head = first.strip_prefix(prefix).unwrap_or_else(|| panic!("not a first line: {first:?}"));
+        let (thread, record, line) = parse_part(head).unwrap_or_else(|| panic!("torn line: {first:?}"));
+        assert_eq!((record, line), (next[thread], 0), "thread {thread}: {first:?}");
+        for expected in 1..RECORD_LINES {
+            let continued = lines.next().unwrap_or_else(|| panic!("record {record} of thread {thread} was cut short"));
+            assert!(continued.starts_with(' '), "another record in between: {continued:?}");
+            assert_eq!(parse_part(continued), Some((thread, record, expected)), "{continued:?}");
+        }
+        next[thread] += 1;
+    }
+    assert_eq!(next, [LINES; THREADS]);
+}
+
+#[test]
+fn multi_line_records_are_never_interleaved() {
+    let (_guard, buffer) = common::capture();
+    let path = std::env::temp_dir().join(format!("rust_logger-torn-lines-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let guard = file::file_sink(&path).session_header(false).open().unwrap();
+
+    std::thread::scope(|scope| {
+        for thread in 0..THREADS {
+            scope.spawn(move || {
+                for record in 0..LINES {
+                    log!(
+                        "thread {thread} record {record} line 0\nthread {thread} record {record} line 1\n\
+                         thread {thread} record {record} line 2\nthread {thread} record {record} line 3\n\
+                         thread {thread} record {record} line 4"
+                    );
+                }
+            });
+        }
+    });
+    drop(guard);
+
+    assert_whole_records(&buffer.text(), " LOG  [TS] torn_lines: ");
+    assert_whole_records(&fs::read_to_string(&path).unwrap(), "[LOG] [TS] torn_lines: ");
+    let _ = fs::remove_file(path);
+}
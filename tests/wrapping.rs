@@ -0,0 +1,124 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rust_logger::console::{self, Wrap};
+use rust_logger::format::{self, Format, Record};
+use rust_logger::sink::{self, Sink};
+use rust_logger::{log, Level};
+
+/// Keeps the raw message and the message of every record.
+#[derive(Clone, Default)]
+struct Messages(Arc<Mutex<Vec<(String, String)>>>);
+
+impl Sink for Messages {
+    fn write(&mut self, record: &Record, _line: &str) {
+        self.0.lock().unwrap().push((record.raw_message().to_string(), record.message.to_string()));
+    }
+}
+
+fn wrapped(columns: usize, message: &str) -> String {
+    let (_guard, buffer) = common::capture();
+    console::set_wrap(Wrap::Columns(columns));
+    log!("{}", message);
+    console::set_wrap(Wrap::Off);
+    buffer.text()
+}
+
+#[test]
+fn lines_break_at_the_last_space_that_fits() {
+    let text = wrapped(40, "the quick brown fox jumps over the lazy dog and runs away");
+    assert_eq!(
+        text,
+        concat!(
+            " LOG  [TS] wrapping: the quick brown \n",
+            "                     fox jumps over the \n",
+            "                     lazy dog and runs \n",
+            "                     away \n",
+        )
+    );
+    assert!(text.lines().all(|line| line.chars().count() <= 40), "the trailing space counts: {text}");
+}
+
+#[test]
+fn long_words_are_cut_at_the_width() {
+    let token = "0123456789".repeat(5);
+    assert_eq!(
+        wrapped(40, &format!("token {token} end")),
+        concat!(
+            " LOG  [TS] wrapping: token \n",
+            "                     012345678901234567↩\n",
+            "                     890123456789012345↩\n",
+            "                     67890123456789 end \n",
+        )
+    );
+    assert_eq!(
+        wrapped(40, &token),
+        concat!(
+            " LOG  [TS] wrapping: 012345678901234567↩\n",
+            "                     890123456789012345↩\n",
+            "                     67890123456789 \n",
+        )
+    );
+}
+
+#[test]
+fn short_lines_and_other_formats_are_unchanged() {
+    assert_eq!(wrapped(40, "fits"), " LOG  [TS] wrapping: fits \n");
+    // Too narrow for a message column: left as it is.
+    assert_eq!(wrapped(24, "the quick brown fox"), " LOG  [TS] wrapping: the quick brown fox \n");
+
+    let (_guard, buffer) = common::capture();
+    console::set_wrap(Wrap::Columns(40));
+    format::set_format(Format::Json);
+    log!("the quick brown fox jumps over the lazy dog and runs away");
+    format::set_format(Format::Pretty);
+    console::set_wrap(Wrap::Off);
+    assert_eq!(buffer.text().lines().count(), 1);
+    assert_eq!(console::wrap(), Wrap::Off);
+}
+
+#[test]
+fn messages_are_cut_before_anything_else() {
+    let (_guard, buffer) = common::capture();
+    let messages = Messages::default();
+    let id = sink::add_sink(Box::new(messages.clone()), Level::Debug, Format::Pretty);
+    format::set_message_limit(10);
+    let blob = "x".repeat(1000);
+    log!("{}", blob);
+    log!("abcdefgh\x1b[1mbold\x1b[0m");
+    log!("abcdefgh<red>red</>");
+    log!("short");
+    format::set_message_limit(0);
+    sink::remove_sink(id);
+
+    assert_eq!(
+        buffer.text(),
+        concat!(
+            " LOG  [TS] wrapping: xxxxxxxxxx…(+990 chars) \n",
+            " LOG  [TS] wrapping: abcdefgh…(+12 chars) \n",
+            " LOG  [TS] wrapping: abcdefgh…(+11 chars) \n",
+            " LOG  [TS] wrapping: short \n",
+        )
+    );
+    let messages = messages.0.lock().unwrap();
+    assert_eq!(messages[0], (blob, String::from("xxxxxxxxxx…(+990 chars)")), "the raw message is whole");
+    assert_eq!(messages[3], (String::from("short"), String::from("short")));
+}
+
+#[test]
+fn a_huge_single_token_is_wrapped_in_one_pass() {
+    let (_guard, buffer) = common::capture();
+    console::set_wrap(Wrap::Columns(100));
+    let token = "a".repeat(1 << 20);
+    log!("{}", token);
+    console::set_wrap(Wrap::Off);
+
+    let text = buffer.text();
+    let lines: Vec<&str> = text.lines().collect();
+    // 78 characters and the glyph on each line after the prefix.
+    assert_eq!(lines.len(), (1 << 20) / 78 + 1);
+    assert!(lines[..lines.len() - 1].iter().all(|line| line.chars().count() == 100 && line.ends_with('↩')));
+    let kept: usize = lines.iter().map(|line| line[21..].trim_end_matches(['↩', ' ']).len()).sum();
+    assert_eq!(kept, 1 << 20);
+}
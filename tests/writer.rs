@@ -0,0 +1,88 @@
+mod common;
+
+use std::io::{self, Write};
+
+use rust_logger::config::set_stable_test_format;
+use rust_logger::console::{self, ColorChoice};
+#[cfg(feature = "timestamps")]
+use rust_logger::time::{self, TimestampFormat};
+use rust_logger::{debug, error, log, warn};
+
+/// Refuses every write.
+struct Full;
+
+impl Write for Full {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("no space left"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Real escape sequences, so no stable format; no timestamp instead.
+fn unstable() {
+    set_stable_test_format(false);
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::None);
+}
+
+fn restore() {
+    #[cfg(feature = "timestamps")]
+    time::set_timestamp_format(TimestampFormat::Standard);
+    set_stable_test_format(true);
+}
+
+#[test]
+fn colored_bytes_for_each_level() {
+    let (_guard, buffer) = common::capture();
+    unstable();
+    console::set_color_choice(ColorChoice::Always);
+    debug!("d");
+    log!("l");
+    warn!("w");
+    error!("e");
+    restore();
+
+    assert_eq!(
+        buffer.contents(),
+        b"\x1b[0;100;38;2;0;0;0m DBG \x1b[0m \x1b[0;2mwriter:\x1b[22m \x1b[90md \x1b[0m\n\
+          \x1b[0;44;38;2;0;0;0m LOG \x1b[0m \x1b[0;2mwriter:\x1b[22m \x1b[37ml \x1b[0m\n\
+          \x1b[0;43;38;2;0;0;0m WRN \x1b[0m \x1b[0;2mwriter:\x1b[22m \x1b[33mw \x1b[0m\n\
+          \x1b[0;41;38;2;0;0;0m ERR \x1b[0m \x1b[0;2mwriter:\x1b[22m \x1b[31me \x1b[0m\n"
+    );
+}
+
+#[test]
+fn plain_bytes_for_each_level() {
+    let (_guard, buffer) = common::capture();
+    unstable();
+    debug!("d");
+    log!("l");
+    warn!("w");
+    error!("e");
+    restore();
+
+    assert_eq!(buffer.contents(), b"[DBG] writer: d\n[LOG] writer: l\n[WRN] writer: w\n[ERR] writer: e\n");
+}
+
+#[test]
+fn a_failing_writer_does_not_panic() {
+    let (_guard, _buffer) = common::capture();
+    console::set_writer(Box::new(Full));
+    for i in 0..10 {
+        error!("lost {i}");
+    }
+    assert!(console::reset_writer().is_some());
+}
+
+#[test]
+fn reset_writer_hands_back_the_writer() {
+    let (_guard, buffer) = common::capture();
+    log!("kept");
+    let mut writer = console::reset_writer().expect("no writer was set");
+    assert!(console::reset_writer().is_none(), "stdout is not a writer");
+    writer.write_all(b"more\n").unwrap();
+    assert_eq!(buffer.text(), " LOG  [TS] writer: kept \nmore\n");
+}